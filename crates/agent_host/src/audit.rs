@@ -0,0 +1,267 @@
+//! Record of the commands the agent ran
+//!
+//! Each command is one line of the log, e.g.
+//! `[2026-10-14T09:30:00Z] [safe] [0] [42] $ ls -la`: when it finished, its
+//! danger level, exit code and how long it took in milliseconds. Commands
+//! that were refused are logged too, with exit code -1. Newlines in a
+//! command are written as `\n` so each entry stays on one line, and
+//! passwords given for sudo never reach the file. Once the log passes
+//! `MAX_LOG_BYTES` it's moved to `audit.log.1`, replacing the one before.
+
+use crate::executor::{CommandResult, DangerLevel};
+use chrono::{DateTime, SecondsFormat, Utc};
+use regex::Regex;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::LazyLock;
+
+/// Written in place of a password
+const MASK: &str = "********";
+
+/// Size the log may grow to before it's rotated
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Read at a time when going back from the end of the log
+const TAIL_BLOCK_BYTES: u64 = 8 * 1024;
+
+/// The password in `echo <password> | sudo -S ...`
+static PIPED_PASSWORD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(echo\s+)\S+(\s*\|\s*sudo\s+(?:-\w+\s+)*-S)").unwrap());
+
+/// Entries written by this process, so readers can tell a cached read is
+/// out of date
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub danger: DangerLevel,
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    pub command: String,
+}
+
+/// `cmd` with each of `secrets` masked, along with the password in an
+/// `echo <password> | sudo -S` pipeline
+pub fn mask_secrets(cmd: &str, secrets: &[&str]) -> String {
+    let mut masked = cmd.to_string();
+    for secret in secrets.iter().filter(|s| !s.is_empty()) {
+        masked = masked.replace(secret, MASK);
+    }
+    PIPED_PASSWORD.replace_all(&masked, format!("${{1}}{}${{2}}", MASK)).into_owned()
+}
+
+/// Add `result` to the log at `path`, masking `secrets`
+pub fn record(path: &Path, danger: DangerLevel, result: &CommandResult, secrets: &[&str]) {
+    append(
+        path,
+        &AuditEntry {
+            timestamp: Utc::now(),
+            danger,
+            exit_code: result.exit_code,
+            duration_ms: result.duration_ms,
+            command: mask_secrets(&result.command, secrets),
+        },
+    );
+}
+
+/// Add `entry` to the log at `path`. A failure to write is only logged,
+/// since it shouldn't fail the command.
+pub fn append(path: &Path, entry: &AuditEntry) {
+    let write = || -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        rotate_if_larger(path, MAX_LOG_BYTES)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", format_entry(entry))
+    };
+    match write() {
+        Ok(()) => {
+            WRITES.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(e) => tracing::warn!("Failed to write audit log {}: {}", path.display(), e),
+    }
+}
+
+/// How many entries this process has added to any log
+pub fn write_count() -> usize {
+    WRITES.load(Ordering::Relaxed)
+}
+
+/// Move the log at `path` to `<path>.1` once it's `max_bytes` or more
+fn rotate_if_larger(path: &Path, max_bytes: u64) -> std::io::Result<()> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.len() >= max_bytes => fs::rename(path, rotated_path(path)),
+        _ => Ok(()),
+    }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".1");
+    path.with_file_name(name)
+}
+
+/// The last `limit` entries of the log at `path`, newest first, read
+/// back from the end of the file. Lines that can't be read are skipped,
+/// and a missing log has no entries.
+pub fn read_audit_log(path: &Path, limit: usize) -> Vec<AuditEntry> {
+    let mut entries = Vec::new();
+    let Ok(mut file) = File::open(path) else { return entries };
+    let Ok(mut pos) = file.seek(SeekFrom::End(0)) else { return entries };
+    // Start of a line that began before the block read last
+    let mut partial = Vec::new();
+    while entries.len() < limit && pos > 0 {
+        let size = TAIL_BLOCK_BYTES.min(pos);
+        pos -= size;
+        let mut block = vec![0; size as usize];
+        if file.seek(SeekFrom::Start(pos)).is_err() || file.read_exact(&mut block).is_err() {
+            break;
+        }
+        block.append(&mut partial);
+        // Unless this is the start of the file, the first line is cut off
+        let lines = match block.iter().position(|b| *b == b'\n') {
+            Some(i) if pos > 0 => {
+                partial = block[..i].to_vec();
+                &block[i + 1..]
+            }
+            None if pos > 0 => {
+                partial = block;
+                continue;
+            }
+            _ => &block[..],
+        };
+        let parsed = lines.split(|b| *b == b'\n').rev().filter_map(|line| parse_entry(&String::from_utf8_lossy(line)));
+        entries.extend(parsed.take(limit - entries.len()));
+    }
+    entries
+}
+
+fn format_entry(entry: &AuditEntry) -> String {
+    format!(
+        "[{}] [{}] [{}] [{}] $ {}",
+        entry.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+        entry.danger.key(),
+        entry.exit_code,
+        entry.duration_ms,
+        escape(&entry.command)
+    )
+}
+
+fn parse_entry(line: &str) -> Option<AuditEntry> {
+    let (timestamp, rest) = bracketed(line)?;
+    let (danger, rest) = bracketed(rest)?;
+    let (exit_code, rest) = bracketed(rest)?;
+    let (duration_ms, rest) = bracketed(rest)?;
+    Some(AuditEntry {
+        timestamp: DateTime::parse_from_rfc3339(timestamp).ok()?.with_timezone(&Utc),
+        danger: DangerLevel::from_key(danger)?,
+        exit_code: exit_code.parse().ok()?,
+        duration_ms: duration_ms.parse().ok()?,
+        command: unescape(rest.strip_prefix("$ ")?),
+    })
+}
+
+/// The text of a leading `[...] ` field, and what follows it
+fn bracketed(s: &str) -> Option<(&str, &str)> {
+    s.strip_prefix('[')?.split_once("] ")
+}
+
+fn escape(command: &str) -> String {
+    command.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn unescape(command: &str) -> String {
+    let mut out = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_log_round_trip() {
+        let path = std::env::temp_dir().join(format!("lh-audit-{}", std::process::id())).join("audit.log");
+        let result = |command: &str, exit_code| CommandResult {
+            command: command.to_string(),
+            exit_code,
+            stdout: String::new(),
+            stderr: String::new(),
+            output: String::new(),
+            duration_ms: 42,
+            success: exit_code == 0,
+            summary: String::new(),
+            needed_sudo: false,
+            truncated_at_bytes: None,
+            structured: None,
+            extracted_paths: Vec::new(),
+            working_dir: None,
+        };
+        record(&path, DangerLevel::Safe, &result("ls -la", 0), &[]);
+        record(&path, DangerLevel::NeedsSudo, &result("sudo apt install hunter2\necho C:\\temp", 1), &["hunter2"]);
+        record(&path, DangerLevel::Dangerous, &result("echo s3cret | sudo -S rm -rf /tmp/x", 0), &[]);
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.lines().next().unwrap().ends_with("] [safe] [0] [42] $ ls -la"));
+        assert!(!text.contains("hunter2") && !text.contains("s3cret"));
+
+        let entries = read_audit_log(&path, 2);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].command, "echo ******** | sudo -S rm -rf /tmp/x");
+        assert_eq!(entries[1].command, "sudo apt install ********\necho C:\\temp");
+        assert_eq!(entries[1].danger, DangerLevel::NeedsSudo);
+        assert_eq!(entries[1].exit_code, 1);
+        assert!(read_audit_log(&path.with_file_name("missing.log"), 10).is_empty());
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_audit_log_tail_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("lh-audit-tail-{}", std::process::id()));
+        let path = dir.join("audit.log");
+        for i in 0..2000 {
+            let entry = AuditEntry {
+                timestamp: Utc::now(),
+                danger: DangerLevel::Safe,
+                exit_code: 0,
+                duration_ms: i,
+                command: format!("echo é{}", i),
+            };
+            append(&path, &entry);
+        }
+        // Many blocks back from the end, newest first
+        let entries = read_audit_log(&path, 1500);
+        assert_eq!(entries.len(), 1500);
+        assert_eq!(entries[0].duration_ms, 1999);
+        assert_eq!(entries[1499].command, "echo é500");
+        assert_eq!(read_audit_log(&path, 5000).len(), 2000);
+
+        let size = fs::metadata(&path).unwrap().len();
+        rotate_if_larger(&path, size + 1).unwrap();
+        assert!(path.exists());
+        rotate_if_larger(&path, size).unwrap();
+        assert!(!path.exists());
+        assert_eq!(read_audit_log(&dir.join("audit.log.1"), 1)[0].duration_ms, 1999);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,198 @@
+//! Context plugins: background knowledge added to the system prompt
+//!
+//! A plugin names the chat modes it's for, a few lines of instructions,
+//! files to include and read-only commands whose output is included. Users
+//! add their own as TOML files in `~/.config/little-helper/context_plugins/`;
+//! one with the same name as a bundled plugin replaces it, which is also
+//! how a bundled plugin is turned off (`enabled = false`).
+//!
+//! Files and commands are held to the same allowed folders as the agent's
+//! own commands, and the commands go in the audit log.
+
+use crate::executor::{
+    classify_command, execute_command_with, expand_home, path_allowed, timeout_for, DangerLevel, ExecutionOptions,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BUNDLED_PLUGINS: &[&str] = &[include_str!("../assets/context_plugins/mcp_campaign.toml")];
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ContextPlugin {
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    /// Mode keys ("research", "content", ...); empty means every mode
+    #[serde(default)]
+    pub trigger_modes: Vec<String>,
+    #[serde(default)]
+    pub system_prompt_prefix: String,
+    #[serde(default)]
+    pub load_files: Vec<PluginFile>,
+    /// Commands whose output is included; only ones that are safe to run
+    /// without asking are run
+    #[serde(default)]
+    pub run_commands: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PluginFile {
+    /// May start with `~/`
+    pub path: String,
+    pub max_chars: usize,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// `~/.config/little-helper/context_plugins`
+pub fn plugins_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("little-helper")
+        .join("context_plugins")
+}
+
+/// Bundled plugins plus every `.toml` file in `dir`. Files that don't
+/// parse are skipped with a warning.
+pub fn load_plugins(dir: &Path) -> Vec<ContextPlugin> {
+    let mut plugins: Vec<ContextPlugin> =
+        BUNDLED_PLUGINS.iter().filter_map(|text| toml::from_str(text).ok()).collect();
+
+    let files = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"));
+    for path in files {
+        let parsed = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| toml::from_str::<ContextPlugin>(&text).map_err(Into::into));
+        let plugin = match parsed {
+            Ok(plugin) => plugin,
+            Err(e) => {
+                tracing::warn!("Skipping context plugin {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        plugins.retain(|p| p.name != plugin.name);
+        plugins.push(plugin);
+    }
+    plugins
+}
+
+impl ContextPlugin {
+    pub fn applies_to(&self, mode: &str) -> bool {
+        self.enabled && (self.trigger_modes.is_empty() || self.trigger_modes.iter().any(|m| m == mode))
+    }
+
+    /// The plugin's section of the system prompt. `None` if it lists files
+    /// and none of them are on this computer or in the allowed folders.
+    /// Commands run with `options`, and a timeout from `timeouts`.
+    pub async fn render(&self, options: &ExecutionOptions, timeouts: &HashMap<String, u64>) -> Option<String> {
+        let mut files = String::new();
+        for file in &self.load_files {
+            if !path_allowed(&expand_home(&file.path).to_string_lossy(), &options.allowed_dirs) {
+                tracing::warn!("Context plugin {} skipped {}: it's outside the allowed folders", self.name, file.path);
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(expand_home(&file.path)) else { continue };
+            let excerpt: String = content.chars().take(file.max_chars).collect();
+            files.push_str(&format!("=== {} ===\n{}", file.path, excerpt));
+            if excerpt.len() < content.len() {
+                files.push_str(&format!(
+                    "...\n[Truncated at {} chars - full file has {} chars]",
+                    file.max_chars,
+                    content.chars().count()
+                ));
+            }
+            files.push_str("\n\n");
+        }
+        if files.is_empty() && !self.load_files.is_empty() {
+            return None;
+        }
+
+        let mut context = format!("=== {} ===\n", self.name.to_uppercase());
+        if !self.system_prompt_prefix.trim().is_empty() {
+            context.push_str(self.system_prompt_prefix.trim());
+            context.push_str("\n\n");
+        }
+        context.push_str(&files);
+        for cmd in &self.run_commands {
+            if classify_command(cmd) != DangerLevel::Safe {
+                tracing::warn!("Context plugin {} skipped `{}`: it isn't read-only", self.name, cmd);
+                continue;
+            }
+            let options = ExecutionOptions { timeout_secs: timeout_for(cmd, timeouts), ..options.clone() };
+            match execute_command_with(cmd, &options).await {
+                Ok(result) if result.success => {
+                    context.push_str(&format!("$ {}\n{}\n\n", cmd, result.output.trim_end()));
+                }
+                Ok(_) | Err(_) => {}
+            }
+        }
+        context.push_str(&format!("=== END {} ===\n\n", self.name.to_uppercase()));
+        Some(context)
+    }
+}
+
+/// Every enabled plugin for `mode`, for the start of the system prompt
+pub async fn context_for_mode(
+    plugins: &[ContextPlugin],
+    mode: &str,
+    options: &ExecutionOptions,
+    timeouts: &HashMap<String, u64>,
+) -> String {
+    let mut context = String::new();
+    for plugin in plugins.iter().filter(|p| p.applies_to(mode)) {
+        context.extend(plugin.render(options, timeouts).await);
+    }
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_context_plugins() {
+        let dir = std::env::temp_dir().join(format!("lh-context-plugins-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let notes = dir.join("notes.md");
+        fs::write(&notes, "Our house style: short sentences.").unwrap();
+        fs::write(
+            dir.join("style.toml"),
+            format!(
+                "name = \"House style\"\ntrigger_modes = [\"content\"]\nsystem_prompt_prefix = \"Follow the style guide.\"\n\
+                run_commands = [\"wc -c {0}\", \"rm -rf /tmp/x\"]\n\n[[load_files]]\npath = \"{0}\"\nmax_chars = 9\n",
+                notes.display()
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("campaign.toml"), "name = \"MCP marine conservation campaign\"\nenabled = false\n").unwrap();
+        fs::write(dir.join("broken.toml"), "name = ").unwrap();
+
+        let plugins = load_plugins(&dir);
+        assert_eq!(plugins.len(), 2);
+        assert!(!plugins.iter().any(|p| p.applies_to("content") && p.name.starts_with("MCP")));
+
+        let options = ExecutionOptions::default();
+        let context = context_for_mode(&plugins, "content", &options, &HashMap::new()).await;
+        assert!(context.starts_with("=== HOUSE STYLE ===\nFollow the style guide.\n\n"));
+        assert!(context.contains("Our house...\n[Truncated at 9 chars"));
+        assert!(context.contains(&format!("$ wc -c {}\n33 ", notes.display())));
+        assert!(!context.contains("rm -rf"));
+        assert!(context_for_mode(&plugins, "find", &options, &HashMap::new()).await.is_empty());
+
+        // Nothing from outside the allowed folders
+        let elsewhere = ExecutionOptions { allowed_dirs: vec!["/nonexistent".to_string()], ..Default::default() };
+        assert!(context_for_mode(&plugins, "content", &elsewhere, &HashMap::new()).await.is_empty());
+        let inside = ExecutionOptions { allowed_dirs: vec![dir.display().to_string()], ..Default::default() };
+        assert!(context_for_mode(&plugins, "content", &inside, &HashMap::new()).await.contains("$ wc -c"));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
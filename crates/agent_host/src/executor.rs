@@ -4,9 +4,17 @@
 //! with safety checks, confirmation requirements, and user-friendly output.
 
 use anyhow::Result;
+use crate::structured_output::{parse_structured_output, OutputFormat, StructuredTable};
 use serde::{Deserialize, Serialize};
+use shared::settings::{validate_command_prefix, AppSettings};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 use std::process::Stdio;
+use std::sync::{LazyLock, RwLock};
 use std::time::{Duration, Instant};
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::process::Command;
 
 /// Danger level for commands
@@ -24,6 +32,43 @@ pub enum DangerLevel {
     Blocked,
 }
 
+impl DangerLevel {
+    /// Settings key for this level (snake_case variant name)
+    pub fn key(&self) -> &'static str {
+        match self {
+            DangerLevel::Safe => "safe",
+            DangerLevel::NeedsConfirmation => "needs_confirmation",
+            DangerLevel::Dangerous => "dangerous",
+            DangerLevel::NeedsSudo => "needs_sudo",
+            DangerLevel::Blocked => "blocked",
+        }
+    }
+
+    /// The level with settings key `key`
+    pub fn from_key(key: &str) -> Option<Self> {
+        [
+            DangerLevel::Safe,
+            DangerLevel::NeedsConfirmation,
+            DangerLevel::Dangerous,
+            DangerLevel::NeedsSudo,
+            DangerLevel::Blocked,
+        ]
+        .into_iter()
+        .find(|level| level.key() == key)
+    }
+}
+
+/// Timeout used when a danger level has no configured value
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Look up the timeout for a command from per-danger-level settings
+pub fn timeout_for(cmd: &str, timeouts: &HashMap<String, u64>) -> u64 {
+    timeouts
+        .get(classify_command(cmd).key())
+        .copied()
+        .unwrap_or(DEFAULT_TIMEOUT_SECS)
+}
+
 /// Result of command execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandResult {
@@ -45,6 +90,57 @@ pub struct CommandResult {
     pub summary: String,
     /// Whether sudo/password was required
     pub needed_sudo: bool,
+    /// Size limit `output` was cut at, if it was truncated
+    #[serde(default)]
+    pub truncated_at_bytes: Option<usize>,
+    /// Output parsed as a table, when it looks like one
+    #[serde(default)]
+    pub structured: Option<StructuredTable>,
+    /// Files named in the output, so they can be opened with a click
+    #[serde(default)]
+    pub extracted_paths: Vec<PathBuf>,
+    /// Directory the command ran in; `None` if it didn't run or wasn't
+    /// recorded
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+}
+
+impl CommandResult {
+    /// Whether `output` was cut short
+    pub fn is_truncated(&self) -> bool {
+        self.truncated_at_bytes.is_some()
+    }
+}
+
+/// Default limit for command output passed back to the AI
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 10_000;
+
+/// Options for running a command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionOptions {
+    pub timeout_secs: u64,
+    /// Combined output beyond this is truncated
+    pub max_output_bytes: usize,
+    /// Directory to run in (the app's current directory if unset)
+    pub working_dir: Option<PathBuf>,
+    /// Commands naming a path outside these folders aren't run. Empty
+    /// allows any path.
+    pub allowed_dirs: Vec<String>,
+    /// Where to record the command once it's done (see `audit`); not
+    /// recorded if unset
+    pub audit_log: Option<PathBuf>,
+}
+
+impl Default for ExecutionOptions {
+    fn default() -> Self {
+        Self {
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            working_dir: None,
+            allowed_dirs: Vec::new(),
+            audit_log: None,
+        }
+    }
 }
 
 /// Safe commands that can run without confirmation
@@ -142,8 +238,93 @@ const BLOCKED_COMMANDS: &[&str] = &[
     "nc -l", "nmap",
 ];
 
+/// User-defined additions to the built-in command lists
+#[derive(Debug, Clone, Default)]
+pub struct CommandRules {
+    pub safe: Vec<String>,
+    pub needs_confirmation: Vec<String>,
+    pub blocked: Vec<String>,
+}
+
+impl CommandRules {
+    /// Rules from settings. Entries that fail validation (e.g. edited by
+    /// hand into settings.json) are ignored.
+    pub fn from_settings(settings: &AppSettings) -> Self {
+        let clean = |entries: &[String]| -> Vec<String> {
+            entries
+                .iter()
+                .filter_map(|e| validate_command_prefix(e).ok())
+                .map(|e| e.to_lowercase())
+                .collect()
+        };
+        Self {
+            safe: clean(&settings.extra_safe_commands),
+            needs_confirmation: clean(&settings.extra_needs_confirmation_commands),
+            blocked: clean(&settings.extra_blocked_commands),
+        }
+    }
+}
+
+/// Commands the user blocked in settings. Every command run here is
+/// checked against them, whichever caller and options it came with.
+static USER_BLOCKED: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Folders the user allowed in settings, for the entry points that don't
+/// take `ExecutionOptions` (`execute_command`, `execute_with_sudo`...)
+static USER_ALLOWED_DIRS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Take the user's blocked commands and allowed folders from `settings`,
+/// for every command run from now on. The app calls this at startup and
+/// when settings are saved.
+pub fn set_command_restrictions(settings: &AppSettings) {
+    *USER_BLOCKED.write().unwrap() = CommandRules::from_settings(settings).blocked;
+    *USER_ALLOWED_DIRS.write().unwrap() = settings.allowed_dirs.clone();
+}
+
+fn user_allowed_dirs() -> Vec<String> {
+    USER_ALLOWED_DIRS.read().unwrap().clone()
+}
+
+/// Classify a command about to run, with the user's blocked commands
+fn classify_for_run(cmd: &str) -> DangerLevel {
+    let rules = CommandRules { blocked: USER_BLOCKED.read().unwrap().clone(), ..Default::default() };
+    classify_command_with(cmd, &rules)
+}
+
+/// Whether `cmd` starts with the whole words of `prefix` ("rg" matches
+/// "rg foo" but not "rgb")
+fn starts_with_words(cmd: &str, prefix: &str) -> bool {
+    cmd.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+/// Whether `cmd` matches a user entry: a prefix, or with `*` or `?` a
+/// glob for each of its first words ("~/bin/*" matches
+/// "~/bin/backup.sh --all" and the same script by its full path)
+fn matches_entry(cmd: &str, entry: &str) -> bool {
+    if !entry.contains(['*', '?']) {
+        return starts_with_words(cmd, entry);
+    }
+    let home = |word: &str| expand_home(word).to_string_lossy().to_lowercase();
+    let words: Vec<&str> = cmd.split_whitespace().collect();
+    let patterns: Vec<&str> = entry.split_whitespace().collect();
+    words.len() >= patterns.len()
+        && patterns.iter().zip(&words).all(|(pattern, word)| {
+            let Ok(glob) = glob::Pattern::new(pattern) else { return false };
+            let expanded = glob::Pattern::new(&home(pattern)).ok();
+            glob.matches(word) || expanded.is_some_and(|glob| glob.matches(&home(word)))
+        })
+}
+
 /// Classify a command by danger level
 pub fn classify_command(cmd: &str) -> DangerLevel {
+    classify_command_with(cmd, &CommandRules::default())
+}
+
+/// Classify a command, taking user-defined rules into account. Built-in
+/// blocked, sudo and dangerous checks always win; user entries come next,
+/// then the built-in safe and confirmation lists.
+pub fn classify_command_with(cmd: &str, rules: &CommandRules) -> DangerLevel {
     let cmd_lower = cmd.to_lowercase();
     let cmd_trimmed = cmd_lower.trim();
     
@@ -153,6 +334,14 @@ pub fn classify_command(cmd: &str) -> DangerLevel {
             return DangerLevel::Blocked;
         }
     }
+
+    // User-blocked commands are caught anywhere in a pipeline or command list
+    let segments = cmd_trimmed.split(['|', ';', '&']).map(str::trim);
+    for segment in segments {
+        if rules.blocked.iter().any(|b| matches_entry(segment, b)) {
+            return DangerLevel::Blocked;
+        }
+    }
     
     // Check if sudo is needed
     if cmd_trimmed.starts_with("sudo ") {
@@ -166,6 +355,14 @@ pub fn classify_command(cmd: &str) -> DangerLevel {
         }
     }
     
+    // User rules: confirmation before safe, so an entry can demote a built-in safe command
+    if rules.needs_confirmation.iter().any(|c| matches_entry(cmd_trimmed, c)) {
+        return DangerLevel::NeedsConfirmation;
+    }
+    if rules.safe.iter().any(|s| matches_entry(cmd_trimmed, s)) {
+        return DangerLevel::Safe;
+    }
+
     // Check needs confirmation
     for confirm in NEEDS_CONFIRMATION {
         if cmd_trimmed.starts_with(confirm) {
@@ -184,116 +381,925 @@ pub fn classify_command(cmd: &str) -> DangerLevel {
     DangerLevel::NeedsConfirmation
 }
 
-/// Execute a command and return structured result
-pub async fn execute_command(cmd: &str, timeout_secs: u64) -> Result<CommandResult> {
-    let danger = classify_command(cmd);
-    
-    if danger == DangerLevel::Blocked {
-        return Ok(CommandResult {
-            command: cmd.to_string(),
-            exit_code: -1,
-            stdout: String::new(),
-            stderr: "This command is blocked for safety reasons.".to_string(),
-            output: "This command is blocked for safety reasons.".to_string(),
-            duration_ms: 0,
-            success: false,
-            summary: "Command blocked for safety".to_string(),
-            needed_sudo: false,
-        });
+/// `s` without terminal escape sequences: colours and other CSI codes
+/// (`\x1b[32m`, `\x1b[2K`) and OSC ones like hyperlinks
+pub fn strip_ansi(s: &str) -> String {
+    if !s.contains('\x1b') {
+        return s.to_string();
     }
-    
-    let start = Instant::now();
-    
-    // Determine shell based on OS
-    let (shell, shell_arg) = if cfg!(windows) {
-        ("cmd", "/C")
+    let ansi = Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-_]").unwrap();
+    ansi.replace_all(s, "").into_owned()
+}
+
+/// Cut `output` to at most `max_bytes` (on a character boundary), noting the full size
+pub(crate) fn truncate_output(output: String, max_bytes: usize) -> (String, Option<usize>) {
+    let total_bytes = output.len();
+    truncate_output_of(output, max_bytes, total_bytes)
+}
+
+/// Like `truncate_output`, for `output` kept from `total_bytes` of it
+fn truncate_output_of(output: String, max_bytes: usize, total_bytes: usize) -> (String, Option<usize>) {
+    if total_bytes <= max_bytes {
+        return (output, None);
+    }
+    let mut end = max_bytes.min(output.len());
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    let truncated = format!(
+        "{}...\n[Output truncated at {} bytes, {} bytes total]",
+        &output[..end],
+        max_bytes,
+        total_bytes
+    );
+    (truncated, Some(max_bytes))
+}
+
+/// Restrict a `find` command to the allowed directories.
+///
+/// If any starting path of `find` lies outside `allowed_dirs`, the search
+/// expression is wrapped with `-path` constraints so only entries inside an
+/// allowed directory can match. Returns `None` if the command is not a
+/// `find`, is already in scope, or no directories are configured.
+pub fn restrict_find_command(cmd: &str, allowed_dirs: &[String]) -> Option<String> {
+    if cfg!(windows) || allowed_dirs.is_empty() {
+        return None;
+    }
+    let trimmed = cmd.trim_start();
+    let args_str = trimmed.strip_prefix("find")?;
+    if !args_str.is_empty() && !args_str.starts_with(char::is_whitespace) {
+        return None; // e.g. "findmnt"
+    }
+
+    // Only the first pipeline stage is the find itself
+    let split = shell_operator_index(args_str).unwrap_or(args_str.len());
+    let (find_args, tail) = args_str.split_at(split);
+
+    // Starting paths come before the first expression token
+    let mut start_paths = Vec::new();
+    let mut expr_start = find_args.len();
+    let mut offset = 0;
+    for token in find_args.split_whitespace() {
+        let pos = offset + find_args[offset..].find(token).unwrap_or(0);
+        offset = pos + token.len();
+        if token.starts_with('-') || token.starts_with('(') || token.starts_with('\\') || token.starts_with('!') {
+            expr_start = pos;
+            break;
+        }
+        start_paths.push(token.trim_matches(|c| c == '"' || c == '\''));
+    }
+    if start_paths.is_empty() {
+        start_paths.push(".");
+    }
+
+    let allowed: Vec<PathBuf> = allowed_dirs.iter().map(|d| normalize_path(d)).collect();
+    let out_of_scope = start_paths
+        .iter()
+        .any(|p| !allowed.iter().any(|dir| normalize_path(p).starts_with(dir)));
+    if !out_of_scope {
+        return None;
+    }
+
+    let constraints = find_constraints(&allowed);
+    let paths = find_args[..expr_start].trim_end();
+    let expr = find_args[expr_start..].trim();
+    let rewritten = if expr.is_empty() {
+        format!("find{} \\( {} \\){}", paths, constraints, tail)
     } else {
-        ("sh", "-c")
+        format!("find{} \\( {} \\) \\( {} \\) {}", paths, constraints, expr, tail.trim_start())
     };
-    
-    let output = tokio::time::timeout(
-        Duration::from_secs(timeout_secs),
-        Command::new(shell)
-            .arg(shell_arg)
-            .arg(cmd)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-    ).await;
-    
-    let duration_ms = start.elapsed().as_millis() as u64;
-    
-    match output {
-        Ok(Ok(output)) => {
-            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            let exit_code = output.status.code().unwrap_or(-1);
-            let success = output.status.success();
-            
-            // Combine output, truncate if too long
-            let mut combined = stdout.clone();
-            if !stderr.is_empty() {
-                if !combined.is_empty() {
-                    combined.push('\n');
+    let rewritten = rewritten.trim_end().to_string();
+
+    tracing::debug!(original = %cmd, rewritten = %rewritten, "restricted find command to allowed directories");
+    Some(rewritten)
+}
+
+/// The `-path` tests `restrict_find_command` adds for `allowed`
+fn find_constraints(allowed: &[PathBuf]) -> String {
+    allowed
+        .iter()
+        .map(|dir| {
+            let dir = dir.to_string_lossy();
+            format!("-path {} -o -path {}", shell_quote(&dir), shell_quote(&format!("{}/*", dir.trim_end_matches('/'))))
+        })
+        .collect::<Vec<_>>()
+        .join(" -o ")
+}
+
+/// Device files commands may always use, e.g. to discard output
+const ALWAYS_ALLOWED_PATHS: &[&str] = &["/dev/null", "/dev/stdin", "/dev/stdout", "/dev/stderr"];
+
+/// An absolute, `~` or `$HOME` path, at the start of a word or after a
+/// quote, redirect (`>/etc/x`, `</etc/x`) or `=` (`if=/etc/x`, `--file=/etc/x`)
+static PATH_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:^|[\s<>=("'])((?:~|\$HOME|\$\{HOME\})(?:/[^\s"'<>;|&()`]*)?|/[^\s"'<>;|&()`]*)"#).unwrap()
+});
+
+/// Paths outside `allowed_dirs` that `cmd` names by absolute, `~` or
+/// `$HOME` path, including in redirects and `option=path` arguments.
+/// Empty when no directories are configured. The starting points of a
+/// `find` rewritten by [`restrict_find_command`] don't count, since
+/// nothing outside the allowed folders can match it.
+pub fn paths_outside_allowed(cmd: &str, allowed_dirs: &[String]) -> Vec<PathBuf> {
+    if allowed_dirs.is_empty() {
+        return Vec::new();
+    }
+    let allowed: Vec<PathBuf> = allowed_dirs.iter().map(|d| normalize_path(d)).collect();
+    let checked = restricted_find_rest(cmd, &allowed).unwrap_or(cmd);
+    let mut paths = Vec::new();
+    for captures in PATH_PATTERN.captures_iter(checked) {
+        let token = &captures[1];
+        if ALWAYS_ALLOWED_PATHS.contains(&token) {
+            continue;
+        }
+        let token = match token.strip_prefix("${HOME}").or_else(|| token.strip_prefix("$HOME")) {
+            Some(rest) => format!("~{}", rest),
+            None => token.to_string(),
+        };
+        let path = normalize_path(&token);
+        if !allowed.iter().any(|dir| path.starts_with(dir)) && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// If `cmd` is exactly what [`restrict_find_command`] makes, `find
+/// <paths> \( <constraints> \)` with at most one `\( <expression> \)`
+/// group after it, everything after the constraints. Anything else, like
+/// an `-o` that would match outside them, gives `None`.
+fn restricted_find_rest<'a>(cmd: &'a str, allowed: &[PathBuf]) -> Option<&'a str> {
+    let args = cmd.trim_start().strip_prefix("find")?;
+    let marker = format!(" \\( {} \\)", find_constraints(allowed));
+    let (start_paths, rest) = args.split_at(args.find(&marker)?);
+    let rest = &rest[marker.len()..];
+
+    let plain_path = |token: &str| {
+        !token.starts_with(['-', '(', '\\', '!'])
+            && !token.contains(['$', '`', '<', '>', '|', ';', '&', '(', ')'])
+    };
+    if !start_paths.starts_with(char::is_whitespace) || !start_paths.split_whitespace().all(plain_path) {
+        return None;
+    }
+
+    let expr = &rest[..shell_operator_index(rest).unwrap_or(rest.len())];
+    let tokens: Vec<&str> = expr.split_whitespace().collect();
+    if tokens.is_empty() {
+        return Some(rest);
+    }
+    if tokens.first() != Some(&"\\(") || tokens.last() != Some(&"\\)") {
+        return None;
+    }
+    // The group opened by the first token must close at the last one
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.trim_matches(|c| c == '"' || c == '\'').trim_start_matches('\\') {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 && i + 1 < tokens.len() {
+            return None;
+        }
+    }
+    (depth == 0).then_some(rest)
+}
+
+/// Folders outside `allowed_dirs` that `cmd` names by absolute or `~`
+/// path - the folder itself for a directory, the containing folder for
+/// anything else. Empty when no directories are configured. The root
+/// folder is never offered.
+pub fn dirs_outside_allowed(cmd: &str, allowed_dirs: &[String]) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for path in paths_outside_allowed(cmd, allowed_dirs) {
+        let dir = if path.is_dir() {
+            path
+        } else {
+            match path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => continue,
+            }
+        };
+        if dir.parent().is_some() && !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Position of the first `|`, `;` or `&` outside of quotes
+fn shell_operator_index(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '|' | ';' | '&') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Expand `~` and resolve `.`/`..` without touching the filesystem
+fn normalize_path(path: &str) -> PathBuf {
+    let expanded = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = std::env::var("HOME").unwrap_or_default();
+            PathBuf::from(format!("{}{}", home, rest))
+        }
+        _ => PathBuf::from(path),
+    };
+    let absolute = if expanded.is_absolute() {
+        expanded
+    } else {
+        std::env::current_dir().unwrap_or_default().join(expanded)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Most paths kept from one command's output
+const MAX_EXTRACTED_PATHS: usize = 50;
+
+/// Existing files mentioned in `text` by absolute or `~` path
+pub fn extract_paths(text: &str) -> Vec<PathBuf> {
+    let path_regex = Regex::new(r#"(?:^|[\s"'(])([~/][^\s"'()]+\.[a-zA-Z0-9]+)"#).unwrap();
+    path_regex
+        .captures_iter(text)
+        .filter_map(|cap| cap.get(1))
+        .map(|m| expand_home(m.as_str()))
+        .filter(|p| p.exists())
+        .collect()
+}
+
+/// Files in a command's output: full paths anywhere (`find`, `grep -l`),
+/// plus the entries of an `ls` listing joined to the listed directory
+fn extract_output_paths(cmd: &str, output: &str, working_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut paths = extract_paths(output);
+    if let Some(dir) = ls_directory(cmd, working_dir) {
+        for line in output.lines() {
+            if let Some(name) = ls_entry_name(line) {
+                paths.push(dir.join(name));
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    paths.retain(|p| p.is_file() && seen.insert(p.clone()));
+    paths.truncate(MAX_EXTRACTED_PATHS);
+    paths
+}
+
+/// The directory a plain `ls [flags] [dir]` lists
+fn ls_directory(cmd: &str, working_dir: Option<&Path>) -> Option<PathBuf> {
+    let mut words = cmd.split_whitespace();
+    if words.next() != Some("ls") || cmd.contains(['|', ';', '&', '>']) {
+        return None;
+    }
+    let args: Vec<&str> = words.filter(|w| !w.starts_with('-')).collect();
+    let dir = match args.as_slice() {
+        [] => PathBuf::from("."),
+        [dir] => expand_home(dir.trim_matches(|c| c == '"' || c == '\'')),
+        _ => return None,
+    };
+    let base = working_dir.map(Path::to_path_buf).or_else(|| std::env::current_dir().ok())?;
+    Some(base.join(dir))
+}
+
+/// The file name on one line of `ls` or `ls -l` output
+fn ls_entry_name(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with("total ") || line.ends_with(':') {
+        return None;
+    }
+    // Long format: "-rw-r--r--  1 me staff  120 Jan 3 10:00 notes.txt"
+    let long = line.starts_with(['-', 'd', 'l']) && line.split_whitespace().count() >= 9;
+    let name = if long {
+        let mut rest = line;
+        for _ in 0..8 {
+            rest = rest.trim_start();
+            rest = &rest[rest.find(char::is_whitespace)?..];
+        }
+        let name = rest.trim_start();
+        // Symlinks: "link -> target"
+        name.split(" -> ").next().unwrap_or(name)
+    } else {
+        line.trim()
+    };
+    (name != "." && name != "..").then_some(name)
+}
+
+/// Whether `path` (absolute, `~` or relative to the current directory)
+/// is inside one of `allowed_dirs`. Any path is when none are configured.
+pub(crate) fn path_allowed(path: &str, allowed_dirs: &[String]) -> bool {
+    let path = normalize_path(path);
+    allowed_dirs.is_empty() || allowed_dirs.iter().any(|dir| path.starts_with(normalize_path(dir)))
+}
+
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().map(|h| h.join(rest)).unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Shell a command is run by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// POSIX `sh -c`
+    Sh,
+    /// Windows `cmd /C`
+    Cmd,
+}
+
+impl Shell {
+    /// The shell `execute_command` uses on this OS
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Shell::Cmd
+        } else {
+            Shell::Sh
+        }
+    }
+
+    fn program(self) -> (&'static str, &'static str) {
+        match self {
+            Shell::Sh => ("sh", "-c"),
+            Shell::Cmd => ("cmd", "/C"),
+        }
+    }
+}
+
+/// The command with environment variables and `~` substituted, as the
+/// shell would see it. Nothing is run: `$(...)` and backticks can't be
+/// previewed without running them, so they are left as written.
+pub fn preview_expansion(cmd: &str, shell: Shell) -> Result<String> {
+    expand_with(cmd, shell, |name| std::env::var(name).ok())
+}
+
+fn expand_with(cmd: &str, shell: Shell, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    match shell {
+        Shell::Sh => expand_sh(cmd, &lookup),
+        Shell::Cmd => Ok(expand_cmd(cmd, &lookup)),
+    }
+}
+
+fn is_var_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn expand_sh(cmd: &str, lookup: &impl Fn(&str) -> Option<String>) -> Result<String> {
+    let chars: Vec<char> = cmd.chars().collect();
+    let mut out = String::with_capacity(cmd.len());
+    let mut single = false;
+    let mut double = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\'' if !double => {
+                single = !single;
+                out.push(c);
+            }
+            _ if single => out.push(c),
+            '"' => {
+                double = !double;
+                out.push(c);
+            }
+            '\\' => {
+                out.push(c);
+                if let Some(next) = chars.get(i + 1) {
+                    out.push(*next);
+                    i += 1;
                 }
-                combined.push_str(&stderr);
             }
-            
-            // Truncate to reasonable size
-            if combined.len() > 10000 {
-                combined = format!("{}...\n[Output truncated, {} bytes total]", 
-                    &combined[..10000], combined.len());
+            // `~` starting an unquoted word
+            '~' if !double
+                && (i == 0 || chars[i - 1].is_whitespace() || chars[i - 1] == '=')
+                && chars.get(i + 1).is_none_or(|n| *n == '/' || n.is_whitespace()) =>
+            {
+                match lookup("HOME") {
+                    Some(home) => out.push_str(&home),
+                    None => out.push(c),
+                }
             }
-            
-            // Generate user-friendly summary
-            let summary = generate_summary(cmd, &stdout, &stderr, success, duration_ms);
-            
-            // Check if command failed due to permission denied
-            let needed_sudo = stderr.contains("Permission denied") 
-                || stderr.contains("Operation not permitted")
-                || stderr.contains("password");
-            
-            Ok(CommandResult {
-                command: cmd.to_string(),
-                exit_code,
-                stdout,
-                stderr,
-                output: combined,
-                duration_ms,
-                success,
-                summary,
-                needed_sudo,
-            })
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                let Some(len) = chars[i + 2..].iter().position(|c| *c == '}') else {
+                    anyhow::bail!("Unterminated ${{ in command");
+                };
+                let name: String = chars[i + 2..i + 2 + len].iter().collect();
+                if !name.is_empty() && name.chars().all(is_var_char) {
+                    out.push_str(&lookup(&name).unwrap_or_default());
+                } else {
+                    // ${VAR:-default} and friends are left as written
+                    out.extend(&chars[i..i + 3 + len]);
+                }
+                i += len + 2;
+            }
+            '$' if chars.get(i + 1).is_some_and(|n| n.is_ascii_alphabetic() || *n == '_') => {
+                let len = chars[i + 1..].iter().take_while(|c| is_var_char(**c)).count();
+                let name: String = chars[i + 1..i + 1 + len].iter().collect();
+                out.push_str(&lookup(&name).unwrap_or_default());
+                i += len;
+            }
+            c => out.push(c),
         }
-        Ok(Err(e)) => {
-            Ok(CommandResult {
-                command: cmd.to_string(),
-                exit_code: -1,
-                stdout: String::new(),
-                stderr: e.to_string(),
-                output: format!("Failed to execute: {}", e),
-                duration_ms,
-                success: false,
-                summary: format!("Command failed: {}", e),
-                needed_sudo: false,
-            })
+        i += 1;
+    }
+
+    if single || double {
+        anyhow::bail!("Unterminated quote in command");
+    }
+    Ok(out)
+}
+
+/// `%NAME%` is replaced when NAME is set; otherwise cmd keeps it as is
+fn expand_cmd(cmd: &str, lookup: &impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(cmd.len());
+    let mut rest = cmd;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(end) if after[..end].chars().all(is_var_char) && end > 0 => {
+                match lookup(&after[..end]) {
+                    Some(value) => out.push_str(&value),
+                    None => out.push_str(&rest[start..start + end + 2]),
+                }
+                rest = &after[end + 1..];
+            }
+            _ => {
+                out.push('%');
+                rest = after;
+            }
         }
-        Err(_) => {
-            Ok(CommandResult {
-                command: cmd.to_string(),
-                exit_code: -1,
-                stdout: String::new(),
-                stderr: "Command timed out".to_string(),
-                output: format!("Command timed out after {} seconds", timeout_secs),
-                duration_ms,
-                success: false,
-                summary: format!("Timed out after {}s", timeout_secs),
-                needed_sudo: false,
-            })
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Execute a command and return structured result, with the timeout for
+/// its danger level from `timeouts` (see `timeout_for`)
+pub async fn execute_command(cmd: &str, timeouts: &HashMap<String, u64>) -> Result<CommandResult> {
+    let options = ExecutionOptions {
+        timeout_secs: timeout_for(cmd, timeouts),
+        working_dir: std::env::current_dir().ok(),
+        allowed_dirs: user_allowed_dirs(),
+        ..Default::default()
+    };
+    execute_command_with(cmd, &options).await
+}
+
+/// Execute a command in `cwd`
+pub async fn execute_command_in(cmd: &str, cwd: &Path, timeout_secs: u64) -> Result<CommandResult> {
+    let options = ExecutionOptions {
+        timeout_secs,
+        working_dir: Some(cwd.to_path_buf()),
+        allowed_dirs: user_allowed_dirs(),
+        ..Default::default()
+    };
+    execute_command_with(cmd, &options).await
+}
+
+/// Execute a command with explicit options
+pub async fn execute_command_with(cmd: &str, options: &ExecutionOptions) -> Result<CommandResult> {
+    let start = Instant::now();
+    let mut chunks = match execute_command_streaming_with(cmd, options).await {
+        Ok(chunks) => std::pin::pin!(chunks),
+        Err(e) => return Ok(failed_result(cmd, &e, start.elapsed().as_millis() as u64)),
+    };
+    while let Some(chunk) = chunks.next().await {
+        if let CommandOutputChunk::Done(result) = chunk {
+            return Ok(*result);
+        }
+    }
+    Ok(failed_result(cmd, &"the command stopped without a result", start.elapsed().as_millis() as u64))
+}
+
+/// Output of a running command, as it arrives
+#[derive(Debug, Clone)]
+pub enum CommandOutputChunk {
+    /// One or more whole lines of standard output
+    Stdout(String),
+    /// One or more whole lines of standard error
+    Stderr(String),
+    /// The command finished; always the last chunk
+    Done(Box<CommandResult>),
+}
+
+/// Longest output is held back before it's sent on, so a burst of output
+/// arrives as a few chunks rather than one per line
+const CHUNK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run a command, yielding its output while it runs and then its result
+pub async fn execute_command_streaming(
+    cmd: &str,
+    timeout_secs: u64,
+) -> Result<impl Stream<Item = CommandOutputChunk>> {
+    let options = ExecutionOptions {
+        timeout_secs,
+        allowed_dirs: user_allowed_dirs(),
+        ..Default::default()
+    };
+    execute_command_streaming_with(cmd, &options).await
+}
+
+/// Run a command with explicit options, yielding its output while it runs.
+/// Fails only if the process can't be started.
+pub async fn execute_command_streaming_with(
+    cmd: &str,
+    options: &ExecutionOptions,
+) -> Result<impl Stream<Item = CommandOutputChunk>> {
+    let (tx, rx) = tokio::sync::mpsc::channel(64);
+    let chunks = futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) });
+
+    let danger = classify_for_run(cmd);
+    if danger == DangerLevel::Blocked {
+        let result = blocked_result(cmd);
+        audit(options, danger, &result);
+        let _ = tx.send(CommandOutputChunk::Done(Box::new(result))).await;
+        return Ok(chunks);
+    }
+    let outside = paths_outside_allowed(cmd, &options.allowed_dirs);
+    if !outside.is_empty() {
+        let result = outside_allowed_result(cmd, &outside, &options.allowed_dirs);
+        audit(options, danger, &result);
+        let _ = tx.send(CommandOutputChunk::Done(Box::new(result))).await;
+        return Ok(chunks);
+    }
+
+    let start = Instant::now();
+    let (shell, shell_arg) = Shell::current().program();
+    let mut command = Command::new(shell);
+    command
+        .arg(shell_arg)
+        .arg(cmd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(dir) = &options.working_dir {
+        command.current_dir(dir);
+    }
+    let working_dir = options.working_dir.clone().or_else(|| std::env::current_dir().ok());
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            audit(options, danger, &failed_result(cmd, &e, start.elapsed().as_millis() as u64));
+            return Err(e.into());
+        }
+    };
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let cmd = cmd.to_string();
+    let options = options.clone();
+    tokio::spawn(async move {
+        let run = async {
+            let (stdout, stderr) = tokio::join!(
+                forward_output(stdout, &tx, CommandOutputChunk::Stdout, options.max_output_bytes),
+                forward_output(stderr, &tx, CommandOutputChunk::Stderr, options.max_output_bytes),
+            );
+            (stdout, stderr, child.wait().await)
+        };
+        // Dropping `run` kills the process: on timeout, or when nobody is
+        // reading the output any more
+        let result = tokio::select! {
+            result = tokio::time::timeout(Duration::from_secs(options.timeout_secs), run) => result,
+            _ = tx.closed() => {
+                let duration_ms = start.elapsed().as_millis() as u64;
+                audit(&options, danger, &failed_result(&cmd, &"cancelled", duration_ms));
+                return;
+            }
+        };
+        let duration_ms = start.elapsed().as_millis() as u64;
+        let mut result = match result {
+            Ok((stdout, stderr, Ok(status))) => finished_result(&cmd, &options, stdout, stderr, status, duration_ms),
+            Ok((_, _, Err(e))) => failed_result(&cmd, &e, duration_ms),
+            Err(_) => timed_out_result(&cmd, options.timeout_secs, duration_ms),
+        };
+        result.working_dir = working_dir;
+        audit(&options, danger, &result);
+        let _ = tx.send(CommandOutputChunk::Done(Box::new(result))).await;
+    });
+    Ok(chunks)
+}
+
+/// Add `result` to the audit log, if `options` has one
+fn audit(options: &ExecutionOptions, danger: DangerLevel, result: &CommandResult) {
+    if let Some(path) = &options.audit_log {
+        crate::audit::record(path, danger, result, &[]);
+    }
+}
+
+/// Send what `reader` produces as `chunk`s of whole lines, at most one per
+/// `CHUNK_INTERVAL`. Returns the first `max_bytes` of it and its full size.
+/// Reading stops once nobody is receiving the chunks.
+async fn forward_output<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    tx: &tokio::sync::mpsc::Sender<CommandOutputChunk>,
+    chunk: fn(String) -> CommandOutputChunk,
+    max_bytes: usize,
+) -> Captured {
+    let Some(mut reader) = reader else { return Captured::default() };
+    let mut all = Vec::new();
+    let mut total_bytes = 0;
+    let mut pending = Vec::new();
+    let mut buf = [0u8; 8192];
+    let mut tick = tokio::time::interval(CHUNK_INTERVAL);
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    total_bytes += n;
+                    let keep = n.min(max_bytes.saturating_sub(all.len()));
+                    all.extend_from_slice(&buf[..keep]);
+                    pending.extend_from_slice(&buf[..n]);
+                }
+            },
+            _ = tick.tick() => {
+                // Progress bars redraw their line with \r
+                if let Some(end) = pending.iter().rposition(|b| matches!(b, b'\n' | b'\r')) {
+                    let lines: Vec<u8> = pending.drain(..=end).collect();
+                    if tx.send(chunk(String::from_utf8_lossy(&lines).into_owned())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    if !pending.is_empty() {
+        let _ = tx.send(chunk(String::from_utf8_lossy(&pending).into_owned())).await;
+    }
+    Captured { text: String::from_utf8_lossy(&all).into_owned(), total_bytes }
+}
+
+/// Output kept from one stream of a command
+#[derive(Default)]
+struct Captured {
+    text: String,
+    /// Size of all of the output, kept or not
+    total_bytes: usize,
+}
+
+fn blocked_result(cmd: &str) -> CommandResult {
+    CommandResult {
+        command: cmd.to_string(),
+        exit_code: -1,
+        stdout: String::new(),
+        stderr: "This command is blocked for safety reasons.".to_string(),
+        output: "This command is blocked for safety reasons.".to_string(),
+        duration_ms: 0,
+        success: false,
+        summary: "Command blocked for safety".to_string(),
+        needed_sudo: false,
+        truncated_at_bytes: None,
+        structured: None,
+        extracted_paths: Vec::new(),
+        working_dir: None,
+    }
+}
+
+fn outside_allowed_result(cmd: &str, outside: &[PathBuf], allowed_dirs: &[String]) -> CommandResult {
+    let paths: Vec<String> = outside.iter().map(|p| p.display().to_string()).collect();
+    let message = format!(
+        "Not run: {} {} outside the allowed folders ({}).",
+        paths.join(", "),
+        if paths.len() == 1 { "is" } else { "are" },
+        allowed_dirs.join(", ")
+    );
+    CommandResult {
+        command: cmd.to_string(),
+        exit_code: -1,
+        stdout: String::new(),
+        stderr: message.clone(),
+        output: message,
+        duration_ms: 0,
+        success: false,
+        summary: "Command uses a path outside the allowed folders".to_string(),
+        needed_sudo: false,
+        truncated_at_bytes: None,
+        structured: None,
+        extracted_paths: Vec::new(),
+        working_dir: None,
+    }
+}
+
+fn failed_result(cmd: &str, e: &dyn std::fmt::Display, duration_ms: u64) -> CommandResult {
+    CommandResult {
+        command: cmd.to_string(),
+        exit_code: -1,
+        stdout: String::new(),
+        stderr: e.to_string(),
+        output: format!("Failed to execute: {}", e),
+        duration_ms,
+        success: false,
+        summary: format!("Command failed: {}", e),
+        needed_sudo: false,
+        truncated_at_bytes: None,
+        structured: None,
+        extracted_paths: Vec::new(),
+        working_dir: None,
+    }
+}
+
+fn timed_out_result(cmd: &str, timeout_secs: u64, duration_ms: u64) -> CommandResult {
+    CommandResult {
+        command: cmd.to_string(),
+        exit_code: -1,
+        stdout: String::new(),
+        stderr: "Command timed out".to_string(),
+        output: format!("Command timed out after {} seconds", timeout_secs),
+        duration_ms,
+        success: false,
+        summary: format!("Timed out after {}s", timeout_secs),
+        needed_sudo: false,
+        truncated_at_bytes: None,
+        structured: None,
+        extracted_paths: Vec::new(),
+        working_dir: None,
+    }
+}
+
+fn finished_result(
+    cmd: &str,
+    options: &ExecutionOptions,
+    stdout: Captured,
+    stderr: Captured,
+    status: std::process::ExitStatus,
+    duration_ms: u64,
+) -> CommandResult {
+    let dropped_bytes = (stdout.total_bytes - stdout.text.len()) + (stderr.total_bytes - stderr.text.len());
+    let (stdout, stderr) = (stdout.text, stderr.text);
+    let exit_code = status.code().unwrap_or(-1);
+    let success = status.success();
+
+    // Combine output without colour codes, truncate if too long. `stdout`
+    // and `stderr` stay as the command wrote them.
+    let clean_stdout = strip_ansi(&stdout);
+    let clean_stderr = strip_ansi(&stderr);
+    let mut combined = clean_stdout.clone();
+    if !clean_stderr.is_empty() {
+        if !combined.is_empty() {
+            combined.push('\n');
         }
+        combined.push_str(&clean_stderr);
+    }
+
+    // Truncate to reasonable size
+    let total_bytes = combined.len() + dropped_bytes;
+    let (combined, truncated_at_bytes) = truncate_output_of(combined, options.max_output_bytes, total_bytes);
+
+    // Generate user-friendly summary
+    let summary = generate_summary(cmd, &clean_stdout, &clean_stderr, success, duration_ms);
+
+    let structured = match parse_structured_output(&clean_stdout) {
+        OutputFormat::Csv(table) => Some(table),
+        OutputFormat::Plain => None,
+    };
+    let extracted_paths = extract_output_paths(cmd, &combined, options.working_dir.as_deref());
+
+    // Check if command failed due to permission denied
+    let needed_sudo = stderr.contains("Permission denied")
+        || stderr.contains("Operation not permitted")
+        || stderr.contains("password");
+
+    CommandResult {
+        command: cmd.to_string(),
+        exit_code,
+        stdout,
+        stderr,
+        output: combined,
+        duration_ms,
+        success,
+        summary,
+        needed_sudo,
+        truncated_at_bytes,
+        structured,
+        extracted_paths,
+        working_dir: None,
     }
 }
 
+/// Longest `man -k` and `--help` lookups for `man_page_summary` may take
+const HELP_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Lines of `--help` output shown
+const HELP_LINES: usize = 5;
+
+/// What an unfamiliar command is, for the confirmation dialog: its `man -k`
+/// description and the start of its `--help` output. `None` for built-in
+/// safe commands and when neither lookup finds anything. `--help` is only
+/// run for plain command names found in `PATH` that aren't dangerous.
+pub fn man_page_summary(cmd: &str) -> Result<Option<String>> {
+    let Some(base) = cmd.split_whitespace().next() else { return Ok(None) };
+    let plain_name = base.chars().all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c));
+    if !plain_name || SAFE_COMMANDS.contains(&base) {
+        return Ok(None);
+    }
+
+    let description = run_for_help("man", &["-k", base], false).and_then(|out| {
+        let lines: Vec<&str> = out.lines().collect();
+        let line = lines
+            .iter()
+            .find(|l| l.starts_with(&format!("{} ", base)))
+            .or(lines.first())?;
+        let description = line.split_once(" - ").map_or(*line, |(_, d)| d).trim();
+        (!description.is_empty()).then(|| description.to_string())
+    });
+    let in_path = which_in_path(base);
+    let help = (in_path && matches!(classify_command(base), DangerLevel::Safe | DangerLevel::NeedsConfirmation))
+        .then(|| run_for_help(base, &["--help"], true))
+        .flatten()
+        .map(|out| out.lines().take(HELP_LINES).collect::<Vec<_>>().join("\n"))
+        .filter(|h| !h.trim().is_empty());
+
+    let mut summary = match &description {
+        Some(description) => format!("This command (`{}`) is: {}.", base, description.trim_end_matches('.')),
+        None if help.is_some() => format!("This command is `{}`.", base),
+        None => return Ok(None),
+    };
+    if let Some(help) = help {
+        summary.push_str(&format!("\nFirst {} lines of --help:\n{}", HELP_LINES, help));
+    }
+    Ok(Some(summary))
+}
+
+fn which_in_path(name: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(name).is_file()))
+}
+
+/// Output of a short lookup, or `None` if it prints nothing or runs past
+/// `HELP_LOOKUP_TIMEOUT`. Plenty of programs print `--help` to stderr and
+/// exit non-zero, so with `lenient` both streams count whatever the exit
+/// status; otherwise only stdout of a successful run does.
+fn run_for_help(program: &str, args: &[&str], lenient: bool) -> Option<String> {
+    use std::io::Read;
+
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    // Read in the background so a chatty command can't fill the pipe and stall
+    let mut stdout = child.stdout.take()?;
+    let mut stderr = child.stderr.take()?;
+    let reader = std::thread::spawn(move || {
+        let mut out = String::new();
+        let _ = stdout.read_to_string(&mut out);
+        let mut err = String::new();
+        let _ = stderr.read_to_string(&mut err);
+        if lenient {
+            out.push_str(&err);
+        }
+        out
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() < HELP_LOOKUP_TIMEOUT => std::thread::sleep(Duration::from_millis(50)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+    let out = reader.join().ok()?;
+    (!out.trim().is_empty() && (lenient || status.success())).then_some(out)
+}
+
+/// Commands `generate_summary` has a specific summary for
+const SIMPLE_SUMMARY_COMMANDS: &[&str] = &[
+    "ls", "find", "tree", "grep", "rg", "ag", "cat", "head", "tail", "cp", "mv", "mkdir", "rm", "rmdir", "git", "cargo",
+];
+
+/// Whether `generate_summary` says something specific about `cmd`. Other
+/// commands only get "Complete (45ms)".
+pub fn has_simple_summary(cmd: &str) -> bool {
+    let cmd_base = cmd.split_whitespace().next().unwrap_or(cmd);
+    SIMPLE_SUMMARY_COMMANDS.contains(&cmd_base)
+}
+
 /// Generate a user-friendly summary of command execution
 fn generate_summary(cmd: &str, stdout: &str, stderr: &str, success: bool, duration_ms: u64) -> String {
     let cmd_base = cmd.split_whitespace().next().unwrap_or(cmd);
@@ -368,6 +1374,29 @@ fn generate_summary(cmd: &str, stdout: &str, stderr: &str, success: bool, durati
     }
 }
 
+/// Progress of a running command from its streamed output. A chunk may
+/// end partway through a line, so the unfinished line is kept and read
+/// again with the next chunk.
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+    unfinished: String,
+    last: Option<u8>,
+}
+
+impl ProgressParser {
+    /// The latest percentage in the output so far
+    pub fn feed(&mut self, chunk: &str) -> Option<u8> {
+        self.unfinished.push_str(chunk);
+        if let Some(percent) = parse_progress(&self.unfinished) {
+            self.last = Some(percent);
+        }
+        if let Some(end) = self.unfinished.rfind(['\n', '\r']) {
+            self.unfinished.drain(..=end);
+        }
+        self.last
+    }
+}
+
 /// Parse progress from command output (for long-running commands)
 pub fn parse_progress(output: &str) -> Option<u8> {
     // Look for percentage patterns
@@ -388,12 +1417,41 @@ pub fn parse_progress(output: &str) -> Option<u8> {
     last_percent
 }
 
-/// Execute a command with sudo, providing password via stdin
+/// Execute a command with sudo, providing password via stdin, and record
+/// it in `audit_log` if given
 /// 
 /// SECURITY: Password is never stored or logged. It's passed directly to sudo via stdin
-/// and cleared from memory after use.
+/// and cleared from memory after use; it's masked if the command itself contains it.
+#[cfg(not(windows))]
+pub async fn execute_with_sudo(
+    cmd: &str,
+    password: &str,
+    timeout_secs: u64,
+    audit_log: Option<&Path>,
+) -> Result<CommandResult> {
+    let start = Instant::now();
+    let danger = classify_for_run(cmd);
+    let allowed_dirs = user_allowed_dirs();
+    let outside = paths_outside_allowed(cmd, &allowed_dirs);
+    let result = match danger {
+        DangerLevel::Blocked => Ok(blocked_result(cmd)),
+        _ if !outside.is_empty() => Ok(outside_allowed_result(cmd, &outside, &allowed_dirs)),
+        _ => run_with_sudo(cmd, password, timeout_secs).await,
+    };
+    if let Some(path) = audit_log {
+        match &result {
+            Ok(result) => crate::audit::record(path, danger, result, &[password]),
+            Err(e) => {
+                let failed = failed_result(cmd, e, start.elapsed().as_millis() as u64);
+                crate::audit::record(path, danger, &failed, &[password]);
+            }
+        }
+    }
+    result
+}
+
 #[cfg(not(windows))]
-pub async fn execute_with_sudo(cmd: &str, password: &str, timeout_secs: u64) -> Result<CommandResult> {
+async fn run_with_sudo(cmd: &str, password: &str, timeout_secs: u64) -> Result<CommandResult> {
     use tokio::io::AsyncWriteExt;
     
     let start = Instant::now();
@@ -478,6 +1536,10 @@ pub async fn execute_with_sudo(cmd: &str, password: &str, timeout_secs: u64) ->
                 success: success && !wrong_password,
                 summary,
                 needed_sudo: true,
+                truncated_at_bytes: None,
+                structured: None,
+                extracted_paths: Vec::new(),
+                working_dir: None,
             })
         }
         Ok(Err(e)) => {
@@ -491,6 +1553,10 @@ pub async fn execute_with_sudo(cmd: &str, password: &str, timeout_secs: u64) ->
                 success: false,
                 summary: format!("Command failed: {}", e),
                 needed_sudo: true,
+                truncated_at_bytes: None,
+                structured: None,
+                extracted_paths: Vec::new(),
+                working_dir: None,
             })
         }
         Err(_) => {
@@ -504,15 +1570,40 @@ pub async fn execute_with_sudo(cmd: &str, password: &str, timeout_secs: u64) ->
                 success: false,
                 summary: format!("Timed out after {}s", timeout_secs),
                 needed_sudo: true,
+                truncated_at_bytes: None,
+                structured: None,
+                extracted_paths: Vec::new(),
+                working_dir: None,
             })
         }
     }
 }
 
-/// Windows equivalent - uses runas for elevation
+/// Windows equivalent - uses runas for elevation, and records the command
+/// in `audit_log` if given
 /// Note: Windows UAC will show a system prompt, we can't programmatically provide credentials
 #[cfg(windows)]
-pub async fn execute_with_elevation(cmd: &str, timeout_secs: u64) -> Result<CommandResult> {
+pub async fn execute_with_elevation(cmd: &str, timeout_secs: u64, audit_log: Option<&Path>) -> Result<CommandResult> {
+    let start = Instant::now();
+    let danger = classify_for_run(cmd);
+    let allowed_dirs = user_allowed_dirs();
+    let outside = paths_outside_allowed(cmd, &allowed_dirs);
+    let result = match danger {
+        DangerLevel::Blocked => Ok(blocked_result(cmd)),
+        _ if !outside.is_empty() => Ok(outside_allowed_result(cmd, &outside, &allowed_dirs)),
+        _ => run_with_elevation(cmd, timeout_secs).await,
+    };
+    if let Some(path) = audit_log {
+        match &result {
+            Ok(result) => crate::audit::record(path, danger, result, &[]),
+            Err(e) => crate::audit::record(path, danger, &failed_result(cmd, e, start.elapsed().as_millis() as u64), &[]),
+        }
+    }
+    result
+}
+
+#[cfg(windows)]
+async fn run_with_elevation(cmd: &str, timeout_secs: u64) -> Result<CommandResult> {
     let start = Instant::now();
     
     // On Windows, we use PowerShell's Start-Process with -Verb RunAs
@@ -559,6 +1650,10 @@ pub async fn execute_with_elevation(cmd: &str, timeout_secs: u64) -> Result<Comm
                     "Failed or was cancelled".to_string()
                 },
                 needed_sudo: true,
+                truncated_at_bytes: None,
+                structured: None,
+                extracted_paths: Vec::new(),
+                working_dir: None,
             })
         }
         Ok(Err(e)) => {
@@ -572,6 +1667,10 @@ pub async fn execute_with_elevation(cmd: &str, timeout_secs: u64) -> Result<Comm
                 success: false,
                 summary: "Failed to request admin privileges".to_string(),
                 needed_sudo: true,
+                truncated_at_bytes: None,
+                structured: None,
+                extracted_paths: Vec::new(),
+                working_dir: None,
             })
         }
         Err(_) => {
@@ -585,6 +1684,10 @@ pub async fn execute_with_elevation(cmd: &str, timeout_secs: u64) -> Result<Comm
                 success: false,
                 summary: "Timed out or cancelled".to_string(),
                 needed_sudo: true,
+                truncated_at_bytes: None,
+                structured: None,
+                extracted_paths: Vec::new(),
+                working_dir: None,
             })
         }
     }
@@ -636,6 +1739,10 @@ pub async fn web_search(query: &str) -> Result<CommandResult> {
             success: false,
             summary: "Search failed".to_string(),
             needed_sudo: false,
+            truncated_at_bytes: None,
+            structured: None,
+            extracted_paths: Vec::new(),
+            working_dir: None,
         });
     }
     
@@ -665,6 +1772,10 @@ pub async fn web_search(query: &str) -> Result<CommandResult> {
         success: true,
         summary: format!("Found {} results ({}ms)", result_count, duration_ms),
         needed_sudo: false,
+        truncated_at_bytes: None,
+        structured: None,
+        extracted_paths: Vec::new(),
+        working_dir: None,
     })
 }
 
@@ -726,6 +1837,133 @@ fn html_decode(s: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn test_has_simple_summary() {
+        assert!(has_simple_summary("grep -r TODO ."));
+        assert!(!has_simple_summary("wc -l notes.txt"));
+    }
+
+    #[test]
+    fn test_man_page_summary_skips_known_and_path_commands() {
+        assert_eq!(man_page_summary("ls -la").unwrap(), None);
+        assert_eq!(man_page_summary("./install.sh --force").unwrap(), None);
+        assert_eq!(man_page_summary("").unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        let (short, truncated) = truncate_output("hello".to_string(), 10);
+        assert_eq!(short, "hello");
+        assert_eq!(truncated, None);
+
+        // Never splits a multi-byte character
+        let (long, truncated) = truncate_output("ééééé".to_string(), 3);
+        assert!(long.starts_with("é..."));
+        assert_eq!(truncated, Some(3));
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;32m   Compiling\x1b[0m app"), "   Compiling app");
+        assert_eq!(strip_ansi("50%\x1b[2K\r\x1b[?25h"), "50%\r");
+        assert_eq!(strip_ansi("\x1b]8;;file:///tmp/a.txt\x1b\\a.txt\x1b]8;;\x1b\\"), "a.txt");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn test_restrict_find_outside_allowed_dirs() {
+        let allowed = vec!["/home/me/Documents".to_string()];
+        let rewritten = restrict_find_command("find / -name '*.pdf' | head -20", &allowed).unwrap();
+        assert_eq!(
+            rewritten,
+            "find / \\( -path '/home/me/Documents' -o -path '/home/me/Documents/*' \\) \\( -name '*.pdf' \\) | head -20"
+        );
+    }
+
+    #[test]
+    fn test_dirs_outside_allowed() {
+        let allowed = vec!["/home/me/Documents".to_string()];
+        assert_eq!(
+            dirs_outside_allowed("cat /etc/hosts /home/me/Documents/notes.txt", &allowed),
+            vec![PathBuf::from("/etc")]
+        );
+        assert!(dirs_outside_allowed("cat /etc/hosts", &[]).is_empty());
+        assert!(dirs_outside_allowed("ls /", &allowed).is_empty());
+
+        // A find limited to the allowed folders may start anywhere
+        let restricted = restrict_find_command("find / -name '*.key' 2> /dev/null", &allowed).unwrap();
+        assert!(paths_outside_allowed(&restricted, &allowed).is_empty());
+        assert_eq!(paths_outside_allowed("find / -name '*.key'", &allowed), vec![PathBuf::from("/")]);
+        let escaping = restrict_find_command("find / -name x -exec cat /etc/shadow ;", &allowed).unwrap();
+        assert_eq!(paths_outside_allowed(&escaping, &allowed), vec![PathBuf::from("/etc/shadow")]);
+    }
+
+    #[test]
+    fn test_paths_outside_allowed_bypasses() {
+        let allowed = vec!["/home/me/Documents".to_string()];
+        let outside = |cmd: &str| paths_outside_allowed(cmd, &allowed);
+        let shadow = vec![PathBuf::from("/etc/shadow")];
+        assert_eq!(outside("cat </etc/shadow"), shadow);
+        assert_eq!(outside("echo x >/etc/shadow"), shadow);
+        assert_eq!(outside("echo x 2>>/etc/shadow"), shadow);
+        assert_eq!(outside("dd if=/etc/shadow of=/home/me/Documents/x"), shadow);
+        assert_eq!(outside("tool --file=/etc/shadow"), shadow);
+        assert_eq!(outside("cat \"/etc/shadow\""), shadow);
+        assert_eq!(outside("cat '/etc/shadow'"), shadow);
+        assert_eq!(outside("cat $(echo /etc/shadow)"), shadow);
+        let home = normalize_path("~/.ssh/id_rsa");
+        assert_eq!(outside("cat $HOME/.ssh/id_rsa"), vec![home.clone()]);
+        assert_eq!(outside("cat ${HOME}/.ssh/id_rsa"), vec![home]);
+        assert!(outside("cat /home/me/Documents/a.txt sed 's/a/b/' 2>/dev/null").is_empty());
+        assert!(outside("curl https://example.com/x").is_empty());
+
+        // Only the exact restricted shape of a find is trusted
+        let constraints = find_constraints(&[PathBuf::from("/home/me/Documents")]);
+        let hidden = format!("find /etc -exec cat /etc/shadow \\; \\( {} \\)", constraints);
+        assert!(outside(&hidden).contains(&PathBuf::from("/etc/shadow")));
+        let alternative = format!("find /etc \\( {} \\) -o -print", constraints);
+        assert_eq!(outside(&alternative), vec![PathBuf::from("/etc")]);
+        let second_group = format!("find /etc \\( {} \\) \\( -name x \\) -o \\( -print \\)", constraints);
+        assert_eq!(outside(&second_group), vec![PathBuf::from("/etc")]);
+        let restricted = restrict_find_command("find /etc \\( -name a -o -name b \\) | head", &allowed).unwrap();
+        assert!(outside(&restricted).is_empty());
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_execute_outside_allowed_dirs() {
+        let options = ExecutionOptions { allowed_dirs: vec!["/home/user/projects".to_string()], ..Default::default() };
+        let result = execute_command_with("cat /etc/shadow", &options).await.unwrap();
+        assert_eq!(result.exit_code, -1);
+        assert!(!result.success);
+        assert!(result.output.contains("/etc/shadow is outside the allowed folders"));
+        assert!(execute_command_with("echo hi > /dev/null", &options).await.unwrap().success);
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_streaming_caps_output_and_stops_when_dropped() {
+        let options = ExecutionOptions { max_output_bytes: 1000, ..Default::default() };
+        let result = execute_command_with("head -c 100000 /dev/zero | tr '\\0' a", &options).await.unwrap();
+        assert_eq!(result.truncated_at_bytes, Some(1000));
+        assert!(result.stdout.len() <= 1000);
+        assert!(result.output.ends_with("[Output truncated at 1000 bytes, 100000 bytes total]"));
+
+        let marker = std::env::temp_dir().join(format!("lh-cancelled-{}", std::process::id()));
+        let cmd = format!("sleep 1 && touch {}", marker.display());
+        drop(execute_command_streaming_with(&cmd, &ExecutionOptions::default()).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn test_restrict_find_inside_allowed_dirs() {
+        let allowed = vec!["/home/me/Documents".to_string()];
+        assert_eq!(restrict_find_command("find /home/me/Documents/work -name x", &allowed), None);
+        assert_eq!(restrict_find_command("ls /", &allowed), None);
+        assert_eq!(restrict_find_command("find / -name x", &[]), None);
+    }
     
     #[test]
     fn test_classify_safe() {
@@ -745,6 +1983,63 @@ mod tests {
         assert_eq!(classify_command("rm -rf /"), DangerLevel::Blocked);
     }
     
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_user_blocked_commands_never_run() {
+        // Both restrictions in one test, since they're process-wide
+        let settings = AppSettings {
+            extra_blocked_commands: vec!["lh-blocked-tool".to_string()],
+            allowed_dirs: vec!["/home/user/projects".to_string()],
+            ..Default::default()
+        };
+        set_command_restrictions(&settings);
+        let result = execute_command("echo hi && lh-blocked-tool --now", &HashMap::new()).await.unwrap();
+        assert_eq!(result.summary, "Command blocked for safety");
+        assert!(!result.stdout.contains("hi"));
+        assert!(execute_command("echo hi", &HashMap::new()).await.unwrap().success);
+
+        let result = execute_command("cat /etc/shadow", &HashMap::new()).await.unwrap();
+        assert_eq!(result.exit_code, -1);
+        assert!(result.output.contains("/etc/shadow is outside the allowed folders"));
+        let result = execute_with_sudo("cat /etc/shadow", "", 10, None).await.unwrap();
+        assert!(result.output.contains("/etc/shadow is outside the allowed folders"));
+        set_command_restrictions(&AppSettings::default());
+    }
+
+    #[test]
+    fn test_classify_with_user_rules() {
+        let rules = CommandRules {
+            safe: vec!["fdfind".to_string()],
+            needs_confirmation: vec!["curl".to_string()],
+            blocked: vec!["telnet".to_string()],
+        };
+        assert_eq!(classify_command_with("fdfind report", &rules), DangerLevel::Safe);
+        assert_eq!(classify_command_with("fdfindx", &rules), DangerLevel::NeedsConfirmation);
+        assert_eq!(classify_command_with("curl example.com", &rules), DangerLevel::NeedsConfirmation);
+        assert_eq!(classify_command_with("ls | telnet host", &rules), DangerLevel::Blocked);
+        // User rules can't make dangerous commands safe
+        let rules = CommandRules { safe: vec!["rm".to_string()], ..Default::default() };
+        assert_eq!(classify_command_with("rm file.txt", &rules), DangerLevel::Dangerous);
+
+        // Patterns
+        let rules = CommandRules {
+            safe: vec!["~/bin/*".to_string()],
+            blocked: vec!["npm run deploy*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(classify_command_with("~/bin/backup.sh --all", &rules), DangerLevel::Safe);
+        if let Some(home) = dirs::home_dir() {
+            let script = format!("{}/bin/backup.sh", home.display());
+            assert_eq!(classify_command_with(&script, &rules), DangerLevel::Safe);
+        }
+        assert_eq!(classify_command_with("~/scripts/backup.sh", &rules), DangerLevel::NeedsConfirmation);
+        assert_eq!(classify_command_with("npm test && npm run deploy-prod", &rules), DangerLevel::Blocked);
+        assert_eq!(validate_command_prefix(" ~/bin/*  --dry-run").as_deref(), Ok("~/bin/* --dry-run"));
+        assert!(validate_command_prefix("*").is_err());
+        assert!(validate_command_prefix("ls ~").is_err());
+        assert!(validate_command_prefix("rg; rm").is_err());
+    }
+    
     #[test]
     fn test_classify_sudo() {
         assert_eq!(classify_command("sudo apt update"), DangerLevel::NeedsSudo);
@@ -755,5 +2050,71 @@ mod tests {
         assert_eq!(parse_progress("Downloading... 50%"), Some(50));
         assert_eq!(parse_progress("Progress: 100%"), Some(100));
         assert_eq!(parse_progress("No progress here"), None);
+
+        let mut progress = ProgressParser::default();
+        assert_eq!(progress.feed("Downloading... 1"), None);
+        assert_eq!(progress.feed("0%\rDownloading... 4"), Some(10));
+        assert_eq!(progress.feed("5%\n"), Some(45));
+        assert_eq!(progress.feed("Unpacking\n"), Some(45));
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_execute_command_streaming() {
+        let cmd = "printf 'one\\ntwo\\n'; sleep 0.3; printf '50%%\\n' >&2; printf three";
+        let chunks: Vec<CommandOutputChunk> = execute_command_streaming(cmd, 10).await.unwrap().collect().await;
+        let (first, rest) = chunks.split_first().unwrap();
+        assert!(matches!(first, CommandOutputChunk::Stdout(text) if text == "one\ntwo\n"));
+        assert!(rest.iter().any(|c| matches!(c, CommandOutputChunk::Stderr(text) if text == "50%\n")));
+        let Some(CommandOutputChunk::Done(result)) = chunks.last() else { panic!("no result") };
+        assert_eq!(result.stdout, "one\ntwo\nthree");
+        assert!(result.success);
+
+        let timeouts = HashMap::from([(classify_command("sleep 5").key().to_string(), 1)]);
+        let result = execute_command("sleep 5", &timeouts).await.unwrap();
+        assert_eq!(result.summary, "Timed out after 1s");
+        assert_eq!(result.working_dir, std::env::current_dir().ok());
+
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let result = execute_command_in("pwd", &dir, 10).await.unwrap();
+        assert_eq!(result.stdout.trim(), dir.to_str().unwrap());
+        assert_eq!(result.working_dir.as_deref(), Some(dir.as_path()));
+    }
+
+    #[test]
+    fn test_preview_expansion() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/alice".to_string()),
+            "PROJECT_DIR" => Some("/home/alice/projects".to_string()),
+            _ => None,
+        };
+        let sh = |cmd| expand_with(cmd, Shell::Sh, lookup).unwrap();
+        assert_eq!(sh("ls ${PROJECT_DIR}"), "ls /home/alice/projects");
+        assert_eq!(sh("echo \"$HOME\" '$HOME' ~/x $UNSET."), "echo \"/home/alice\" '$HOME' /home/alice/x .");
+        assert_eq!(sh("echo $(whoami) ${X:-y}"), "echo $(whoami) ${X:-y}");
+        assert!(expand_with("echo 'open", Shell::Sh, lookup).is_err());
+
+        assert_eq!(expand_with("dir %HOME%\\%NOPE% 50%", Shell::Cmd, lookup).unwrap(), "dir /home/alice\\%NOPE% 50%");
+    }
+
+    #[test]
+    fn test_extract_output_paths_from_ls() {
+        let dir = std::env::temp_dir().join(format!("lh-ls-paths-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("my notes.txt"), "hi").unwrap();
+        std::fs::write(dir.join("a.csv"), "x").unwrap();
+
+        let output = "total 8\n\
+            drwxr-xr-x  2 me staff  64 Jan  3 10:00 sub\n\
+            -rw-r--r--  1 me staff   2 Jan  3 10:00 my notes.txt\n\
+            -rw-r--r--  1 me staff   1 Jan  3 10:00 a.csv\n";
+        let cmd = format!("ls -la {}", dir.display());
+        let paths = extract_output_paths(&cmd, output, None);
+        assert_eq!(paths, vec![dir.join("my notes.txt"), dir.join("a.csv")]);
+
+        // Plain listing relative to the working directory
+        assert_eq!(extract_output_paths("ls", "a.csv\nsub\n", Some(&dir)), vec![dir.join("a.csv")]);
+
+        std::fs::remove_dir_all(&dir).ok();
     }
 }
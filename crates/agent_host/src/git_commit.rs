@@ -0,0 +1,125 @@
+//! Offering to commit files the agent edited
+//!
+//! After a turn that wrote files inside a git repository, the agent asks
+//! the AI for a commit message and the app shows it for the user to edit.
+//! Nothing is committed until the user confirms.
+
+use crate::executor::shell_quote;
+use std::path::{Path, PathBuf};
+
+/// Diff text sent to the AI for the commit message
+const DIFF_CHARS: usize = 4000;
+
+/// A commit the user can make of files the agent edited
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitSuggestion {
+    /// Top folder of the repository
+    pub repo: PathBuf,
+    /// Edited files, relative to `repo`
+    pub files: Vec<PathBuf>,
+    pub message: String,
+}
+
+impl CommitSuggestion {
+    /// "I've edited `src/main.rs`. Would you like me to commit ..."
+    pub fn prompt(&self) -> String {
+        let files: Vec<String> = self.files.iter().map(|f| format!("`{}`", f.display())).collect();
+        let change = if self.files.len() == 1 { "this change" } else { "these changes" };
+        format!(
+            "I've edited {}. Would you like me to commit {}? Suggested commit message: '{}'.",
+            files.join(", "),
+            change,
+            self.message
+        )
+    }
+
+    /// The `git add` and `git commit` commands for `message`, run in `repo`.
+    /// Only the edited files are committed, even if others are staged.
+    pub fn commands(&self, message: &str) -> [String; 2] {
+        let files: Vec<String> = self.files.iter().map(|f| shell_quote(&f.to_string_lossy())).collect();
+        let files = files.join(" ");
+        [
+            format!("git add -- {}", files),
+            format!("git commit -m {} -- {}", shell_quote(message.trim()), files),
+        ]
+    }
+}
+
+/// The repository `path` is in: the nearest folder with a `.git` entry
+pub fn find_repo(path: &Path) -> Option<PathBuf> {
+    path.ancestors().skip(1).find(|dir| dir.join(".git").exists()).map(Path::to_path_buf)
+}
+
+/// The first edited file's repository and every edited file inside it
+pub(crate) fn edited_in_repo(edited: &[PathBuf]) -> Option<(PathBuf, Vec<PathBuf>)> {
+    let repo = edited.iter().find_map(|path| find_repo(path))?;
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path in edited {
+        if let Ok(relative) = path.strip_prefix(&repo) {
+            if !files.iter().any(|f| f == relative) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+    Some((repo, files))
+}
+
+/// What changed in `files`, for the AI: their diff against the last commit,
+/// or the start of the file for one git doesn't know yet
+pub(crate) async fn describe_changes(repo: &Path, files: &[PathBuf]) -> String {
+    let mut text = String::new();
+    for file in files {
+        let diff = tokio::process::Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["diff", "HEAD", "--"])
+            .arg(file)
+            .output()
+            .await
+            .map(|out| String::from_utf8_lossy(&out.stdout).into_owned())
+            .unwrap_or_default();
+        if diff.trim().is_empty() {
+            let content = tokio::fs::read_to_string(repo.join(file)).await.unwrap_or_default();
+            text.push_str(&format!("New file {}:\n{}\n", file.display(), content));
+        } else {
+            text.push_str(&diff);
+        }
+    }
+    text.chars().take(DIFF_CHARS).collect()
+}
+
+/// First line of the AI's reply without quotes or a "Commit message:" label
+pub(crate) fn clean_message(reply: &str) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with("```"))?;
+    let line = line.strip_prefix("Commit message:").unwrap_or(line).trim();
+    let message = line.trim_matches(['"', '\'', '`']).trim();
+    (!message.is_empty()).then(|| message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_suggestion() {
+        let dir = std::env::temp_dir().join(format!("lh-git-commit-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let edited = vec![dir.join("src/main.rs"), std::env::temp_dir().join("elsewhere.txt"), dir.join("src/main.rs")];
+        let (repo, files) = edited_in_repo(&edited).unwrap();
+        assert_eq!(repo, dir);
+        assert_eq!(files, vec![PathBuf::from("src/main.rs")]);
+
+        let suggestion = CommitSuggestion { repo, files, message: "Fix off-by-one error".to_string() };
+        assert_eq!(
+            suggestion.prompt(),
+            "I've edited `src/main.rs`. Would you like me to commit this change? Suggested commit message: 'Fix off-by-one error'."
+        );
+        assert_eq!(
+            suggestion.commands("Don't panic"),
+            ["git add -- 'src/main.rs'".to_string(), "git commit -m 'Don'\\''t panic' -- 'src/main.rs'".to_string()]
+        );
+        assert_eq!(clean_message("Commit message: \"Add tests\"\n\nMore text").as_deref(), Some("Add tests"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,186 @@
+//! Session budget tracking for the agent
+//!
+//! Keeps a running tally of estimated API cost and commands executed, and
+//! the time spent on the current request, and checks them against the
+//! user's `AgentGuardrails` before the agent is allowed to call a model or
+//! run another command.
+
+use providers::models::{find_model, known_models};
+use shared::agent_api::{token_count, ChatMessage};
+use shared::settings::AgentGuardrails;
+use std::time::{Duration, Instant};
+
+/// USD prices per 1K tokens (input, output) from the known model list.
+/// Unknown models are priced like the provider's first listed model.
+/// Only used for budget estimates, not billing.
+fn price_per_1k(provider: &str, model: &str) -> (f64, f64) {
+    find_model(provider, model)
+        .or_else(|| known_models(provider).into_iter().next())
+        .map(|m| (m.cost_per_1k_input_tokens, m.cost_per_1k_output_tokens))
+        .unwrap_or((0.0, 0.0))
+}
+
+/// Estimated cost of one model call
+pub fn estimate_cost(provider: &str, model: &str, input_tokens: usize, output_tokens: usize) -> f64 {
+    let (input_price, output_price) = price_per_1k(provider, model);
+    (input_tokens as f64 * input_price + output_tokens as f64 * output_price) / 1_000.0
+}
+
+/// Which guardrail stopped the agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardrailLimit {
+    Cost,
+    Time,
+    Commands,
+}
+
+/// Running usage for the current session
+#[derive(Debug, Clone)]
+pub struct SessionUsage {
+    /// When the agent started on the current request; the time limit
+    /// counts from here
+    pub started: Instant,
+    pub session_cost: f64,
+    pub commands_executed_count: u32,
+}
+
+impl Default for SessionUsage {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            session_cost: 0.0,
+            commands_executed_count: 0,
+        }
+    }
+}
+
+impl SessionUsage {
+    /// Restart the clock for a new request
+    pub fn start_run(&mut self) {
+        self.started = Instant::now();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn elapsed_minutes(&self) -> u32 {
+        (self.elapsed().as_secs() / 60) as u32
+    }
+
+    /// Add the estimated cost of a model call
+    pub fn record_generation(&mut self, provider: &str, model: &str, messages: &[ChatMessage], response: &str) {
+        let input_tokens: usize = messages.iter().map(|m| token_count(&m.content)).sum();
+        self.session_cost += estimate_cost(provider, model, input_tokens, token_count(response));
+    }
+
+    pub fn record_command(&mut self) {
+        self.commands_executed_count += 1;
+    }
+
+    /// Check the limits that apply before calling a model
+    pub fn check_generation(&self, limits: &AgentGuardrails) -> Option<GuardrailLimit> {
+        if let Some(max_cost) = limits.max_cost_usd {
+            if self.session_cost >= max_cost {
+                return Some(GuardrailLimit::Cost);
+            }
+        }
+        if let Some(max_minutes) = limits.max_session_minutes {
+            if self.elapsed_minutes() >= max_minutes {
+                return Some(GuardrailLimit::Time);
+            }
+        }
+        None
+    }
+
+    /// Check the limits that apply before running a command
+    pub fn check_command(&self, limits: &AgentGuardrails) -> Option<GuardrailLimit> {
+        if self.commands_executed_count >= limits.max_commands_per_session {
+            return Some(GuardrailLimit::Commands);
+        }
+        self.check_generation(limits)
+    }
+}
+
+/// Friendly explanation shown to the user when a limit is reached
+pub fn limit_message(limit: GuardrailLimit, usage: &SessionUsage, limits: &AgentGuardrails) -> String {
+    match limit {
+        GuardrailLimit::Cost => format!(
+            "I've stopped because this session reached its spending limit (about ${:.2} of ${:.2}).\n\n\
+            You can raise `max_cost_usd` in your settings, or restart Little Helper to start a new session.",
+            usage.session_cost,
+            limits.max_cost_usd.unwrap_or_default()
+        ),
+        GuardrailLimit::Time => format!(
+            "I've stopped because I've been working on this for {} minutes (limit: {}).\n\n\
+            You can raise `max_session_minutes` in your settings.",
+            usage.elapsed_minutes(),
+            limits.max_session_minutes.unwrap_or_default()
+        ),
+        GuardrailLimit::Commands => format!(
+            "I've stopped because I've already run {} commands this session (limit: {}).\n\n\
+            You can raise `max_commands_per_session` in your settings, or restart Little Helper to start a new session.",
+            usage.commands_executed_count, limits.max_commands_per_session
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_limit() {
+        let limits = AgentGuardrails {
+            max_cost_usd: None,
+            max_session_minutes: None,
+            max_commands_per_session: 2,
+        };
+        let mut usage = SessionUsage::default();
+        assert_eq!(usage.check_command(&limits), None);
+        usage.record_command();
+        usage.record_command();
+        assert_eq!(usage.check_command(&limits), Some(GuardrailLimit::Commands));
+        // Model calls are still allowed
+        assert_eq!(usage.check_generation(&limits), None);
+    }
+
+    #[test]
+    fn test_cost_limit() {
+        let limits = AgentGuardrails {
+            max_cost_usd: Some(0.01),
+            max_session_minutes: None,
+            max_commands_per_session: 10,
+        };
+        let usage = SessionUsage {
+            session_cost: 0.02,
+            ..Default::default()
+        };
+        assert_eq!(usage.check_generation(&limits), Some(GuardrailLimit::Cost));
+    }
+
+    #[test]
+    fn test_time_limit_counts_from_the_request() {
+        let limits = AgentGuardrails {
+            max_cost_usd: None,
+            max_session_minutes: Some(5),
+            max_commands_per_session: 10,
+        };
+        let mut usage = SessionUsage {
+            started: Instant::now() - Duration::from_secs(10 * 60),
+            ..Default::default()
+        };
+        assert_eq!(usage.check_generation(&limits), Some(GuardrailLimit::Time));
+        assert_eq!(usage.check_command(&limits), Some(GuardrailLimit::Time));
+        usage.start_run();
+        assert_eq!(usage.check_generation(&limits), None);
+    }
+
+    #[test]
+    fn test_local_models_are_free() {
+        assert_eq!(estimate_cost("local", "llama3.2:3b", 10_000, 10_000), 0.0);
+        assert!(estimate_cost("anthropic", "claude-3-5", 1_000_000, 0) > 0.0);
+        // Unknown models still get an estimate
+        assert!(estimate_cost("openai", "my-finetune", 1_000_000, 0) > 0.0);
+    }
+}
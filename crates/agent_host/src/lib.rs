@@ -6,14 +6,39 @@
 //! - Parse and extract commands from AI responses
 //! - Provide user-friendly summaries of command output
 
+pub mod audit;
+pub mod context_plugins;
 pub mod executor;
+pub mod git_commit;
+pub mod guardrails;
+pub mod structured_output;
+pub mod system_context;
+pub mod tools;
+pub mod turn_state;
 
 use anyhow::Result;
+use providers::models::ModelCapability;
 use regex::Regex;
+use services::file_tools::FileHash;
+use services::stats::DirStats;
 use shared::agent_api::ChatMessage;
-use shared::settings::AppSettings;
+use shared::settings::{AppSettings, ContentSchedule};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-pub use executor::{CommandResult, DangerLevel, classify_command, execute_command, parse_progress, needs_elevation, web_search};
+pub use audit::{read_audit_log, AuditEntry};
+pub use executor::{CommandOutputChunk, CommandResult, CommandRules, DangerLevel, ExecutionOptions, ProgressParser, classify_command, classify_command_with, dirs_outside_allowed, execute_command, execute_command_streaming, execute_command_in, execute_command_streaming_with, execute_command_with, extract_paths, man_page_summary, parse_progress, needs_elevation, paths_outside_allowed, preview_expansion, restrict_find_command, set_command_restrictions, Shell, strip_ansi, timeout_for, web_search};
+pub use git_commit::CommitSuggestion;
+pub use guardrails::{GuardrailLimit, SessionUsage};
+pub use structured_output::{parse_structured_output, OutputFormat, StructuredTable};
+pub use tools::{BoxFuture, ExecutionContext, JoinedCsv, Tool, ToolArtifact, ToolCall, ToolOutput, ToolRegistry};
+pub use turn_state::AgentTurnState;
+use tools::ShellCommandTool;
 
 #[cfg(not(windows))]
 pub use executor::execute_with_sudo;
@@ -21,6 +46,39 @@ pub use executor::execute_with_sudo;
 #[cfg(windows)]
 pub use executor::execute_with_elevation;
 
+/// Lets the organizer ask for rename suggestions
+impl services::organizer::TextGenerator for AgentHost {
+    fn generate<'a>(&'a self, instruction: &'a str, content: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.generate_one_off(instruction, content))
+    }
+}
+
+/// Put `prompt` first in `messages` as the system message
+fn prepend_system_prompt(messages: &mut Vec<ChatMessage>, prompt: &str) {
+    messages.insert(0, ChatMessage {
+        role: "system".to_string(),
+        content: prompt.to_string(),
+        attachments: Vec::new(),
+    });
+}
+
+/// Merge runs of consecutive system messages into one, so a caller's
+/// system prompt and notes arrive as a single system message
+pub fn normalize_messages(messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    let mut normalized: Vec<ChatMessage> = Vec::with_capacity(messages.len());
+    for message in messages {
+        match normalized.last_mut() {
+            Some(last) if last.role == "system" && message.role == "system" => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+                last.attachments.extend(message.attachments);
+            }
+            _ => normalized.push(message),
+        }
+    }
+    normalized
+}
+
 /// Tool result from command execution
 #[derive(Debug, Clone)]
 pub struct ToolResult {
@@ -28,158 +86,879 @@ pub struct ToolResult {
     pub result: CommandResult,
 }
 
+/// Final result of an agent chat
+#[derive(Debug, Clone, Default)]
+pub struct AgentReply {
+    pub response: String,
+    pub tool_results: Vec<ToolResult>,
+    /// File the AI asked to show in the preview panel
+    pub preview_file: Option<PathBuf>,
+    /// Set when a guardrail ended the chat early
+    pub limit_reached: Option<GuardrailLimit>,
+    /// Posts the AI proposed with <schedule-post> tags
+    pub scheduled_posts: Vec<ContentSchedule>,
+    /// Interactive program to open in a terminal (<interactive> tag)
+    pub interactive_command: Option<String>,
+    /// Directory statistics gathered with <dir-stats> tags
+    pub dir_stats: Vec<DirStats>,
+    /// Result of the last <csv-join> tag, to show in the preview panel
+    pub joined_csv: Option<JoinedCsv>,
+    /// File hashes computed with <hash-file> tags
+    pub file_hashes: Vec<FileHash>,
+    /// Offer to commit files written with <edit-file> tags, when they're
+    /// in a git repository
+    pub commit_suggestion: Option<CommitSuggestion>,
+    /// Where the time went, for the debug view
+    pub debug_info: TurnDebugInfo,
+}
+
+impl AgentReply {
+    /// The reply gathered so far, finished with `response`, the commands
+    /// that ran and the turn's timings
+    fn finished(self, response: String, tool_results: Vec<ToolResult>, debug_info: TurnDebugInfo) -> Self {
+        Self { response, tool_results, debug_info, ..self }
+    }
+}
+
+/// Timings for one agent chat
+#[derive(Debug, Clone, Default)]
+pub struct TurnDebugInfo {
+    /// Each AI generation, in order
+    pub generations: Vec<Duration>,
+    /// Each executed command and how long it took
+    pub commands: Vec<(String, Duration)>,
+    pub total: Duration,
+    /// Provider and model that produced the last response
+    pub provider: String,
+    pub model: String,
+}
+
+impl TurnDebugInfo {
+    fn finish(mut self, started: Instant) -> Self {
+        self.total = started.elapsed();
+        self
+    }
+}
+
+/// Responses longer than this (in words) get a key takeaways summary
+pub const KEY_TAKEAWAYS_MIN_WORDS: usize = 500;
+
+/// Reply when no AI provider can be reached
+pub const OFFLINE_MESSAGE: &str = "I'm not connected to any AI provider right now. \
+I can still help you with local file operations - start a message with `$` to run a safe command, like `$ ls ~/Documents`.";
+
+/// Command output longer than this (in bytes) may get an AI summary
+const AI_SUMMARY_MIN_OUTPUT: usize = 200;
+/// Output sent along for the AI summary
+const AI_SUMMARY_OUTPUT_CHARS: usize = 500;
+const AI_SUMMARY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Follow-up questions shown under a Research response
+const MAX_FOLLOWUP_QUESTIONS: usize = 3;
+
+/// Platforms Content mode adapts posts for: (key, name, what suits it)
+pub const CONTENT_PLATFORMS: &[(&str, &str, &str)] = &[
+    ("twitter", "Twitter", "Use thread format: number the tweets and keep each under 280 characters."),
+    ("instagram", "Instagram", "Write a visual-first caption that describes the image to pair it with, and end with a block of relevant hashtags."),
+    ("linkedin", "LinkedIn", "Use a professional tone in one post, not a thread, with short paragraphs and at most 3 hashtags."),
+    ("facebook", "Facebook", "Keep it conversational, a few short paragraphs, and end with a question to invite comments."),
+    ("blog", "Blog", "Expand it into a blog post with a title, an introduction, subheadings and a conclusion."),
+    ("email", "Email", "Write it as a newsletter email with a subject line, a greeting and one clear call to action."),
+];
+
+/// Display name of a Content mode platform, or the key itself
+pub fn platform_name(platform: &str) -> &str {
+    CONTENT_PLATFORMS
+        .iter()
+        .find(|(key, _, _)| key.eq_ignore_ascii_case(platform))
+        .map(|(_, name, _)| *name)
+        .unwrap_or(platform)
+}
+
+/// Questions from a reply, one per line, with list markers ("1.", "-", "*") removed
+pub fn parse_followup_questions(reply: &str) -> Vec<String> {
+    reply
+        .lines()
+        .map(|line| {
+            let line = line.trim().trim_start_matches(['-', '*', '•']);
+            let line = line.trim_start_matches(|c: char| c.is_ascii_digit());
+            line.trim_start_matches(['.', ')']).trim().trim_matches('"').to_string()
+        })
+        .filter(|q| q.ends_with('?'))
+        .take(MAX_FOLLOWUP_QUESTIONS)
+        .collect()
+}
+
+/// First line of a title reply, without quotes, "Title:" or a trailing period
+fn clean_title(reply: &str) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line.strip_prefix("Title:").unwrap_or(line).trim();
+    let title = line.trim_matches(['"', '\'', '*']).trim_end_matches('.').trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// Parse a schedule date: RFC 3339, "YYYY-MM-DD HH:MM" or "YYYY-MM-DD"
+fn parse_schedule_date(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M") {
+        return Some(dt.and_utc());
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(9, 0, 0)?.and_utc())
+}
+
+/// Asks the user whether to run a command that needs confirmation.
+/// Resolves to `true` to run it.
+pub type ConfirmationCallback =
+    Arc<dyn Fn(&str, DangerLevel) -> Pin<Box<dyn Future<Output = bool> + Send>> + Send + Sync>;
+
+/// Every command in one AI response, for review before any of them run
+#[derive(Debug, Clone)]
+pub struct BatchPreview {
+    pub commands: Vec<(String, DangerLevel)>,
+}
+
+/// Shows a [`BatchPreview`] to the user. Resolves to one entry per command,
+/// `true` for the ones to run.
+pub type BatchPreviewCallback =
+    Arc<dyn Fn(BatchPreview) -> Pin<Box<dyn Future<Output = Vec<bool>> + Send>> + Send + Sync>;
+
+/// Asks whether a folder outside `allowed_dirs` may be used for the rest
+/// of the session. Resolves to `true` to allow it.
+pub type DirAccessCallback =
+    Arc<dyn Fn(&Path) -> Pin<Box<dyn Future<Output = bool> + Send>> + Send + Sync>;
+
+/// Folders the user answered about this session, with
+/// `settings.auto_extend_allowed_dirs`. Never saved.
+#[derive(Debug, Default)]
+struct SessionDirs {
+    allowed: Vec<String>,
+    declined: Vec<PathBuf>,
+}
+
 /// Agent host manages AI chat and command execution
+#[derive(Clone)]
 pub struct AgentHost {
     pub settings: AppSettings,
+    /// Timeout and output limit for commands the agent runs
+    pub execution_options: ExecutionOptions,
+    /// Called for commands that need confirmation or are dangerous. Without
+    /// one those commands are skipped and listed as pending.
+    pub confirmation_callback: Option<ConfirmationCallback>,
+    /// With `settings.preview_batch`, called with all of a response's
+    /// commands; the ones chosen run without asking again
+    pub batch_preview_callback: Option<BatchPreviewCallback>,
+    /// With `settings.auto_extend_allowed_dirs`, called once per folder a
+    /// command needs from outside `allowed_dirs`
+    pub dir_access_callback: Option<DirAccessCallback>,
+    /// Chat mode the next chats are for ("find", "research", ...), which
+    /// picks the providers from `mode_providers`
+    pub chat_mode: Option<String>,
+    /// Content mode persona, formatted for the system prompt. `None` means
+    /// all discovered personas are used.
+    pub active_persona: Option<String>,
+    /// Tools the AI can call from its responses
+    tools: ToolRegistry,
+    /// Shared between clones so background chats update the same session
+    usage: Arc<Mutex<SessionUsage>>,
+    /// AI summaries of command output, by hash of the output
+    summary_cache: Arc<Mutex<HashMap<u64, String>>>,
+    /// Folders allowed or refused for this session only
+    session_dirs: Arc<Mutex<SessionDirs>>,
+    /// Context plugin text by chat mode, worked out once per session
+    plugin_context: Arc<Mutex<HashMap<String, String>>>,
+    /// Latest system details, kept current by the app
+    pub system_context: Arc<RwLock<String>>,
+    /// System details as the AI last heard them
+    system_context_seen: Arc<Mutex<Option<String>>>,
 }
 
 impl AgentHost {
     pub fn new(settings: AppSettings) -> Self {
-        Self { settings }
+        Self {
+            settings,
+            execution_options: ExecutionOptions::default(),
+            confirmation_callback: None,
+            batch_preview_callback: None,
+            dir_access_callback: None,
+            chat_mode: None,
+            active_persona: None,
+            tools: ToolRegistry::default(),
+            usage: Arc::new(Mutex::new(SessionUsage::default())),
+            summary_cache: Arc::new(Mutex::new(HashMap::new())),
+            session_dirs: Arc::new(Mutex::new(SessionDirs::default())),
+            plugin_context: Arc::new(Mutex::new(HashMap::new())),
+            system_context: Arc::new(RwLock::new(String::new())),
+            system_context_seen: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Write Content mode output for a single persona. Only that persona's
+    /// file is loaded; the chat history is left alone.
+    pub fn set_active_persona(&mut self, persona_name: &str) -> Result<()> {
+        self.active_persona = Some(services::personas::load_persona_by_name(persona_name)?);
+        Ok(())
+    }
+
+    /// Go back to using all personas
+    pub fn clear_active_persona(&mut self) {
+        self.active_persona = None;
+    }
+
+    /// Let the AI use another tool. A tool with the same name as a built-in
+    /// one replaces it.
+    pub fn register_tool(&mut self, tool: Box<dyn Tool>) {
+        self.tools.register(tool);
+    }
+
+    /// A "[System context updated]" note when the system details changed
+    /// since the AI last heard them. The first call only records them.
+    fn system_context_update(&self) -> Option<String> {
+        let current = self.system_context.read().unwrap().clone();
+        if current.is_empty() {
+            return None;
+        }
+        let mut seen = self.system_context_seen.lock().unwrap();
+        let previous = seen.replace(current.clone())?;
+        (previous != current).then(|| system_context::update_message(&previous, &current))
+    }
+
+    /// Snapshot of the current session's usage
+    pub fn usage(&self) -> SessionUsage {
+        self.usage.lock().unwrap().clone()
+    }
+
+    /// Folders allowed for this session on top of `settings.allowed_dirs`
+    pub fn session_allowed_dirs(&self) -> Vec<String> {
+        self.session_dirs.lock().unwrap().allowed.clone()
+    }
+
+    /// Stop allowing a session folder, e.g. once it's been saved to settings
+    pub fn forget_session_dir(&self, dir: &str) {
+        self.session_dirs.lock().unwrap().allowed.retain(|d| d != dir);
+    }
+
+    /// Settings with this session's extra folders added to `allowed_dirs`.
+    /// No configured folders means no restriction, so nothing is added then.
+    fn session_settings(&self) -> AppSettings {
+        let mut settings = self.settings.clone();
+        if !settings.allowed_dirs.is_empty() {
+            settings.allowed_dirs.extend(self.session_allowed_dirs());
+        }
+        settings
+    }
+
+    /// Ask about each folder `input` needs from outside the allowed ones
+    /// that hasn't been answered yet this session
+    async fn extend_allowed_dirs(&self, input: &str, settings: &AppSettings) {
+        let Some(ask) = self.dir_access_callback.as_ref().filter(|_| self.settings.auto_extend_allowed_dirs) else {
+            return;
+        };
+        for dir in dirs_outside_allowed(input, &settings.allowed_dirs) {
+            if self.session_dirs.lock().unwrap().declined.contains(&dir) {
+                continue;
+            }
+            let allowed = ask(&dir).await;
+            let mut session = self.session_dirs.lock().unwrap();
+            if allowed {
+                tracing::info!(dir = %dir.display(), "allowed folder for this session");
+                session.allowed.push(dir.display().to_string());
+            } else {
+                session.declined.push(dir);
+            }
+        }
     }
 
-    /// Simple chat - just AI response, no command execution
-    pub async fn chat(&self, messages: Vec<ChatMessage>) -> Result<String> {
+    /// Start a fresh session budget. Context plugins are loaded and run
+    /// again too, picking up any changes to them.
+    pub fn reset_usage(&self) {
+        *self.usage.lock().unwrap() = SessionUsage::default();
+        self.plugin_context.lock().unwrap().clear();
+    }
+
+    /// Context plugin text for `mode`, run the first time it's needed in
+    /// a session
+    async fn plugin_context_for(&self, mode: &str) -> String {
+        if let Some(context) = self.plugin_context.lock().unwrap().get(mode) {
+            return context.clone();
+        }
+        let plugins = context_plugins::load_plugins(&context_plugins::plugins_dir());
+        let options = ExecutionOptions {
+            allowed_dirs: self.session_settings().allowed_dirs,
+            audit_log: self.settings.audit_log_path.clone(),
+            ..self.execution_options.clone()
+        };
+        let context = context_plugins::context_for_mode(&plugins, mode, &options, &self.settings.timeouts).await;
+        self.plugin_context.lock().unwrap().insert(mode.to_string(), context.clone());
+        context
+    }
+
+    /// Returns the reply to send if a guardrail says we must stop
+    fn check_limit(&self, before_command: bool) -> Option<(GuardrailLimit, String)> {
+        let usage = self.usage.lock().unwrap();
+        let limits = &self.settings.guardrails;
+        let limit = if before_command {
+            usage.check_command(limits)
+        } else {
+            usage.check_generation(limits)
+        }?;
+        Some((limit, guardrails::limit_message(limit, &usage, limits)))
+    }
+
+    /// Simple chat - just AI response, no command execution. With
+    /// `system_prompt`, it goes first as a system message.
+    pub async fn chat(&self, mut messages: Vec<ChatMessage>, system_prompt: Option<&str>) -> Result<String> {
+        if let Some(prompt) = system_prompt {
+            prepend_system_prompt(&mut messages, prompt);
+        }
+        self.router().generate(messages).await
+    }
+
+    /// `chat`, sending the reply on `tx` as it arrives
+    pub async fn chat_streaming(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        system_prompt: Option<&str>,
+        tx: tokio::sync::mpsc::Sender<String>,
+    ) -> Result<String> {
+        if let Some(prompt) = system_prompt {
+            prepend_system_prompt(&mut messages, prompt);
+        }
+        self.router().generate_streaming(messages, tx).await
+    }
+
+    /// Router for the current chat mode
+    fn router(&self) -> providers::router::ProviderRouter {
         use providers::router::ProviderRouter;
-        let router = ProviderRouter::new(self.settings.model.clone());
-        router.generate(messages).await
+        match &self.chat_mode {
+            Some(mode) => ProviderRouter::new_for_mode(self.settings.model.clone(), mode),
+            None => ProviderRouter::new(self.settings.model.clone()),
+        }
+    }
+
+    /// Extract 3-5 key facts from a long response as a bullet list, as a
+    /// one-off request to the providers that are set up
+    pub async fn key_takeaways(&self, response: &str) -> Result<String> {
+        self.generate_one_off(
+            "Extract 3–5 key facts from this response as a bullet list with no explanation.",
+            response,
+        )
+        .await
+    }
+
+    /// Three questions that would take the research in `response` further.
+    /// Sent like `key_takeaways`.
+    pub async fn followup_questions(&self, response: &str) -> Result<Vec<String>> {
+        let reply = self
+            .generate_one_off(
+                "Given this response, what are 3 important follow-up questions to deepen this research? \
+                Reply with only the questions, one per line.",
+                response,
+            )
+            .await?;
+        Ok(parse_followup_questions(&reply))
     }
 
-    /// Agent chat - AI can request command execution
-    /// Returns the final response and any tool results
+    /// A short title for a conversation that starts with `first_message`.
+    /// Sent like `key_takeaways`.
+    pub async fn conversation_title(&self, first_message: &str) -> Result<String> {
+        let reply = self
+            .generate_one_off(
+                "Generate a 4-6 word title for this conversation. Reply with only the title.",
+                first_message,
+            )
+            .await?;
+        clean_title(&reply).ok_or_else(|| anyhow::anyhow!("The AI didn't suggest a title"))
+    }
+
+    /// Rewrite a post written for `from_platform` to suit `to_platform`,
+    /// keeping its message. Uses the user's preferred provider.
+    pub async fn adapt_content(&self, original: &str, from_platform: &str, to_platform: &str) -> Result<String> {
+        let constraints = CONTENT_PLATFORMS
+            .iter()
+            .find(|(key, _, _)| key.eq_ignore_ascii_case(to_platform))
+            .map(|(_, _, constraints)| *constraints)
+            .unwrap_or_default();
+        let instruction = format!(
+            "Adapt this content from {} format to {} format while keeping the core message. {} \
+            Reply with only the adapted content.",
+            platform_name(from_platform),
+            platform_name(to_platform),
+            constraints
+        );
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: original.to_string(),
+            attachments: Vec::new(),
+        }];
+        Ok(self.chat(messages, Some(&instruction)).await?.trim().to_string())
+    }
+
+    /// A one sentence AI summary of a command's output, for commands that
+    /// `generate_summary` can't say much about. Short output and commands
+    /// with a built-in summary keep `result.summary`. Gives up after
+    /// `AI_SUMMARY_TIMEOUT`.
+    pub async fn ai_summarize_result(&self, result: &CommandResult) -> Result<String> {
+        if result.output.len() <= AI_SUMMARY_MIN_OUTPUT || executor::has_simple_summary(&result.command) {
+            return Ok(result.summary.clone());
+        }
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        result.output.hash(&mut hasher);
+        let key = hasher.finish();
+        if let Some(summary) = self.summary_cache.lock().unwrap().get(&key) {
+            return Ok(summary.clone());
+        }
+
+        let output: String = result.output.chars().take(AI_SUMMARY_OUTPUT_CHARS).collect();
+        let prompt = format!(
+            "In one sentence, what does this command output tell us? Command: `{}` Output: `{}`",
+            result.command, output
+        );
+        let summary = tokio::time::timeout(AI_SUMMARY_TIMEOUT, self.generate_one_off("Answer in one sentence.", &prompt))
+            .await
+            .map_err(|_| anyhow::anyhow!("Summary timed out"))??;
+        self.summary_cache.lock().unwrap().insert(key, summary.clone());
+        Ok(summary)
+    }
+
+    /// One-off request with `instruction` as the system prompt, sent to the
+    /// user's providers that are set up, in their order, and counted in
+    /// the session usage. Fails without a request if none are.
+    async fn generate_one_off(&self, instruction: &str, content: &str) -> Result<String> {
+        use providers::router::ProviderRouter;
+
+        let providers = self.router().configured_providers();
+        if providers.is_empty() {
+            anyhow::bail!("No AI provider is set up");
+        }
+        let mut config = self.settings.model.clone();
+        config.provider_preference = providers;
+        let router = ProviderRouter::new(config);
+
+        let mut messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+            attachments: Vec::new(),
+        }];
+        prepend_system_prompt(&mut messages, instruction);
+        let (reply, provider) = router.generate_with_provider(messages.clone()).await?;
+        let model = self.settings.model.model_for(&provider).unwrap_or_default();
+        self.usage.lock().unwrap().record_generation(&provider, model, &messages, &reply);
+        Ok(reply.trim().to_string())
+    }
+
+    /// `messages` with the agent system prompt first. A system prompt the
+    /// caller sent is kept, merged in after the agent's.
+    fn with_agent_prompt(&self, messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+        let mut messages = normalize_messages(messages);
+        prepend_system_prompt(&mut messages, &self.get_agent_system_prompt());
+        normalize_messages(messages)
+    }
+
+    /// Agent chat - AI can search the web, run safe commands and open previews.
     pub async fn agent_chat(
         &self,
         messages: Vec<ChatMessage>,
         auto_execute_safe: bool,
-    ) -> Result<(String, Vec<ToolResult>)> {
-        use providers::router::ProviderRouter;
-        
-        let router = ProviderRouter::new(self.settings.model.clone());
-        let mut all_messages = messages.clone();
-        let mut tool_results = Vec::new();
-        
-        // Add agent system prompt
-        let system_prompt = self.get_agent_system_prompt();
-        all_messages.insert(0, ChatMessage {
-            role: "system".to_string(),
-            content: system_prompt,
-        });
-        
-        // Loop for multi-turn command execution (max 10 iterations)
+    ) -> Result<AgentReply> {
+        let router = self.router();
+        let mut all_messages = self.with_agent_prompt(messages);
+        let mut state = AgentTurnState::default();
+        // Previews, posts and other things the turn produced
+        let mut reply = AgentReply::default();
+        let mut edited_files = Vec::new();
+        let started = Instant::now();
+        let mut debug = TurnDebugInfo::default();
+        self.usage.lock().unwrap().start_run();
+
+        // Background knowledge from the context plugins for this mode
+        if let Some(mode) = &self.chat_mode {
+            let plugin_context = self.plugin_context_for(mode).await;
+            if !plugin_context.is_empty() {
+                all_messages[0].content = format!("{}{}", plugin_context, all_messages[0].content);
+            }
+        }
+
+        let base_system_prompt = all_messages[0].content.clone();
+        let user_input = all_messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+
+        // Don't wait for providers to time out again once we know we're offline
+        if providers::connectivity::is_offline() && user_input.trim_start().starts_with('$') {
+            let (response, result) = self.offline_reply(&user_input).await;
+            state.completed_commands.extend(result);
+            return Ok(reply.finished(response, state.completed_commands, debug.finish(started)));
+        }
+
+        // Images need a vision model; once a tool call needs confirmation the
+        // rest of the turn goes to a model that handles tool use properly
+        let mut requirements = Vec::new();
+        if all_messages.iter().any(|m| !m.attachments.is_empty()) {
+            requirements.push(ModelCapability::VisionInput);
+        }
+
+        // Loop for multi-turn tool use (max 10 iterations)
         for _ in 0..10 {
-            let response = router.generate(all_messages.clone()).await?;
-            
-            // Extract commands from response
-            let commands = self.extract_commands(&response);
-            
-            if commands.is_empty() {
-                // No commands, return final response
-                return Ok((response, tool_results));
+            // Give the AI a structured view of what has happened so far
+            all_messages[0].content = state.system_prompt_with_state(&base_system_prompt);
+            if let Some(update) = self.system_context_update() {
+                all_messages.push(ChatMessage {
+                    role: "system".to_string(),
+                    content: update,
+                    attachments: Vec::new(),
+                });
+            }
+
+            if let Some((limit, message)) = self.check_limit(false) {
+                reply.limit_reached = Some(limit);
+                return Ok(reply.finished(message, state.completed_commands, debug.finish(started)));
+            }
+
+            let generation_started = Instant::now();
+            let (response, provider) = match router.generate_with_provider_requiring(all_messages.clone(), &requirements).await {
+                Ok(generated) => generated,
+                Err(e) if matches!(e.downcast_ref(), Some(providers::error::ProviderError::Offline)) => {
+                    let (response, result) = self.offline_reply(&user_input).await;
+                    state.completed_commands.extend(result);
+                    return Ok(reply.finished(response, state.completed_commands, debug.finish(started)));
+                }
+                Err(e) => return Err(e),
+            };
+            debug.generations.push(generation_started.elapsed());
+            let model = self.settings.model.model_for(&provider).unwrap_or_default();
+            self.usage.lock().unwrap().record_generation(&provider, model, &all_messages, &response);
+            debug.model = providers::models::normalize_model_name(model, &provider);
+            debug.provider = provider;
+
+            if let Some(path) = self.extract_preview(&response) {
+                reply.preview_file = Some(path);
             }
-            
-            // Process each command
-            let mut executed_any = false;
-            for cmd in commands {
-                let danger = classify_command(&cmd);
-                
-                // Only auto-execute safe commands if enabled
+            reply.scheduled_posts.extend(self.extract_scheduled_posts(&response));
+            if let Some(cmd) = self.extract_interactive(&response) {
+                reply.interactive_command = Some(cmd);
+            }
+
+            let calls = self.tools.extract_all(&response);
+            if calls.is_empty() {
+                // No actions, return final response
+                reply.commit_suggestion = self.suggest_commit(&edited_files).await;
+                return Ok(reply.finished(response, state.completed_commands, debug.finish(started)));
+            }
+
+            all_messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: response.clone(),
+                attachments: Vec::new(),
+            });
+
+            let mut results = Vec::new();
+            let mut calls = calls;
+            let batch_choices = self.preview_batch(&mut calls, &state).await;
+
+            for (i, mut call) in calls.into_iter().enumerate() {
+                let Some(tool) = self.tools.get(&call.tool).cloned() else { continue };
+                let is_shell = call.tool == ShellCommandTool::NAME;
+
+                self.extend_allowed_dirs(&call.input, &self.session_settings()).await;
+                let settings = self.session_settings();
+
+                if is_shell {
+                    // Keep file searches inside the folders the user allowed
+                    if let Some(restricted) = restrict_find_command(&call.input, &settings.allowed_dirs) {
+                        call.input = restricted;
+                    }
+
+                    // Don't re-run a command just because the AI repeated it on a later turn
+                    if state.has_run(&call.input) {
+                        results.push(format!("[Command '{}' already ran - see its output above]", call.input));
+                        continue;
+                    }
+
+                    // Track `cd` ourselves since each command runs in a fresh shell
+                    if let Some(message) = state.change_directory(&call.input) {
+                        results.push(message);
+                        continue;
+                    }
+                }
+
+                let ctx = ExecutionContext {
+                    settings: &settings,
+                    execution_options: &self.execution_options,
+                    working_dir: state.working_directory().map(|d| d.to_path_buf()),
+                };
+                let danger = tool.danger(&call, &ctx);
+                let description = tool.describe(&call);
+                if danger == DangerLevel::NeedsConfirmation && !requirements.contains(&ModelCapability::FunctionCalling) {
+                    requirements.push(ModelCapability::FunctionCalling);
+                }
+
+                // Only auto-execute safe calls if enabled; ask about the rest
+                let mut declined = false;
+                let batch_choice = batch_choices.as_ref().and_then(|choices| choices.get(&i).copied());
                 let should_execute = match danger {
+                    DangerLevel::NeedsSudo | DangerLevel::Blocked => false,
+                    _ if batch_choice.is_some() => {
+                        let selected = batch_choice == Some(true);
+                        declined = !selected;
+                        selected
+                    }
                     DangerLevel::Safe => auto_execute_safe,
-                    DangerLevel::Blocked => false,
-                    _ => false, // Needs confirmation from UI
+                    DangerLevel::NeedsConfirmation | DangerLevel::Dangerous => {
+                        match &self.confirmation_callback {
+                            Some(confirm) => {
+                                let approved = confirm(&description, danger).await;
+                                declined = !approved;
+                                approved
+                            }
+                            None => false,
+                        }
+                    }
                 };
-                
+
                 if should_execute {
-                    let result = execute_command(&cmd, 30).await?;
-                    
-                    // Add result to conversation
-                    all_messages.push(ChatMessage {
-                        role: "assistant".to_string(),
-                        content: response.clone(),
-                    });
-                    all_messages.push(ChatMessage {
-                        role: "user".to_string(),
-                        content: format!(
-                            "[Command Output]\n$ {}\n{}\nExit code: {}",
-                            cmd, result.output, result.exit_code
-                        ),
-                    });
-                    
-                    tool_results.push(ToolResult {
-                        command: cmd.clone(),
-                        result,
-                    });
-                    executed_any = true;
+                    if is_shell {
+                        if let Some((limit, message)) = self.check_limit(true) {
+                            reply.limit_reached = Some(limit);
+                            return Ok(reply.finished(message, state.completed_commands, debug.finish(started)));
+                        }
+                    }
+
+                    let tool_started = Instant::now();
+                    let output = tool.execute(call, &ctx).await;
+                    debug.commands.push((description.clone(), tool_started.elapsed()));
+                    match output {
+                        Ok(output) => {
+                            results.push(output.text);
+                            match output.artifact {
+                                Some(ToolArtifact::Command(mut result)) => {
+                                    self.usage.lock().unwrap().record_command();
+                                    // A failed AI summary keeps the built-in one
+                                    if self.settings.ai_command_summaries {
+                                        if let Ok(summary) = self.ai_summarize_result(&result.result).await {
+                                            result.result.summary = summary;
+                                        }
+                                    }
+                                    state.completed_commands.push(result);
+                                }
+                                Some(ToolArtifact::DirStats(stats)) => reply.dir_stats.push(stats),
+                                Some(ToolArtifact::JoinedCsv(joined)) => reply.joined_csv = Some(joined),
+                                Some(ToolArtifact::FileHash(hash)) => reply.file_hashes.push(hash),
+                                Some(ToolArtifact::FileEdit(path)) => edited_files.push(path),
+                                None => {}
+                            }
+                        }
+                        Err(e) => results.push(format!("[{} failed: {}]: {}", tool.name(), description, e)),
+                    }
                 } else if danger == DangerLevel::Blocked {
-                    // Inform AI the command is blocked
-                    all_messages.push(ChatMessage {
-                        role: "assistant".to_string(),
-                        content: response.clone(),
-                    });
-                    all_messages.push(ChatMessage {
-                        role: "user".to_string(),
-                        content: format!(
-                            "[Command Blocked]\n$ {}\nThis command is blocked for safety reasons.",
-                            cmd
-                        ),
-                    });
-                    executed_any = true;
+                    results.push(format!(
+                        "[Command Blocked]\n$ {}\nThis command is blocked for safety reasons.",
+                        description
+                    ));
+                } else if declined {
+                    results.push(format!(
+                        "[Command Declined]\n$ {}\nThe user chose not to run this. Don't retry it; suggest another way if there is one.",
+                        description
+                    ));
+                } else {
+                    state.pending_questions.push(format!("Run `{}`? (needs user confirmation)", description));
+                    results.push(format!("['{}' needs user confirmation - skipping for now]", description));
                 }
             }
-            
-            if !executed_any {
-                // Commands need confirmation, return response with pending commands
-                return Ok((response, tool_results));
-            }
+
+            // Add results back to conversation
+            all_messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: results.join("\n\n"),
+                attachments: Vec::new(),
+            });
         }
-        
+
         // Max iterations reached
-        Ok((
-            "I've reached the maximum number of command iterations. Please continue manually.".to_string(),
-            tool_results,
-        ))
+        reply.commit_suggestion = self.suggest_commit(&edited_files).await;
+        let response = "I've reached the maximum number of command iterations. Please continue manually.".to_string();
+        Ok(reply.finished(response, state.completed_commands, debug.finish(started)))
     }
 
-    /// Extract commands from AI response
-    fn extract_commands(&self, response: &str) -> Vec<String> {
-        let mut commands = Vec::new();
-        
-        // Pattern 1: <command>...</command> tags
-        let tag_re = Regex::new(r"<command>(.*?)</command>").unwrap();
-        for cap in tag_re.captures_iter(response) {
-            if let Some(m) = cap.get(1) {
-                let cmd = m.as_str().trim();
-                if !cmd.is_empty() {
-                    commands.push(cmd.to_string());
-                }
+    /// A commit of the files edited this turn, if they're in a git
+    /// repository, with a message the AI wrote from the diff
+    async fn suggest_commit(&self, edited: &[PathBuf]) -> Option<CommitSuggestion> {
+        let (repo, files) = git_commit::edited_in_repo(edited)?;
+        let changes = git_commit::describe_changes(&repo, &files).await;
+        let message = self
+            .generate_one_off(
+                "Write a git commit message for this change: one line in the imperative mood, \
+                under 72 characters. Reply with only the message.",
+                &changes,
+            )
+            .await
+            .ok()
+            .and_then(|reply| git_commit::clean_message(&reply))
+            .unwrap_or_else(|| {
+                let names: Vec<String> = files
+                    .iter()
+                    .filter_map(|f| f.file_name())
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .collect();
+                format!("Update {}", names.join(", "))
+            });
+        Some(CommitSuggestion { repo, files, message })
+    }
+
+    /// Stage and commit `suggestion`'s files with `message`. The user
+    /// confirmed by choosing to commit, so both commands run without asking.
+    pub async fn commit(&self, suggestion: &CommitSuggestion, message: &str) -> Result<CommandResult> {
+        if message.trim().is_empty() {
+            anyhow::bail!("The commit message is empty");
+        }
+        let mut last = None;
+        for cmd in suggestion.commands(message) {
+            let options = ExecutionOptions {
+                timeout_secs: timeout_for(&cmd, &self.settings.timeouts),
+                working_dir: Some(suggestion.repo.clone()),
+                audit_log: self.settings.audit_log_path.clone(),
+                ..self.execution_options.clone()
+            };
+            let result = execute_command_with(&cmd, &options).await?;
+            self.usage.lock().unwrap().record_command();
+            if !result.success {
+                anyhow::bail!("`{}` failed: {}", cmd, result.output.trim());
             }
+            last = Some(result);
         }
-        
-        // Pattern 2: ```bash or ```sh code blocks with [RUN] marker
-        let block_re = Regex::new(r"(?s)\[RUN\].*?```(?:bash|sh|shell)?\n(.*?)```").unwrap();
-        for cap in block_re.captures_iter(response) {
-            if let Some(m) = cap.get(1) {
-                for line in m.as_str().lines() {
-                    let cmd = line.trim();
-                    if !cmd.is_empty() && !cmd.starts_with('#') {
-                        commands.push(cmd.to_string());
-                    }
-                }
+        last.ok_or_else(|| anyhow::anyhow!("Nothing to commit"))
+    }
+
+    /// What to say with no AI provider reachable. A `$ command` message is
+    /// run directly if the command is safe; anything else gets
+    /// [`OFFLINE_MESSAGE`].
+    async fn offline_reply(&self, input: &str) -> (String, Option<ToolResult>) {
+        let Some(cmd) = input.trim().strip_prefix('$').map(str::trim).filter(|c| !c.is_empty()) else {
+            return (OFFLINE_MESSAGE.to_string(), None);
+        };
+        if self.get_danger_level(cmd) != DangerLevel::Safe {
+            return (
+                format!("I'm offline, so I can only run safe commands right now. `{}` will have to wait until I'm connected again.", cmd),
+                None,
+            );
+        }
+
+        let options = ExecutionOptions {
+            timeout_secs: timeout_for(cmd, &self.settings.timeouts),
+            allowed_dirs: self.session_settings().allowed_dirs,
+            audit_log: self.settings.audit_log_path.clone(),
+            ..self.execution_options.clone()
+        };
+        match execute_command_with(cmd, &options).await {
+            Ok(result) => {
+                self.usage.lock().unwrap().record_command();
+                let response = format!("I'm offline, so I ran this directly:\n\n$ {}\n```\n{}\n```", cmd, result.output.trim_end());
+                (response, Some(ToolResult { command: cmd.to_string(), result }))
             }
+            Err(e) => (format!("I'm offline and couldn't run `{}`: {}", cmd, e), None),
         }
-        
-        // Pattern 3: [EXECUTE] marker followed by inline code
-        let exec_re = Regex::new(r"\[EXECUTE\]\s*`([^`]+)`").unwrap();
-        for cap in exec_re.captures_iter(response) {
-            if let Some(m) = cap.get(1) {
-                let cmd = m.as_str().trim();
-                if !cmd.is_empty() {
-                    commands.push(cmd.to_string());
+    }
+
+    /// With batch preview on, show every command `calls` would run and
+    /// return whether each was chosen, by index in `calls`. Commands that
+    /// won't run anyway (repeats, `cd`) aren't shown. `find` commands are
+    /// restricted first so the preview shows what will really run.
+    async fn preview_batch(&self, calls: &mut [ToolCall], state: &AgentTurnState) -> Option<HashMap<usize, bool>> {
+        let preview = self.batch_preview_callback.as_ref().filter(|_| self.settings.preview_batch)?;
+        // Settle folder access first so the preview shows what will really run
+        for call in calls.iter() {
+            self.extend_allowed_dirs(&call.input, &self.session_settings()).await;
+        }
+        let settings = self.session_settings();
+        let ctx = ExecutionContext {
+            settings: &settings,
+            execution_options: &self.execution_options,
+            working_dir: state.working_directory().map(|d| d.to_path_buf()),
+        };
+        // Stands in for the real state so `cd`s are followed the same way
+        let mut scratch = state.clone();
+        let mut shown = Vec::new();
+        let mut commands = Vec::new();
+        for (i, call) in calls.iter_mut().enumerate() {
+            let Some(tool) = self.tools.get(&call.tool) else { continue };
+            if call.tool == ShellCommandTool::NAME {
+                if let Some(restricted) = restrict_find_command(&call.input, &settings.allowed_dirs) {
+                    call.input = restricted;
+                }
+                if scratch.has_run(&call.input) || scratch.change_directory(&call.input).is_some() {
+                    continue;
                 }
             }
+            shown.push(i);
+            commands.push((tool.describe(call), tool.danger(call, &ctx)));
+        }
+        if commands.is_empty() {
+            return None;
         }
-        
-        commands
+        let selected = preview(BatchPreview { commands }).await;
+        Some(shown.into_iter().zip(selected.into_iter().chain(std::iter::repeat(false))).collect())
+    }
+
+    /// Extract the file to preview from a <preview> tag, if it exists
+    fn extract_preview(&self, response: &str) -> Option<PathBuf> {
+        let preview_re = Regex::new(r"<preview>([^<]+)</preview>").unwrap();
+        let path_str = preview_re.captures(response)?.get(1)?.as_str().trim();
+        let expanded = match path_str.strip_prefix("~/") {
+            Some(stripped) => dirs::home_dir()
+                .map(|h| h.join(stripped))
+                .unwrap_or_else(|| PathBuf::from(path_str)),
+            None => PathBuf::from(path_str),
+        };
+        expanded.exists().then_some(expanded)
+    }
+
+    /// Extract proposed posts from
+    /// `<schedule-post platform="twitter" date="2024-12-31">...</schedule-post>` tags.
+    /// A bare date is scheduled for 09:00 UTC; RFC 3339 timestamps are used as-is.
+    pub fn extract_scheduled_posts(&self, response: &str) -> Vec<ContentSchedule> {
+        let tag_re = Regex::new(r"(?s)<schedule-post\s+([^>]*)>(.*?)</schedule-post>").unwrap();
+        let attr_re = Regex::new(r#"(\w+)\s*=\s*"([^"]*)""#).unwrap();
+
+        tag_re
+            .captures_iter(response)
+            .filter_map(|cap| {
+                let attrs = cap.get(1)?.as_str();
+                let post = cap.get(2)?.as_str().trim().to_string();
+                let mut platform = None;
+                let mut date = None;
+                for attr in attr_re.captures_iter(attrs) {
+                    match &attr[1] {
+                        "platform" => platform = Some(attr[2].trim().to_string()),
+                        "date" => date = parse_schedule_date(&attr[2]),
+                        _ => {}
+                    }
+                }
+                if post.is_empty() {
+                    return None;
+                }
+                Some(ContentSchedule {
+                    post,
+                    platform: platform.unwrap_or_else(|| "general".to_string()),
+                    scheduled_at: date?,
+                })
+            })
+            .collect()
+    }
+
+    /// Extract an interactive program from an <interactive> tag
+    fn extract_interactive(&self, response: &str) -> Option<String> {
+        let interactive_re = Regex::new(r"<interactive>([^<]+)</interactive>").unwrap();
+        let cmd = interactive_re.captures(response)?.get(1)?.as_str().trim();
+        (!cmd.is_empty()).then(|| cmd.to_string())
     }
 
     /// Get the agent system prompt (cross-platform aware)
@@ -231,12 +1010,63 @@ Example:
    <command>dir</command>  (Windows)
    <command>ls -la</command>  (Unix)
 
+## Folder Overviews
+When the user asks what kind of files are in a folder or what takes up space,
+use this instead of `find` or `du`:
+   <dir-stats path="~/Projects"/>
+
+You'll get file counts and sizes per type plus the largest and newest files.
+The user sees the numbers as a table, so summarize the highlights rather than
+repeating every row.
+
+## Finding Files
+To find files by name in the folders the user allowed, use a glob pattern:
+   <search-files pattern="**/*.toml"/>
+   <search-files pattern="*.test.ts" in="~/Code/web"/>
+
+A pattern without "/" matches file names anywhere (pattern="Cargo.toml").
+Add text="budget" to only keep files with that text in their name or content,
+best matches first; matching lines are shown under each file.
+
+## Joining CSV Files
+To combine two CSV files on a shared column (like a VLOOKUP), use:
+   <csv-join left="~/Data/people.csv" right="~/Data/orders.csv" on="id"/>
+
+Add right-on="customer_id" if the right file names its key column differently,
+and how="left" or how="right" to keep rows without a match. The joined table
+opens in the preview panel.
+
+## Reading and Editing Files
+To read a text file:
+   <read-file path="~/Documents/notes.txt"/>
+
+To change part of a file, give the exact text to replace and its replacement:
+   <edit-file path="~/Documents/notes.txt" find="Monday">Tuesday</edit-file>
+
+Leave out find to write the whole file. The user is asked before any file
+is written, and offered a commit afterwards if it's in a git repository.
+
+## Checking File Hashes
+To check a download or a file the user worries was changed, use:
+   <hash-file path="~/Downloads/installer.dmg" algo="sha256" expected="<hash the user gave>"/>
+
+algo can be md5, sha1, sha256 or sha512. Leave out expected if the user
+didn't give one. The user sees the hash and whether it matches.
+
+## Command Output
+Command output is cut off after {} bytes. For large files or long listings use
+`head -n 50`, `tail`, or `grep` instead of `cat` so you see the part that matters.
+
 ## Safety Rules
 - NEVER run destructive commands without explicit user confirmation
 - NEVER access sensitive files without permission
 - NEVER run commands you don't understand
 - If a command fails due to permissions, explain what happened and suggest alternatives
 
+## Interactive Programs
+When the user wants to work in a REPL or other interactive program, open it in the terminal panel:
+   <interactive>python3</interactive>
+
 ## File Viewing
 When you find or create files that the user should see, use:
    <preview>path/to/file</preview>
@@ -248,22 +1078,138 @@ The file will automatically open in the preview panel.
 - Explain what commands do before running them
 - Summarize results in plain English
 - If something fails, explain why and suggest alternatives
-"#, os_context)
+"#, os_context, self.execution_options.max_output_bytes)
     }
 
     /// Execute a specific command (for UI-triggered execution)
     pub async fn execute(&self, cmd: &str) -> Result<CommandResult> {
-        execute_command(cmd, 60).await
+        let options = ExecutionOptions {
+            timeout_secs: timeout_for(cmd, &self.settings.timeouts),
+            working_dir: std::env::current_dir().ok(),
+            allowed_dirs: self.session_settings().allowed_dirs,
+            audit_log: self.settings.audit_log_path.clone(),
+            ..self.execution_options.clone()
+        };
+        execute_command_with(cmd, &options).await
+    }
+
+    /// Built-in command lists plus the user's additions from settings
+    pub fn command_rules(&self) -> CommandRules {
+        CommandRules::from_settings(&self.settings)
     }
 
     /// Check if a command needs confirmation
     pub fn needs_confirmation(&self, cmd: &str) -> bool {
-        let danger = classify_command(cmd);
+        let danger = self.get_danger_level(cmd);
         matches!(danger, DangerLevel::NeedsConfirmation | DangerLevel::Dangerous | DangerLevel::NeedsSudo)
     }
 
     /// Get danger level for a command
     pub fn get_danger_level(&self, cmd: &str) -> DangerLevel {
-        classify_command(cmd)
+        classify_command_with(cmd, &self.command_rules())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_followup_questions() {
+        let reply = "Here are some questions:\n1. What changed in 2023?\n- Who funds it?\n\n3) \"How is it measured?\"\n4. One more?";
+        assert_eq!(
+            parse_followup_questions(reply),
+            vec!["What changed in 2023?", "Who funds it?", "How is it measured?"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_messages_single_system_message() {
+        let message = |role: &str, content: &str| ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            attachments: Vec::new(),
+        };
+        let normalized = normalize_messages(vec![
+            message("system", "You are helpful."),
+            message("system", "Note: be brief."),
+            message("user", "hi"),
+            message("system", "Context changed."),
+            message("assistant", "hello"),
+        ]);
+        let roles: Vec<&str> = normalized.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["system", "user", "system", "assistant"]);
+        assert_eq!(normalized[0].content, "You are helpful.\n\nNote: be brief.");
+
+        let mut with_prompt = normalize_messages(vec![message("user", "hi")]);
+        prepend_system_prompt(&mut with_prompt, "You are helpful.");
+        assert_eq!(normalize_messages(with_prompt).iter().filter(|m| m.role == "system").count(), 1);
+    }
+
+    #[test]
+    fn test_agent_prompt_kept_with_caller_system_prompt() {
+        let host = AgentHost::new(AppSettings::default());
+        let message = |role: &str, content: &str| ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            attachments: Vec::new(),
+        };
+        let messages = host.with_agent_prompt(vec![message("system", "You are in Data mode."), message("user", "hi")]);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "system");
+        for docs in ["<dir-stats", "<search-files", "<csv-join", "You are in Data mode."] {
+            assert!(messages[0].content.contains(docs), "missing {}", docs);
+        }
+
+        let messages = host.with_agent_prompt(vec![message("user", "hi")]);
+        assert!(messages[0].content.contains("<search-files"));
+        assert_eq!(messages[1].content, "hi");
+    }
+
+    #[tokio::test]
+    async fn test_agent_chat_stops_at_time_limit() {
+        let mut settings = AppSettings::default();
+        settings.guardrails.max_session_minutes = Some(0);
+        let host = AgentHost::new(settings);
+        let message = ChatMessage { role: "user".to_string(), content: "hi".to_string(), attachments: Vec::new() };
+        let reply = host.agent_chat(vec![message], true).await.unwrap();
+        assert_eq!(reply.limit_reached, Some(GuardrailLimit::Time));
+        assert!(reply.response.contains("working on this for 0 minutes"));
+        assert!(reply.tool_results.is_empty());
+    }
+
+    #[test]
+    fn test_extract_scheduled_posts() {
+        use chrono::TimeZone;
+
+        let host = AgentHost::new(AppSettings::default());
+        let response = r#"Here's the plan:
+<schedule-post platform="twitter" date="2024-12-31">New year, new posts</schedule-post>
+<schedule-post date = "2025-01-02 14:30" platform=" mastodon ">
+  Line one,
+  line two
+</schedule-post>
+<schedule-post date="2025-01-03T08:00:00+02:00">No platform</schedule-post>
+<schedule-post platform="twitter" date="tomorrow">Unparseable date</schedule-post>
+<schedule-post platform="twitter">No date</schedule-post>
+<schedule-post platform="twitter" date="2025-01-04">   </schedule-post>
+<schedule-post platform="twitter" date="2025-01-05">Never closed"#;
+        let posts = host.extract_scheduled_posts(response);
+        assert_eq!(posts.len(), 3);
+
+        assert_eq!(posts[0].platform, "twitter");
+        assert_eq!(posts[0].scheduled_at, chrono::Utc.with_ymd_and_hms(2024, 12, 31, 9, 0, 0).unwrap());
+        assert_eq!(posts[1].platform, "mastodon");
+        assert_eq!(posts[1].post, "Line one,\n  line two");
+        assert_eq!(posts[1].scheduled_at, chrono::Utc.with_ymd_and_hms(2025, 1, 2, 14, 30, 0).unwrap());
+        assert_eq!(posts[2].platform, "general");
+        assert_eq!(posts[2].scheduled_at, chrono::Utc.with_ymd_and_hms(2025, 1, 3, 6, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_clean_title() {
+        assert_eq!(clean_title("\nTitle: \"Planning a Garden Layout.\"\n").as_deref(), Some("Planning a Garden Layout"));
+        assert_eq!(clean_title("**Fixing Wifi Drops**").as_deref(), Some("Fixing Wifi Drops"));
+        assert_eq!(clean_title("  \n"), None);
     }
 }
@@ -0,0 +1,149 @@
+//! Detect tabular command output
+//!
+//! Commands like `ps aux`, `df -h` or `docker ps` print a header row
+//! followed by whitespace-aligned columns, and some tools print CSV. Both
+//! are parsed into a [`StructuredTable`] so the chat can show a real table.
+
+use serde::{Deserialize, Serialize};
+
+/// Rows beyond this are dropped from the table (the text output keeps them)
+const MAX_ROWS: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StructuredTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    /// Rows of columns: comma separated, or aligned with whitespace under a header row
+    Csv(StructuredTable),
+    /// Anything else
+    Plain,
+}
+
+/// Try to read `output` as a table
+pub fn parse_structured_output(output: &str) -> OutputFormat {
+    let lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() < 3 {
+        return OutputFormat::Plain;
+    }
+    parse_csv(&lines)
+        .or_else(|| parse_columns(&lines))
+        .map(OutputFormat::Csv)
+        .unwrap_or(OutputFormat::Plain)
+}
+
+/// Every line has the same number of commas (at least one)
+fn parse_csv(lines: &[&str]) -> Option<StructuredTable> {
+    let fields: Vec<Vec<String>> = lines.iter().map(|l| split_csv_line(l)).collect();
+    let width = fields[0].len();
+    if width < 2 || fields.iter().any(|f| f.len() != width) {
+        return None;
+    }
+    let mut fields = fields.into_iter();
+    let headers = fields.next()?;
+    Some(StructuredTable { headers, rows: fields.take(MAX_ROWS).collect() })
+}
+
+/// Split one CSV line, honoring double quotes
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+/// A header of words followed by rows with at least as many fields. The
+/// last column takes the rest of the line, since it's often a command or
+/// path with spaces (`ps aux`). Headers with more words than the rows have
+/// fields ("Mounted on" in `df`) have their trailing words joined.
+fn parse_columns(lines: &[&str]) -> Option<StructuredTable> {
+    // Column headers are mostly capitalized words ("PID", "%CPU",
+    // "Mounted on"), which keeps ordinary text from being read as a table
+    let header_words: Vec<&str> = lines[0].split_whitespace().collect();
+    let all_words = header_words
+        .iter()
+        .all(|w| w.chars().any(|c| c.is_alphabetic()) && w.parse::<f64>().is_err());
+    let capitalized = header_words.iter().filter(|w| is_capitalized(w)).count();
+    if !all_words || capitalized * 2 <= header_words.len() || !is_capitalized(header_words[0]) {
+        return None;
+    }
+
+    let width = lines[1..].iter().map(|l| l.split_whitespace().count()).min()?;
+    if width < 2 || header_words.len() < width || header_words.len() > width + 2 {
+        return None;
+    }
+    let mut headers: Vec<String> = header_words[..width - 1].iter().map(|w| w.to_string()).collect();
+    headers.push(header_words[width - 1..].join(" "));
+
+    // Rows that are much wider than the header aren't the same table
+    if lines[1..].iter().any(|l| l.split_whitespace().count() > width * 3) {
+        return None;
+    }
+
+    let rows = lines[1..]
+        .iter()
+        .take(MAX_ROWS)
+        .map(|line| split_columns(line, width))
+        .collect();
+    Some(StructuredTable { headers, rows })
+}
+
+/// First letter is uppercase ("PID", "%CPU", "Size")
+fn is_capitalized(word: &str) -> bool {
+    word.chars().find(|c| c.is_alphabetic()).is_some_and(|c| c.is_uppercase())
+}
+
+/// Split on whitespace into `width` fields, the last keeping the remainder
+fn split_columns(line: &str, width: usize) -> Vec<String> {
+    let mut fields = Vec::with_capacity(width);
+    let mut rest = line.trim();
+    while fields.len() + 1 < width {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(rest[..end].to_string());
+        rest = rest[end..].trim_start();
+    }
+    fields.push(rest.to_string());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df_output() {
+        let output = "Filesystem      Size  Used Avail Use% Mounted on\n\
+                      /dev/sda1       100G   40G   60G  40% /\n\
+                      tmpfs           7.8G     0  7.8G   0% /dev/shm\n";
+        let OutputFormat::Csv(table) = parse_structured_output(output) else { panic!("not a table") };
+        assert_eq!(table.headers.last().unwrap(), "Mounted on");
+        assert_eq!(table.rows[1], vec!["tmpfs", "7.8G", "0", "7.8G", "0%", "/dev/shm"]);
+    }
+
+    #[test]
+    fn test_parse_csv_and_plain() {
+        let OutputFormat::Csv(table) = parse_structured_output("name,size\n\"a, b\",1\nc,2\n") else {
+            panic!("not a table")
+        };
+        assert_eq!(table.rows[0], vec!["a, b", "1"]);
+
+        assert_eq!(parse_structured_output("total 8\n-rw-r--r-- 1 me me 0 a\n-rw-r--r-- 1 me me 0 b"), OutputFormat::Plain);
+        assert_eq!(parse_structured_output("hello"), OutputFormat::Plain);
+    }
+}
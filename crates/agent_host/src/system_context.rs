@@ -0,0 +1,65 @@
+//! What the AI is told about the computer
+//!
+//! The app gathers system details (OS, installed tools, project folders)
+//! and refreshes them in the background, since drives get plugged in and
+//! software installed mid-session. When they change between turns the
+//! agent tells the AI what changed instead of leaving it with stale facts.
+
+/// Note added to the conversation when the system details changed
+pub const UPDATE_MARKER: &str = "[System context updated]";
+
+/// One line per change, reading "Key: value" lines as keyed values:
+/// "Available tools: git → git, docker", "Added Projects: site", ...
+pub fn diff_summary(old: &str, new: &str) -> String {
+    let entries = |text: &str| -> Vec<(String, String)> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.split_once(": ") {
+                Some((key, value)) => (key.to_string(), value.trim().to_string()),
+                None => (line.to_string(), String::new()),
+            })
+            .collect()
+    };
+    let (old, new) = (entries(old), entries(new));
+    let find = |entries: &[(String, String)], key: &str| {
+        entries.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+    };
+    let show = |key: &str, value: &str| if value.is_empty() { key.to_string() } else { format!("{}: {}", key, value) };
+
+    let mut changes = Vec::new();
+    for (key, value) in &new {
+        match find(&old, key) {
+            None => changes.push(format!("Added {}", show(key, value))),
+            Some(before) if &before != value => changes.push(format!("{}: {} → {}", key, before, value)),
+            Some(_) => {}
+        }
+    }
+    for (key, value) in &old {
+        if find(&new, key).is_none() {
+            changes.push(format!("Removed {}", show(key, value)));
+        }
+    }
+    changes.join("\n")
+}
+
+/// The system message telling the AI about the change
+pub fn update_message(old: &str, new: &str) -> String {
+    format!("{}\n{}\n\nCurrent system context:\n{}", UPDATE_MARKER, diff_summary(old, new), new.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_summary() {
+        let old = "OS: Linux\nAvailable tools: git, curl\nDocuments: taxes\n";
+        let new = "OS: Linux\nAvailable tools: git, curl, docker\nProjects: site\n";
+        assert_eq!(
+            diff_summary(old, new),
+            "Available tools: git, curl → git, curl, docker\nAdded Projects: site\nRemoved Documents: taxes"
+        );
+        assert_eq!(diff_summary(old, old), "");
+    }
+}
@@ -0,0 +1,596 @@
+//! Tools the agent can call from its responses
+//!
+//! Each tool finds its own calls in the AI's text (a tag like
+//! `<search>...</search>`) and runs them. The agent loop asks every
+//! registered tool for calls, decides which may run, and feeds the output
+//! back to the AI. New tools are added with [`crate::AgentHost::register_tool`].
+
+use crate::executor::{classify_command_with, execute_command_with, truncate_output, web_search, CommandRules, DangerLevel, ExecutionOptions};
+use crate::ToolResult;
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use services::csv_tools::{to_csv_string, CsvJoiner, JoinType};
+use services::file_search::{self, FinderOptions};
+use services::file_tools::{verify_hash, FileHash, HashAlgorithm};
+use services::stats::{resolve_allowed_path, DirStats, DirectoryStats};
+use shared::search_types::SearchQuery;
+use shared::settings::AppSettings;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// One call of a tool found in an AI response
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    /// Name of the tool that found it
+    pub tool: String,
+    /// The main argument: a command, query or path
+    pub input: String,
+    /// Other tag attributes
+    pub args: HashMap<String, String>,
+    /// Text between the opening and closing tag, for tools that take one
+    pub body: Option<String>,
+}
+
+impl ToolCall {
+    pub fn new(tool: &str, input: impl Into<String>) -> Self {
+        Self {
+            tool: tool.to_string(),
+            input: input.into(),
+            args: HashMap::new(),
+            body: None,
+        }
+    }
+
+    fn arg(&self, name: &str) -> Option<&str> {
+        self.args.get(name).map(String::as_str).filter(|v| !v.is_empty())
+    }
+}
+
+/// What a tool needs to run
+pub struct ExecutionContext<'a> {
+    pub settings: &'a AppSettings,
+    pub execution_options: &'a ExecutionOptions,
+    /// Where shell commands run, after any `cd` the AI did
+    pub working_dir: Option<PathBuf>,
+}
+
+/// Two CSV files joined with a <csv-join> tag
+#[derive(Debug, Clone)]
+pub struct JoinedCsv {
+    /// E.g. "people.csv + orders.csv on id"
+    pub title: String,
+    /// Header first
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Data a tool produced for the UI, besides its text
+#[derive(Debug, Clone)]
+pub enum ToolArtifact {
+    Command(ToolResult),
+    DirStats(DirStats),
+    JoinedCsv(JoinedCsv),
+    FileHash(FileHash),
+    /// A file written by <edit-file>
+    FileEdit(PathBuf),
+}
+
+/// Result of running a tool call
+#[derive(Debug, Clone)]
+pub struct ToolOutput {
+    /// Sent back to the AI on its next turn
+    pub text: String,
+    pub artifact: Option<ToolArtifact>,
+}
+
+impl ToolOutput {
+    fn text(text: String) -> Self {
+        Self { text, artifact: None }
+    }
+}
+
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Calls of this tool in an AI response, in order
+    fn extract(&self, response: &str) -> Vec<ToolCall>;
+
+    /// How risky the call is. Anything but `Safe` asks the user first.
+    fn danger(&self, _call: &ToolCall, _ctx: &ExecutionContext) -> DangerLevel {
+        DangerLevel::Safe
+    }
+
+    /// The call as shown to the user when asking for confirmation
+    fn describe(&self, call: &ToolCall) -> String {
+        call.input.clone()
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>>;
+}
+
+/// The tools an agent can use, in the order their calls are run
+#[derive(Clone)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl Default for ToolRegistry {
+    /// The built-in tools
+    fn default() -> Self {
+        let mut registry = Self { tools: Vec::new() };
+        registry.register(Box::new(SearchTool));
+        registry.register(Box::new(DirStatsTool));
+        registry.register(Box::new(FileSearchTool));
+        registry.register(Box::new(CsvJoinTool));
+        registry.register(Box::new(HashFileTool));
+        registry.register(Box::new(FileReadTool));
+        registry.register(Box::new(FileEditTool));
+        registry.register(Box::new(ShellCommandTool));
+        registry
+    }
+}
+
+impl ToolRegistry {
+    /// Add a tool. A tool with the same name replaces the existing one.
+    pub fn register(&mut self, tool: Box<dyn Tool>) {
+        let tool: Arc<dyn Tool> = Arc::from(tool);
+        match self.tools.iter().position(|t| t.name() == tool.name()) {
+            Some(index) => self.tools[index] = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool>> {
+        self.tools.iter().find(|t| t.name() == name)
+    }
+
+    /// Every tool call in `response`, grouped by tool in registration order
+    pub fn extract_all(&self, response: &str) -> Vec<ToolCall> {
+        self.tools.iter().flat_map(|t| t.extract(response)).collect()
+    }
+}
+
+/// Attributes of a tag like `<tool a="1" b-c="2"/>`
+fn parse_attrs(attrs: &str) -> HashMap<String, String> {
+    let attr_re = Regex::new(r#"([\w-]+)\s*=\s*"([^"]*)""#).unwrap();
+    attr_re
+        .captures_iter(attrs)
+        .map(|a| (a[1].to_string(), a[2].trim().to_string()))
+        .collect()
+}
+
+/// Calls of a self-closing tag, with `input` taken from attribute `key`
+fn extract_tag(tool: &str, tag: &str, key: &str, response: &str) -> Vec<ToolCall> {
+    let tag_re = Regex::new(&format!(r"<{}\s+([^>]*?)/?>", regex::escape(tag))).unwrap();
+    tag_re
+        .captures_iter(response)
+        .filter_map(|cap| {
+            let mut args = parse_attrs(cap.get(1)?.as_str());
+            let input = args.remove(key).filter(|v| !v.is_empty())?;
+            Some(ToolCall { tool: tool.to_string(), input, args, body: None })
+        })
+        .collect()
+}
+
+/// Output shown to the AI is cut at the same limit as command output
+fn limit_output(text: String, ctx: &ExecutionContext) -> String {
+    truncate_output(text, ctx.execution_options.max_output_bytes).0
+}
+
+/// Runs shell commands from `<command>` tags, `[RUN]` code blocks and
+/// `[EXECUTE]` inline code
+pub struct ShellCommandTool;
+
+impl ShellCommandTool {
+    pub const NAME: &'static str = "command";
+}
+
+impl Tool for ShellCommandTool {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn extract(&self, response: &str) -> Vec<ToolCall> {
+        let mut commands = Vec::new();
+
+        // Pattern 1: <command>...</command> tags
+        let tag_re = Regex::new(r"<command>(.*?)</command>").unwrap();
+        for cap in tag_re.captures_iter(response) {
+            if let Some(m) = cap.get(1) {
+                let cmd = m.as_str().trim();
+                if !cmd.is_empty() {
+                    commands.push(cmd.to_string());
+                }
+            }
+        }
+
+        // Pattern 2: ```bash or ```sh code blocks with [RUN] marker
+        let block_re = Regex::new(r"(?s)\[RUN\].*?```(?:bash|sh|shell)?\n(.*?)```").unwrap();
+        for cap in block_re.captures_iter(response) {
+            if let Some(m) = cap.get(1) {
+                for line in m.as_str().lines() {
+                    let cmd = line.trim();
+                    if !cmd.is_empty() && !cmd.starts_with('#') {
+                        commands.push(cmd.to_string());
+                    }
+                }
+            }
+        }
+
+        // Pattern 3: [EXECUTE] marker followed by inline code
+        let exec_re = Regex::new(r"\[EXECUTE\]\s*`([^`]+)`").unwrap();
+        for cap in exec_re.captures_iter(response) {
+            if let Some(m) = cap.get(1) {
+                let cmd = m.as_str().trim();
+                if !cmd.is_empty() {
+                    commands.push(cmd.to_string());
+                }
+            }
+        }
+
+        // The same command may appear in several patterns - keep the first occurrence
+        let mut seen = HashSet::new();
+        commands.retain(|cmd| seen.insert(cmd.clone()));
+
+        commands.into_iter().map(|cmd| ToolCall::new(Self::NAME, cmd)).collect()
+    }
+
+    fn danger(&self, call: &ToolCall, ctx: &ExecutionContext) -> DangerLevel {
+        classify_command_with(&call.input, &CommandRules::from_settings(ctx.settings))
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>> {
+        Box::pin(async move {
+            let cmd = call.input;
+            let options = ExecutionOptions {
+                timeout_secs: crate::executor::timeout_for(&cmd, &ctx.settings.timeouts),
+                working_dir: ctx.working_dir.clone(),
+                allowed_dirs: ctx.settings.allowed_dirs.clone(),
+                audit_log: ctx.settings.audit_log_path.clone(),
+                ..ctx.execution_options.clone()
+            };
+            let result = execute_command_with(&cmd, &options).await?;
+            Ok(ToolOutput {
+                text: format!("[Command Output]\n$ {}\n{}\nExit code: {}", cmd, result.output, result.exit_code),
+                artifact: Some(ToolArtifact::Command(ToolResult { command: cmd, result })),
+            })
+        })
+    }
+}
+
+/// Web searches from `<search>query</search>` tags
+pub struct SearchTool;
+
+impl Tool for SearchTool {
+    fn name(&self) -> &str {
+        "search"
+    }
+
+    fn extract(&self, response: &str) -> Vec<ToolCall> {
+        let search_re = Regex::new(r"<search>([^<]+)</search>").unwrap();
+        search_re
+            .captures_iter(response)
+            .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
+            .filter(|q| !q.is_empty())
+            .map(|q| ToolCall::new(self.name(), q))
+            .collect()
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, _ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>> {
+        Box::pin(async move {
+            let result = web_search(&call.input).await?;
+            Ok(ToolOutput::text(format!("[Search Results for '{}']\n{}", call.input, result.output)))
+        })
+    }
+}
+
+/// Folder overviews from `<dir-stats path="..."/>` tags
+pub struct DirStatsTool;
+
+impl Tool for DirStatsTool {
+    fn name(&self) -> &str {
+        "dir-stats"
+    }
+
+    fn extract(&self, response: &str) -> Vec<ToolCall> {
+        let mut seen = HashSet::new();
+        let mut calls = extract_tag(self.name(), "dir-stats", "path", response);
+        calls.retain(|c| seen.insert(c.input.clone()));
+        calls
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>> {
+        Box::pin(async move {
+            let stats = DirectoryStats::compute(Path::new(&call.input), &ctx.settings.allowed_dirs)?;
+            Ok(ToolOutput {
+                text: format!("[Directory Stats]\n{}", stats.to_markdown()),
+                artifact: Some(ToolArtifact::DirStats(stats)),
+            })
+        })
+    }
+}
+
+/// Files listed for one `<search-files>` call
+const FILE_SEARCH_MAX_RESULTS: usize = 50;
+
+/// File searches from `<search-files pattern="**/*.rs" text="main" in="~/Code"/>`
+/// tags. `text` keeps files with it in their name or content, best matches
+/// first, and `in` narrows to one allowed folder; otherwise every allowed
+/// folder is searched.
+pub struct FileSearchTool;
+
+impl Tool for FileSearchTool {
+    fn name(&self) -> &str {
+        "search-files"
+    }
+
+    fn extract(&self, response: &str) -> Vec<ToolCall> {
+        extract_tag(self.name(), "search-files", "pattern", response)
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>> {
+        Box::pin(async move {
+            let allowed = &ctx.settings.allowed_dirs;
+            let dirs: Vec<PathBuf> = match call.arg("in") {
+                Some(dir) => vec![resolve_allowed_path(Path::new(dir), allowed)?],
+                None => allowed.iter().filter_map(|d| resolve_allowed_path(Path::new(d), allowed).ok()).collect(),
+            };
+            if dirs.is_empty() {
+                bail!("No folders are allowed to search - add one in Settings");
+            }
+            let query = SearchQuery {
+                text: call.arg("text").unwrap_or_default().to_string(),
+                extensions: None,
+                pattern: Some(call.input.clone()),
+            };
+            let options = FinderOptions { allowed_dirs: dirs, max_results: FILE_SEARCH_MAX_RESULTS };
+            let results = file_search::search(options, query)?;
+            let listing = if results.is_empty() {
+                "No files found.".to_string()
+            } else {
+                let lines: Vec<String> = results
+                    .iter()
+                    .map(|r| match &r.content_snippet {
+                        Some(snippet) => format!("{}\n    {}", r.path, snippet.replace('\n', "\n    ")),
+                        None => r.path.clone(),
+                    })
+                    .collect();
+                lines.join("\n")
+            };
+            Ok(ToolOutput::text(format!("[Files matching '{}']\n{}", call.input, listing)))
+        })
+    }
+}
+
+/// Rows of a join result shown to the AI
+const CSV_JOIN_PREVIEW_ROWS: usize = 20;
+
+/// Joins from `<csv-join left="a.csv" right="b.csv" on="id"/>` tags.
+/// `right-on` names a different key column in the right file and `how`
+/// is "inner" (default), "left" or "right".
+pub struct CsvJoinTool;
+
+impl Tool for CsvJoinTool {
+    fn name(&self) -> &str {
+        "csv-join"
+    }
+
+    fn extract(&self, response: &str) -> Vec<ToolCall> {
+        extract_tag(self.name(), "csv-join", "left", response)
+            .into_iter()
+            .filter(|c| c.arg("right").is_some() && c.arg("on").is_some())
+            .collect()
+    }
+
+    fn describe(&self, call: &ToolCall) -> String {
+        format!("{} + {}", call.input, call.arg("right").unwrap_or_default())
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>> {
+        Box::pin(async move {
+            let allowed = &ctx.settings.allowed_dirs;
+            let left = resolve_allowed_path(Path::new(&call.input), allowed)?;
+            let right = resolve_allowed_path(Path::new(call.arg("right").unwrap_or_default()), allowed)?;
+            let left_key = call.arg("on").unwrap_or_default();
+            let right_key = call.arg("right-on").unwrap_or(left_key);
+            let join_type = call.arg("how").and_then(JoinType::parse).unwrap_or(JoinType::Inner);
+
+            let rows = CsvJoiner::join(&left, &right, left_key, right_key, join_type)?;
+            let name = |p: &Path| p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let joined = JoinedCsv {
+                title: format!("{} + {} on {}", name(&left), name(&right), left_key),
+                rows,
+            };
+            let preview = to_csv_string(&joined.rows[..joined.rows.len().min(CSV_JOIN_PREVIEW_ROWS + 1)]);
+            Ok(ToolOutput {
+                text: format!(
+                    "[CSV Join] {}\n{} rows (shown to the user in the preview panel). First rows:\n{}",
+                    joined.title,
+                    joined.rows.len() - 1,
+                    preview
+                ),
+                artifact: Some(ToolArtifact::JoinedCsv(joined)),
+            })
+        })
+    }
+}
+
+/// File checksums from `<hash-file path="..." algo="sha256" expected="..."/>`
+/// tags. `algo` defaults to SHA-256 and `expected` is optional.
+pub struct HashFileTool;
+
+impl Tool for HashFileTool {
+    fn name(&self) -> &str {
+        "hash-file"
+    }
+
+    fn extract(&self, response: &str) -> Vec<ToolCall> {
+        extract_tag(self.name(), "hash-file", "path", response)
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>> {
+        Box::pin(async move {
+            let path = resolve_allowed_path(Path::new(&call.input), &ctx.settings.allowed_dirs)?;
+            let algorithm = call.arg("algo").and_then(HashAlgorithm::parse).unwrap_or(HashAlgorithm::Sha256);
+            let hash = verify_hash(&path, call.arg("expected"), algorithm)?;
+            let verdict = match hash.matches() {
+                Some(true) => "\nMatches the expected hash.",
+                Some(false) => "\nDOES NOT match the expected hash.",
+                None => "",
+            };
+            Ok(ToolOutput {
+                text: format!("[File Hash]\n{}\n{}: {}{}", hash.path.display(), hash.algorithm.name(), hash.hash, verdict),
+                artifact: Some(ToolArtifact::FileHash(hash)),
+            })
+        })
+    }
+}
+
+/// Reads a text file from `<read-file path="..."/>` tags
+pub struct FileReadTool;
+
+impl Tool for FileReadTool {
+    fn name(&self) -> &str {
+        "read-file"
+    }
+
+    fn extract(&self, response: &str) -> Vec<ToolCall> {
+        extract_tag(self.name(), "read-file", "path", response)
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>> {
+        Box::pin(async move {
+            let path = resolve_allowed_path(Path::new(&call.input), &ctx.settings.allowed_dirs)?;
+            if !path.is_file() {
+                bail!("{} is not a file", path.display());
+            }
+            let bytes = tokio::fs::read(&path).await?;
+            let content = String::from_utf8_lossy(&bytes).into_owned();
+            Ok(ToolOutput::text(format!("[File Contents]\n{}\n{}", path.display(), limit_output(content, ctx))))
+        })
+    }
+}
+
+/// Writes a file from `<edit-file path="...">new content</edit-file>` tags.
+/// With `find="old text"` only the first occurrence of that text is
+/// replaced by the tag's content; otherwise the whole file is.
+pub struct FileEditTool;
+
+impl FileEditTool {
+    /// The file, which may not exist yet, with its folder checked against
+    /// the allowed folders
+    fn target(path: &str, allowed_dirs: &[String]) -> Result<PathBuf> {
+        let path = Path::new(path);
+        let name = path.file_name().ok_or_else(|| anyhow!("{} is not a file path", path.display()))?;
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        Ok(resolve_allowed_path(parent, allowed_dirs)?.join(name))
+    }
+}
+
+impl Tool for FileEditTool {
+    fn name(&self) -> &str {
+        "edit-file"
+    }
+
+    fn extract(&self, response: &str) -> Vec<ToolCall> {
+        let tag_re = Regex::new(r"(?s)<edit-file\s+([^>]*)>\n?(.*?)</edit-file>").unwrap();
+        tag_re
+            .captures_iter(response)
+            .filter_map(|cap| {
+                let mut args = parse_attrs(cap.get(1)?.as_str());
+                let input = args.remove("path").filter(|p| !p.is_empty())?;
+                Some(ToolCall {
+                    tool: self.name().to_string(),
+                    input,
+                    args,
+                    body: Some(cap.get(2)?.as_str().to_string()),
+                })
+            })
+            .collect()
+    }
+
+    fn danger(&self, _call: &ToolCall, _ctx: &ExecutionContext) -> DangerLevel {
+        DangerLevel::NeedsConfirmation
+    }
+
+    fn describe(&self, call: &ToolCall) -> String {
+        let lines = call.body.as_deref().unwrap_or_default().lines().count();
+        match call.arg("find") {
+            Some(_) => format!("Edit {} (replace text with {} lines)", call.input, lines),
+            None => format!("Write {} ({} lines)", call.input, lines),
+        }
+    }
+
+    fn execute<'a>(&'a self, call: ToolCall, ctx: &'a ExecutionContext<'a>) -> BoxFuture<'a, Result<ToolOutput>> {
+        Box::pin(async move {
+            let path = Self::target(&call.input, &ctx.settings.allowed_dirs)?;
+            let new_text = call.body.clone().unwrap_or_default();
+            let content = match call.arg("find") {
+                Some(find) => {
+                    let current = tokio::fs::read_to_string(&path).await?;
+                    if !current.contains(find) {
+                        bail!("The text to replace wasn't found in {}", path.display());
+                    }
+                    current.replacen(find, &new_text, 1)
+                }
+                None => new_text,
+            };
+            tokio::fs::write(&path, &content).await?;
+            Ok(ToolOutput {
+                text: format!("[File Written]\n{} ({} bytes)", path.display(), content.len()),
+                artifact: Some(ToolArtifact::FileEdit(path)),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_commands_dedup() {
+        let response = "<command>ls -la</command>\n[EXECUTE] `ls -la`\n<command>pwd</command>";
+        let commands: Vec<String> = ShellCommandTool.extract(response).into_iter().map(|c| c.input).collect();
+        assert_eq!(commands, vec!["ls -la", "pwd"]);
+    }
+
+    #[test]
+    fn test_extract_csv_joins() {
+        let response = r#"<csv-join right="b.csv" left="a.csv" on="id" right-on="customer_id" how="left"/> <csv-join left="a.csv" on="id"/>"#;
+        let calls = CsvJoinTool.extract(response);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].input, "a.csv");
+        assert_eq!(calls[0].arg("right"), Some("b.csv"));
+        assert_eq!(calls[0].arg("right-on"), Some("customer_id"));
+        assert_eq!(calls[0].arg("how").and_then(JoinType::parse), Some(JoinType::Left));
+    }
+
+    #[tokio::test]
+    async fn test_edit_file_replaces_text() {
+        let dir = std::env::temp_dir().join(format!("lh-edit-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "Meeting on Monday\n").unwrap();
+
+        let response = format!(r#"<edit-file path="{}" find="Monday">Tuesday</edit-file>"#, path.display());
+        let calls = FileEditTool.extract(&response);
+        let settings = AppSettings { allowed_dirs: vec![dir.display().to_string()], ..AppSettings::default() };
+        let ctx = ExecutionContext { settings: &settings, execution_options: &ExecutionOptions::default(), working_dir: None };
+        FileEditTool.execute(calls[0].clone(), &ctx).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Meeting on Tuesday\n");
+
+        // Outside the allowed folders
+        let outside = ToolCall::new("edit-file", "/definitely/not/allowed.txt");
+        assert!(FileEditTool.execute(outside, &ctx).await.is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,122 @@
+//! Structured state carried between iterations of `agent_chat`
+//!
+//! Rather than making the AI re-read its own prose history, each iteration
+//! gets a compact JSON summary of what has been run, what is waiting on the
+//! user and where commands are running, appended to the system prompt.
+
+use crate::ToolResult;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct AgentTurnState {
+    /// Commands executed so far in this chat
+    pub completed_commands: Vec<ToolResult>,
+    /// Things waiting on the user (e.g. commands that need confirmation)
+    pub pending_questions: Vec<String>,
+    /// Directory commands run in, changed with `cd`
+    pub working_directory: Option<PathBuf>,
+}
+
+impl AgentTurnState {
+    pub fn is_empty(&self) -> bool {
+        self.completed_commands.is_empty()
+            && self.pending_questions.is_empty()
+            && self.working_directory.is_none()
+    }
+
+    /// Whether a command already ran in this chat
+    pub fn has_run(&self, cmd: &str) -> bool {
+        self.completed_commands.iter().any(|t| t.command == cmd)
+    }
+
+    /// Handle a plain `cd <dir>` command. Returns the message for the AI,
+    /// or `None` if the command isn't a bare `cd`.
+    pub fn change_directory(&mut self, cmd: &str) -> Option<String> {
+        let target = cmd.trim().strip_prefix("cd")?;
+        if !target.is_empty() && !target.starts_with(char::is_whitespace) {
+            return None; // e.g. "cdrecord"
+        }
+        let target = target.trim().trim_matches(|c| c == '"' || c == '\'');
+        if target.contains(['&', '|', ';']) {
+            return None;
+        }
+
+        let home = dirs::home_dir().unwrap_or_default();
+        let dir = match target {
+            "" | "~" => home,
+            t if t.starts_with("~/") => home.join(&t[2..]),
+            t => {
+                let base = self.working_directory.clone().unwrap_or(home);
+                base.join(t)
+            }
+        };
+
+        Some(match dir.canonicalize() {
+            Ok(dir) if dir.is_dir() => {
+                let message = format!("[Changed directory to {}]", dir.display());
+                self.working_directory = Some(dir);
+                message
+            }
+            _ => format!("[cd failed: {} is not a directory]", dir.display()),
+        })
+    }
+
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.working_directory.as_deref()
+    }
+
+    /// JSON summary for the system prompt. Command output itself is left in
+    /// the conversation; only outcomes are listed here.
+    pub fn to_context_json(&self) -> String {
+        let commands: Vec<_> = self
+            .completed_commands
+            .iter()
+            .map(|t| {
+                json!({
+                    "command": t.command,
+                    "exit_code": t.result.exit_code,
+                    "success": t.result.success,
+                    "summary": t.result.summary,
+                    "output_truncated": t.result.is_truncated(),
+                })
+            })
+            .collect();
+
+        json!({
+            "completed_commands": commands,
+            "pending_questions": self.pending_questions,
+            "working_directory": self.working_directory.as_ref().map(|d| d.display().to_string()),
+        })
+        .to_string()
+    }
+
+    /// System prompt with the current state appended
+    pub fn system_prompt_with_state(&self, base: &str) -> String {
+        if self.is_empty() {
+            return base.to_string();
+        }
+        format!(
+            "{}\n\n## Current Agent State\nWhat has happened so far in this request (JSON):\n{}\n\
+            Don't repeat completed commands unless the user asks.",
+            base,
+            self.to_context_json()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_directory() {
+        let mut state = AgentTurnState::default();
+        assert!(state.change_directory("cdrecord --help").is_none());
+        assert!(state.change_directory("cd / && ls").is_none());
+
+        state.change_directory("cd /").unwrap();
+        assert_eq!(state.working_directory(), Some(Path::new("/")));
+        assert!(state.system_prompt_with_state("base").contains("\"working_directory\":\"/\""));
+    }
+}
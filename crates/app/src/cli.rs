@@ -0,0 +1,124 @@
+//! Command-line options
+//!
+//! Little Helper is a GUI app, but a few options choose how it starts:
+//! the chat mode, the provider to try first and files to open. Run with
+//! `--completions <shell>` to print a completion script for them.
+
+use crate::settings_ui::PROVIDERS;
+use crate::ChatMode;
+use clap::builder::PossibleValuesParser;
+use clap::{CommandFactory, Parser, ValueHint};
+use clap_complete::Shell;
+use shared::settings::ModelProvider;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(about = "Little Helper - Personal AI Assistant")]
+pub struct Args {
+    /// Print a completion script for SHELL and exit
+    #[arg(long, value_name = "SHELL")]
+    pub completions: Option<Shell>,
+    /// Chat mode to start in
+    #[arg(long, value_parser = PossibleValuesParser::new(ChatMode::ALL.map(|m| m.key())))]
+    pub mode: Option<String>,
+    /// Provider to try first this session; the saved order isn't changed
+    #[arg(long, value_parser = PossibleValuesParser::new(PROVIDERS.iter().copied()))]
+    pub provider: Option<String>,
+    /// File to open in the preview panel; may be given more than once
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub file: Vec<PathBuf>,
+}
+
+/// The completion script for `shell`, with how to install it at the top
+pub fn write_completions(shell: Shell, out: &mut impl Write) -> io::Result<()> {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, &name, &mut script);
+    let script = String::from_utf8_lossy(&script);
+
+    let header: String = install_instructions(shell, &name).iter().map(|line| format!("# {}\n", line)).collect();
+    // zsh only looks for `#compdef` on the first line
+    match script.split_once('\n') {
+        Some((first, rest)) if first.starts_with("#compdef") => write!(out, "{}\n{}\n{}", first, header, rest),
+        _ => write!(out, "{}\n{}", header, script),
+    }
+}
+
+fn install_instructions(shell: Shell, name: &str) -> Vec<String> {
+    match shell {
+        Shell::Bash => vec![
+            format!("Bash completion for {}. To install:", name),
+            format!("  {} --completions bash > ~/.local/share/bash-completion/completions/{}", name, name),
+            "then open a new terminal (needs the bash-completion package).".to_string(),
+        ],
+        Shell::Zsh => vec![
+            format!("Zsh completion for {}. To install:", name),
+            format!("  mkdir -p ~/.zfunc && {} --completions zsh > ~/.zfunc/_{}", name, name),
+            "then add these lines to ~/.zshrc, before any other compinit, and open a new terminal:".to_string(),
+            "  fpath=(~/.zfunc $fpath)".to_string(),
+            "  autoload -Uz compinit && compinit".to_string(),
+        ],
+        Shell::Fish => vec![
+            format!("Fish completion for {}. To install:", name),
+            format!("  {} --completions fish > ~/.config/fish/completions/{}.fish", name, name),
+            "Fish picks it up in new sessions.".to_string(),
+        ],
+        Shell::PowerShell => vec![
+            format!("PowerShell completion for {}. To install, add it to your profile:", name),
+            format!("  {} --completions powershell >> $PROFILE", name),
+            "then restart PowerShell.".to_string(),
+        ],
+        _ => vec![format!("{} completion for {}. See your shell's documentation for where to put it.", shell, name)],
+    }
+}
+
+/// Put `provider` first in the general order and in every mode that has
+/// its own
+pub fn prefer_provider(model: &mut ModelProvider, provider: &str) {
+    let mode_orders = model.mode_providers.values_mut().filter(|order| !order.is_empty());
+    for order in std::iter::once(&mut model.provider_preference).chain(mode_orders) {
+        order.retain(|p| p != provider);
+        order.insert(0, provider.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions() {
+        Args::command().debug_assert();
+        let args = Args::try_parse_from(["app", "--mode", "research", "--file", "a.csv", "--file", "b.md"]).unwrap();
+        assert_eq!(args.mode.as_deref(), Some("research"));
+        assert_eq!(args.file, vec![PathBuf::from("a.csv"), PathBuf::from("b.md")]);
+        assert!(Args::try_parse_from(["app", "--provider", "nope"]).is_err());
+
+        let script = |shell| {
+            let mut out = Vec::new();
+            write_completions(shell, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let bash = script(Shell::Bash);
+        assert!(bash.starts_with("# Bash completion for app. To install:\n"));
+        assert!(bash.contains("find fix research data content"));
+        assert!(bash.contains("local openai anthropic gemini cohere"));
+        let zsh = script(Shell::Zsh);
+        assert!(zsh.starts_with("#compdef app\n# Zsh completion"));
+        assert!(zsh.contains("_files"));
+    }
+
+    #[test]
+    fn test_prefer_provider() {
+        let mut model = shared::settings::AppSettings::default().model;
+        model.provider_preference = vec!["local".into(), "openai".into()];
+        model.mode_providers.insert("research".into(), vec!["anthropic".into()]);
+        model.mode_providers.insert("find".into(), Vec::new());
+        prefer_provider(&mut model, "openai");
+        assert_eq!(model.provider_preference, ["openai", "local"]);
+        assert_eq!(model.mode_providers["research"], ["openai", "anthropic"]);
+        assert!(model.mode_providers["find"].is_empty());
+    }
+}
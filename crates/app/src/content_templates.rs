@@ -0,0 +1,176 @@
+//! Content mode template picker and "Save as template" form
+
+use crate::AppState;
+use eframe::egui;
+use services::templates::ContentTemplate;
+
+/// Characters of the example shown on a template card
+const CARD_PREVIEW_CHARS: usize = 120;
+const CARD_WIDTH: f32 = 200.0;
+
+/// Platforms offered in the save form; templates on disk may use others
+const PLATFORMS: &[&str] = &["twitter", "instagram", "linkedin", "facebook", "blog", "email"];
+
+/// Window with a card per template. "Use" makes a template active for
+/// Content mode and puts its prompt prefix in the chat input.
+pub fn render_templates_window(s: &mut AppState, ctx: &egui::Context) {
+    let mut open = s.show_templates;
+    let mut chosen = None;
+
+    egui::Window::new("Templates")
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(660.0)
+        .show(ctx, |ui| {
+            ui.label(
+                egui::RichText::new(format!("Your templates are saved in {}", s.templates.dir().display()))
+                    .size(12.0)
+                    .weak(),
+            );
+            ui.add_space(6.0);
+            egui::ScrollArea::vertical().max_height(420.0).show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for (i, template) in s.templates.templates.iter().enumerate() {
+                        if template_card(ui, template) {
+                            chosen = Some(i);
+                        }
+                    }
+                });
+            });
+        });
+
+    if let Some(i) = chosen {
+        let template = s.templates.templates[i].clone();
+        s.input_text = template.prompt_prefix.clone();
+        s.active_template = Some(template);
+        s.focus_input_at_end(ctx, egui::Id::new("chat_input"));
+        open = false;
+    }
+    s.show_templates = open;
+}
+
+/// Returns true when "Use" is clicked
+fn template_card(ui: &mut egui::Ui, template: &ContentTemplate) -> bool {
+    let mut used = false;
+    egui::Frame::group(ui.style())
+        .rounding(egui::Rounding::same(8.0))
+        .show(ui, |ui| {
+            ui.set_width(CARD_WIDTH);
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(&template.name).strong());
+                if template.builtin {
+                    ui.label(egui::RichText::new("built-in").size(11.0).weak());
+                }
+            });
+            ui.label(egui::RichText::new(&template.platform).size(12.0).color(egui::Color32::from_rgb(70, 130, 180)));
+            if !template.tags.is_empty() {
+                ui.label(egui::RichText::new(template.tags.join(" · ")).size(11.0).weak());
+            }
+            let example = template.example_output.trim();
+            if !example.is_empty() {
+                let mut preview: String = example.chars().take(CARD_PREVIEW_CHARS).collect();
+                if example.chars().count() > CARD_PREVIEW_CHARS {
+                    preview.push('…');
+                }
+                ui.label(egui::RichText::new(preview).size(12.0).italics());
+            }
+            ui.add_space(4.0);
+            used = ui.button("Use").clicked();
+        });
+    used
+}
+
+/// Draft a template from what's in the chat: the input (or the last
+/// request) as the prompt prefix and the last reply as the example
+pub fn draft_from_chat(s: &AppState) -> ContentTemplate {
+    let last = |role: &str| {
+        s.chat_history
+            .iter()
+            .rev()
+            .find(|m| m.role == role)
+            .map(|m| m.content.clone())
+            .unwrap_or_default()
+    };
+    let prompt_prefix = if s.input_text.trim().is_empty() { last("user") } else { s.input_text.clone() };
+    ContentTemplate {
+        name: String::new(),
+        platform: s
+            .active_template
+            .as_ref()
+            .map(|t| t.platform.clone())
+            .unwrap_or_else(|| PLATFORMS[0].to_string()),
+        prompt_prefix,
+        example_output: last("assistant"),
+        tags: Vec::new(),
+        builtin: false,
+    }
+}
+
+/// The "Save as template" form, shown while there's a draft
+pub fn render_save_template_window(s: &mut AppState, ctx: &egui::Context) {
+    let Some(mut draft) = s.template_draft.take() else { return };
+    let mut open = true;
+    let mut save = false;
+    let tags_id = egui::Id::new("template_tags");
+
+    egui::Window::new("Save as template")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            let mut tags: String = ui.data_mut(|d| d.get_temp(tags_id).unwrap_or_else(|| draft.tags.join(", ")));
+            egui::Grid::new("save_template").num_columns(2).spacing(egui::vec2(12.0, 6.0)).show(ui, |ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut draft.name);
+                ui.end_row();
+
+                ui.label("Platform");
+                egui::ComboBox::from_id_source("template_platform")
+                    .selected_text(&draft.platform)
+                    .show_ui(ui, |ui| {
+                        for platform in PLATFORMS {
+                            ui.selectable_value(&mut draft.platform, platform.to_string(), *platform);
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Tags");
+                ui.add(egui::TextEdit::singleline(&mut tags).hint_text("comma separated"));
+                ui.end_row();
+
+                ui.label("Prompt");
+                ui.add(egui::TextEdit::multiline(&mut draft.prompt_prefix).desired_rows(2));
+                ui.end_row();
+
+                ui.label("Example");
+                ui.add(egui::TextEdit::multiline(&mut draft.example_output).desired_rows(6));
+                ui.end_row();
+            });
+            draft.tags = tags.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect();
+            ui.data_mut(|d| d.insert_temp(tags_id, tags));
+
+            if let Some(error) = &s.template_status {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            ui.add_space(6.0);
+            save = ui.button("Save").clicked();
+        });
+
+    if save {
+        match s.templates.save(draft.clone()) {
+            Ok(_) => {
+                s.template_status = None;
+                ctx.data_mut(|d| d.remove::<String>(tags_id));
+                return;
+            }
+            Err(e) => s.template_status = Some(format!("Couldn't save template: {}", e)),
+        }
+    }
+    if open {
+        s.template_draft = Some(draft);
+    } else {
+        s.template_status = None;
+        ctx.data_mut(|d| d.remove::<String>(tags_id));
+    }
+}
@@ -2,155 +2,93 @@
 //!
 //! Preloads knowledge for the agent:
 //! - System information for tech support
-//! - Campaign documents for content creation
 //! - Persona files for audience targeting
 //! - Project knowledge for research
+//! - The file currently open in the preview panel
 
+use services::personas::{discover_personas, format_persona};
 use std::fs;
+use std::path::Path;
 use std::process::Command;
-
-/// Load campaign context documents for the agent
-/// Returns full content of key campaign files for deep context
-pub fn load_campaign_context() -> String {
-    let mut context = String::new();
-
-    // MCP project paths
-    let mcp_base = dirs::home_dir()
-        .map(|h| h.join("Projects/MCP-research-content-automation-engine"))
-        .unwrap_or_default();
-
-    // Check if the project exists
-    if !mcp_base.exists() {
-        return "CAMPAIGN CONTEXT: MCP project not found on this system. Campaign-specific features will be limited.\n".to_string();
-    }
-
-    // Priority documents to load (in order of importance)
-    let docs = [
-        // Core campaign strategy
-        (
-            "Campaign Spec",
-            "specs/002-mcp-marine-conservation/spec.md",
-            15000,
-        ),
-        (
-            "Campaign Plan",
-            "specs/002-mcp-marine-conservation/plan.md",
-            15000,
-        ),
-        // Content
-        ("Content Summary", "MCP_Content_Summary_FINAL.md", 20000),
-        ("Content Calendar", "FINAL_MCP_Content_Calendar.json", 20000),
-        // Video content
-        ("Video Specs", "MCP_Video_Content_Specifications.md", 10000),
-        (
-            "Video Scripts (Northern BC Voice)",
-            "MCP_Video_Scripts_NorthernBC_Voice.md",
-            12000,
-        ),
-        // Visual assets
-        (
-            "Visual Requirements",
-            "MCP_Visual_Asset_Requirements.md",
-            8000,
-        ),
-    ];
-
-    context.push_str("=== MCP MARINE CONSERVATION CAMPAIGN - FULL CONTEXT ===\n\n");
-    context.push_str(
-        "You have FULL ACCESS to the Marine Conservation Plan (MCP) campaign materials.\n",
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use viewers::FileType;
+
+/// How much of the open file is shown to the agent
+const FILE_CONTEXT_MAX_CHARS: usize = 2000;
+
+/// Files larger than this are described by name and type only
+const FILE_CONTEXT_MAX_BYTES: u64 = 1024 * 1024;
+
+/// How often system info is gathered again, to catch new drives and installs
+const SYSTEM_INFO_REFRESH: Duration = Duration::from_secs(5 * 60);
+
+/// Describe the file open in the preview panel for the system prompt.
+/// Text files include their first 2000 characters; binary or very large
+/// files only get their name and type.
+pub fn load_file_context(path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+    let file_type = FileType::from_path(path);
+
+    let is_text = matches!(
+        file_type,
+        FileType::Text
+            | FileType::Markdown
+            | FileType::Html
+            | FileType::Csv
+            | FileType::Json
+            | FileType::Yaml
+            | FileType::Unknown
     );
-    context
-        .push_str("Use this detailed knowledge for content creation, research, and strategy.\n\n");
-
-    let mut loaded_count = 0;
-    for (name, path, max_chars) in docs {
-        let full_path = mcp_base.join(path);
-        if let Ok(content) = fs::read_to_string(&full_path) {
-            loaded_count += 1;
-            // Truncate if over limit but keep more content
-            let truncated = if content.len() > max_chars {
-                format!(
-                    "{}...\n[Truncated at {} chars - full file has {} chars]",
-                    &content[..max_chars],
-                    max_chars,
-                    content.len()
-                )
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(u64::MAX);
+
+    // Binary content fails UTF-8 decoding, which is good enough to tell them apart
+    let content = if is_text && size <= FILE_CONTEXT_MAX_BYTES {
+        fs::read_to_string(path).ok()
+    } else {
+        None
+    };
+
+    match content {
+        Some(content) => {
+            let excerpt: String = content.chars().take(FILE_CONTEXT_MAX_CHARS).collect();
+            let truncated = if excerpt.len() < content.len() {
+                "\n[... truncated]"
             } else {
-                content
+                ""
             };
-
-            context.push_str(&format!("=== {} ===\n", name));
-            context.push_str(&format!("Source: {}\n\n", path));
-            context.push_str(&truncated);
-            context.push_str("\n\n");
-        }
-    }
-
-    // Load research reports if they exist
-    let reports_dir = mcp_base.join("data/reports");
-    if reports_dir.exists() {
-        if let Ok(entries) = fs::read_dir(&reports_dir) {
-            let reports: Vec<_> = entries.flatten().collect();
-            if !reports.is_empty() {
-                context.push_str("=== Available Research Reports ===\n");
-                for entry in reports {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    context.push_str(&format!("- {}\n", name));
-                }
-                context.push('\n');
-            }
+            format!(
+                "The user currently has `{}` open ({}) with content:\n```\n{}{}\n```\n\n\
+                When they say \"this file\" or \"this code\", they mean this one.\n\n",
+                file_name,
+                path.display(),
+                excerpt,
+                truncated
+            )
         }
+        None => format!(
+            "The user currently has `{}` open ({}, {} file). Its content isn't shown to you.\n\n",
+            file_name,
+            path.display(),
+            file_type.display_name()
+        ),
     }
-
-    context.push_str(&format!(
-        "=== END CAMPAIGN CONTEXT ({} documents loaded) ===\n\n",
-        loaded_count
-    ));
-
-    context
 }
 
-/// Load persona files from ~/Process/personas/
+/// Load persona files from ~/Process/personas/ (and the other persona folders)
 /// Returns all personas as context for content generation
 pub fn load_personas() -> String {
     let mut context = String::new();
-
-    // Check multiple possible persona locations
-    let persona_dirs: Vec<std::path::PathBuf> = vec![
-        dirs::home_dir()
-            .map(|h| h.join("Process/personas"))
-            .unwrap_or_default(),
-        dirs::home_dir()
-            .map(|h| h.join("Projects/personas"))
-            .unwrap_or_default(),
-        dirs::home_dir()
-            .map(|h| h.join("Documents/personas"))
-            .unwrap_or_default(),
-    ];
-
     let mut loaded_count = 0;
     let mut all_personas = Vec::new();
 
-    for persona_dir in &persona_dirs {
-        if !persona_dir.exists() {
-            continue;
-        }
-
-        if let Ok(entries) = fs::read_dir(persona_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().map(|e| e == "md").unwrap_or(false) {
-                    if let Ok(content) = fs::read_to_string(&path) {
-                        let name = path
-                            .file_stem()
-                            .map(|s| s.to_string_lossy().to_string())
-                            .unwrap_or_else(|| "Unknown".to_string());
-
-                        all_personas.push((name, content, path.display().to_string()));
-                        loaded_count += 1;
-                    }
-                }
-            }
+    for persona in discover_personas() {
+        if let Ok(content) = fs::read_to_string(&persona.path) {
+            all_personas.push(format_persona(&persona.name, &content, &persona.path));
+            loaded_count += 1;
         }
     }
 
@@ -162,14 +100,8 @@ pub fn load_personas() -> String {
     context.push_str("Use these personas to tailor content to specific audiences.\n");
     context.push_str("Match language, concerns, and messaging to the target persona.\n\n");
 
-    for (name, content, path) in all_personas {
-        context.push_str(&format!(
-            "=== PERSONA: {} ===\n",
-            name.to_uppercase().replace("-", " ")
-        ));
-        context.push_str(&format!("Source: {}\n\n", path));
-        context.push_str(&content);
-        context.push_str("\n\n");
+    for persona in all_personas {
+        context.push_str(&persona);
     }
 
     context.push_str(&format!(
@@ -320,10 +252,20 @@ pub fn get_system_info() -> String {
     info
 }
 
-/// Get a brief campaign summary for system prompts (includes system info)
-pub fn get_campaign_summary() -> String {
-    let system_info = get_system_info();
+/// Keep `target` filled with the latest `get_system_info`, starting now
+/// and then every few minutes, on a background thread
+pub fn keep_system_info_fresh(target: Arc<RwLock<String>>) {
+    std::thread::spawn(move || loop {
+        let info = get_system_info();
+        if target.read().map_or(true, |current| *current != info) {
+            *target.write().unwrap_or_else(|e| e.into_inner()) = info;
+        }
+        std::thread::sleep(SYSTEM_INFO_REFRESH);
+    });
+}
 
+/// Get a brief campaign summary for system prompts (includes system info)
+pub fn get_campaign_summary(system_info: &str) -> String {
     format!(
         r#"
 SYSTEM CONTEXT:
@@ -0,0 +1,215 @@
+//! Files side panel
+//!
+//! A tree of the allowed folders. Each folder's contents are read on a
+//! background thread the first time it is expanded, so large or slow
+//! folders don't stall the UI.
+
+use eframe::egui;
+use services::stats::format_bytes;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// What the user did with a file in the panel
+pub enum FileAction {
+    Open(PathBuf),
+    CheckHash(PathBuf),
+    /// Ask the AI for clearer names for the files in a folder
+    SuggestRenames(PathBuf),
+    /// Plan renames and moves in a folder by hand
+    Organize(PathBuf),
+}
+
+#[derive(Clone)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+enum Listing {
+    Loading,
+    Loaded(Vec<FileEntry>),
+    Failed(String),
+}
+
+pub struct FileBrowser {
+    pub filter: String,
+    listings: HashMap<PathBuf, Listing>,
+    tx: Sender<(PathBuf, Result<Vec<FileEntry>, String>)>,
+    rx: Receiver<(PathBuf, Result<Vec<FileEntry>, String>)>,
+}
+
+impl Default for FileBrowser {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            filter: String::new(),
+            listings: HashMap::new(),
+            tx,
+            rx,
+        }
+    }
+}
+
+impl FileBrowser {
+    /// Draw the tree for `roots`. Returns what the user did with a file.
+    pub fn ui(&mut self, ui: &mut egui::Ui, roots: &[PathBuf]) -> Option<FileAction> {
+        while let Ok((dir, result)) = self.rx.try_recv() {
+            let listing = match result {
+                Ok(entries) => Listing::Loaded(entries),
+                Err(e) => Listing::Failed(e),
+            };
+            self.listings.insert(dir, listing);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.filter)
+                    .hint_text("Filter by name")
+                    .desired_width(ui.available_width() - 28.0),
+            );
+            if ui.small_button("↻").on_hover_text("Reload folders").clicked() {
+                self.listings.clear();
+            }
+        });
+        ui.add_space(4.0);
+
+        let filter = self.filter.trim().to_lowercase();
+        let mut clicked = None;
+        egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+            for root in roots {
+                let name = root.display().to_string();
+                let header = egui::CollapsingHeader::new(egui::RichText::new(format!("📁 {}", name)).strong())
+                    .id_source(("file_browser", root));
+                header.show(ui, |ui| self.children_ui(ui, root, &filter, &mut clicked));
+            }
+        });
+        if self.listings.values().any(|l| matches!(l, Listing::Loading)) {
+            ui.ctx().request_repaint();
+        }
+        clicked
+    }
+
+    fn children_ui(&mut self, ui: &mut egui::Ui, dir: &Path, filter: &str, clicked: &mut Option<FileAction>) {
+        let entries = match self.listings.get(dir) {
+            None => {
+                self.load(dir);
+                return;
+            }
+            Some(Listing::Loading) => {
+                ui.spinner();
+                return;
+            }
+            Some(Listing::Failed(e)) => {
+                ui.colored_label(egui::Color32::RED, e);
+                return;
+            }
+            Some(Listing::Loaded(entries)) => entries.clone(),
+        };
+
+        let visible: Vec<&FileEntry> = entries.iter().filter(|e| self.matches(e, filter)).collect();
+        if visible.is_empty() {
+            ui.label(egui::RichText::new(if filter.is_empty() { "Empty" } else { "No matches" }).weak());
+        }
+        for entry in visible {
+            if entry.is_dir {
+                let folder = egui::CollapsingHeader::new(format!("📁 {}", entry.name))
+                    .id_source(("file_browser", &entry.path))
+                    .show(ui, |ui| self.children_ui(ui, &entry.path, filter, clicked));
+                folder.header_response.context_menu(|ui| {
+                    if ui.button("Suggest better names…").clicked() {
+                        *clicked = Some(FileAction::SuggestRenames(entry.path.clone()));
+                        ui.close_menu();
+                    }
+                    if ui.button("Organize…").clicked() {
+                        *clicked = Some(FileAction::Organize(entry.path.clone()));
+                        ui.close_menu();
+                    }
+                });
+            } else {
+                ui.horizontal(|ui| {
+                    let response = ui.selectable_label(false, &entry.name).on_hover_text(entry.path.display().to_string());
+                    if response.clicked() {
+                        *clicked = Some(FileAction::Open(entry.path.clone()));
+                    }
+                    response.context_menu(|ui| {
+                        if ui.button("Check hash…").clicked() {
+                            *clicked = Some(FileAction::CheckHash(entry.path.clone()));
+                            ui.close_menu();
+                        }
+                    });
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label(egui::RichText::new(format_bytes(entry.size)).small().color(egui::Color32::GRAY));
+                    });
+                });
+            }
+        }
+    }
+
+    /// An entry is shown if its name contains the filter, or it is a folder
+    /// with an already loaded entry that does
+    fn matches(&self, entry: &FileEntry, filter: &str) -> bool {
+        if filter.is_empty() || entry.name.to_lowercase().contains(filter) {
+            return true;
+        }
+        match self.listings.get(&entry.path) {
+            Some(Listing::Loaded(children)) if entry.is_dir => children.iter().any(|c| self.matches(c, filter)),
+            _ => false,
+        }
+    }
+
+    fn load(&mut self, dir: &Path) {
+        self.listings.insert(dir.to_path_buf(), Listing::Loading);
+        let tx = self.tx.clone();
+        let dir = dir.to_path_buf();
+        std::thread::spawn(move || {
+            let result = list_dir(&dir).map_err(|e| format!("Couldn't read folder: {}", e));
+            let _ = tx.send((dir, result));
+        });
+    }
+}
+
+/// Immediate children of `dir`, folders first, hidden files skipped
+pub fn list_dir(dir: &Path) -> std::io::Result<Vec<FileEntry>> {
+    let mut entries: Vec<FileEntry> = fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                return None;
+            }
+            let meta = e.metadata().ok()?;
+            Some(FileEntry {
+                path: e.path(),
+                name,
+                is_dir: meta.is_dir(),
+                size: meta.len(),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_dir_sorts_folders_first() {
+        let dir = std::env::temp_dir().join(format!("lh-files-{}", std::process::id()));
+        fs::create_dir_all(dir.join("zeta")).unwrap();
+        fs::write(dir.join("Alpha.txt"), "hello").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+
+        let entries = list_dir(&dir).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["zeta", "Alpha.txt"]);
+        assert_eq!(entries[1].size, 5);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,111 @@
+//! Recorded sequences of chat requests
+//!
+//! While recording, every message sent is kept with the mode it was sent
+//! in. A saved macro sends the same messages again, one after another, in
+//! the same modes. Macros are TOML files in `~/.config/little-helper/macros/`.
+
+use crate::ChatMode;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroStep {
+    pub mode: ChatMode,
+    pub input: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+}
+
+pub struct MacroLibrary {
+    dir: PathBuf,
+    pub macros: Vec<Macro>,
+}
+
+impl Default for MacroLibrary {
+    fn default() -> Self {
+        let dir = dirs::config_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("little-helper")
+            .join("macros");
+        Self::load_from(dir)
+    }
+}
+
+impl MacroLibrary {
+    /// Every `.toml` file in `dir`, sorted by name. Files that don't parse
+    /// are skipped.
+    pub fn load_from(dir: PathBuf) -> Self {
+        let mut macros: Vec<Macro> = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .filter_map(|text| toml::from_str(&text).ok())
+            .collect();
+        macros.sort_by_key(|m| m.name.to_lowercase());
+        Self { dir, macros }
+    }
+
+    /// Write `recorded` to the macros folder, replacing one with the same name
+    pub fn save(&mut self, recorded: Macro) -> Result<PathBuf> {
+        let name = recorded.name.trim();
+        if name.is_empty() {
+            bail!("Give the macro a name");
+        }
+        if recorded.steps.is_empty() {
+            bail!("Nothing was recorded");
+        }
+        let path = self.dir.join(format!("{}.toml", file_stem(name)));
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&path, toml::to_string_pretty(&recorded)?)?;
+
+        *self = Self::load_from(std::mem::take(&mut self.dir));
+        Ok(path)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// "Morning checks!" -> "morning_checks"
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    stem.split('_').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_reload() {
+        let dir = std::env::temp_dir().join(format!("lh-macros-{}", std::process::id()));
+        let mut library = MacroLibrary::load_from(dir.clone());
+        assert!(library.save(Macro { name: "Empty".to_string(), steps: Vec::new() }).is_err());
+
+        let recorded = Macro {
+            name: "Morning checks".to_string(),
+            steps: vec![
+                MacroStep { mode: ChatMode::Fix, input: "run the tests".to_string() },
+                MacroStep { mode: ChatMode::Find, input: "show git status".to_string() },
+            ],
+        };
+        let path = library.save(recorded.clone()).unwrap();
+        assert_eq!(path.file_name().unwrap(), "morning_checks.toml");
+        assert_eq!(MacroLibrary::load_from(dir.clone()).macros, vec![recorded]);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,25 +1,154 @@
-use agent_host::AgentHost;
+use agent_host::{extract_paths, AgentHost, CommandResult, CommitSuggestion, JoinedCsv, StructuredTable, TurnDebugInfo};
 use eframe::egui;
 use parking_lot::Mutex;
-use shared::agent_api::ChatMessage as ApiChatMessage;
-use shared::settings::AppSettings;
+use services::file_tools::{FileHash, HashAlgorithm};
+use services::organizer::{self, OrganizeHistoryEntry};
+use services::stats::{format_bytes, DirStats};
+use services::templates::{ContentTemplate, TemplateLibrary};
+use shared::agent_api::{token_count, ChatMessage as ApiChatMessage, MessageAttachment};
+use shared::settings::{AppSettings, ContentSchedule, ResponseLength, TimeFormat, WatchMode};
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use viewers::{
-    csv_viewer::CsvViewer, image_viewer::ImageViewer, json_viewer::JsonViewer,
-    text_viewer::TextViewer, html_viewer::HtmlViewer, pdf_viewer::PdfViewer,
-    FileType,
+    csv_viewer::CsvViewer, image_viewer::{self, ImageViewer}, json_viewer::JsonViewer,
+    text_viewer::TextViewer, yaml_viewer::YamlViewer, FileType, LoadedContent, NullViewer, Viewer,
 };
 
 /// Result from background AI generation
+#[derive(Default)]
 struct AiResult {
+    /// "assistant", or "summary" for a /summarize reply
+    role: &'static str,
     response: String,
     preview_file: Option<PathBuf>,
+    scheduled_posts: Vec<ContentSchedule>,
+    interactive_command: Option<String>,
+    /// Key takeaways for long Research responses
+    summary: Option<String>,
+    dir_stats: Vec<DirStats>,
+    debug_info: Option<TurnDebugInfo>,
+    /// Table-shaped command output, by command
+    tables: Vec<(String, StructuredTable)>,
+    /// Shown in the preview panel when set
+    joined_csv: Option<JoinedCsv>,
+    file_hashes: Vec<FileHash>,
+    /// Files listed in command output, by command
+    command_paths: Vec<(String, Vec<PathBuf>)>,
+    /// Suggested next questions for Research responses
+    followup_questions: Vec<String>,
+    /// Offer to commit files the agent edited
+    commit_suggestion: Option<CommitSuggestion>,
+    /// A command that failed for lack of admin rights
+    needs_elevation: Option<String>,
     error: Option<String>,
 }
 
+/// Something open in the preview panel
+struct PreviewTab {
+    /// None for things that aren't files, like a terminal
+    path: Option<PathBuf>,
+    /// Shown in the tab bar
+    title: String,
+    viewer: Box<dyn Viewer>,
+}
+
+/// Commit the agent offered, with the message as the user edits it
+struct PendingCommit {
+    suggestion: CommitSuggestion,
+    message: String,
+}
+
+/// Offer to rerun a command that failed for lack of admin rights
+struct SudoDialogState {
+    command: String,
+    /// Assistant message whose result for `command` gets replaced
+    message: usize,
+    error: Option<String>,
+    /// Edited in place by the dialog and wiped when it closes. Reserved up
+    /// front so typing doesn't leave copies behind in freed memory.
+    password: String,
+}
+
+impl SudoDialogState {
+    fn new(command: String, message: usize) -> Self {
+        Self { command, message, error: None, password: String::with_capacity(256) }
+    }
+}
+
+impl Drop for SudoDialogState {
+    fn drop(&mut self) {
+        wipe(&mut self.password);
+    }
+}
+
+/// File picked for "Check hash" in the Files panel
+struct HashCheck {
+    path: PathBuf,
+    expected: String,
+    algorithm: HashAlgorithm,
+}
+
+/// A command the agent wants to run, waiting for the user's answer
+struct ConfirmationRequest {
+    command: String,
+    danger: agent_host::DangerLevel,
+    /// The command after variable expansion, when that differs
+    expanded: Option<String>,
+    /// What an unfamiliar command is, from its man page and --help
+    help: Option<String>,
+    respond: tokio::sync::oneshot::Sender<bool>,
+}
+
+impl ConfirmationRequest {
+    /// Ask about `command`, with its expansion and man page summary
+    fn new(command: &str, danger: agent_host::DangerLevel, respond: tokio::sync::oneshot::Sender<bool>) -> Self {
+        let expanded = agent_host::preview_expansion(command, agent_host::Shell::current())
+            .ok()
+            .filter(|expanded| expanded != command);
+        let help = match danger {
+            agent_host::DangerLevel::NeedsConfirmation => agent_host::man_page_summary(command).ok().flatten(),
+            _ => None,
+        };
+        Self { command: command.to_string(), danger, expanded, help, respond }
+    }
+}
+
+/// All the commands in one AI response, waiting for the user to pick
+/// which to run
+struct BatchPreviewRequest {
+    commands: Vec<(String, agent_host::DangerLevel)>,
+    selected: Vec<bool>,
+    respond: tokio::sync::oneshot::Sender<Vec<bool>>,
+}
+
+/// A command needs a folder outside the allowed ones
+struct DirAccessRequest {
+    dir: PathBuf,
+    respond: tokio::sync::oneshot::Sender<bool>,
+}
+
+/// Messages of chat history sent with each request
+const API_HISTORY_MESSAGES: usize = 10;
+/// Assumed context window for models we don't know
+const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+/// Tabs in the preview panel; the oldest closes to make room for more
+const MAX_PREVIEW_TABS: usize = 8;
+/// How often open text files are checked for changes in `WatchMode::Poll`
+const FILE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How often to check whether a provider is reachable again while offline
+const RECONNECT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Inline image previews in chat bubbles
+const THUMBNAIL_MAX_SIZE: u32 = 200;
+const MAX_INLINE_IMAGES: usize = 3;
+
+/// Loaded chat thumbnails by path (`None` if the image failed to load)
+type ThumbnailCache = HashMap<PathBuf, Option<egui::TextureHandle>>;
+
 // Default mascot image (boss's dog!)
 const DEFAULT_MASCOT: &[u8] = include_bytes!("../assets/default_mascot.png");
 
@@ -29,7 +158,68 @@ use secrets::OPENAI_API_KEY;
 
 // Campaign context loader
 mod context;
-use context::{get_campaign_summary, load_campaign_context, load_personas, load_ddd_workflow};
+use context::{get_campaign_summary, get_system_info, keep_system_info_fresh, load_file_context, load_personas, load_ddd_workflow};
+
+// Inline file path completion for the chat input
+mod path_completer;
+use path_completer::PathCompleter;
+
+// Content mode post schedule
+mod schedule;
+
+// Settings window
+mod settings_ui;
+
+// Saved conversations, import and export
+mod persistence;
+use persistence::{Conversation, ConversationStore, SearchHit, SessionNote};
+
+// Text-to-speech for assistant messages
+mod tts;
+
+// Files side panel
+mod file_browser;
+use file_browser::{FileAction, FileBrowser};
+
+// Terminal panel for interactive commands
+mod terminal;
+use terminal::TerminalPanel;
+
+// Debounced, atomic settings saves off the UI thread
+mod settings_writer;
+
+// Content mode template picker
+mod content_templates;
+
+// Recorded sequences of requests
+mod macros;
+use macros::{Macro, MacroLibrary, MacroStep};
+
+// Local, locale-aware message times
+mod timestamp;
+
+// Commands typed into the chat input
+mod slash_commands;
+use slash_commands::SlashCommand;
+
+// Review AI file name suggestions
+mod rename_review;
+use rename_review::RenameReview;
+
+// Edit organizer plans before they run
+mod plan_editor;
+use plan_editor::PlanEditor;
+
+// Onboarding step that finds AI providers
+mod provider_setup;
+use provider_setup::ProviderSetup;
+
+// Upgrading settings files from older versions
+mod migrations;
+
+// Command-line options and shell completions
+mod cli;
+use clap::Parser;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum AppScreen {
@@ -37,7 +227,8 @@ enum AppScreen {
     Chat,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum ChatMode {
     Find,     // Help me find something
     Fix,      // Help me fix something
@@ -46,61 +237,213 @@ enum ChatMode {
     Content,  // Content creation/management
 }
 
-#[derive(Clone)]
+impl ChatMode {
+    const ALL: [ChatMode; 5] = [ChatMode::Find, ChatMode::Fix, ChatMode::Research, ChatMode::Data, ChatMode::Content];
+
+    /// Key used for per-mode settings
+    fn key(&self) -> &'static str {
+        match self {
+            ChatMode::Find => "find",
+            ChatMode::Fix => "fix",
+            ChatMode::Research => "research",
+            ChatMode::Data => "data",
+            ChatMode::Content => "content",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.key() == key)
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            ChatMode::Find => "Find",
+            ChatMode::Fix => "Fix",
+            ChatMode::Research => "Research",
+            ChatMode::Data => "Data",
+            ChatMode::Content => "Content",
+        }
+    }
+}
+
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
 struct ChatMessage {
     role: String, // "user" or "assistant"
     content: String,
-    #[allow(dead_code)] // Will be used for chat history display
+    /// Estimated tokens in `content`, shown on hover (not saved)
+    #[serde(skip)]
+    token_count: usize,
+    /// RFC 3339 UTC ("HH:MM" in older saved conversations)
     timestamp: String,
+    /// Images attached by the user (sent to vision-capable models)
+    #[serde(default)]
+    attachments: Vec<PathBuf>,
+    /// "Key Takeaways" bullet list shown under long Research responses
+    #[serde(default)]
+    summary: Option<String>,
+    /// Directory statistics shown as tables in the bubble (not saved)
+    #[serde(skip)]
+    dir_stats: Vec<DirStats>,
+    /// Timings shown in debug mode (not saved)
+    #[serde(skip)]
+    debug_info: Option<TurnDebugInfo>,
+    /// Command output shown as sortable tables, by command
+    #[serde(default)]
+    tables: Vec<(String, StructuredTable)>,
+    /// File hashes, with whether they matched the expected value
+    #[serde(default)]
+    hashes: Vec<FileHash>,
+    /// Files listed in command output, by command
+    #[serde(default)]
+    command_paths: Vec<(String, Vec<PathBuf>)>,
+    /// Suggested next questions, shown as chips under Research responses
+    #[serde(default)]
+    followup_questions: Vec<String>,
+    /// Thumbs up (1) or down (-1) given to an assistant reply
+    #[serde(default)]
+    rating: Option<i8>,
 }
 
-/// Active viewer in the preview panel
-enum ActiveViewer {
-    None,
-    Text(TextViewer),
-    Image(ImageViewer),
-    Csv(CsvViewer),
-    Json(JsonViewer),
-    Html(HtmlViewer),
-    Pdf(PdfViewer),
+impl ChatMessage {
+    /// A message sent now, with its tokens counted
+    fn new(role: &str, content: String) -> Self {
+        Self {
+            role: role.to_string(),
+            token_count: token_count(&content),
+            content,
+            timestamp: timestamp::now(),
+            ..Default::default()
+        }
+    }
+
+    fn user(content: String) -> Self {
+        Self::new("user", content)
+    }
+
+    fn assistant(content: String) -> Self {
+        Self::new("assistant", content)
+    }
 }
 
 struct AppState {
     settings: AppSettings,
     current_screen: AppScreen,
     current_mode: ChatMode,
+    provider_override: Option<String>,  // From --provider, tried first this session
     input_text: String,
+    input_error: Option<String>,  // Shown under the input, e.g. for an unknown slash command
+    path_completer: PathCompleter,
     chat_history: Vec<ChatMessage>,
+    conversation: Conversation,  // Id and title of the chat shown; messages live in chat_history
+    conversation_store: ConversationStore,
+    saved_conversations: Vec<Conversation>,  // For the conversations menu, newest first
+    show_conversation_search: bool,
+    conversation_search: String,
+    conversation_search_hits: Vec<SearchHit>,
+    highlighted_message: Option<usize>,  // Search result shown in the chat
+    scroll_to_message: Option<usize>,  // Scrolled into view on the next frame
     is_thinking: bool,
+    system_prompt_tokens: usize,  // Estimated size of the last system prompt, for the context bar
     thinking_status: String,  // What the agent is currently doing
-    #[allow(dead_code)] // Available for future agentic features
+    batch: Option<BatchRun>,  // Questions from /batch still to ask
     agent_host: AgentHost,
 
     // Preview panel
     show_preview: bool,
-    preview_path: Option<PathBuf>,
-    active_viewer: ActiveViewer,
+    preview_tabs: Vec<PreviewTab>,
+    active_tab: usize,
     pending_preview: Option<PathBuf>,  // File to auto-open after response
+    more_found_files: Vec<PathBuf>,  // Other files in a reply when the first was opened for the user
+    pending_commit: Option<PendingCommit>,  // Offer to commit the agent's file edits
+    commit_rx: Option<Receiver<anyhow::Result<CommandResult>>>,  // Running `git commit`
+    sudo_dialog: Option<SudoDialogState>,  // Asking to rerun a command with admin rights
+    sudo_rx: Option<Receiver<(SudoDialogState, anyhow::Result<CommandResult>)>>,  // Command running with admin rights
+    viewer_load_rx: Option<Receiver<Vec<(PathBuf, LoadedContent)>>>,  // Dropped files loading in the background
+    pending_terminal: Option<String>,  // Interactive command to open after response
+    pending_attachments: Vec<PathBuf>,  // Images dropped into the chat, sent with the next message
+    file_context_prompt: Option<String>,  // Describes the open file to the agent
+    open_file_language: Option<services::language::ProgrammingLanguage>,
 
     // Onboarding
     onboarding_name: String,
+    provider_setup: Option<ProviderSetup>,  // Second onboarding step, after the name
 
     // Background mascot texture
     mascot_texture: Option<egui::TextureHandle>,
     mascot_loaded: bool,
+
+    // Inline image previews for paths mentioned in chat
+    thumbnails: ThumbnailCache,
     
     // Async AI response channel
     ai_result_rx: Option<Receiver<AiResult>>,
+    ai_task: Option<tokio::task::AbortHandle>,  // Aborted by the Stop button while thinking
+    reply_chunks_rx: Option<tokio::sync::mpsc::Receiver<String>>,  // Text of a one-off reply as it's generated
+    partial_reply: String,  // Shown while thinking until the whole reply is in
+    title_rx: Option<Receiver<(uuid::Uuid, anyhow::Result<String>)>>,  // AI title for a conversation after its first reply
+    // Reconnection checks while no provider is reachable
+    ping_rx: Option<Receiver<bool>>,
+    last_ping: Option<std::time::Instant>,
+    // Commands the agent asks to run, answered in a modal dialog
+    confirmation_tx: Sender<ConfirmationRequest>,
+    confirmation_rx: Receiver<ConfirmationRequest>,
+    pending_confirmation: Option<ConfirmationRequest>,
+    /// Interactive command waiting on the confirmation dialog before it
+    /// opens in the terminal
+    terminal_confirmation: Option<(String, tokio::sync::oneshot::Receiver<bool>)>,
+    batch_preview_rx: Receiver<BatchPreviewRequest>,
+    pending_batch_preview: Option<BatchPreviewRequest>,
+    dir_access_rx: Receiver<DirAccessRequest>,
+    pending_dir_access: Option<DirAccessRequest>,
     
     // Slack integration
     show_slack_dialog: bool,
     slack_message_to_send: Option<String>,
     slack_selected_channel: String,
     slack_status: Option<String>,  // Status message after send attempt
+
+    // Settings window
+    show_settings: bool,
+
+    // Past organizer runs, for undo across sessions
+    organize_history: Vec<OrganizeHistoryEntry>,
+    organize_status: Option<String>,
+    audit_log_cache: Option<settings_ui::AuditLogCache>,  // Latest command log entries while Settings shows them
+    rename_review: Option<RenameReview>,  // "Suggest better names" window for a folder
+    plan_editor: Option<PlanEditor>,  // Organizer plan being reviewed before it runs
+    last_schema_path: Option<PathBuf>,  // JSON Schema last used by the JSON viewer's "Validate"
+
+    // Content mode schedule
+    schedule_month: chrono::NaiveDate,  // First day of the month shown
+    schedule_status: Option<String>,
+    personas: Vec<String>,  // Persona names found on disk
+
+    // Files side panel
+    show_file_browser: bool,
+    file_browser: FileBrowser,
+    hash_check: Option<HashCheck>,  // "Check hash" window for a file
+    selected_persona: Option<String>,  // None = all personas
+    research_followups: bool,  // Suggest follow-up questions after Research answers (this session only)
+
+    // Content templates
+    templates: TemplateLibrary,
+    show_templates: bool,
+    active_template: Option<ContentTemplate>,  // Guides Content mode replies
+    template_draft: Option<ContentTemplate>,  // "Save as template" form
+    template_status: Option<String>,  // Last template save error
+
+    // Macros
+    macros: MacroLibrary,
+    recording: Option<Vec<MacroStep>>,  // Messages sent since "Record" was pressed
+    macro_name: String,  // Name typed for the macro being recorded
+    macro_status: Option<String>,  // Last save error
+    benchmark: settings_ui::ModelBenchmarks,  // Local model benchmark in settings
+    runtime: Arc<tokio::runtime::Runtime>,  // Runs AI calls and other background work
+    ctx: egui::Context,  // For background tasks to repaint when they finish
 }
 
-impl Default for AppState {
-    fn default() -> Self {
+impl AppState {
+    fn new(runtime: Arc<tokio::runtime::Runtime>, ctx: egui::Context) -> Self {
         let (settings, _) = load_settings_or_default();
         let needs_onboarding = !settings.user_profile.onboarding_complete;
 
@@ -110,17 +453,47 @@ impl Default for AppState {
             settings.user_profile.name.clone()
         };
 
-        let welcome_msg = ChatMessage {
-            role: "assistant".to_string(),
-            content: format!(
-                "Hi {}! I'm your Little Helper. What would you like me to help you with today?\n\n\
-                You can ask me to find files, fix problems, do deep research, work with data, or create content.",
-                user_name
-            ),
-            timestamp: chrono::Utc::now().format("%H:%M").to_string(),
-        };
+        let conversation_store = ConversationStore::default();
+
+        // The agent asks before running commands that change things
+        let (confirmation_tx, confirmation_rx) = channel::<ConfirmationRequest>();
+        agent_host::set_command_restrictions(&settings);
+        let mut agent_host = AgentHost::new(settings.clone());
+        keep_system_info_fresh(agent_host.system_context.clone());
+        let agent_confirmation_tx = confirmation_tx.clone();
+        agent_host.confirmation_callback = Some(Arc::new(move |command, danger| {
+            let (respond, answer) = tokio::sync::oneshot::channel();
+            let _ = agent_confirmation_tx.send(ConfirmationRequest::new(command, danger, respond));
+            // A dropped request (e.g. the dialog went away) counts as "no"
+            Box::pin(async move { answer.await.unwrap_or(false) })
+        }));
+        let (batch_preview_tx, batch_preview_rx) = channel::<BatchPreviewRequest>();
+        agent_host.batch_preview_callback = Some(Arc::new(move |preview| {
+            let (respond, answer) = tokio::sync::oneshot::channel();
+            // Everything that can run starts ticked
+            let selected = preview
+                .commands
+                .iter()
+                .map(|(_, danger)| !matches!(danger, agent_host::DangerLevel::Blocked | agent_host::DangerLevel::NeedsSudo))
+                .collect();
+            let _ = batch_preview_tx.send(BatchPreviewRequest { commands: preview.commands, selected, respond });
+            Box::pin(async move { answer.await.unwrap_or_default() })
+        }));
+        let (dir_access_tx, dir_access_rx) = channel::<DirAccessRequest>();
+        agent_host.dir_access_callback = Some(Arc::new(move |dir| {
+            let (respond, answer) = tokio::sync::oneshot::channel();
+            let _ = dir_access_tx.send(DirAccessRequest { dir: dir.to_path_buf(), respond });
+            Box::pin(async move { answer.await.unwrap_or(false) })
+        }));
+
+        let welcome = format!(
+            "Hi {}! I'm your Little Helper. What would you like me to help you with today?\n\n\
+            You can ask me to find files, fix problems, do deep research, work with data, or create content.",
+            user_name
+        );
+        let welcome_msg = ChatMessage::assistant(welcome);
 
-        Self {
+        let mut state = Self {
             settings: settings.clone(),
             current_screen: if needs_onboarding {
                 AppScreen::Onboarding
@@ -128,64 +501,347 @@ impl Default for AppState {
                 AppScreen::Chat
             },
             current_mode: ChatMode::Find,
+            provider_override: None,
             input_text: String::new(),
+            input_error: None,
+            path_completer: PathCompleter::default(),
             chat_history: vec![welcome_msg],
+            conversation: Conversation::new(Vec::new()),
+            saved_conversations: conversation_store.list(),
+            show_conversation_search: false,
+            conversation_search: String::new(),
+            conversation_search_hits: Vec::new(),
+            highlighted_message: None,
+            scroll_to_message: None,
+            conversation_store,
             is_thinking: false,
+            system_prompt_tokens: 0,
             thinking_status: String::new(),
-            agent_host: AgentHost::new(settings),
+            batch: None,
+            agent_host,
             show_preview: false,
-            preview_path: None,
-            active_viewer: ActiveViewer::None,
+            preview_tabs: Vec::new(),
+            active_tab: 0,
             pending_preview: None,
+            more_found_files: Vec::new(),
+            pending_commit: None,
+            commit_rx: None,
+            sudo_dialog: None,
+            sudo_rx: None,
+            viewer_load_rx: None,
+            pending_terminal: None,
+            pending_attachments: Vec::new(),
+            file_context_prompt: None,
+            open_file_language: None,
             onboarding_name: String::new(),
+            provider_setup: None,
             mascot_texture: None,
             mascot_loaded: false,
+            thumbnails: HashMap::new(),
             ai_result_rx: None,
+            ai_task: None,
+            reply_chunks_rx: None,
+            partial_reply: String::new(),
+            title_rx: None,
+            ping_rx: None,
+            last_ping: None,
+            confirmation_tx,
+            confirmation_rx,
+            pending_confirmation: None,
+            terminal_confirmation: None,
+            batch_preview_rx,
+            pending_batch_preview: None,
+            dir_access_rx,
+            pending_dir_access: None,
             show_slack_dialog: false,
             slack_message_to_send: None,
             slack_selected_channel: "#general".to_string(),
             slack_status: None,
+            show_settings: false,
+            organize_history: organizer::load_history(),
+            organize_status: None,
+            audit_log_cache: None,
+            rename_review: None,
+            plan_editor: None,
+            last_schema_path: None,
+            schedule_month: schedule::month_start(chrono::Utc::now().date_naive()),
+            schedule_status: None,
+            personas: services::personas::discover_personas().into_iter().map(|p| p.name).collect(),
+            selected_persona: None,
+            research_followups: true,
+            templates: TemplateLibrary::default(),
+            show_templates: false,
+            active_template: None,
+            template_draft: None,
+            template_status: None,
+            macros: MacroLibrary::default(),
+            recording: None,
+            macro_name: String::new(),
+            macro_status: None,
+            benchmark: settings_ui::ModelBenchmarks::default(),
+            show_file_browser: false,
+            file_browser: FileBrowser::default(),
+            hash_check: None,
+            runtime,
+            ctx,
+        };
+        // Pick up where the user left off
+        if let Some(conversation) = state.conversation_store.latest() {
+            state.open_conversation(conversation);
         }
+        state
     }
 }
 
 impl AppState {
     /// Check for completed AI responses (called each frame)
     fn poll_ai_response(&mut self) {
-        if let Some(rx) = &self.ai_result_rx {
-            // Non-blocking check for result
-            if let Ok(result) = rx.try_recv() {
-                self.is_thinking = false;
-                self.thinking_status.clear();
-                self.ai_result_rx = None;
-                
-                if let Some(error) = result.error {
-                    // Format error message with helpful info
-                    let error_content = format_error_message(&error);
-                    let error_msg = ChatMessage {
-                        role: "assistant".to_string(),
-                        content: error_content,
-                        timestamp: chrono::Utc::now().format("%H:%M").to_string(),
-                    };
-                    self.chat_history.push(error_msg);
-                } else {
-                    // Store file to preview
-                    self.pending_preview = result.preview_file;
-                    
-                    // Clean up response - remove action tags
-                    let clean_response = clean_ai_response(&result.response);
-                    
-                    let assistant_msg = ChatMessage {
-                        role: "assistant".to_string(),
-                        content: if clean_response.is_empty() { result.response } else { clean_response },
-                        timestamp: chrono::Utc::now().format("%H:%M").to_string(),
-                    };
-                    self.chat_history.push(assistant_msg);
-                }
+        // Non-blocking check for result
+        if let Some(rx) = &mut self.reply_chunks_rx {
+            while let Ok(text) = rx.try_recv() {
+                self.partial_reply.push_str(&text);
             }
         }
+        let Some(result) = self.ai_result_rx.as_ref().and_then(|rx| rx.try_recv().ok()) else { return };
+        self.is_thinking = false;
+        self.thinking_status.clear();
+        self.ai_result_rx = None;
+        self.ai_task = None;
+        self.reply_chunks_rx = None;
+        self.partial_reply.clear();
+
+        self.push_ai_result(result);
+        if let Some(batch) = &mut self.batch {
+            batch.answered += 1;
+            self.ask_next_in_batch();
+        }
     }
-    
+
+    /// Add a finished AI result to the chat
+    fn push_ai_result(&mut self, result: AiResult) {
+        if let Some(error) = result.error {
+            // Format error message with helpful info
+            let error_content = format_error_message(&error);
+            self.chat_history.push(ChatMessage::assistant(error_content));
+            return;
+        }
+
+        // Store file to preview
+        self.pending_preview = result.preview_file;
+        self.pending_terminal = result.interactive_command;
+        if let Some(joined) = result.joined_csv {
+            self.show_joined_csv(joined);
+        }
+
+        // Add any proposed posts to the content schedule
+        let mut schedule_changed = false;
+        for post in result.scheduled_posts {
+            if !self.settings.schedule.contains(&post) {
+                self.settings.schedule.push(post);
+                schedule_changed = true;
+            }
+        }
+        if schedule_changed {
+            save_settings(&self.settings);
+        }
+
+        // Clean up response - remove action tags
+        let clean_response = clean_ai_response(&result.response);
+
+        // Open the first file the reply mentions unless the agent picked one
+        self.more_found_files.clear();
+        if self.settings.auto_open_files && self.pending_preview.is_none() {
+            let mut paths = extract_paths(&clean_response).into_iter().filter(|p| p.is_file());
+            self.pending_preview = paths.next();
+            self.more_found_files = paths.collect();
+        }
+
+        let content = if clean_response.is_empty() { result.response } else { clean_response };
+        let assistant_msg = ChatMessage {
+            summary: result.summary,
+            dir_stats: result.dir_stats,
+            debug_info: result.debug_info,
+            tables: result.tables,
+            hashes: result.file_hashes,
+            command_paths: result.command_paths,
+            followup_questions: result.followup_questions,
+            ..ChatMessage::new(result.role, content)
+        };
+        if self.settings.tts_enabled {
+            tts::speak(&assistant_msg.content, self.settings.tts_voice.as_deref());
+        }
+        self.chat_history.push(assistant_msg);
+        self.save_conversation();
+        if let Some(suggestion) = result.commit_suggestion {
+            self.push_assistant_message(suggestion.prompt(), Vec::new());
+            self.pending_commit = Some(PendingCommit { message: suggestion.message.clone(), suggestion });
+        }
+        if let Some(command) = result.needs_elevation {
+            let message = self.chat_history.len() - 1;
+            self.sudo_dialog = Some(SudoDialogState::new(command, message));
+        }
+        if self.chat_history.iter().filter(|m| m.role == "user").count() == 1 {
+            self.start_title_generation();
+        }
+    }
+
+    /// Ask the AI for a title after the first exchange. Until it answers,
+    /// and if it fails, the title is the start of the first message.
+    fn start_title_generation(&mut self) {
+        if self.conversation.title_generated || self.title_rx.is_some() {
+            return;
+        }
+        let Some(first) = self.chat_history.iter().find(|m| m.role == "user").map(|m| m.content.clone()) else {
+            return;
+        };
+        let (tx, rx) = channel();
+        self.title_rx = Some(rx);
+        let id = self.conversation.id;
+        self.agent_host.settings = self.settings.clone();
+        let agent_host = self.agent_host.clone();
+        let ctx = self.ctx.clone();
+        self.runtime.spawn(async move {
+            let title = agent_host.conversation_title(&first).await;
+            let _ = tx.send((id, title));
+            ctx.request_repaint();
+        });
+    }
+
+    /// While offline, ping the providers every `RECONNECT_INTERVAL` in the
+    /// background; a reply clears the offline flag
+    fn check_connectivity(&mut self) {
+        if let Some(online) = self.ping_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.ping_rx = None;
+            if online {
+                tracing::info!("AI provider reachable again");
+            }
+        }
+        if !providers::connectivity::is_offline()
+            || self.ping_rx.is_some()
+            || self.last_ping.is_some_and(|t| t.elapsed() < RECONNECT_INTERVAL)
+        {
+            return;
+        }
+        let (tx, rx) = channel();
+        self.ping_rx = Some(rx);
+        self.last_ping = Some(std::time::Instant::now());
+        let config = self.settings.model.clone();
+        let ctx = self.ctx.clone();
+        self.runtime.spawn(async move {
+            let online = providers::connectivity::ping(&config).await;
+            let _ = tx.send(online);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Commit the agent's edits with the message the user settled on
+    fn start_commit(&mut self) {
+        let Some(pending) = self.pending_commit.take() else { return };
+        let (tx, rx) = channel();
+        self.commit_rx = Some(rx);
+        let agent_host = self.agent_host.clone();
+        let ctx = self.ctx.clone();
+        self.runtime.spawn(async move {
+            let result = agent_host.commit(&pending.suggestion, &pending.message).await;
+            let _ = tx.send(result);
+            ctx.request_repaint();
+        });
+    }
+
+    fn poll_commit(&mut self) {
+        let Some(result) = self.commit_rx.as_ref().and_then(|rx| rx.try_recv().ok()) else { return };
+        self.commit_rx = None;
+        let content = match result {
+            Ok(result) => format!("Committed.\n```\n{}\n```", result.output.trim_end()),
+            Err(e) => format!("I couldn't commit the change: {}", e),
+        };
+        self.push_assistant_message(content, Vec::new());
+    }
+
+    /// Rerun the sudo dialog's command with admin rights: through sudo with
+    /// `password`, or on Windows through the UAC prompt
+    fn start_elevated(&mut self) {
+        let Some(mut dialog) = self.sudo_dialog.take() else { return };
+        // Moved, not copied, into the task that wipes it
+        let mut password = std::mem::take(&mut dialog.password);
+        let (tx, rx) = channel();
+        self.sudo_rx = Some(rx);
+        let timeout = agent_host::timeout_for(&dialog.command, &self.settings.timeouts);
+        let audit_log = self.settings.audit_log_path.clone();
+        let ctx = self.ctx.clone();
+        self.runtime.spawn(async move {
+            #[cfg(not(windows))]
+            let result = agent_host::execute_with_sudo(&dialog.command, &password, timeout, audit_log.as_deref()).await;
+            #[cfg(windows)]
+            let result = agent_host::execute_with_elevation(&dialog.command, timeout, audit_log.as_deref()).await;
+            wipe(&mut password);
+            let _ = tx.send((dialog, result));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Replace the failed result with the elevated one, or ask again after
+    /// a wrong password
+    fn poll_elevated(&mut self) {
+        let Some((mut dialog, result)) = self.sudo_rx.as_ref().and_then(|rx| rx.try_recv().ok()) else { return };
+        self.sudo_rx = None;
+        let result = match result {
+            Ok(result) if result.summary == "Incorrect password" => {
+                dialog.error = Some("That password didn't work. Try again?".to_string());
+                self.sudo_dialog = Some(dialog);
+                return;
+            }
+            Ok(result) => result,
+            Err(e) => {
+                self.push_assistant_message(format!("I couldn't run `{}` with admin rights: {}", dialog.command, e), Vec::new());
+                return;
+            }
+        };
+
+        if let Some(message) = self.chat_history.get_mut(dialog.message) {
+            message.tables.retain(|(command, _)| *command != dialog.command);
+            message.command_paths.retain(|(command, _)| *command != dialog.command);
+            if let Some(table) = &result.structured {
+                message.tables.push((dialog.command.clone(), table.clone()));
+            }
+            if !result.extracted_paths.is_empty() {
+                message.command_paths.push((dialog.command.clone(), result.extracted_paths.clone()));
+            }
+        }
+        let content = if result.success {
+            format!("Ran `{}` with admin rights.\n```\n{}\n```", dialog.command, result.output.trim_end())
+        } else {
+            format!("`{}` failed even with admin rights: {}", dialog.command, result.summary)
+        };
+        self.push_assistant_message(content, Vec::new());
+    }
+
+    /// Store a generated title on its conversation, which may no longer be
+    /// the one that's open
+    fn poll_conversation_title(&mut self) {
+        let Some((id, title)) = self.title_rx.as_ref().and_then(|rx| rx.try_recv().ok()) else { return };
+        self.title_rx = None;
+        let title = match title {
+            Ok(title) => title,
+            Err(e) => {
+                tracing::warn!("Failed to generate conversation title: {}", e);
+                return;
+            }
+        };
+        if id == self.conversation.id {
+            self.conversation.title = title;
+            self.conversation.title_generated = true;
+            self.save_conversation();
+        } else if let Ok(mut conversation) = self.conversation_store.load(id) {
+            conversation.title = title;
+            conversation.title_generated = true;
+            if let Err(e) = self.conversation_store.save(&conversation) {
+                tracing::warn!("Failed to save conversation: {}", e);
+            }
+            persistence::update_index(&mut self.saved_conversations, &conversation);
+        }
+    }
+
     /// Load the mascot image as a texture (custom or default)
     fn load_mascot_texture(&mut self, ctx: &egui::Context) {
         if self.mascot_loaded {
@@ -227,24 +883,242 @@ impl AppState {
         self.load_mascot_texture(ctx);
     }
 
+    /// Attach images dropped onto the window to the next message, and open
+    /// the dropped files in the preview panel
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+        let mut to_open = Vec::new();
+        for path in dropped.into_iter().filter_map(|file| file.path) {
+            if is_attachable_image(&path) && !self.pending_attachments.contains(&path) {
+                self.pending_attachments.push(path.clone());
+            }
+            if path.is_file() && FileType::from_path(&path).is_supported() {
+                to_open.push(path);
+            }
+        }
+        if !to_open.is_empty() {
+            self.open_files(to_open, ctx);
+        }
+    }
+
+    /// Refresh file path completions for the current input
+    fn update_path_completions(&mut self) {
+        self.path_completer
+            .update(&self.input_text, &self.settings.allowed_dirs);
+    }
+
+    /// Apply a path completion and keep the cursor at the end of the input
+    fn apply_path_completion(&mut self, index: usize, ctx: &egui::Context, input_id: egui::Id) {
+        self.path_completer.apply(&mut self.input_text, index);
+        self.focus_input_at_end(ctx, input_id);
+    }
+
+    /// Focus the input with the cursor after the text
+    fn focus_input_at_end(&self, ctx: &egui::Context, input_id: egui::Id) {
+        if let Some(mut state) = egui::TextEdit::load_state(ctx, input_id) {
+            let end = egui::text::CCursor::new(self.input_text.chars().count());
+            state
+                .cursor
+                .set_char_range(Some(egui::text::CCursorRange::one(end)));
+            state.store(ctx, input_id);
+        }
+        ctx.memory_mut(|m| m.request_focus(input_id));
+    }
+
+    /// Save the current chat once the user has said something
+    fn save_conversation(&mut self) {
+        if !self.chat_history.iter().any(|m| m.role == "user") {
+            return;
+        }
+        self.conversation.messages = self.chat_history.clone();
+        self.conversation.update_title();
+        if let Err(e) = self.conversation_store.save(&self.conversation) {
+            tracing::warn!("Failed to save conversation: {}", e);
+        }
+        self.conversation.messages.clear();
+        persistence::update_index(&mut self.saved_conversations, &self.conversation);
+    }
+
+    /// Show a saved conversation in the chat
+    fn open_conversation(&mut self, mut conversation: Conversation) {
+        if self.is_thinking {
+            return;
+        }
+        self.chat_history = std::mem::take(&mut conversation.messages);
+        for msg in &mut self.chat_history {
+            msg.token_count = token_count(&msg.content);
+        }
+        self.conversation = conversation;
+        self.highlighted_message = None;
+    }
+
+    fn new_conversation(&mut self) {
+        if self.is_thinking {
+            return;
+        }
+        self.save_conversation();
+        let welcome = self.chat_history.first().filter(|m| m.role == "assistant").cloned();
+        self.chat_history = welcome.into_iter().collect();
+        self.conversation = Conversation::new(Vec::new());
+        self.highlighted_message = None;
+    }
+
+    fn import_conversation(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Conversation", &["md", "json"])
+            .pick_file()
+        else {
+            return;
+        };
+        match self.conversation_store.import(&path) {
+            Ok(conversation) => {
+                persistence::update_index(&mut self.saved_conversations, &conversation);
+                self.open_conversation(conversation);
+            }
+            Err(e) => {
+                let content = format!("I couldn't import that conversation: {}", e);
+                self.chat_history.push(ChatMessage::assistant(content));
+            }
+        }
+    }
+
+    fn export_conversation(&mut self, markdown: bool) {
+        let (name, ext) = if markdown { ("Markdown", "md") } else { ("JSON", "json") };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(name, &[ext])
+            .set_file_name(format!("conversation.{}", ext))
+            .save_file()
+        else {
+            return;
+        };
+        let result = if markdown {
+            ConversationStore::export_markdown(&self.chat_history, &path)
+        } else {
+            let conversation = Conversation { messages: self.chat_history.clone(), ..self.conversation.clone() };
+            ConversationStore::export_json(&conversation, &path)
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to export conversation: {}", e);
+        }
+    }
+
+    /// Save rated replies from every conversation as fine-tuning JSONL
+    fn export_rated_replies(&mut self, min_rating: i8) {
+        let (name, icon) = if min_rating > 0 { ("good-replies.jsonl", "👍") } else { ("poor-replies.jsonl", "👎") };
+        if self.conversation_store.export_rated_messages(min_rating).is_ok_and(|rated| rated.is_empty()) {
+            self.input_error = Some(format!("No replies have been rated {} yet", icon));
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON Lines", &["jsonl"])
+            .set_file_name(name)
+            .save_file()
+        else {
+            return;
+        };
+        if let Err(e) = self.conversation_store.export_fine_tuning(min_rating, &path) {
+            tracing::warn!("Failed to export rated replies: {}", e);
+        }
+    }
+
     fn send_message(&mut self) {
-        if self.input_text.trim().is_empty() {
+        if self.input_text.trim().is_empty() && self.pending_attachments.is_empty() {
+            return;
+        }
+        if self.input_text.trim_start().starts_with('/') {
+            match slash_commands::parse(&self.input_text) {
+                Ok(command) => {
+                    self.input_text.clear();
+                    self.input_error = None;
+                    self.run_slash_command(command);
+                }
+                Err(e) => self.input_error = Some(e),
+            }
             return;
         }
 
+        let query = std::mem::take(&mut self.input_text);
+        if let Some(steps) = &mut self.recording {
+            steps.push(MacroStep { mode: self.current_mode, input: query.clone() });
+        }
+        self.ask(query);
+    }
+
+    /// Keep each message sent from now on, with its mode, for a macro
+    fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+        self.macro_name.clear();
+        self.macro_status = None;
+    }
+
+    /// The messages sent since `start_recording`, as an unnamed macro
+    fn stop_recording(&mut self) -> Macro {
+        Macro { name: String::new(), steps: self.recording.take().unwrap_or_default() }
+    }
+
+    /// Send each step's message in its mode, waiting for each reply before
+    /// the next, like `send_batch`
+    fn replay_macro(&mut self, m: &Macro) {
+        let steps = m.steps.iter().map(|step| (Some(step.mode), step.input.clone()));
+        self.start_batch("Running macro", steps.collect());
+    }
+
+    /// Add `query` to the chat as the user's message and start the reply.
+    /// Attachments waiting in the input go with it.
+    fn ask(&mut self, query: String) {
         // Add user message to chat
         let user_msg = ChatMessage {
-            role: "user".to_string(),
-            content: self.input_text.clone(),
-            timestamp: chrono::Utc::now().format("%H:%M").to_string(),
+            attachments: std::mem::take(&mut self.pending_attachments),
+            ..ChatMessage::user(query)
         };
         self.chat_history.push(user_msg);
+        self.save_conversation();
 
-        // Clear input and show thinking state
-        let _query = self.input_text.clone();
-        self.input_text.clear();
-        self.is_thinking = true;
+        let api_messages = self.api_messages();
+
+        // Start async AI generation
+        self.start_ai_generation(api_messages);
+    }
+
+    /// System prompt for the current mode, followed by recent history
+    fn api_messages(&mut self) -> Vec<ApiChatMessage> {
+        let system_prompt = self.system_prompt();
+        self.system_prompt_tokens = token_count(&system_prompt);
+
+        // Convert chat history to API format
+        let mut api_messages = vec![ApiChatMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+            attachments: Vec::new(),
+        }];
+        // Notes go right after the system prompt so they never fall out of
+        // the history window
+        for note in &self.conversation.notes {
+            api_messages.push(ApiChatMessage {
+                role: "system".to_string(),
+                content: format!("Note from the user for this conversation: {}", note.content),
+                attachments: Vec::new(),
+            });
+        }
+        for msg in self.api_history() {
+            api_messages.push(ApiChatMessage {
+                role: msg.role.clone(),
+                content: msg.content.clone(),
+                attachments: msg
+                    .attachments
+                    .iter()
+                    .filter_map(|path| fs::read(path).ok())
+                    .map(MessageAttachment::Image)
+                    .collect(),
+            });
+        }
+
+        api_messages
+    }
 
+    /// System prompt for the current mode, with the template, response
+    /// length and open file folded in
+    fn system_prompt(&self) -> String {
         // Prepare context based on current mode
         let user_name = if self.settings.user_profile.name.is_empty() {
             "friend".to_string()
@@ -258,6 +1132,12 @@ impl AppState {
         #[cfg(not(target_os = "windows"))]
         let is_windows = false;
 
+        // Gathered in the background; only the first message can beat it
+        let mut system_info = self.agent_host.system_context.read().map(|s| s.clone()).unwrap_or_default();
+        if system_info.is_empty() {
+            system_info = get_system_info();
+        }
+
         // Core capabilities the agent should know about
         let capabilities = format!("
 CRITICAL: YOU ARE A TERMINAL AGENT. You MUST use <command> tags to actually run commands.
@@ -268,12 +1148,18 @@ CAPABILITIES:
 - You can SEARCH THE WEB using <search>your query</search> tags. ALWAYS search when you need current info!
 - You can AUTO-OPEN FILES in the preview panel using <preview>/path/to/file</preview> tags.
 - Supported preview types: text files, images (png/jpg/gif), CSV/data files, JSON, HTML, Markdown
+- You can OPEN AN INTERACTIVE PROGRAM (python3, psql, irb...) in a terminal panel using <interactive>program</interactive> tags.
+- You can READ A TEXT FILE using <read-file path=\"/path/to/file\"/> tags.
+- You can EDIT A FILE using <edit-file path=\"/path/to/file\" find=\"exact old text\">new text</edit-file> tags (leave out find to write the whole file). The user is asked first.
+- You can CHECK A FILE'S HASH using <hash-file path=\"/path/to/file\" algo=\"sha256\" expected=\"hash the user gave\"/> tags (algo: md5, sha1, sha256, sha512; expected is optional).
 
 IMPORTANT: When the user asks you to do something, DO IT by running commands. Don't just explain - execute!
 Example: If user says 'list my documents', you respond with <command>dir Documents</command> or <command>ls Documents</command>
 
+OUTPUT LIMIT: Command output is cut off after {} bytes. Use head -n 50, tail or grep instead of cat for big files.
+
 {}
-", get_campaign_summary());
+", self.agent_host.execution_options.max_output_bytes, get_campaign_summary(&system_info));
 
         // Platform-specific Find mode commands
         let find_commands = if is_windows {
@@ -355,22 +1241,44 @@ EXAMPLE - User says "my computer is slow":
 "#
         };
 
+        // Find mode only searches the folders the user allowed
+        let search_scope = if self.settings.allowed_dirs.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "\nSEARCH SCOPE: Only search inside these folders:\n{}\n\
+                Always start find and ls commands from one of these paths. Never search /, \
+                ~ or any other folder - searches outside these folders will be restricted automatically.\n",
+                self.settings
+                    .allowed_dirs
+                    .iter()
+                    .map(|d| format!("- {}", d))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        };
+
         let system_prompt = match self.current_mode {
             ChatMode::Find => format!(
                 r#"You are Little Helper in FIND mode, a terminal agent helping {}.
 
 YOUR JOB: Find files on their computer by RUNNING COMMANDS. Don't just explain - EXECUTE!
-
+{}
 {}
 
+SEARCHING BY NAME: To find files by name in the allowed folders, use a glob pattern:
+<search-files pattern="**/*.toml"/>
+<search-files pattern="*.pdf" in="~/Documents" text="invoice"/>
+A pattern without "/" matches file names anywhere. in="..." limits the search to one folder, and text="..." only keeps files with that text in their name or content.
+
 WORKFLOW:
-1. When user asks to find something, IMMEDIATELY run search commands
+1. When user asks to find something, IMMEDIATELY run search commands or <search-files>
 2. Show the results with full paths
 3. Use <preview>path</preview> to open found files in the preview panel
 
 {}
 "#,
-                user_name, find_commands, capabilities
+                user_name, search_scope, find_commands, capabilities
             ),
             ChatMode::Fix => format!(
                 r#"You are Little Helper in FIX mode, a terminal agent helping {}.
@@ -461,13 +1369,13 @@ ALWAYS:
                 )
             },
             ChatMode::Data => format!(
-                "You are Little Helper, a data assistant helping {}. Help work with CSV files, JSON data, and databases. Use <command></command> to examine files. ALWAYS open data files in the preview panel so the user can see what you're working with. Walk them through the data visually.\n{}",
+                "You are Little Helper, a data assistant helping {}. Help work with CSV files, JSON data, and databases. Use <command></command> to examine files. To get an overview of a folder use <dir-stats path=\"~/Data\"/>: you get file counts and sizes per type plus the largest and newest files, shown to the user as a table, so summarize the highlights. To combine two CSV files on a shared column use <csv-join left=\"a.csv\" right=\"b.csv\" on=\"id\"/> (add right-on=\"...\" if the right file's key column has another name, how=\"left\" or how=\"right\" to keep unmatched rows); the result opens in the preview panel. ALWAYS open data files in the preview panel so the user can see what you're working with. Walk them through the data visually.\n{}",
                 user_name, capabilities
             ),
             ChatMode::Content => {
-                // Load full campaign context + personas + DDD workflow for Content mode
-                let campaign_docs = load_campaign_context();
-                let personas = load_personas();
+                // Personas + DDD workflow for Content mode; campaign documents
+                // come from the context plugins
+                let personas = self.agent_host.active_persona.clone().unwrap_or_else(load_personas);
                 let ddd_workflow = load_ddd_workflow();
                 
                 format!(
@@ -479,9 +1387,6 @@ YOUR ROLE: Content strategist using Data Driven Designs methodology.
 
 {}
 
-{}
-
-CONTENT CALENDAR LOCATION: ~/Projects/MCP-research-content-automation-engine/FINAL_MCP_Content_Calendar.json
 DRAFTS FOLDER: ~/Process/drafts/
 
 WORKFLOW (Data Driven Designs):
@@ -491,6 +1396,11 @@ WORKFLOW (Data Driven Designs):
 4. Save drafts to ~/Process/drafts/ with format: YYYY-MM-DD_platform_topic.md
 5. Content will sync to Google Drive for team review
 
+SCHEDULING POSTS:
+When you propose when a post should go out, wrap it in a schedule tag so it is added to the user's calendar:
+   <schedule-post platform="twitter" date="2024-12-31">Post text here</schedule-post>
+The date can be YYYY-MM-DD or "YYYY-MM-DD HH:MM" (UTC).
+
 CONTENT TYPES:
 - Twitter/X: Short, punchy, hashtags (280 chars) - match persona voice
 - LinkedIn: Professional, detailed, stats-focused - use persona's trusted language
@@ -513,284 +1423,580 @@ ALWAYS:
 
 {}
 "#,
-                    user_name, ddd_workflow, personas, campaign_docs, capabilities
+                    user_name, ddd_workflow, personas, capabilities
                 )
             },
         };
 
-        // Convert chat history to API format
-        let mut api_messages = vec![ApiChatMessage {
-            role: "system".to_string(),
-            content: system_prompt.to_string(),
-        }];
+        // The chosen template shapes Content mode replies
+        let system_prompt = match self.active_template.as_ref().filter(|_| self.current_mode == ChatMode::Content) {
+            Some(template) => format!("{}\n\n{}", system_prompt, template.prompt_context()),
+            None => system_prompt,
+        };
 
-        // Add recent chat history (last 10 messages to keep context manageable)
-        let recent_messages = self.chat_history.iter().rev().take(10).rev();
-        for msg in recent_messages {
-            api_messages.push(ApiChatMessage {
-                role: msg.role.clone(),
-                content: msg.content.clone(),
-            });
-        }
+        // Per-mode answer length
+        let system_prompt = match self.settings.response_length_for(self.current_mode.key()).prompt_suffix() {
+            Some(suffix) => format!("{}\n\n{}", system_prompt, suffix),
+            None => system_prompt,
+        };
 
-        // Start async AI generation
-        self.start_ai_generation(api_messages);
+        // Let the agent see what the user is looking at
+        let system_prompt = match &self.file_context_prompt {
+            Some(file_context) => format!("{}{}", file_context, system_prompt),
+            None => system_prompt,
+        };
+
+        // Answers in the language of the open file
+        let system_prompt = match self.open_file_language.and_then(|l| self.settings.language_hint(l.key())) {
+            Some(hint) => format!("{}\n\n{}", system_prompt, hint),
+            None => system_prompt,
+        };
+
+        system_prompt
+    }
+
+    /// Chat history sent with the next message: the last 10 messages, to
+    /// keep context manageable. /summarize replies are for the user, not the AI.
+    fn api_history(&self) -> Vec<&ChatMessage> {
+        let history: Vec<&ChatMessage> = self.chat_history.iter().filter(|m| m.role != "summary").collect();
+        history[history.len().saturating_sub(API_HISTORY_MESSAGES)..].to_vec()
+    }
+
+    /// Estimated tokens the next request will use, and the model's context window
+    fn context_usage(&self) -> (usize, usize) {
+        let used = self.system_prompt_tokens
+            + self.api_history().iter().map(|m| token_count(&m.content)).sum::<usize>();
+        let provider = self.settings.model.provider_preference.first().map(String::as_str).unwrap_or_default();
+        let window = self
+            .settings
+            .model
+            .model_for(provider)
+            .and_then(|model| providers::models::find_model(provider, model))
+            .map(|info| info.context_window)
+            .unwrap_or(DEFAULT_CONTEXT_WINDOW);
+        (used, window)
+    }
+
+    fn run_slash_command(&mut self, command: SlashCommand) {
+        match command {
+            SlashCommand::Summarize => self.summarize_conversation(),
+            SlashCommand::Clear => {
+                if self.is_thinking {
+                    self.input_error = Some("Wait for the current reply before clearing the chat".to_string());
+                    return;
+                }
+                self.new_conversation();
+                self.agent_host.reset_usage();
+            }
+            SlashCommand::Mode(mode) => self.current_mode = mode,
+            SlashCommand::Help => self.push_assistant_message(slash_commands::help_text(), Vec::new()),
+            SlashCommand::Note(content) => {
+                self.conversation.notes.push(SessionNote { content, created_at: chrono::Utc::now() });
+                self.save_conversation();
+            }
+            SlashCommand::Batch(queries) => {
+                if self.is_thinking {
+                    self.input_error = Some("Wait for the current reply before starting a batch".to_string());
+                    return;
+                }
+                self.send_batch(queries);
+            }
+        }
+    }
+
+    /// Ask each query in turn with the current mode's system prompt, waiting
+    /// for each reply before sending the next. Questions and replies are
+    /// added to the chat as they go.
+    fn send_batch(&mut self, queries: Vec<String>) {
+        self.start_batch("Processing batch", queries.into_iter().map(|q| (None, q)).collect());
+    }
+
+    /// Queue `queries`, each switching to its mode first if it has one
+    fn start_batch(&mut self, label: &'static str, queries: VecDeque<(Option<ChatMode>, String)>) {
+        self.batch = Some(BatchRun {
+            label,
+            total: queries.len(),
+            pending: queries,
+            answered: 0,
+        });
+        self.ask_next_in_batch();
+    }
+
+    /// Send the next /batch question, or finish the batch when none are left
+    fn ask_next_in_batch(&mut self) {
+        let Some(batch) = &mut self.batch else { return };
+        let Some((mode, query)) = batch.pending.pop_front() else {
+            self.batch = None;
+            return;
+        };
+        let status = format!("{}: {}/{} complete", batch.label, batch.answered, batch.total);
+        if let Some(mode) = mode {
+            self.current_mode = mode;
+        }
+        self.ask(query);
+        self.thinking_status = status;
+    }
+
+    /// Ask the AI for a 3 bullet summary of the last 20 messages, shown as
+    /// a "summary" message
+    fn summarize_conversation(&mut self) {
+        if self.is_thinking {
+            self.input_error = Some("Wait for the current reply before summarizing".to_string());
+            return;
+        }
+        let history: Vec<&ChatMessage> =
+            self.chat_history.iter().filter(|m| m.role == "user" || m.role == "assistant").collect();
+        let transcript: Vec<String> = history[history.len().saturating_sub(20)..]
+            .iter()
+            .map(|m| format!("{}: {}", if m.role == "user" { "User" } else { "Helper" }, m.content))
+            .collect();
+        if transcript.is_empty() {
+            self.input_error = Some("There's nothing to summarize yet".to_string());
+            return;
+        }
+        let messages = vec![ApiChatMessage {
+            role: "user".to_string(),
+            content: transcript.join("\n\n"),
+            attachments: Vec::new(),
+        }];
+
+        self.start_single_reply("summary", "Summarizing...", move |agent_host, chunks| {
+            Box::pin(async move {
+                agent_host
+                    .chat_streaming(messages, Some("Summarize this conversation in 3 bullet points"), chunks)
+                    .await
+            })
+        });
+    }
+
+    /// Rewrite an assistant reply for another platform, posted as a new reply
+    fn adapt_content(&mut self, content: String, to_platform: &'static str) {
+        if self.is_thinking {
+            self.input_error = Some("Wait for the current reply before adapting a post".to_string());
+            return;
+        }
+        let from_platform = self.active_template.as_ref().map_or("general".to_string(), |t| t.platform.clone());
+        let name = agent_host::platform_name(to_platform);
+        self.start_single_reply("assistant", &format!("Adapting for {}...", name), move |agent_host, _| {
+            Box::pin(async move {
+                let adapted = agent_host.adapt_content(&content, &from_platform, to_platform).await?;
+                Ok(format!("**{} version:**\n\n{}", name, adapted))
+            })
+        });
+    }
+
+    /// Run one AI call in the background and add its reply to the chat as
+    /// a `role` message. Text the call sends on its channel is shown while
+    /// it runs.
+    fn start_single_reply<F>(&mut self, role: &'static str, status: &str, call: F)
+    where
+        F: FnOnce(AgentHost, tokio::sync::mpsc::Sender<String>) -> agent_host::BoxFuture<'static, anyhow::Result<String>> + Send + 'static,
+    {
+        let (tx, rx) = channel::<AiResult>();
+        let (chunks_tx, chunks_rx) = tokio::sync::mpsc::channel(32);
+        self.ai_result_rx = Some(rx);
+        self.reply_chunks_rx = Some(chunks_rx);
+        self.partial_reply.clear();
+        self.is_thinking = true;
+        self.thinking_status = status.to_string();
+        self.agent_host.settings = self.settings.clone();
+        let agent_host = self.agent_host.clone();
+        let ctx = self.ctx.clone();
+        let task = self.runtime.spawn(async move {
+            let result = call(agent_host, chunks_tx).await;
+            let (response, error) = match result {
+                Ok(reply) => (reply.trim().to_string(), None),
+                Err(e) => (String::new(), Some(e.to_string())),
+            };
+            let _ = tx.send(AiResult { role, response, error, ..Default::default() });
+            ctx.request_repaint();
+        });
+        self.ai_task = Some(task.abort_handle());
     }
 
     fn start_ai_generation(&mut self, messages: Vec<ApiChatMessage>) {
         let (tx, rx) = channel::<AiResult>();
         self.ai_result_rx = Some(rx);
+        self.is_thinking = true;
         self.thinking_status = "Thinking...".to_string();
         
-        let settings = self.settings.model.clone();
-        
-        // Spawn background thread for AI work
-        std::thread::spawn(move || {
-            run_ai_generation(messages, settings, tx);
+        // Keep the agent in sync with any settings changed since startup
+        self.agent_host.settings = self.settings.clone();
+        if let Some(provider) = &self.provider_override {
+            cli::prefer_provider(&mut self.agent_host.settings.model, provider);
+        }
+        self.agent_host.chat_mode = Some(self.current_mode.key().to_string());
+        let agent_host = self.agent_host.clone();
+        let summarize = self.current_mode == ChatMode::Research;
+        let followups = summarize && self.research_followups;
+
+        // The UI keeps drawing while the agent works
+        let ctx = self.ctx.clone();
+        let task = self.runtime.spawn(async move {
+            let _ = tx.send(run_ai_generation(messages, agent_host, summarize, followups).await);
+            ctx.request_repaint();
         });
+        self.ai_task = Some(task.abort_handle());
+    }
+
+    /// Stop waiting for the reply being generated. The task is dropped,
+    /// which stops any command it was running, and a /batch run in
+    /// progress asks nothing more. Questions the task was
+    /// waiting on are taken down, since nothing would act on the answer.
+    fn cancel_generation(&mut self) {
+        if let Some(task) = self.ai_task.take() {
+            task.abort();
+        }
+        self.ai_result_rx = None;
+        self.reply_chunks_rx = None;
+        self.partial_reply.clear();
+        self.is_thinking = false;
+        self.thinking_status.clear();
+        self.pending_confirmation = None;
+        self.pending_batch_preview = None;
+        self.pending_dir_access = None;
+        while self.confirmation_rx.try_recv().is_ok() {}
+        while self.batch_preview_rx.try_recv().is_ok() {}
+        while self.dir_access_rx.try_recv().is_ok() {}
+        self.batch = None;
     }
     
+    /// Hash a file and post the result (with ✓ or ✗ against `expected`) in the chat
+    fn verify_hash(&mut self, path: &Path, expected: Option<&str>, algo: HashAlgorithm) {
+        let content = match services::file_tools::verify_hash(path, expected, algo) {
+            Ok(hash) => {
+                let verdict = match hash.matches() {
+                    Some(true) => " It matches the expected hash.",
+                    Some(false) => " It does **not** match the expected hash.",
+                    None => "",
+                };
+                let content = format!("{} of `{}`:{}", algo.name(), path.display(), verdict);
+                self.push_assistant_message(content, vec![hash]);
+                return;
+            }
+            Err(e) => format!("Couldn't hash `{}`: {}", path.display(), e),
+        };
+        self.push_assistant_message(content, Vec::new());
+    }
+
+    fn push_assistant_message(&mut self, content: String, hashes: Vec<FileHash>) {
+        self.chat_history.push(ChatMessage { hashes, ..ChatMessage::assistant(content) });
+        self.save_conversation();
+    }
+
+    /// Show a <csv-join> result in the preview panel. It isn't saved
+    /// anywhere, so there's no preview path.
+    fn show_joined_csv(&mut self, joined: JoinedCsv) {
+        let mut viewer = CsvViewer::new();
+        match viewer.load_from_string(&services::csv_tools::to_csv_string(&joined.rows), b',') {
+            Ok(()) => self.add_preview_tab(PreviewTab { path: None, title: joined.title, viewer: Box::new(viewer) }),
+            Err(e) => tracing::warn!("Failed to show joined CSV {}: {}", joined.title, e),
+        }
+    }
+
     /// Open a file in the preview panel
     fn open_file(&mut self, path: &Path, ctx: &egui::Context) {
-        let file_type = FileType::from_path(path);
-
-        match file_type {
-            FileType::Text | FileType::Markdown | FileType::Unknown => {
-                let mut viewer = TextViewer::new();
-                if viewer.load(path).is_ok() {
-                    self.active_viewer = ActiveViewer::Text(viewer);
-                    self.preview_path = Some(path.to_path_buf());
-                    self.show_preview = true;
+        if let Ok(content) = LoadedContent::load(path) {
+            self.show_viewer(path.to_path_buf(), viewer_for(content, ctx));
+        }
+    }
+
+    /// Load several files at once on background threads. Each gets a tab
+    /// in the preview panel and the first one loaded is shown.
+    fn open_files(&mut self, paths: Vec<PathBuf>, ctx: &egui::Context) {
+        let (tx, rx) = channel();
+        self.viewer_load_rx = Some(rx);
+        let ctx = ctx.clone();
+        self.runtime.spawn(async move {
+            let tasks: Vec<_> = paths
+                .into_iter()
+                .map(|path| tokio::task::spawn_blocking(move || LoadedContent::load(&path).map(|c| (path, c))))
+                .collect();
+            let mut loaded = Vec::new();
+            for task in tasks {
+                if let Ok(Ok(content)) = task.await {
+                    loaded.push(content);
                 }
             }
-            FileType::Image => {
-                let mut viewer = ImageViewer::new();
-                if viewer.load(path, ctx).is_ok() {
-                    self.active_viewer = ActiveViewer::Image(viewer);
-                    self.preview_path = Some(path.to_path_buf());
-                    self.show_preview = true;
+            let _ = tx.send(loaded);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Show files loaded by `open_files` once they're ready (called each frame)
+    fn poll_loaded_files(&mut self, ctx: &egui::Context) {
+        let Some(loaded) = self.viewer_load_rx.as_ref().and_then(|rx| rx.try_recv().ok()) else { return };
+        self.viewer_load_rx = None;
+        let first = loaded.first().map(|(path, _)| path.clone());
+        for (path, content) in loaded {
+            self.show_viewer(path, viewer_for(content, ctx));
+        }
+        if let Some(index) = first.and_then(|first| self.preview_tabs.iter().position(|t| t.path.as_ref() == Some(&first))) {
+            self.switch_preview_tab(index);
+        }
+    }
+
+    /// Path of the file in the active preview tab
+    fn preview_path(&self) -> Option<&Path> {
+        self.preview_tabs.get(self.active_tab).and_then(|tab| tab.path.as_deref())
+    }
+
+    /// Show `tab` in the preview panel, or the tab already open for its
+    /// file. The oldest tab closes if there are too many.
+    fn add_preview_tab(&mut self, tab: PreviewTab) {
+        let existing = tab.path.as_ref().and_then(|path| self.preview_tabs.iter().position(|t| t.path.as_ref() == Some(path)));
+        let index = match existing {
+            Some(index) => index,
+            None => {
+                if self.preview_tabs.len() >= MAX_PREVIEW_TABS {
+                    self.preview_tabs.remove(0);
                 }
+                self.preview_tabs.push(tab);
+                self.preview_tabs.len() - 1
             }
-            FileType::Csv => {
-                let mut viewer = CsvViewer::new();
-                if viewer.load(path).is_ok() {
-                    self.active_viewer = ActiveViewer::Csv(viewer);
-                    self.preview_path = Some(path.to_path_buf());
-                    self.show_preview = true;
-                }
+        };
+        self.switch_preview_tab(index);
+    }
+
+    fn switch_preview_tab(&mut self, index: usize) {
+        self.active_tab = index;
+        self.show_preview = true;
+        // Refresh what the agent knows about the open file
+        match self.preview_path().map(Path::to_path_buf) {
+            Some(path) => {
+                self.file_context_prompt = Some(load_file_context(&path));
+                self.open_file_language = services::language::detect_language_from_file(&path);
             }
-            FileType::Json => {
-                let mut viewer = JsonViewer::new();
-                if viewer.load(path).is_ok() {
-                    self.active_viewer = ActiveViewer::Json(viewer);
-                    self.preview_path = Some(path.to_path_buf());
-                    self.show_preview = true;
-                }
+            None => {
+                self.file_context_prompt = None;
+                self.open_file_language = None;
             }
-            FileType::Html => {
-                let mut viewer = HtmlViewer::new();
-                if viewer.load(path).is_ok() {
-                    self.active_viewer = ActiveViewer::Html(viewer);
-                    self.preview_path = Some(path.to_path_buf());
-                    self.show_preview = true;
+        }
+    }
+
+    fn show_viewer(&mut self, path: PathBuf, mut viewer: Box<dyn Viewer>) {
+        if let Some(text) = viewer.downcast_mut::<TextViewer>() {
+            match self.settings.file_watch {
+                WatchMode::Notify => {
+                    if let Err(e) = text.enable_notify_watch() {
+                        // Some filesystems (e.g. network drives) don't send events
+                        tracing::info!("Watching {} by polling: {}", path.display(), e);
+                        text.enable_poll_watch(FILE_POLL_INTERVAL);
+                    }
                 }
+                WatchMode::Poll => text.enable_poll_watch(FILE_POLL_INTERVAL),
+                WatchMode::Off => text.disable_watch(),
             }
-            FileType::Pdf => {
-                let mut viewer = PdfViewer::new();
-                if viewer.load(path).is_ok() {
-                    self.active_viewer = ActiveViewer::Pdf(viewer);
-                    self.preview_path = Some(path.to_path_buf());
-                    self.show_preview = true;
-                }
+        }
+        let title = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        self.add_preview_tab(PreviewTab { path: Some(path), title, viewer });
+    }
+
+    /// Run an interactive command in the terminal panel
+    fn open_terminal(&mut self, command: &str, ctx: &egui::Context) {
+        use agent_host::DangerLevel;
+
+        let outside = agent_host::paths_outside_allowed(command, &self.settings.allowed_dirs);
+        let danger = self.agent_host.get_danger_level(command);
+        let refusal = match danger {
+            DangerLevel::Blocked | DangerLevel::Dangerous | DangerLevel::NeedsSudo => Some(format!(
+                "I didn't open `{}` in the terminal because it could change your system. \
+                You can run it yourself if you're sure.",
+                command
+            )),
+            _ if !outside.is_empty() => Some(format!(
+                "I didn't open `{}` in the terminal because it uses {}, outside the folders I'm allowed to use.",
+                command,
+                outside.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+            )),
+            _ => None,
+        };
+        if let Some(content) = refusal {
+            self.push_assistant_message(content, Vec::new());
+        } else if danger == DangerLevel::Safe {
+            self.launch_terminal(command, danger, ctx);
+        } else {
+            // Asked in the same dialog as the agent's own commands
+            let (respond, answer) = tokio::sync::oneshot::channel();
+            let _ = self.confirmation_tx.send(ConfirmationRequest::new(command, danger, respond));
+            self.terminal_confirmation = Some((command.to_string(), answer));
+        }
+    }
+
+    /// Open the terminal once its command is approved
+    fn poll_terminal_confirmation(&mut self, ctx: &egui::Context) {
+        use tokio::sync::oneshot::error::TryRecvError;
+
+        let Some((_, answer)) = &mut self.terminal_confirmation else { return };
+        let approved = match answer.try_recv() {
+            Ok(approved) => approved,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Closed) => false,
+        };
+        let Some((command, _)) = self.terminal_confirmation.take() else { return };
+        if approved {
+            let danger = self.agent_host.get_danger_level(&command);
+            self.launch_terminal(&command, danger, ctx);
+        }
+    }
+
+    fn launch_terminal(&mut self, command: &str, danger: agent_host::DangerLevel, ctx: &egui::Context) {
+        match TerminalPanel::spawn(command, danger, self.settings.audit_log_path.clone(), ctx) {
+            Ok(panel) => {
+                let title = format!("Terminal: {}", panel.command());
+                self.add_preview_tab(PreviewTab { path: None, title, viewer: Box::new(panel) });
             }
-            _ => {
-                // Unsupported type - try as text
-                let mut viewer = TextViewer::new();
-                if viewer.load(path).is_ok() {
-                    self.active_viewer = ActiveViewer::Text(viewer);
-                    self.preview_path = Some(path.to_path_buf());
-                    self.show_preview = true;
-                }
+            Err(e) => {
+                let content = format!("I couldn't open a terminal for `{}`: {}", command, e);
+                self.push_assistant_message(content, Vec::new());
             }
         }
     }
 
+    /// Close the active tab, and the panel with the last one
     fn close_preview(&mut self) {
-        self.show_preview = false;
-        self.preview_path = None;
-        self.active_viewer = ActiveViewer::None;
+        self.close_preview_tab(self.active_tab);
     }
-}
 
-/// Run AI generation in background thread (non-blocking)
-fn run_ai_generation(
-    messages: Vec<ApiChatMessage>,
-    settings: shared::settings::ModelProvider,
-    tx: Sender<AiResult>,
-) {
-    use agent_host::{execute_command, web_search, classify_command, DangerLevel};
-    use providers::router::ProviderRouter;
-    
-    let rt = match tokio::runtime::Runtime::new() {
-        Ok(rt) => rt,
-        Err(e) => {
-            let _ = tx.send(AiResult {
-                response: String::new(),
-                preview_file: None,
-                error: Some(format!("Failed to start async runtime: {}", e)),
-            });
+    fn close_preview_tab(&mut self, index: usize) {
+        if index >= self.preview_tabs.len() {
             return;
         }
-    };
-    
-    let router = ProviderRouter::new(settings);
+        self.preview_tabs.remove(index);
+        if self.preview_tabs.is_empty() {
+            self.close_all_previews();
+        } else if index < self.active_tab {
+            self.active_tab -= 1;
+        } else if index == self.active_tab {
+            self.switch_preview_tab(index.min(self.preview_tabs.len() - 1));
+        }
+    }
 
-    // Pre-compile regexes
-    let preview_re = regex::Regex::new(r"<preview>([^<]+)</preview>").unwrap();
-    let search_re = regex::Regex::new(r"<search>([^<]+)</search>").unwrap();
-    let cmd_re = regex::Regex::new(r"<command>([^<]+)</command>").unwrap();
-    
-    let result = rt.block_on(async {
-        let mut msgs = messages;
-        let mut file_to_preview: Option<PathBuf> = None;
-        
-        // Loop for multi-turn interactions (max 5 iterations)
-        for _iteration in 0..5 {
-            // Get AI response
-            let response = router.generate(msgs.clone()).await?;
-            
-            // Check for preview tags
-            if let Some(cap) = preview_re.captures(&response) {
-                if let Some(m) = cap.get(1) {
-                    let path_str = m.as_str().trim();
-                    let expanded = if let Some(stripped) = path_str.strip_prefix("~/") {
-                        dirs::home_dir()
-                            .map(|h| h.join(stripped))
-                            .unwrap_or_else(|| PathBuf::from(path_str))
-                    } else {
-                        PathBuf::from(path_str)
-                    };
-                    if expanded.exists() {
-                        file_to_preview = Some(expanded);
-                    }
-                }
-            }
-            
-            // Check for search and command tags
-            let searches: Vec<String> = search_re.captures_iter(&response)
-                .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
+    fn close_all_previews(&mut self) {
+        self.show_preview = false;
+        self.preview_tabs.clear();
+        self.active_tab = 0;
+        self.file_context_prompt = None;
+        self.open_file_language = None;
+    }
+}
+
+/// Run AI generation on the app's runtime.
+/// With `summarize`, long responses also get a key takeaways summary, and
+/// with `followups` three follow-up questions.
+async fn run_ai_generation(
+    messages: Vec<ApiChatMessage>,
+    agent_host: AgentHost,
+    summarize: bool,
+    followups: bool,
+) -> AiResult {
+    // The agent handles searches, safe commands and preview tags
+    let result = agent_host.agent_chat(messages, true).await;
+
+    match result {
+        Ok(reply) => {
+            let long = reply.response.split_whitespace().count() > agent_host::KEY_TAKEAWAYS_MIN_WORDS;
+            // A failed summary isn't worth an error; the response still stands
+            let offline = providers::connectivity::is_offline();
+            let summary = if summarize && long && !offline {
+                agent_host.key_takeaways(&reply.response).await.ok()
+            } else {
+                None
+            };
+            let followup_questions = if followups && !offline {
+                agent_host.followup_questions(&reply.response).await.unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            let needs_elevation = reply
+                .tool_results
+                .iter()
+                .find(|t| !t.result.success && agent_host::needs_elevation(&t.result))
+                .map(|t| t.command.clone());
+            let tables = reply
+                .tool_results
+                .iter()
+                .filter_map(|t| Some((t.command.clone(), t.result.structured.clone()?)))
                 .collect();
-            
-            let commands: Vec<String> = cmd_re.captures_iter(&response)
-                .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
+            let command_paths = reply
+                .tool_results
+                .into_iter()
+                .filter(|t| !t.result.extracted_paths.is_empty())
+                .map(|t| (t.command, t.result.extracted_paths))
                 .collect();
-            
-            // If no actions needed, return the response
-            if searches.is_empty() && commands.is_empty() {
-                return Ok::<(String, Option<PathBuf>), anyhow::Error>((response, file_to_preview));
-            }
-            
-            // Add assistant response to conversation
-            msgs.push(ApiChatMessage {
-                role: "assistant".to_string(),
-                content: response.clone(),
-            });
-            
-            let mut results = Vec::new();
-            
-            // Execute searches
-            for query in &searches {
-                match web_search(query).await {
-                    Ok(result) => {
-                        results.push(format!("[Search Results for '{}']\n{}", query, result.output));
-                    }
-                    Err(e) => {
-                        results.push(format!("[Search failed for '{}']: {}", query, e));
-                    }
-                }
-            }
-            
-            // Execute safe commands
-            for cmd in &commands {
-                let danger = classify_command(cmd);
-                match danger {
-                    DangerLevel::Safe => {
-                        match execute_command(cmd, 30).await {
-                            Ok(result) => {
-                                results.push(format!("[Command Output: {}]\n{}", cmd, result.output));
-                            }
-                            Err(e) => {
-                                results.push(format!("[Command failed: {}]: {}", cmd, e));
-                            }
-                        }
-                    }
-                    DangerLevel::Blocked => {
-                        results.push(format!("[Command blocked for safety: {}]", cmd));
-                    }
-                    _ => {
-                        results.push(format!("[Command '{}' needs user confirmation - skipping for now]", cmd));
-                    }
-                }
-            }
-            
-            // Add results back to conversation
-            if !results.is_empty() {
-                msgs.push(ApiChatMessage {
-                    role: "user".to_string(),
-                    content: results.join("\n\n"),
-                });
+            AiResult {
+                role: "assistant",
+                response: reply.response,
+                preview_file: reply.preview_file,
+                scheduled_posts: reply.scheduled_posts,
+                interactive_command: reply.interactive_command,
+                summary: summary.filter(|s| !s.is_empty()),
+                dir_stats: reply.dir_stats,
+                debug_info: Some(reply.debug_info),
+                tables,
+                joined_csv: reply.joined_csv,
+                file_hashes: reply.file_hashes,
+                command_paths,
+                followup_questions,
+                commit_suggestion: reply.commit_suggestion,
+                needs_elevation,
+                error: None,
             }
         }
-        
-        Ok(("I've done several steps of research. Let me know if you need more details!".to_string(), file_to_preview))
-    });
-
-    // Send result back to UI
-    let ai_result = match result {
-        Ok((response, preview_file)) => AiResult {
-            response,
-            preview_file,
-            error: None,
-        },
         Err(e) => AiResult {
-            response: String::new(),
-            preview_file: None,
+            role: "assistant",
             error: Some(e.to_string()),
+            ..Default::default()
         },
-    };
-    
-    let _ = tx.send(ai_result);
+    }
 }
 
-/// Extract file paths from text
-fn extract_paths(text: &str) -> Vec<PathBuf> {
-    let mut paths = Vec::new();
-
-    // Match absolute paths like /home/user/file.txt or ~/file.txt
-    // Match paths like /home/user/file.txt or ~/file.txt
-    let path_regex = regex::Regex::new(r#"(?:^|[\s"'(])([~/][^\s"'()]+\.[a-zA-Z0-9]+)"#).unwrap();
-
-    for cap in path_regex.captures_iter(text) {
-        if let Some(m) = cap.get(1) {
-            let path_str = m.as_str();
-            // Expand ~ to home directory
-            let expanded = if let Some(stripped) = path_str.strip_prefix("~/") {
-                if let Some(home) = dirs::home_dir() {
-                    home.join(stripped)
-                } else {
-                    PathBuf::from(path_str)
-                }
-            } else {
-                PathBuf::from(path_str)
-            };
+/// A /batch run or macro replay in progress
+struct BatchRun {
+    /// "Processing batch" or "Running macro", for the status line
+    label: &'static str,
+    /// Questions still to ask, with the mode to ask each in
+    pending: VecDeque<(Option<ChatMode>, String)>,
+    total: usize,
+    answered: usize,
+}
 
-            if expanded.exists() {
-                paths.push(expanded);
-            }
+/// Make the viewer for loaded content. Runs on the UI thread, which owns
+/// the egui context image textures are created in.
+fn viewer_for(content: LoadedContent, ctx: &egui::Context) -> Box<dyn Viewer> {
+    match content {
+        LoadedContent::Text(viewer) => Box::new(viewer),
+        LoadedContent::Image { path, image } => {
+            let mut viewer = ImageViewer::new();
+            viewer.load_decoded(&path, image, ctx);
+            Box::new(viewer)
         }
+        LoadedContent::Csv(viewer) => Box::new(viewer),
+        LoadedContent::Json(viewer) => Box::new(viewer),
+        LoadedContent::Yaml(viewer) => viewer,
+        LoadedContent::Html(viewer) => Box::new(viewer),
+        LoadedContent::Pdf(viewer) => Box::new(viewer),
+        LoadedContent::Sqlite(viewer) => Box::new(viewer),
+    }
+}
+
+/// The JSON tree of a JSON or YAML viewer, which can validate against a
+/// schema the app picks
+fn json_tree(viewer: &mut dyn Viewer) -> Option<&mut JsonViewer> {
+    if viewer.downcast_ref::<YamlViewer>().is_some() {
+        viewer.downcast_mut::<YamlViewer>().and_then(YamlViewer::tree_mut)
+    } else {
+        viewer.downcast_mut::<JsonViewer>()
     }
+}
 
-    paths
+/// Image formats the vision APIs accept
+fn is_attachable_image(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    matches!(ext.as_deref(), Some("png" | "jpg" | "jpeg" | "gif" | "webp"))
 }
 
 fn config_path() -> Option<std::path::PathBuf> {
@@ -808,19 +2014,32 @@ fn load_settings_or_default() -> (AppSettings, bool) {
     if let Some(path) = config_path() {
         if path.exists() {
             if let Ok(bytes) = fs::read(&path) {
-                if let Ok(mut s) = serde_json::from_slice::<AppSettings>(&bytes) {
-                    // Force OpenAI as primary provider with pre-loaded key
-                    s.model.provider_preference = vec!["openai".to_string()];
-                    s.model.openai_auth.api_key = Some(OPENAI_API_KEY.to_string());
-                    return (s, false);
+                match migrations::load(&bytes) {
+                    Ok(mut s) => {
+                        // Fall back to the pre-loaded key; the provider order is
+                        // whatever onboarding or the settings window chose
+                        if s.model.openai_auth.api_key.as_deref().is_none_or(str::is_empty) {
+                            s.model.openai_auth.api_key = Some(OPENAI_API_KEY.to_string());
+                        }
+                        return (s, false);
+                    }
+                    Err(e) => {
+                        // Keep the file so the settings can be recovered by hand
+                        let backup = path.with_extension("json.bak");
+                        tracing::warn!("Couldn't load settings ({}), saved a copy to {}", e, backup.display());
+                        let _ = fs::copy(&path, &backup);
+                    }
                 }
             }
         }
     }
-    // Fresh install - use OpenAI with pre-loaded key
-    let mut default_settings = AppSettings::default();
-    default_settings.allowed_dirs = vec![];
-    default_settings.enable_internet_research = true;
+    // Fresh install - use OpenAI with pre-loaded key until onboarding finds
+    // the providers available
+    let mut default_settings = AppSettings {
+        allowed_dirs: vec![],
+        enable_internet_research: true,
+        ..Default::default()
+    };
     default_settings.model.provider_preference = vec!["openai".to_string()];
     default_settings.model.openai_auth.api_key = Some(OPENAI_API_KEY.to_string());
     (default_settings, true)
@@ -832,10 +2051,40 @@ fn clean_ai_response(response: &str) -> String {
     let re_preview = regex::Regex::new(r"<preview>[^<]*</preview>").unwrap();
     let re_search = regex::Regex::new(r"<search>[^<]*</search>").unwrap();
     let re_command = regex::Regex::new(r"<command>[^<]*</command>").unwrap();
+    let re_dir_stats = regex::Regex::new(r"<dir-stats[^>]*>").unwrap();
+    let re_search_files = regex::Regex::new(r"<search-files[^>]*>").unwrap();
+    let re_csv_join = regex::Regex::new(r"<csv-join[^>]*>").unwrap();
+    let re_hash_file = regex::Regex::new(r"<hash-file[^>]*>").unwrap();
+    let re_read_file = regex::Regex::new(r"<read-file[^>]*>").unwrap();
+    let re_edit_file = regex::Regex::new(r"(?s)<edit-file\s[^>]*>.*?</edit-file>").unwrap();
     
     let cleaned = re_preview.replace_all(response, "");
     let cleaned = re_search.replace_all(&cleaned, "");
     let cleaned = re_command.replace_all(&cleaned, "");
+    let cleaned = re_dir_stats.replace_all(&cleaned, "");
+    let cleaned = re_search_files.replace_all(&cleaned, "");
+    let cleaned = re_csv_join.replace_all(&cleaned, "");
+    let cleaned = re_hash_file.replace_all(&cleaned, "");
+    let cleaned = re_read_file.replace_all(&cleaned, "");
+    let cleaned = re_edit_file.replace_all(&cleaned, "");
+    let re_interactive = regex::Regex::new(r"<interactive>([^<]*)</interactive>").unwrap();
+    let cleaned = re_interactive.replace_all(&cleaned, "▶ `$1` (opened in the terminal panel)");
+
+    // Show proposed posts as plain text; they're added to the schedule separately
+    let re_schedule = regex::Regex::new(r"(?s)<schedule-post\s+([^>]*)>(.*?)</schedule-post>").unwrap();
+    let re_attr = regex::Regex::new(r#"(\w+)\s*=\s*"([^"]*)""#).unwrap();
+    let cleaned = re_schedule.replace_all(&cleaned, |cap: &regex::Captures| {
+        let mut platform = "post";
+        let mut date = "";
+        for attr in re_attr.captures_iter(cap.get(1).map_or("", |m| m.as_str())) {
+            match attr.get(1).map(|m| m.as_str()) {
+                Some("platform") => platform = attr.get(2).map_or("post", |m| m.as_str()),
+                Some("date") => date = attr.get(2).map_or("", |m| m.as_str()),
+                _ => {}
+            }
+        }
+        format!("📅 Scheduled {} for {}:\n{}", platform, date, cap[2].trim())
+    });
     
     // Clean up extra whitespace
     cleaned.trim().to_string()
@@ -844,6 +2093,10 @@ fn clean_ai_response(response: &str) -> String {
 /// Format error message with helpful troubleshooting info
 fn format_error_message(error: &str) -> String {
     let error_lower = error.to_lowercase();
+
+    if error_lower.contains("no ai provider is reachable") {
+        return agent_host::OFFLINE_MESSAGE.to_string();
+    }
     
     // API key issues
     if error_lower.contains("unauthorized") || error_lower.contains("401") || error_lower.contains("invalid api key") {
@@ -892,7 +2145,24 @@ fn format_error_message(error: &str) -> String {
 }
 
 fn main() -> eframe::Result<()> {
+    let args = cli::Args::parse();
+    if let Some(shell) = args.completions {
+        if let Err(e) = cli::write_completions(shell, &mut std::io::stdout()) {
+            eprintln!("Couldn't write completions: {}", e);
+        }
+        return Ok(());
+    }
+
     tracing_subscriber::fmt().with_env_filter("info").init();
+    // One runtime for the app's background work, so AI calls don't each
+    // start their own
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => Arc::new(runtime),
+        Err(e) => {
+            eprintln!("Couldn't start the async runtime: {}", e);
+            std::process::exit(1);
+        }
+    };
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1200.0, 800.0])
@@ -902,9 +2172,17 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "Little Helper",
         options,
-        Box::new(|_cc| {
+        Box::new(move |cc| {
+            let mut state = AppState::new(runtime, cc.egui_ctx.clone());
+            if let Some(mode) = args.mode.as_deref().and_then(ChatMode::from_key) {
+                state.current_mode = mode;
+            }
+            state.provider_override = args.provider;
+            if !args.file.is_empty() {
+                state.open_files(args.file, &cc.egui_ctx);
+            }
             Box::new(LittleHelperApp {
-                state: Arc::new(Mutex::new(AppState::default())),
+                state: Arc::new(Mutex::new(state)),
             })
         }),
     )
@@ -915,13 +2193,25 @@ struct LittleHelperApp {
 }
 
 impl eframe::App for LittleHelperApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        settings_writer::flush();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         let mut s = self.state.lock();
         
         // Poll for AI response (non-blocking)
         s.poll_ai_response();
+        s.poll_conversation_title();
+        s.poll_commit();
+        s.poll_elevated();
+        s.poll_terminal_confirmation(ctx);
+        s.check_connectivity();
+        s.handle_dropped_files(ctx);
+        s.poll_loaded_files(ctx);
         
-        // Request repaint if we're waiting for AI (to keep polling)
+        // Keep the thinking status moving; background tasks repaint when
+        // they finish
         if s.is_thinking {
             ctx.request_repaint();
         }
@@ -983,6 +2273,22 @@ impl eframe::App for LittleHelperApp {
                     mode_button(ui, "Data", ChatMode::Data, &mut s.current_mode);
                     mode_button(ui, "Content", ChatMode::Content, &mut s.current_mode);
 
+                    ui.add_space(16.0);
+
+                    // Answer length for the current mode
+                    let mode_key = s.current_mode.key();
+                    let current_length = s.settings.response_length_for(mode_key);
+                    if let Some(length) = response_length_control(ui, current_length) {
+                        s.settings.response_length.insert(mode_key.to_string(), length);
+                        save_settings(&s.settings);
+                    }
+
+                    if s.current_mode == ChatMode::Research {
+                        ui.add_space(8.0);
+                        ui.checkbox(&mut s.research_followups, "Follow-ups")
+                            .on_hover_text("Suggest 3 follow-up questions after each answer");
+                    }
+
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         ui.add_space(16.0);
 
@@ -1008,6 +2314,22 @@ impl eframe::App for LittleHelperApp {
                             save_settings(&s.settings);
                         }
 
+                        ui.add_space(8.0);
+
+                        // Saved conversations
+                        conversations_menu(&mut s, ui);
+
+                        ui.add_space(8.0);
+
+                        // Settings
+                        if ui
+                            .add(egui::Button::new(egui::RichText::new("⚙").size(18.0)).frame(false))
+                            .on_hover_text("Settings")
+                            .clicked()
+                        {
+                            s.show_settings = !s.show_settings;
+                        }
+
                         ui.add_space(12.0);
 
                         // Model indicator
@@ -1022,6 +2344,7 @@ impl eframe::App for LittleHelperApp {
                             "openai" => &s.settings.model.openai_model,
                             "anthropic" => &s.settings.model.anthropic_model,
                             "gemini" => &s.settings.model.gemini_model,
+                            "cohere" => &s.settings.model.cohere_model,
                             "local" => &s.settings.model.local_model,
                             _ => "unknown",
                         };
@@ -1038,15 +2361,103 @@ impl eframe::App for LittleHelperApp {
 
                         ui.add_space(8.0);
 
-                        if s.show_preview {
-                            if ui.button("Close Preview").clicked() {
-                                s.close_preview();
-                            }
+                        if s.show_preview && ui.button("Close Preview").clicked() {
+                            s.close_all_previews();
                         }
                     });
                 });
                 ui.add_space(12.0);
+
+                if s.chat_history.iter().any(|m| m.role == "user") {
+                    let (used, window) = s.context_usage();
+                    context_bar(ui, used, window);
+                }
+            });
+
+        if s.show_settings {
+            settings_ui::render_settings_window(&mut s, ctx);
+        }
+        if s.show_conversation_search {
+            render_conversation_search(&mut s, ctx);
+        }
+        if s.hash_check.is_some() {
+            render_hash_check(&mut s, ctx);
+        }
+        if s.show_templates {
+            content_templates::render_templates_window(&mut s, ctx);
+        }
+        if s.rename_review.is_some() {
+            rename_review::render(&mut s, ctx);
+        }
+        if s.plan_editor.is_some() {
+            plan_editor::render(&mut s, ctx);
+        }
+        if s.template_draft.is_some() {
+            content_templates::render_save_template_window(&mut s, ctx);
+        }
+        if s.pending_confirmation.is_none() {
+            s.pending_confirmation = s.confirmation_rx.try_recv().ok();
+        }
+        if s.pending_confirmation.is_some() {
+            render_confirmation_dialog(&mut s, ctx);
+        }
+        if s.pending_batch_preview.is_none() {
+            s.pending_batch_preview = s.batch_preview_rx.try_recv().ok();
+        }
+        if s.pending_batch_preview.is_some() {
+            render_batch_preview_dialog(&mut s, ctx);
+        }
+        if s.pending_dir_access.is_none() {
+            s.pending_dir_access = s.dir_access_rx.try_recv().ok();
+        }
+        if s.pending_dir_access.is_some() {
+            render_dir_access_dialog(&mut s, ctx);
+        }
+        if s.sudo_dialog.is_some() {
+            render_sudo_dialog(&mut s, ctx);
+        }
+
+        // Status bar with session usage vs. guardrails
+        egui::TopBottomPanel::bottom("status_bar")
+            .frame(egui::Frame::none().fill(if dark {
+                egui::Color32::from_rgb(35, 35, 42)
+            } else {
+                egui::Color32::from_rgb(240, 240, 245)
+            }).inner_margin(egui::Margin::symmetric(16.0, 4.0)))
+            .show(ctx, |ui| {
+                let usage = s.agent_host.usage();
+                let limits = &s.settings.guardrails;
+                let cost = match limits.max_cost_usd {
+                    Some(max) => format!("${:.2} / ${:.2}", usage.session_cost, max),
+                    None => format!("${:.2}", usage.session_cost),
+                };
+                let mut parts = vec![cost];
+                // The time limit is per request, so it only counts while one runs
+                if s.is_thinking {
+                    parts.push(match limits.max_session_minutes {
+                        Some(max) => format!("{} / {} min", usage.elapsed_minutes(), max),
+                        None => format!("{} min", usage.elapsed_minutes()),
+                    });
+                }
+                parts.push(format!(
+                    "{} / {} commands",
+                    usage.commands_executed_count, limits.max_commands_per_session
+                ));
+                ui.horizontal(|ui| {
+                    if providers::connectivity::is_offline() {
+                        ui.label(egui::RichText::new("● Offline").size(11.0).color(egui::Color32::from_rgb(220, 150, 50)))
+                            .on_hover_text("No AI provider is reachable. Local files and safe `$` commands still work; checking again every 30 seconds.");
+                    }
+                    ui.label(
+                        egui::RichText::new(format!("Session: {}", parts.join("  ·  ")))
+                            .size(11.0)
+                            .weak(),
+                    )
+                    .on_hover_text("Estimated usage for this session and your limits");
+                });
             });
+        // Keep the session timer current
+        ctx.request_repaint_after(std::time::Duration::from_secs(30));
 
         // Preview panel (right side)
         if s.show_preview {
@@ -1063,30 +2474,38 @@ impl eframe::App for LittleHelperApp {
                         .inner_margin(egui::Margin::same(12.0)),
                 )
                 .show(ctx, |ui| {
-                    // Header with file name and actions
-                    ui.horizontal(|ui| {
-                        if let Some(path) = &s.preview_path {
-                            ui.label(
-                                egui::RichText::new(
-                                    path.file_name()
-                                        .unwrap_or_default()
-                                        .to_string_lossy()
-                                        .to_string(),
-                                )
-                                .size(16.0)
-                                .strong(),
-                            );
+                    // Tab bar, with the panel's close button at the end
+                    let mut switch_to = None;
+                    let mut close = None;
+                    ui.horizontal_wrapped(|ui| {
+                        for (i, tab) in s.preview_tabs.iter().enumerate() {
+                            let hover = tab.path.as_ref().map_or_else(|| tab.title.clone(), |p| p.display().to_string());
+                            let label = ui.selectable_label(i == s.active_tab, egui::RichText::new(&tab.title).strong());
+                            if label.on_hover_text(hover).clicked() {
+                                switch_to = Some(i);
+                            }
+                            if ui.small_button("✕").on_hover_text("Close tab (Ctrl+W)").clicked() {
+                                close = Some(i);
+                            }
+                            ui.add_space(4.0);
                         }
-
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            if ui.small_button("X").clicked() {
-                                s.close_preview();
+                            if ui.small_button("X").on_hover_text("Close all").clicked() {
+                                s.close_all_previews();
                             }
                         });
                     });
-                    
+                    if let Some(i) = switch_to {
+                        s.switch_preview_tab(i);
+                    }
+                    if let Some(i) = close {
+                        s.close_preview_tab(i);
+                    } else if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::W)) {
+                        s.close_preview();
+                    }
+
                     // Action buttons
-                    if let Some(path) = s.preview_path.clone() {
+                    if let Some(path) = s.preview_path().map(Path::to_path_buf) {
                         ui.horizontal(|ui| {
                             if ui.small_button("Open in App").on_hover_text("Open with default application").clicked() {
                                 let _ = open::that(&path);
@@ -1107,19 +2526,138 @@ impl eframe::App for LittleHelperApp {
                     ui.separator();
 
                     // Render active viewer
-                    match &mut s.active_viewer {
-                        ActiveViewer::None => {
-                            ui.centered_and_justified(|ui| {
-                                ui.label("No file open");
-                            });
-                        }
-                        ActiveViewer::Text(viewer) => viewer.ui(ui),
-                        ActiveViewer::Image(viewer) => viewer.ui(ui),
-                        ActiveViewer::Csv(viewer) => viewer.ui(ui),
-                        ActiveViewer::Json(viewer) => viewer.ui(ui),
-                        ActiveViewer::Html(viewer) => viewer.ui(ui),
-                        ActiveViewer::Pdf(viewer) => viewer.ui(ui),
-                    }
+                    let state = &mut *s;
+                    let last_schema_path = &mut state.last_schema_path;
+                    match state.preview_tabs.get_mut(state.active_tab).map(|tab| tab.viewer.as_mut()) {
+                        Some(viewer) => {
+                            if let Some(tree) = json_tree(viewer) {
+                                if tree.schema_path().is_none() {
+                                    if let Some(schema) = last_schema_path {
+                                        tree.set_schema_path(schema.clone());
+                                    }
+                                }
+                            }
+                            viewer.ui(ui);
+                            if let Some(tree) = json_tree(viewer) {
+                                if tree.take_schema_request() {
+                                    let mut dialog = rfd::FileDialog::new().add_filter("JSON Schema", &["json"]);
+                                    if let Some(dir) = last_schema_path.as_deref().and_then(|p| p.parent()) {
+                                        dialog = dialog.set_directory(dir);
+                                    }
+                                    if let Some(schema) = dialog.pick_file() {
+                                        let _ = tree.validate_against_schema(&schema);
+                                        *last_schema_path = Some(schema);
+                                    }
+                                }
+                            }
+                        }
+                        None => NullViewer.ui(ui),
+                    }
+                });
+        }
+
+        // Toggle for the Files panel, along the left edge
+        egui::SidePanel::left("files_toggle")
+            .resizable(false)
+            .exact_width(32.0)
+            .frame(egui::Frame::none().fill(if dark {
+                egui::Color32::from_rgb(35, 35, 42)
+            } else {
+                egui::Color32::from_rgb(245, 247, 250)
+            }).inner_margin(egui::Margin::symmetric(4.0, 8.0)))
+            .show(ctx, |ui| {
+                let icon = egui::RichText::new("📁").size(18.0);
+                if ui
+                    .add(egui::SelectableLabel::new(s.show_file_browser, icon))
+                    .on_hover_text(if s.show_file_browser { "Hide files" } else { "Show files" })
+                    .clicked()
+                {
+                    s.show_file_browser = !s.show_file_browser;
+                }
+            });
+
+        // Files in the allowed folders (left side)
+        if s.show_file_browser {
+            let panel = egui::SidePanel::left("files")
+                .default_width(s.settings.file_browser_width)
+                .min_width(160.0)
+                .resizable(true)
+                .frame(
+                    egui::Frame::none()
+                        .fill(if dark {
+                            egui::Color32::from_rgb(35, 35, 42)
+                        } else {
+                            egui::Color32::from_rgb(240, 240, 245)
+                        })
+                        .inner_margin(egui::Margin::same(8.0)),
+                )
+                .show(ctx, |ui| {
+                    ui.label(egui::RichText::new("Files").strong());
+                    let roots = path_completer::allowed_roots(&s.settings.allowed_dirs);
+                    s.file_browser.ui(ui, &roots)
+                });
+            match panel.inner {
+                Some(FileAction::Open(path)) => s.open_file(&path, ctx),
+                Some(FileAction::CheckHash(path)) => {
+                    s.hash_check = Some(HashCheck {
+                        path,
+                        expected: String::new(),
+                        algorithm: HashAlgorithm::Sha256,
+                    });
+                }
+                Some(FileAction::SuggestRenames(dir)) => rename_review::start(&mut s, dir, ctx),
+                Some(FileAction::Organize(dir)) => {
+                    s.plan_editor = Some(PlanEditor::new(organizer::ProposedPlan { actions: Vec::new() }, dir));
+                }
+                None => {}
+            }
+
+            // Remember the width once the user lets go of the divider
+            let width = panel.response.rect.width();
+            if (width - s.settings.file_browser_width).abs() > 1.0 && !ctx.input(|i| i.pointer.any_down()) {
+                s.settings.file_browser_width = width;
+                save_settings(&s.settings);
+            }
+        }
+
+        // Post schedule (left side, Content mode only)
+        if s.current_mode == ChatMode::Content {
+            egui::SidePanel::left("content_schedule")
+                .default_width(260.0)
+                .resizable(false)
+                .frame(
+                    egui::Frame::none()
+                        .fill(if dark {
+                            egui::Color32::from_rgb(35, 35, 42)
+                        } else {
+                            egui::Color32::from_rgb(240, 240, 245)
+                        })
+                        .inner_margin(egui::Margin::same(12.0)),
+                )
+                .show(ctx, |ui| {
+                    let state = &mut *s;
+                    persona_selector(state, ui);
+                    ui.add_space(4.0);
+                    template_controls(state, ui);
+                    ui.add_space(8.0);
+                    let action = schedule::render_schedule_panel(
+                        ui,
+                        &mut state.settings.schedule,
+                        &mut state.schedule_month,
+                    );
+                    if action.changed {
+                        save_settings(&state.settings);
+                    }
+                    if action.status.is_some() {
+                        state.schedule_status = action.status;
+                    }
+                    if let Some(status) = &state.schedule_status {
+                        if status.starts_with("Error") {
+                            ui.colored_label(egui::Color32::RED, status);
+                        } else {
+                            ui.colored_label(egui::Color32::GREEN, status);
+                        }
+                    }
                 });
         }
 
@@ -1166,22 +2704,61 @@ impl eframe::App for LittleHelperApp {
                 let chat_height = ui.available_height() - 70.0;
 
                 let mut clicked_path: Option<PathBuf> = None;
+                let mut stop_thinking = false;
                 let mut slack_msg: Option<String> = None;
+                let mut followup: Option<String> = None;
+                let mut rated: Option<(usize, Option<i8>)> = None;
+                let mut adapt: Option<(String, &'static str)> = None;
 
                 egui::ScrollArea::vertical()
                     .max_height(chat_height)
                     .auto_shrink([false, false])
                     .stick_to_bottom(true)
                     .show(ui, |ui| {
-                        for msg in &s.chat_history {
+                        let state = &mut *s;
+                        for (i, msg) in state.chat_history.iter().enumerate() {
                             ui.add_space(6.0);
-                            let action = render_message(ui, msg, dark);
+                            let scope = ui.scope(|ui| {
+                                render_message(
+                                    ui,
+                                    msg,
+                                    dark,
+                                    state.settings.debug_mode,
+                                    state.settings.time_format,
+                                    state.current_mode == ChatMode::Content,
+                                    &mut state.thumbnails,
+                                )
+                            });
+                            if state.highlighted_message == Some(i) {
+                                ui.painter().rect_stroke(
+                                    scope.response.rect.expand(2.0),
+                                    egui::Rounding::same(12.0),
+                                    egui::Stroke::new(2.0, egui::Color32::from_rgb(235, 140, 75)),
+                                );
+                            }
+                            if state.scroll_to_message == Some(i) {
+                                scope.response.scroll_to_me(Some(egui::Align::Center));
+                                state.scroll_to_message = None;
+                            }
+                            let action = scope.inner;
                             if action.clicked_path.is_some() {
                                 clicked_path = action.clicked_path;
                             }
                             if action.send_to_slack.is_some() {
                                 slack_msg = action.send_to_slack;
                             }
+                            if let Some(text) = action.speak {
+                                tts::speak(&text, state.settings.tts_voice.as_deref());
+                            }
+                            if let Some(question) = action.followup {
+                                followup = Some(question);
+                            }
+                            if let Some(rating) = action.rate {
+                                rated = Some((i, rating));
+                            }
+                            if action.adapt_for.is_some() {
+                                adapt = action.adapt_for;
+                            }
                             ui.add_space(6.0);
                         }
 
@@ -1221,7 +2798,14 @@ impl eframe::App for LittleHelperApp {
                                                 })
                                                 .italics(),
                                         );
+                                        stop_thinking = ui
+                                            .small_button("Stop")
+                                            .on_hover_text("Stop waiting for this reply")
+                                            .clicked();
                                     });
+                                    if !s.partial_reply.is_empty() {
+                                        ui.label(&s.partial_reply);
+                                    }
                                 });
                             // Request repaint to animate
                             ctx.request_repaint();
@@ -1232,12 +2816,35 @@ impl eframe::App for LittleHelperApp {
                 if let Some(path) = clicked_path {
                     s.open_file(&path, ctx);
                 }
+                if stop_thinking {
+                    s.cancel_generation();
+                }
                 
                 // Handle pending preview from agent (auto-open)
                 if let Some(path) = s.pending_preview.take() {
                     s.open_file(&path, ctx);
                 }
+                if let Some(command) = s.pending_terminal.take() {
+                    s.open_terminal(&command, ctx);
+                }
                 
+                // Follow-up chip: ready to send from the input
+                if let Some(question) = followup {
+                    s.input_text = question;
+                    s.input_error = None;
+                    s.focus_input_at_end(ctx, egui::Id::new("chat_input"));
+                }
+
+                if let Some((content, platform)) = adapt {
+                    s.adapt_content(content, platform);
+                }
+
+                // Thumbs up/down on a reply
+                if let Some((i, rating)) = rated {
+                    s.chat_history[i].rating = rating;
+                    s.save_conversation();
+                }
+
                 // Handle Slack send request
                 if let Some(msg) = slack_msg {
                     s.slack_message_to_send = Some(msg);
@@ -1247,24 +2854,122 @@ impl eframe::App for LittleHelperApp {
 
                 ui.add_space(8.0);
 
+                // Standing /note instructions for this conversation
+                if !s.conversation.notes.is_empty() {
+                    let mut remove = None;
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(egui::RichText::new("Notes:").size(12.0).weak());
+                        for (i, note) in s.conversation.notes.iter().enumerate() {
+                            if ui
+                                .small_button(format!("📌 {}  ✕", note.content))
+                                .on_hover_text("Remove note")
+                                .clicked()
+                            {
+                                remove = Some(i);
+                            }
+                        }
+                    });
+                    if let Some(i) = remove {
+                        s.conversation.notes.remove(i);
+                        s.save_conversation();
+                    }
+                    ui.add_space(4.0);
+                }
+
+                // Images waiting to be sent with the next message
+                if !s.pending_attachments.is_empty() {
+                    let mut remove = None;
+                    ui.horizontal_wrapped(|ui| {
+                        for (i, path) in s.pending_attachments.iter().enumerate() {
+                            let name = path.file_name().unwrap_or_default().to_string_lossy();
+                            if ui
+                                .small_button(format!("🖼 {}  ✕", name))
+                                .on_hover_text("Remove attachment")
+                                .clicked()
+                            {
+                                remove = Some(i);
+                            }
+                        }
+                    });
+                    if let Some(i) = remove {
+                        s.pending_attachments.remove(i);
+                    }
+                    ui.add_space(4.0);
+                }
+
                 // Input area
                 ui.horizontal(|ui| {
                     let hint = match s.current_mode {
-                        ChatMode::Find => "What would you like me to find?",
+                        ChatMode::Find => "What would you like me to find? (e.g. all **/*.toml files)",
                         ChatMode::Fix => "What needs fixing?",
                         ChatMode::Research => "What should I research?",
                         ChatMode::Data => "What data would you like to work with?",
                         ChatMode::Content => "What content would you like to create?",
                     };
 
+                    let input_id = egui::Id::new("chat_input");
+                    let popup_id = egui::Id::new("path_completions");
+                    let completing = s.path_completer.is_active()
+                        && ui.memory(|m| m.is_popup_open(popup_id) && m.has_focus(input_id));
+
+                    // Take navigation keys before the text field sees them
+                    let mut accepted: Option<usize> = None;
+                    if completing {
+                        ui.input_mut(|i| {
+                            if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown) {
+                                s.path_completer.select_next();
+                            }
+                            if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp) {
+                                s.path_completer.select_prev();
+                            }
+                            if i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)
+                                || i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)
+                            {
+                                accepted = Some(s.path_completer.selected());
+                            }
+                            if i.consume_key(egui::Modifiers::NONE, egui::Key::Escape) {
+                                s.path_completer.dismiss();
+                            }
+                        });
+                    }
+
                     let response = ui.add_sized(
                         [ui.available_width() - 80.0, 40.0],
                         egui::TextEdit::singleline(&mut s.input_text)
+                            .id(input_id)
                             .hint_text(hint)
+                            .lock_focus(completing)
                             .font(egui::FontId::new(15.0, egui::FontFamily::Proportional)),
                     );
 
-                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    if response.changed() {
+                        s.input_error = None;
+                    }
+                    s.update_path_completions();
+                    if s.path_completer.is_active() && response.has_focus() {
+                        ui.memory_mut(|m| m.open_popup(popup_id));
+                    } else if !s.path_completer.is_active() && ui.memory(|m| m.is_popup_open(popup_id)) {
+                        ui.memory_mut(|m| m.close_popup());
+                    }
+
+                    egui::popup_below_widget(ui, popup_id, &response, |ui| {
+                        let selected = s.path_completer.selected();
+                        for (i, completion) in s.path_completer.completions().iter().enumerate() {
+                            if ui
+                                .selectable_label(
+                                    i == selected,
+                                    egui::RichText::new(&completion.label).monospace(),
+                                )
+                                .clicked()
+                            {
+                                accepted = Some(i);
+                            }
+                        }
+                    });
+
+                    if let Some(index) = accepted {
+                        s.apply_path_completion(index, ctx, input_id);
+                    } else if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
                         s.send_message();
                     }
 
@@ -1278,6 +2983,48 @@ impl eframe::App for LittleHelperApp {
                         s.send_message();
                     }
                 });
+                if let Some(error) = &s.input_error {
+                    ui.colored_label(egui::Color32::from_rgb(200, 60, 60), error);
+                }
+                if !s.more_found_files.is_empty() {
+                    ui.horizontal(|ui| {
+                        let count = s.more_found_files.len();
+                        let label = format!("Also found {} more file{} — click to view", count, if count == 1 { "" } else { "s" });
+                        if ui.link(label).clicked() {
+                            // The file already open stays in front, the rest become tabs
+                            let paths = s.preview_path().map(Path::to_path_buf).into_iter().chain(std::mem::take(&mut s.more_found_files)).collect();
+                            s.open_files(paths, ctx);
+                        }
+                        if ui.small_button("✕").on_hover_text("Dismiss").clicked() {
+                            s.more_found_files.clear();
+                        }
+                    });
+                }
+                if let Some(pending) = &mut s.pending_commit {
+                    let mut commit = false;
+                    let mut dismiss = false;
+                    ui.horizontal(|ui| {
+                        ui.label("Commit message:");
+                        ui.add(egui::TextEdit::singleline(&mut pending.message).desired_width(ui.available_width() - 170.0));
+                        let files: Vec<String> = pending.suggestion.files.iter().map(|f| f.display().to_string()).collect();
+                        commit = ui
+                            .add_enabled(!pending.message.trim().is_empty(), egui::Button::new("Commit"))
+                            .on_hover_text(format!("git add and git commit {} in {}", files.join(" "), pending.suggestion.repo.display()))
+                            .clicked();
+                        dismiss = ui.button("Not now").clicked();
+                    });
+                    if commit {
+                        s.start_commit();
+                    } else if dismiss {
+                        s.pending_commit = None;
+                    }
+                }
+                if s.commit_rx.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Committing...");
+                    });
+                }
             });
         
         // Slack dialog window (modal-ish)
@@ -1449,19 +3196,87 @@ fn mode_button(ui: &mut egui::Ui, label: &str, mode: ChatMode, current: &mut Cha
     }
 }
 
+/// Brief / Balanced / Detailed segmented control, returning the new choice if it changed
+fn response_length_control(ui: &mut egui::Ui, current: ResponseLength) -> Option<ResponseLength> {
+    let mut changed = None;
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 2.0;
+        for length in ResponseLength::ALL {
+            if ui
+                .selectable_label(current == length, egui::RichText::new(length.label()).size(11.0))
+                .on_hover_text("How long answers should be in this mode")
+                .clicked()
+                && current != length
+            {
+                changed = Some(length);
+            }
+        }
+    });
+    changed
+}
+
 /// Result from rendering a message
 struct MessageAction {
     clicked_path: Option<PathBuf>,
     send_to_slack: Option<String>,
+    speak: Option<String>,
+    followup: Option<String>,
+    rate: Option<Option<i8>>,  // New rating for the message; Some(None) clears it
+    adapt_for: Option<(String, &'static str)>,  // Content to rewrite for a platform
+}
+
+/// Render a small clickable thumbnail, loading the texture only once it scrolls into view.
+/// Returns `None` if the image can't be decoded, so the caller can fall back to a link.
+fn render_thumbnail(
+    ui: &mut egui::Ui,
+    path: &Path,
+    thumbnails: &mut ThumbnailCache,
+) -> Option<egui::Response> {
+    match thumbnails.get(path) {
+        Some(Some(texture)) => {
+            let sized = egui::load::SizedTexture::new(texture.id(), texture.size_vec2());
+            Some(
+                ui.add(egui::Image::new(sized).sense(egui::Sense::click()))
+                    .on_hover_text(path.to_string_lossy()),
+            )
+        }
+        Some(None) => None,
+        None => {
+            // Placeholder until the message is actually on screen
+            let size = egui::vec2(THUMBNAIL_MAX_SIZE as f32, THUMBNAIL_MAX_SIZE as f32);
+            let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+            if ui.is_rect_visible(rect) {
+                ui.painter()
+                    .rect_filled(rect, egui::Rounding::same(8.0), ui.visuals().faint_bg_color);
+                let texture = image_viewer::load_texture(path, ui.ctx(), Some(THUMBNAIL_MAX_SIZE)).ok();
+                thumbnails.insert(path.to_path_buf(), texture);
+                ui.ctx().request_repaint();
+            }
+            Some(response)
+        }
+    }
 }
 
 /// Render a chat message, returning any actions taken
-fn render_message(ui: &mut egui::Ui, msg: &ChatMessage, dark: bool) -> MessageAction {
+fn render_message(
+    ui: &mut egui::Ui,
+    msg: &ChatMessage,
+    dark: bool,
+    debug_mode: bool,
+    time_format: TimeFormat,
+    content_mode: bool,
+    thumbnails: &mut ThumbnailCache,
+) -> MessageAction {
     let is_user = msg.role == "user";
     let mut action = MessageAction {
         clicked_path: None,
         send_to_slack: None,
+        speak: None,
+        followup: None,
+        rate: None,
+        adapt_for: None,
     };
+    let top = ui.cursor().top();
 
     if is_user {
         // User message - right aligned, blue
@@ -1473,16 +3288,48 @@ fn render_message(ui: &mut egui::Ui, msg: &ChatMessage, dark: bool) -> MessageAc
                 .inner_margin(egui::Margin::same(12.0))
                 .show(ui, |ui| {
                     ui.set_max_width(500.0);
-                    ui.label(
-                        egui::RichText::new(&msg.content)
-                            .color(egui::Color32::WHITE)
-                            .size(15.0),
-                    );
+                    if !msg.attachments.is_empty() {
+                        ui.horizontal_wrapped(|ui| {
+                            for path in msg.attachments.iter().take(MAX_INLINE_IMAGES) {
+                                if let Some(response) = render_thumbnail(ui, path, thumbnails) {
+                                    if response.clicked() {
+                                        action.clicked_path = Some(path.clone());
+                                    }
+                                }
+                            }
+                        });
+                    }
+                    if !msg.content.is_empty() {
+                        ui.label(
+                            egui::RichText::new(&msg.content)
+                                .color(egui::Color32::WHITE)
+                                .size(15.0),
+                        );
+                    }
                 });
         });
-    } else {
-        // Assistant message - left aligned, with clickable paths
+    } else if msg.role == "summary" {
+        // /summarize reply - grey italic, set apart from the conversation
         egui::Frame::none()
+            .fill(if dark {
+                egui::Color32::from_rgb(40, 40, 44)
+            } else {
+                egui::Color32::from_rgb(232, 232, 235)
+            })
+            .rounding(egui::Rounding::same(12.0))
+            .inner_margin(egui::Margin::same(12.0))
+            .show(ui, |ui| {
+                ui.set_max_width(600.0);
+                ui.label(egui::RichText::new("Conversation summary").size(12.0).weak());
+                ui.add_space(4.0);
+                ui.label(egui::RichText::new(&msg.content).italics().color(egui::Color32::GRAY).size(14.0));
+            });
+    } else {
+        // Assistant message - left aligned, with clickable paths.
+        // Rating buttons show while the bubble was hovered last frame.
+        let hover_id = egui::Id::new(("message_hover", &msg.timestamp, &msg.content));
+        let show_rating = msg.rating.is_some() || ui.data(|d| d.get_temp::<bool>(hover_id).unwrap_or(false));
+        let bubble = egui::Frame::none()
             .fill(if dark {
                 egui::Color32::from_rgb(50, 50, 58)
             } else {
@@ -1516,12 +3363,34 @@ fn render_message(ui: &mut egui::Ui, msg: &ChatMessage, dark: bool) -> MessageAc
                             .size(15.0),
                     );
 
+                    // Inline previews for the first few images, links for everything else
+                    let mut link_paths = Vec::new();
+                    let mut inline_count = 0;
                     ui.add_space(8.0);
-                    ui.separator();
-                    ui.add_space(4.0);
-                    ui.label(egui::RichText::new("Files found:").size(12.0).weak());
+                    ui.horizontal_wrapped(|ui| {
+                        for path in paths {
+                            let is_image = FileType::from_path(&path) == FileType::Image;
+                            if is_image && inline_count < MAX_INLINE_IMAGES {
+                                if let Some(response) = render_thumbnail(ui, &path, thumbnails) {
+                                    inline_count += 1;
+                                    if response.clicked() {
+                                        action.clicked_path = Some(path);
+                                    }
+                                    continue;
+                                }
+                            }
+                            link_paths.push(path);
+                        }
+                    });
 
-                    for path in paths {
+                    if !link_paths.is_empty() {
+                        ui.add_space(8.0);
+                        ui.separator();
+                        ui.add_space(4.0);
+                        ui.label(egui::RichText::new("Files found:").size(12.0).weak());
+                    }
+
+                    for path in link_paths {
                         let file_name = path
                             .file_name()
                             .unwrap_or_default()
@@ -1534,6 +3403,57 @@ fn render_message(ui: &mut egui::Ui, msg: &ChatMessage, dark: bool) -> MessageAc
                     }
                 }
                 
+                for (command, table) in &msg.tables {
+                    ui.add_space(8.0);
+                    render_structured_table(ui, command, table, text_color);
+                }
+
+                for (command, paths) in &msg.command_paths {
+                    ui.add_space(8.0);
+                    if let Some(path) = render_command_paths(ui, command, paths) {
+                        action.clicked_path = Some(path);
+                    }
+                }
+
+                for stats in &msg.dir_stats {
+                    ui.add_space(8.0);
+                    render_dir_stats(ui, stats, text_color);
+                }
+
+                for hash in &msg.hashes {
+                    ui.add_space(8.0);
+                    render_file_hash(ui, hash, text_color);
+                }
+
+                if let Some(summary) = &msg.summary {
+                    ui.add_space(8.0);
+                    egui::CollapsingHeader::new(egui::RichText::new("Key Takeaways").strong())
+                        .id_source(("key_takeaways", &msg.content))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            ui.label(egui::RichText::new(summary).color(text_color).size(14.0));
+                        });
+                }
+
+                if !msg.followup_questions.is_empty() {
+                    ui.add_space(8.0);
+                    ui.label(egui::RichText::new("Dig deeper:").size(12.0).weak());
+                    ui.horizontal_wrapped(|ui| {
+                        for question in &msg.followup_questions {
+                            let chip = egui::Button::new(egui::RichText::new(question).size(13.0))
+                                .rounding(egui::Rounding::same(10.0));
+                            if ui.add(chip).on_hover_text("Ask this next").clicked() {
+                                action.followup = Some(question.clone());
+                            }
+                        }
+                    });
+                }
+
+                if let Some(debug) = msg.debug_info.as_ref().filter(|_| debug_mode) {
+                    ui.add_space(8.0);
+                    render_debug_info(ui, debug, ("debug_info", &msg.content));
+                }
+
                 // Action buttons for assistant responses
                 ui.add_space(8.0);
                 ui.horizontal(|ui| {
@@ -1544,13 +3464,775 @@ fn render_message(ui: &mut egui::Ui, msg: &ChatMessage, dark: bool) -> MessageAc
                     if ui.small_button("Send to Slack").on_hover_text("Share this response to a Slack channel").clicked() {
                         action.send_to_slack = Some(msg.content.clone());
                     }
+                    ui.add_space(8.0);
+                    if ui.small_button("🔊").on_hover_text("Read aloud").clicked() {
+                        action.speak = Some(msg.content.clone());
+                    }
+                    if content_mode {
+                        ui.add_space(8.0);
+                        ui.menu_button("Adapt for…", |ui| {
+                            for (key, name, _) in agent_host::CONTENT_PLATFORMS {
+                                if ui.button(*name).clicked() {
+                                    action.adapt_for = Some((msg.content.clone(), *key));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+                    if show_rating {
+                        ui.add_space(8.0);
+                        for (rating, icon, hint) in [(1, "👍", "Good reply"), (-1, "👎", "Poor reply")] {
+                            let selected = msg.rating == Some(rating);
+                            if ui.add(egui::SelectableLabel::new(selected, icon)).on_hover_text(hint).clicked() {
+                                action.rate = Some(if selected { None } else { Some(rating) });
+                            }
+                        }
+                    }
                 });
             });
+        let hovered = bubble.response.contains_pointer();
+        ui.data_mut(|d| d.insert_temp(hover_id, hovered));
     }
 
+    // Time under the bubble, on the same side, and the token count while
+    // the message was hovered last frame
+    let tokens_hover_id = egui::Id::new(("message_tokens_hover", &msg.timestamp, &msg.content));
+    let show_tokens = ui.data(|d| d.get_temp::<bool>(tokens_hover_id).unwrap_or(false));
+    let layout = if is_user {
+        egui::Layout::right_to_left(egui::Align::Min)
+    } else {
+        egui::Layout::left_to_right(egui::Align::Min)
+    };
+    ui.with_layout(layout, |ui| {
+        ui.add_space(if is_user { 12.0 } else { 4.0 });
+        let time = ui.label(egui::RichText::new(timestamp::format(&msg.timestamp, time_format)).size(11.0).weak());
+        if let Some(hover) = timestamp::hover_text(&msg.timestamp) {
+            time.on_hover_text(hover);
+        }
+        if show_tokens && msg.token_count > 0 {
+            ui.add_space(6.0);
+            token_chip(ui, msg.token_count);
+        }
+    });
+    let message_rect = egui::Rect::from_x_y_ranges(ui.max_rect().x_range(), top..=ui.cursor().top());
+    let hovered = ui.rect_contains_pointer(message_rect);
+    ui.data_mut(|d| d.insert_temp(tokens_hover_id, hovered));
+
     action
 }
 
+/// Messages above this many tokens take up a noticeable share of the context
+const LARGE_MESSAGE_TOKENS: usize = 1000;
+
+/// "~420 tokens" in a small grey chip, amber for large messages
+fn token_chip(ui: &mut egui::Ui, tokens: usize) {
+    let large = tokens > LARGE_MESSAGE_TOKENS;
+    let color = if large { egui::Color32::from_rgb(220, 150, 50) } else { egui::Color32::GRAY };
+    let chip = egui::Frame::none()
+        .stroke(egui::Stroke::new(1.0, color.gamma_multiply(0.6)))
+        .rounding(egui::Rounding::same(6.0))
+        .inner_margin(egui::Margin::symmetric(5.0, 0.0))
+        .show(ui, |ui| {
+            ui.label(egui::RichText::new(format!("~{} tokens", tokens)).size(10.5).color(color));
+        });
+    if large {
+        chip.response.on_hover_text("This message uses a lot of the conversation's context");
+    }
+}
+
+/// Templates button, the active template and "Save as template"
+fn template_controls(s: &mut AppState, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        if ui.button("📝 Templates").clicked() {
+            s.show_templates = true;
+        }
+        if ui.button("Save as template").on_hover_text("Save this request and the last reply as a template").clicked() {
+            s.template_draft = Some(content_templates::draft_from_chat(s));
+        }
+    });
+    if let Some(template) = &s.active_template {
+        let mut clear = false;
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(format!("Template: {}", template.name)).size(12.0));
+            clear = ui.small_button("✕").on_hover_text("Stop using this template").clicked();
+        });
+        if clear {
+            s.active_template = None;
+        }
+    }
+}
+
+/// Dropdown choosing which persona Content mode writes for. Changing it
+/// affects the next message; the conversation carries on.
+fn persona_selector(s: &mut AppState, ui: &mut egui::Ui) {
+    let mut selected = s.selected_persona.clone();
+    ui.horizontal(|ui| {
+        ui.label("Persona:");
+        egui::ComboBox::from_id_source("content_persona")
+            .selected_text(selected.as_deref().unwrap_or("All personas"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut selected, None, "All personas");
+                for name in &s.personas {
+                    ui.selectable_value(&mut selected, Some(name.clone()), name);
+                }
+            });
+        if ui.small_button("↻").on_hover_text("Look for new persona files").clicked() {
+            s.personas = services::personas::discover_personas().into_iter().map(|p| p.name).collect();
+        }
+    });
+
+    if selected == s.selected_persona {
+        return;
+    }
+    match &selected {
+        Some(name) => match s.agent_host.set_active_persona(name) {
+            Ok(()) => s.selected_persona = selected,
+            Err(e) => tracing::warn!("Failed to load persona: {}", e),
+        },
+        None => {
+            s.agent_host.clear_active_persona();
+            s.selected_persona = None;
+        }
+    }
+}
+
+/// Window for hashing a file and comparing with a published hash
+fn render_hash_check(s: &mut AppState, ctx: &egui::Context) {
+    let Some(check) = &mut s.hash_check else { return };
+    let mut open = true;
+    let mut run = false;
+
+    egui::Window::new("Check file hash")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            ui.label(egui::RichText::new(check.path.display().to_string()).monospace());
+            ui.add_space(6.0);
+            egui::ComboBox::from_label("Algorithm")
+                .selected_text(check.algorithm.name())
+                .show_ui(ui, |ui| {
+                    for algorithm in HashAlgorithm::ALL {
+                        ui.selectable_value(&mut check.algorithm, algorithm, algorithm.name());
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut check.expected)
+                    .hint_text("Expected hash (optional)")
+                    .desired_width(360.0),
+            );
+            ui.add_space(6.0);
+            run = ui.button("Check").clicked();
+        });
+
+    if run {
+        if let Some(check) = s.hash_check.take() {
+            s.verify_hash(&check.path, Some(&check.expected), check.algorithm);
+        }
+    } else if !open {
+        s.hash_check = None;
+    }
+}
+
+/// Modal asking whether the agent may run a command
+fn render_confirmation_dialog(s: &mut AppState, ctx: &egui::Context) {
+    use agent_host::DangerLevel;
+
+    let Some(request) = &s.pending_confirmation else { return };
+    let mut answer = None;
+
+    egui::Window::new("Run this command?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            ui.set_max_width(420.0);
+            let warning = match request.danger {
+                DangerLevel::Dangerous => "⚠ This command can delete or change things in ways that are hard to undo.",
+                _ => "This command changes files on your computer.",
+            };
+            ui.label(warning);
+            ui.add_space(6.0);
+            egui::Frame::none()
+                .fill(ui.visuals().extreme_bg_color)
+                .rounding(egui::Rounding::same(6.0))
+                .inner_margin(egui::Margin::same(8.0))
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new(&request.command).monospace());
+                });
+            if let Some(expanded) = &request.expanded {
+                ui.horizontal_wrapped(|ui| {
+                    ui.spacing_mut().item_spacing.x = 4.0;
+                    ui.label(egui::RichText::new("Will run as:").small().color(egui::Color32::GRAY));
+                    ui.label(egui::RichText::new(expanded).small().monospace().color(egui::Color32::GRAY));
+                });
+            }
+            if let Some(help) = &request.help {
+                ui.add_space(6.0);
+                let (about, usage) = help.split_once('\n').unwrap_or((help, ""));
+                ui.label(egui::RichText::new(about).small());
+                if !usage.is_empty() {
+                    ui.label(egui::RichText::new(usage).small().monospace().color(egui::Color32::GRAY));
+                }
+            }
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                let run = if request.danger == DangerLevel::Dangerous {
+                    egui::Button::new(egui::RichText::new("Run anyway").color(egui::Color32::WHITE))
+                        .fill(egui::Color32::from_rgb(200, 70, 60))
+                } else {
+                    egui::Button::new("Run")
+                };
+                if ui.add(run).clicked() {
+                    answer = Some(true);
+                }
+                if ui.button("Don't run").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    answer = Some(false);
+                }
+            });
+        });
+
+    if let Some(approved) = answer {
+        if let Some(request) = s.pending_confirmation.take() {
+            let _ = request.respond.send(approved);
+        }
+    }
+}
+
+/// The commands from one response with a checkbox each; only the ticked
+/// ones run
+fn render_batch_preview_dialog(s: &mut AppState, ctx: &egui::Context) {
+    use agent_host::DangerLevel;
+
+    let Some(request) = &mut s.pending_batch_preview else { return };
+    let mut answer = None;
+
+    egui::Window::new("Run these commands?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            ui.set_max_width(480.0);
+            ui.label("Untick anything you don't want to run.");
+            ui.add_space(6.0);
+            egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                for ((command, danger), selected) in request.commands.iter().zip(request.selected.iter_mut()) {
+                    let (color, note) = match danger {
+                        DangerLevel::Safe => (egui::Color32::from_rgb(60, 160, 80), "read-only"),
+                        DangerLevel::NeedsConfirmation => (egui::Color32::from_rgb(220, 160, 40), "changes files"),
+                        DangerLevel::Dangerous => (egui::Color32::from_rgb(200, 70, 60), "hard to undo"),
+                        DangerLevel::NeedsSudo => (egui::Color32::GRAY, "needs admin, won't run"),
+                        DangerLevel::Blocked => (egui::Color32::GRAY, "blocked"),
+                    };
+                    let can_run = !matches!(danger, DangerLevel::Blocked | DangerLevel::NeedsSudo);
+                    ui.horizontal(|ui| {
+                        ui.add_enabled(can_run, egui::Checkbox::without_text(selected));
+                        ui.label(egui::RichText::new("●").color(color)).on_hover_text(note);
+                        ui.label(egui::RichText::new(command).monospace());
+                    });
+                }
+            });
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                let count = request.selected.iter().filter(|s| **s).count();
+                if ui.add_enabled(count > 0, egui::Button::new(format!("Run {} selected", count))).clicked() {
+                    answer = Some(request.selected.clone());
+                }
+                if ui.button("Don't run any").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    answer = Some(vec![false; request.commands.len()]);
+                }
+            });
+        });
+
+    if let Some(selected) = answer {
+        if let Some(request) = s.pending_batch_preview.take() {
+            let _ = request.respond.send(selected);
+        }
+    }
+}
+
+/// Allow a folder outside the allowed ones until the app closes. Keeping
+/// it for good is done in Settings.
+fn render_dir_access_dialog(s: &mut AppState, ctx: &egui::Context) {
+    let Some(request) = &s.pending_dir_access else { return };
+    let mut answer = None;
+
+    egui::Window::new("Allow this folder?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            ui.set_max_width(420.0);
+            ui.label(format!("Allow read access to {} for this session?", request.dir.display()));
+            ui.label(
+                egui::RichText::new("It's outside the folders Little Helper may use. You can keep it for good in Settings.")
+                    .small()
+                    .color(egui::Color32::GRAY),
+            );
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if ui.button("Allow").clicked() {
+                    answer = Some(true);
+                }
+                if ui.button("Don't allow").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    answer = Some(false);
+                }
+            });
+        });
+
+    if let Some(allowed) = answer {
+        if let Some(request) = s.pending_dir_access.take() {
+            let _ = request.respond.send(allowed);
+        }
+    }
+}
+
+/// Offer to rerun a command that failed for lack of permissions, asking
+/// for the sudo password outside Windows
+fn render_sudo_dialog(s: &mut AppState, ctx: &egui::Context) {
+    let Some(dialog) = &mut s.sudo_dialog else { return };
+    let password_id = egui::Id::new("sudo_password");
+    let mut answer = None;
+
+    egui::Window::new("Run with admin rights?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            ui.set_max_width(420.0);
+            ui.label("This command failed because it needs administrator rights:");
+            ui.add_space(6.0);
+            egui::Frame::none()
+                .fill(ui.visuals().extreme_bg_color)
+                .rounding(egui::Rounding::same(6.0))
+                .inner_margin(egui::Margin::same(8.0))
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new(&dialog.command).monospace());
+                });
+            ui.add_space(6.0);
+            let mut submitted = false;
+            if cfg!(windows) {
+                ui.label(egui::RichText::new("Windows will ask you to allow it.").small().color(egui::Color32::GRAY));
+            } else {
+                let edit = ui.add(
+                    egui::TextEdit::singleline(&mut dialog.password)
+                        .id(password_id)
+                        .password(true)
+                        .hint_text("Your password")
+                        .desired_width(f32::INFINITY),
+                );
+                edit.request_focus();
+                submitted = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            }
+            if let Some(error) = &dialog.error {
+                ui.label(egui::RichText::new(error).small().color(egui::Color32::from_rgb(200, 70, 60)));
+            }
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if ui.button("Run as admin").clicked() || submitted {
+                    answer = Some(true);
+                }
+                if ui.button("Cancel").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    answer = Some(false);
+                }
+            });
+        });
+
+    if let Some(run) = answer {
+        // The text field's undo history holds what was typed too
+        ctx.data_mut(|d| d.remove::<egui::text_edit::TextEditState>(password_id));
+        if run {
+            s.start_elevated();
+        } else {
+            s.sudo_dialog = None;
+        }
+    }
+}
+
+/// Overwrite a password, including any capacity past its end that held
+/// deleted characters, and leave it empty
+fn wipe(password: &mut String) {
+    let mut bytes = std::mem::take(password).into_bytes();
+    bytes.resize(bytes.capacity(), 0);
+    bytes.fill(0);
+    std::hint::black_box(&bytes);
+}
+
+/// Window for searching every saved conversation
+fn render_conversation_search(s: &mut AppState, ctx: &egui::Context) {
+    let mut open = s.show_conversation_search;
+    let mut selected = None;
+
+    egui::Window::new("Search conversations")
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut s.conversation_search)
+                    .hint_text("Search all conversations")
+                    .desired_width(f32::INFINITY),
+            );
+            if response.changed() {
+                s.conversation_search_hits = s
+                    .conversation_store
+                    .search(&s.conversation_search)
+                    .unwrap_or_default();
+            }
+            ui.add_space(6.0);
+
+            if !s.conversation_search.trim().is_empty() && s.conversation_search_hits.is_empty() {
+                ui.label(egui::RichText::new("No matches").weak());
+            }
+            egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                let text_color = ui.visuals().text_color();
+                let highlight = egui::Color32::from_rgb(235, 140, 75);
+                for hit in &s.conversation_search_hits {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(&hit.title).strong());
+                        ui.label(
+                            egui::RichText::new(hit.created_at.with_timezone(&chrono::Local).format("%b %d, %Y").to_string())
+                                .size(12.0)
+                                .weak(),
+                        );
+                    });
+                    let mut job = egui::text::LayoutJob::default();
+                    let plain = egui::TextFormat { color: text_color, ..Default::default() };
+                    let matched = egui::TextFormat { color: egui::Color32::BLACK, background: highlight, ..Default::default() };
+                    job.append(&hit.preview[..hit.matched.start], 0.0, plain.clone());
+                    job.append(&hit.preview[hit.matched.clone()], 0.0, matched);
+                    job.append(&hit.preview[hit.matched.end..], 0.0, plain);
+                    job.wrap.max_width = ui.available_width();
+                    if ui.add(egui::Label::new(job).sense(egui::Sense::click())).on_hover_text("Open conversation").clicked() {
+                        selected = Some((hit.conversation_id, hit.message_index));
+                    }
+                    ui.separator();
+                }
+            });
+        });
+
+    s.show_conversation_search = open;
+    if let Some((id, index)) = selected {
+        match s.conversation_store.load(id) {
+            Ok(conversation) if !s.is_thinking => {
+                s.open_conversation(conversation);
+                s.highlighted_message = Some(index);
+                s.scroll_to_message = Some(index);
+                s.show_conversation_search = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// "☰" menu for starting, switching, importing and exporting conversations
+fn conversations_menu(s: &mut AppState, ui: &mut egui::Ui) {
+    let mut open = None;
+    ui.menu_button(egui::RichText::new("☰").size(18.0), |ui| {
+        ui.set_min_width(220.0);
+        if ui.add_enabled(!s.is_thinking, egui::Button::new("New conversation")).clicked() {
+            s.new_conversation();
+            ui.close_menu();
+        }
+        if ui.button("Search all conversations…").clicked() {
+            s.show_conversation_search = true;
+            ui.close_menu();
+        }
+        if ui.button("Import conversation…").clicked() {
+            ui.close_menu();
+            s.import_conversation();
+        }
+        if ui.button("Export as Markdown…").clicked() {
+            ui.close_menu();
+            s.export_conversation(true);
+        }
+        if ui.button("Export as JSON…").clicked() {
+            ui.close_menu();
+            s.export_conversation(false);
+        }
+        ui.menu_button("Export rated replies", |ui| {
+            if ui.button("👍 Good replies…").on_hover_text("Fine-tuning JSONL of replies rated thumbs up").clicked() {
+                ui.close_menu();
+                s.export_rated_replies(1);
+            }
+            if ui.button("👎 Poor replies…").on_hover_text("Fine-tuning JSONL of replies rated thumbs down").clicked() {
+                ui.close_menu();
+                s.export_rated_replies(-1);
+            }
+        });
+
+        if !s.saved_conversations.is_empty() {
+            ui.separator();
+            ui.label(egui::RichText::new("Recent conversations").size(12.0).weak());
+            for conversation in s.saved_conversations.iter().take(15) {
+                let current = conversation.id == s.conversation.id;
+                let label = format!(
+                    "{} · {}",
+                    conversation.created_at.with_timezone(&chrono::Local).format("%b %d"),
+                    conversation.title
+                );
+                if ui
+                    .add_enabled(!s.is_thinking, egui::SelectableLabel::new(current, label))
+                    .clicked()
+                {
+                    open = Some(conversation.id);
+                    ui.close_menu();
+                }
+            }
+        }
+    })
+    .response
+    .on_hover_text("Conversations");
+
+    if let Some(conversation) = open.and_then(|id| s.conversation_store.load(id).ok()) {
+        s.open_conversation(conversation);
+    }
+}
+
+/// Rows shown per command table; the full output is still in the text
+const MAX_TABLE_ROWS: usize = 200;
+
+/// Thin strip under the header showing how full the context window is
+fn context_bar(ui: &mut egui::Ui, used: usize, window: usize) {
+    let fraction = used as f32 / window.max(1) as f32;
+    let color = if fraction < 0.5 {
+        egui::Color32::from_rgb(90, 170, 90)
+    } else if fraction <= 0.8 {
+        egui::Color32::from_rgb(230, 170, 50)
+    } else {
+        egui::Color32::from_rgb(210, 70, 60)
+    };
+    ui.add(
+        egui::ProgressBar::new(fraction.min(1.0))
+            .desired_height(3.0)
+            .rounding(0.0)
+            .fill(color),
+    )
+    .on_hover_text(format!(
+        "Context: {} / {} tokens",
+        format_thousands(used),
+        format_thousands(window)
+    ));
+}
+
+/// 3200 -> "3,200"
+fn format_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Command output as a grid. Clicking a header sorts by that column,
+/// clicking again reverses the order.
+/// Files from a command's output as links. Returns the one clicked.
+fn render_command_paths(ui: &mut egui::Ui, command: &str, paths: &[PathBuf]) -> Option<PathBuf> {
+    let mut clicked = None;
+    let title = if paths.len() == 1 { "1 file".to_string() } else { format!("{} files", paths.len()) };
+    egui::CollapsingHeader::new(egui::RichText::new(format!("📂 {} from `{}`", title, command)).size(12.0))
+        .id_source(("command_paths", command))
+        .default_open(paths.len() <= 10)
+        .show(ui, |ui| {
+            for path in paths {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if ui.link(name).on_hover_text(path.display().to_string()).clicked() {
+                    clicked = Some(path.clone());
+                }
+            }
+        });
+    clicked
+}
+
+fn render_structured_table(ui: &mut egui::Ui, command: &str, table: &StructuredTable, text_color: egui::Color32) {
+    let sort_id = ui.id().with(("table_sort", command));
+    let sort: Option<(usize, bool)> = ui.data(|d| d.get_temp(sort_id)).flatten();
+
+    let mut rows: Vec<&Vec<String>> = table.rows.iter().collect();
+    if let Some((column, ascending)) = sort {
+        rows.sort_by(|a, b| {
+            let (a, b) = (a.get(column), b.get(column));
+            let order = match (a.and_then(|v| sort_number(v)), b.and_then(|v| sort_number(v))) {
+                (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal),
+                _ => a.cmp(&b),
+            };
+            if ascending { order } else { order.reverse() }
+        });
+    }
+
+    ui.label(egui::RichText::new(format!("$ {}", command)).monospace().size(12.0).weak());
+    egui::ScrollArea::both()
+        .id_source(("table_scroll", command))
+        .max_height(300.0)
+        .show(ui, |ui| {
+            egui::Grid::new(("table", command))
+                .striped(true)
+                .spacing(egui::vec2(14.0, 3.0))
+                .show(ui, |ui| {
+                    let mut clicked = None;
+                    for (i, header) in table.headers.iter().enumerate() {
+                        let arrow = match sort {
+                            Some((c, true)) if c == i => " ▲",
+                            Some((c, false)) if c == i => " ▼",
+                            _ => "",
+                        };
+                        let text = egui::RichText::new(format!("{}{}", header, arrow)).strong().size(12.0);
+                        if ui.add(egui::Label::new(text).sense(egui::Sense::click())).on_hover_text("Sort").clicked() {
+                            clicked = Some(i);
+                        }
+                    }
+                    ui.end_row();
+                    if let Some(column) = clicked {
+                        let next = match sort {
+                            Some((c, ascending)) if c == column => Some((column, !ascending)),
+                            _ => Some((column, true)),
+                        };
+                        ui.data_mut(|d| d.insert_temp(sort_id, next));
+                    }
+
+                    for row in rows.iter().take(MAX_TABLE_ROWS) {
+                        for cell in row.iter() {
+                            ui.label(egui::RichText::new(cell).monospace().size(12.0).color(text_color));
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    if table.rows.len() > MAX_TABLE_ROWS {
+        ui.label(
+            egui::RichText::new(format!("Showing {} of {} rows", MAX_TABLE_ROWS, table.rows.len()))
+                .size(12.0)
+                .weak(),
+        );
+    }
+}
+
+/// Numeric value for sorting cells like "40%", "7.8G" or "1,024"
+fn sort_number(cell: &str) -> Option<f64> {
+    let cell = cell.trim().replace(',', "");
+    let (number, multiplier) = match cell.chars().last()? {
+        'K' | 'k' => (&cell[..cell.len() - 1], 1e3),
+        'M' => (&cell[..cell.len() - 1], 1e6),
+        'G' => (&cell[..cell.len() - 1], 1e9),
+        'T' => (&cell[..cell.len() - 1], 1e12),
+        '%' => (&cell[..cell.len() - 1], 1.0),
+        _ => (cell.as_str(), 1.0),
+    };
+    number.parse::<f64>().ok().map(|n| n * multiplier)
+}
+
+/// Collapsible per-turn timings for debug mode
+fn render_debug_info(ui: &mut egui::Ui, debug: &TurnDebugInfo, id: impl std::hash::Hash) {
+    egui::CollapsingHeader::new(egui::RichText::new("Debug").size(12.0).weak())
+        .id_source(id)
+        .show(ui, |ui| {
+            let line = |ui: &mut egui::Ui, text: String| {
+                ui.label(egui::RichText::new(text).monospace().size(12.0));
+            };
+            if !debug.provider.is_empty() {
+                line(ui, format!("Model: {} ({})", debug.model, debug.provider));
+            }
+            let generations = debug.generations.len();
+            for (i, duration) in debug.generations.iter().enumerate() {
+                let label = if generations > 1 {
+                    format!("AI generation {}", i + 1)
+                } else {
+                    "AI generation".to_string()
+                };
+                line(ui, format!("{}: {}ms", label, duration.as_millis()));
+            }
+            for (i, (cmd, duration)) in debug.commands.iter().enumerate() {
+                line(ui, format!("Command {} ({}): {}ms", i + 1, cmd, duration.as_millis()));
+            }
+            line(ui, format!("Total turn: {}ms", debug.total.as_millis()));
+        });
+}
+
+/// Directory statistics as a table of file types plus largest/newest files
+fn render_dir_stats(ui: &mut egui::Ui, stats: &DirStats, text_color: egui::Color32) {
+    let label = |text: String| egui::RichText::new(text).color(text_color).size(13.0);
+
+    ui.label(
+        egui::RichText::new(format!(
+            "📊 {} · {} files · {}",
+            stats.root.display(),
+            stats.total_files,
+            format_bytes(stats.total_bytes)
+        ))
+        .strong()
+        .color(text_color),
+    );
+    ui.add_space(4.0);
+    egui::Grid::new(("dir_stats", &stats.root))
+        .striped(true)
+        .num_columns(3)
+        .spacing(egui::vec2(16.0, 4.0))
+        .show(ui, |ui| {
+            ui.label(label("Type".to_string()).strong());
+            ui.label(label("Files".to_string()).strong());
+            ui.label(label("Size".to_string()).strong());
+            ui.end_row();
+            for (ext, count, bytes) in stats.extensions_by_size() {
+                ui.label(label(ext.to_string()));
+                ui.label(label(count.to_string()));
+                ui.label(label(format_bytes(bytes)));
+                ui.end_row();
+            }
+        });
+
+    let file_name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    if !stats.largest.is_empty() {
+        ui.add_space(6.0);
+        ui.label(egui::RichText::new("Largest files").size(12.0).weak());
+        for (path, size) in &stats.largest {
+            ui.label(label(format!("{} ({})", file_name(path), format_bytes(*size))))
+                .on_hover_text(path.display().to_string());
+        }
+    }
+    if !stats.newest.is_empty() {
+        ui.add_space(6.0);
+        ui.label(egui::RichText::new("Recently modified").size(12.0).weak());
+        for (path, modified) in &stats.newest {
+            let when: chrono::DateTime<chrono::Local> = (*modified).into();
+            ui.label(label(format!("{} ({})", file_name(path), when.format("%b %d %H:%M"))))
+                .on_hover_text(path.display().to_string());
+        }
+    }
+}
+
+fn render_file_hash(ui: &mut egui::Ui, hash: &FileHash, text_color: egui::Color32) {
+    let file_name = hash.path.file_name().unwrap_or_default().to_string_lossy();
+    ui.label(egui::RichText::new(format!("🔒 {} of {}", hash.algorithm.name(), file_name)).strong().color(text_color))
+        .on_hover_text(hash.path.display().to_string());
+    egui::Grid::new(("file_hash", &hash.path, &hash.hash))
+        .num_columns(2)
+        .spacing(egui::vec2(12.0, 4.0))
+        .show(ui, |ui| {
+            ui.label(egui::RichText::new("Hash").size(12.0).weak());
+            ui.label(egui::RichText::new(&hash.hash).monospace().size(12.0).color(text_color));
+            ui.end_row();
+            if let Some(expected) = &hash.expected {
+                ui.label(egui::RichText::new("Expected").size(12.0).weak());
+                ui.label(egui::RichText::new(expected).monospace().size(12.0).color(text_color));
+                ui.end_row();
+            }
+        });
+    match hash.matches() {
+        Some(true) => {
+            ui.colored_label(egui::Color32::from_rgb(60, 160, 80), "✓ Hashes match");
+        }
+        Some(false) => {
+            ui.colored_label(egui::Color32::from_rgb(210, 70, 60), "✗ Hashes don't match - the file may be damaged or changed");
+        }
+        None => {}
+    }
+}
+
 /// Render the onboarding screen for first-time users
 fn render_onboarding_screen(s: &mut AppState, ctx: &egui::Context) {
     let dark = s.settings.user_profile.dark_mode;
@@ -1671,6 +4353,13 @@ fn render_onboarding_screen(s: &mut AppState, ctx: &egui::Context) {
                     .show(ui, |ui| {
                         ui.set_max_width(420.0);
 
+                        if s.provider_setup.is_some() {
+                            if provider_setup::render(s, ui) {
+                                finish_onboarding(s);
+                            }
+                            return;
+                        }
+
                         // Name input - friendlier
                         ui.label(
                             egui::RichText::new("First, what's your name?")
@@ -1796,28 +4485,9 @@ fn render_onboarding_screen(s: &mut AppState, ctx: &egui::Context) {
                                 if !s.onboarding_name.trim().is_empty() {
                                     s.settings.user_profile.name = s.onboarding_name.trim().to_string();
                                 }
-                                s.settings.user_profile.onboarding_complete = true;
 
-                                // Update welcome message with user's name - warm and friendly
-                                let user_name = if s.settings.user_profile.name.is_empty() {
-                                    "friend".to_string()
-                                } else {
-                                    s.settings.user_profile.name.clone()
-                                };
-                                if let Some(first_msg) = s.chat_history.first_mut() {
-                                    first_msg.content = format!(
-                                        "Hey {}! Great to meet you.\n\n\
-                                        I'm here whenever you need a hand. Just tell me what you're working on \
-                                        and I'll do my best to help out.",
-                                        user_name
-                                    );
-                                }
-
-                                // Save settings
-                                save_settings(&s.settings);
-
-                                // Switch to chat
-                                s.current_screen = AppScreen::Chat;
+                                // Next: AI providers
+                                s.provider_setup = Some(ProviderSetup::start(&s.settings.model));
                             }
                         });
                     });
@@ -1838,6 +4508,7 @@ fn render_onboarding_screen(s: &mut AppState, ctx: &egui::Context) {
                     .clicked()
                 {
                     s.settings.user_profile.onboarding_complete = true;
+                    s.provider_setup = None;
                     save_settings(&s.settings);
                     s.current_screen = AppScreen::Chat;
                 }
@@ -1845,11 +4516,34 @@ fn render_onboarding_screen(s: &mut AppState, ctx: &egui::Context) {
         });
 }
 
-/// Save settings to disk
-fn save_settings(settings: &AppSettings) {
-    if let Some(path) = config_path() {
-        if let Ok(bytes) = serde_json::to_vec_pretty(settings) {
-            let _ = fs::write(path, bytes);
-        }
+/// Save what onboarding collected and switch to the chat
+fn finish_onboarding(s: &mut AppState) {
+    s.settings.user_profile.onboarding_complete = true;
+    s.provider_setup = None;
+
+    // Update welcome message with user's name - warm and friendly
+    let user_name = if s.settings.user_profile.name.is_empty() {
+        "friend".to_string()
+    } else {
+        s.settings.user_profile.name.clone()
+    };
+    if let Some(first_msg) = s.chat_history.first_mut() {
+        first_msg.content = format!(
+            "Hey {}! Great to meet you.\n\n\
+            I'm here whenever you need a hand. Just tell me what you're working on \
+            and I'll do my best to help out.",
+            user_name
+        );
+        first_msg.token_count = token_count(&first_msg.content);
     }
+
+    save_settings(&s.settings);
+    s.current_screen = AppScreen::Chat;
+}
+
+/// Save settings to disk in the background. Commands the user blocked and
+/// the allowed folders apply from here on.
+fn save_settings(settings: &AppSettings) {
+    agent_host::set_command_restrictions(settings);
+    settings_writer::save(settings);
 }
@@ -0,0 +1,122 @@
+//! Upgrading `settings.json` files written by older versions
+//!
+//! Each migration takes the raw JSON of one settings version to the next,
+//! so a file from any earlier version still loads instead of failing to
+//! deserialize and being replaced by defaults. When `AppSettings` changes
+//! in a way old files can't deserialize into, bump `SETTINGS_VERSION` and
+//! add a migration from the previous version here.
+
+use serde_json::{json, Value};
+use shared::settings::SETTINGS_VERSION;
+
+/// Turns settings of one version into the next
+type Migration = fn(Value) -> Value;
+
+/// Migrations by the version they upgrade from
+const MIGRATIONS: &[(u32, Migration)] = &[(1, add_dark_mode)];
+
+/// 1 → 2: `user_profile.dark_mode` became required
+fn add_dark_mode(mut settings: Value) -> Value {
+    if let Some(profile) = settings.get_mut("user_profile").and_then(Value::as_object_mut) {
+        profile.entry("dark_mode").or_insert(json!(false));
+    }
+    settings
+}
+
+pub struct MigrationRunner {
+    migrations: Vec<(u32, Migration)>,
+}
+
+impl Default for MigrationRunner {
+    fn default() -> Self {
+        Self::new(MIGRATIONS.to_vec())
+    }
+}
+
+impl MigrationRunner {
+    pub fn new(mut migrations: Vec<(u32, Migration)>) -> Self {
+        migrations.sort_by_key(|(from, _)| *from);
+        Self { migrations }
+    }
+
+    /// Version of raw settings; files from before versions were recorded
+    /// are version 1
+    pub fn version_of(settings: &Value) -> u32 {
+        settings.get("settings_version").and_then(Value::as_u64).map_or(1, |v| v as u32)
+    }
+
+    /// Apply every migration from the file's version on, in order, and
+    /// record the version reached. Files from a newer build are left alone.
+    pub fn run(&self, mut settings: Value) -> Value {
+        let mut version = Self::version_of(&settings);
+        for (from, migrate) in &self.migrations {
+            if *from == version {
+                settings = migrate(settings);
+                version = from + 1;
+                tracing::info!("Migrated settings from version {} to {}", from, version);
+            }
+        }
+        if let Some(map) = settings.as_object_mut() {
+            map.insert("settings_version".to_string(), json!(version));
+        }
+        settings
+    }
+}
+
+/// `settings.json` contents as current settings
+pub fn load(bytes: &[u8]) -> anyhow::Result<shared::settings::AppSettings> {
+    let raw: Value = serde_json::from_slice(bytes)?;
+    let version = MigrationRunner::version_of(&raw);
+    if version > SETTINGS_VERSION {
+        tracing::warn!("Settings are from a newer version ({}), loading what this one knows", version);
+    }
+    Ok(serde_json::from_value(MigrationRunner::default().run(raw))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings as written before versions were recorded
+    fn version_1() -> Value {
+        let mut settings = serde_json::to_value(shared::settings::AppSettings::default()).unwrap();
+        let map = settings.as_object_mut().unwrap();
+        map.remove("settings_version");
+        map["user_profile"].as_object_mut().unwrap().remove("dark_mode");
+        settings
+    }
+
+    #[test]
+    fn test_migrations_reach_current_version() {
+        assert_eq!(MIGRATIONS.last().map(|(from, _)| from + 1), Some(SETTINGS_VERSION));
+        for (i, (from, _)) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(*from, i as u32 + 1, "every version needs a migration to the next");
+        }
+    }
+
+    #[test]
+    fn test_version_1_to_2() {
+        let bytes = serde_json::to_vec(&version_1()).unwrap();
+        assert!(serde_json::from_slice::<shared::settings::AppSettings>(&bytes).is_err());
+
+        let settings = load(&bytes).unwrap();
+        assert_eq!(settings.settings_version, 2);
+        assert!(!settings.user_profile.dark_mode);
+
+        // A value the user chose survives
+        let mut dark = version_1();
+        dark["user_profile"]["dark_mode"] = json!(true);
+        assert!(load(&serde_json::to_vec(&dark).unwrap()).unwrap().user_profile.dark_mode);
+    }
+
+    #[test]
+    fn test_current_and_newer_versions_untouched() {
+        let current = serde_json::to_value(shared::settings::AppSettings::default()).unwrap();
+        assert_eq!(MigrationRunner::default().run(current.clone()), current);
+
+        let mut newer = current;
+        newer["settings_version"] = json!(SETTINGS_VERSION + 1);
+        let settings = load(&serde_json::to_vec(&newer).unwrap()).unwrap();
+        assert_eq!(settings.settings_version, SETTINGS_VERSION + 1);
+    }
+}
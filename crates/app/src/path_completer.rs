@@ -0,0 +1,239 @@
+//! Shell-style file path completion for the chat input
+//!
+//! Looks at the last path-like token in the input (`~/Pro`, `/home/`),
+//! lists the matching entries of that directory and offers them in a
+//! popup below the text field. Only entries inside the allowed
+//! directories (or on the way to one) are offered.
+
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of entries shown in the popup
+const MAX_COMPLETIONS: usize = 12;
+
+/// A single completion candidate
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// Text shown in the popup (entry name, `/` appended for directories)
+    pub label: String,
+    /// Replacement for the path token, keeping the form the user typed (`~/` stays `~/`)
+    pub replacement: String,
+}
+
+/// Completion state for the chat input
+#[derive(Default)]
+pub struct PathCompleter {
+    last_input: String,
+    token_start: usize,
+    completions: Vec<Completion>,
+    selected: usize,
+}
+
+impl PathCompleter {
+    /// Recompute completions if the input changed since the last call
+    pub fn update(&mut self, input: &str, allowed_dirs: &[String]) {
+        if input == self.last_input {
+            return;
+        }
+        self.last_input = input.to_string();
+        self.selected = 0;
+        self.completions.clear();
+
+        if let Some((start, token)) = last_path_token(input) {
+            self.token_start = start;
+            self.completions = list_completions(token, &allowed_roots(allowed_dirs));
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.completions.is_empty()
+    }
+
+    pub fn completions(&self) -> &[Completion] {
+        &self.completions
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.completions.is_empty() {
+            self.selected = (self.selected + 1) % self.completions.len();
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if !self.completions.is_empty() {
+            self.selected = (self.selected + self.completions.len() - 1) % self.completions.len();
+        }
+    }
+
+    /// Hide completions until the input changes again
+    pub fn dismiss(&mut self) {
+        self.completions.clear();
+    }
+
+    /// Replace the path token in `input` with the chosen completion
+    pub fn apply(&mut self, input: &mut String, index: usize) {
+        if let Some(completion) = self.completions.get(index) {
+            input.truncate(self.token_start);
+            input.push_str(&completion.replacement);
+        }
+    }
+}
+
+/// Find the path-like token at the end of the input.
+///
+/// Uses the same token rules as `extract_paths` (starts with `~` or `/`,
+/// no whitespace, quotes or parens) but anchored at the end of the input
+/// and without requiring a file extension.
+fn last_path_token(input: &str) -> Option<(usize, &str)> {
+    let re = Regex::new(r#"(?:^|[\s"'(])([~/][^\s"'()]*)$"#).unwrap();
+    let m = re.captures(input)?.get(1)?;
+    Some((m.start(), m.as_str()))
+}
+
+/// Directories completions are restricted to (home directory if none are configured)
+pub(crate) fn allowed_roots(allowed_dirs: &[String]) -> Vec<PathBuf> {
+    if allowed_dirs.is_empty() {
+        return dirs::home_dir().into_iter().collect();
+    }
+    allowed_dirs.iter().map(|d| expand_tilde(d)).collect()
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    if path == "~" {
+        return dirs::home_dir().unwrap_or_else(|| PathBuf::from(path));
+    }
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .map(|h| h.join(rest))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+/// An entry may be offered if it is inside an allowed root, or is a parent
+/// directory the user has to pass through to reach one.
+fn is_reachable(path: &Path, roots: &[PathBuf]) -> bool {
+    roots
+        .iter()
+        .any(|root| path.starts_with(root) || root.starts_with(path))
+}
+
+fn list_completions(token: &str, roots: &[PathBuf]) -> Vec<Completion> {
+    let Some((dir_part, partial)) = token.rsplit_once('/') else {
+        // A bare "~" - complete it to the home directory itself
+        return vec![Completion {
+            label: "~/".to_string(),
+            replacement: "~/".to_string(),
+        }];
+    };
+
+    let dir = if dir_part.is_empty() {
+        PathBuf::from("/")
+    } else {
+        expand_tilde(dir_part)
+    };
+
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let partial_lower = partial.to_lowercase();
+    let mut matches: Vec<(bool, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Hidden entries only when the user starts typing a dot
+            if name.starts_with('.') && !partial.starts_with('.') {
+                return None;
+            }
+            if !name.to_lowercase().starts_with(&partial_lower) {
+                return None;
+            }
+            let path = entry.path();
+            if !is_reachable(&path, roots) {
+                return None;
+            }
+            Some((path.is_dir(), name))
+        })
+        .collect();
+
+    // Directories first, then alphabetical
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.to_lowercase().cmp(&b.1.to_lowercase())));
+
+    matches
+        .into_iter()
+        .map(|(is_dir, name)| {
+            let suffix = if is_dir { "/" } else { "" };
+            Completion {
+                label: format!("{}{}", name, suffix),
+                replacement: format!("{}/{}{}", dir_part, name, suffix),
+            }
+        })
+        // An exact match for a file is already complete
+        .filter(|c| c.replacement != token)
+        .take(MAX_COMPLETIONS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_path_token() {
+        assert_eq!(last_path_token("look in ~/Pro"), Some((8, "~/Pro")));
+        assert_eq!(last_path_token("(/tmp/x"), Some((1, "/tmp/x")));
+        assert_eq!(last_path_token("~"), Some((0, "~")));
+        // Relative paths and slashes inside words aren't completed
+        assert_eq!(last_path_token("open src/ma"), None);
+        assert_eq!(last_path_token("either/or"), None);
+        assert_eq!(last_path_token("~/Pro and more"), None);
+    }
+
+    #[test]
+    fn test_completions() {
+        let root = std::env::temp_dir().join(format!("lh-complete-{}", std::process::id()));
+        fs::create_dir_all(root.join("Projects")).unwrap();
+        fs::write(root.join("notes.txt"), "").unwrap();
+        fs::write(root.join("Pride.txt"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+        let roots = vec![root.clone()];
+        let base = root.to_string_lossy().into_owned();
+
+        let mut completer = PathCompleter::default();
+        let mut input = format!("open {}/pr", base);
+        completer.update(&input, std::slice::from_ref(&base));
+        let labels: Vec<&str> = completer.completions().iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, ["Projects/", "Pride.txt"]);
+        completer.apply(&mut input, 0);
+        assert_eq!(input, format!("open {}/Projects/", base));
+
+        assert_eq!(list_completions(&format!("{}/", base), &roots).len(), 3);
+        assert_eq!(list_completions(&format!("{}/.h", base), &roots)[0].label, ".hidden");
+        // Nonexistent directories and prefixes give nothing
+        assert!(list_completions(&format!("{}/missing/no", base), &roots).is_empty());
+        assert!(list_completions(&format!("{}/zz", base), &roots).is_empty());
+        // Nor do folders outside the allowed ones
+        assert!(list_completions(&format!("{}/", base), &[PathBuf::from("/nonexistent")]).is_empty());
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_home_completions() {
+        let completions = list_completions("~", &[]);
+        assert_eq!(completions[0].replacement, "~/");
+        let Some(home) = dirs::home_dir() else { return };
+        assert_eq!(expand_tilde("~/Projects"), home.join("Projects"));
+        // Entries keep the `~/` the user typed
+        for completion in list_completions("~/", &[home]) {
+            assert!(completion.replacement.starts_with("~/"));
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! Saved conversations
+//!
+//! Each conversation is a JSON file in `<data dir>/little-helper/conversations/`
+//! named after its id; the most recently saved one is reopened at startup.
+//! New fields get serde defaults so older files, including ones users keep
+//! in version control or share, still load.
+//!
+//! Conversations can also be exported as Markdown ("**You:** ..." /
+//! "**Helper:** ..." paragraphs separated by `---`) or JSON, and imported
+//! back from either format.
+//!
+//! Rated replies can be exported as JSONL in the OpenAI fine-tuning format:
+//! one `{"messages": [...], "rating": 1}` line per reply, with the
+//! conversation up to and including it.
+//!
+//! Search scans the saved files directly; there are few enough
+//! conversations that no index is needed.
+
+use crate::ChatMessage;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const USER_PREFIX: &str = "**You:**";
+const ASSISTANT_PREFIX: &str = "**Helper:**";
+const MESSAGE_SEPARATOR: &str = "\n\n---\n\n";
+
+/// Longest title taken from the first user message
+const MAX_TITLE_CHARS: usize = 40;
+
+/// Characters of context shown around a search match
+const PREVIEW_BEFORE: usize = 40;
+const PREVIEW_AFTER: usize = 80;
+/// Most hits returned by a search
+const MAX_SEARCH_HITS: usize = 100;
+
+/// A message matching a search across all conversations
+#[derive(Clone)]
+pub struct SearchHit {
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    /// Index of the message in the conversation
+    pub message_index: usize,
+    /// Text around the match
+    pub preview: String,
+    /// Byte range of the match within `preview`
+    pub matched: std::ops::Range<usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: Uuid,
+    pub title: String,
+    /// The title was written by the AI, so it isn't redone from the first message
+    #[serde(default)]
+    pub title_generated: bool,
+    pub created_at: DateTime<Utc>,
+    pub messages: Vec<ChatMessage>,
+    /// Instructions added with /note, sent with every message
+    #[serde(default)]
+    pub notes: Vec<SessionNote>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionNote {
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Conversation {
+    pub fn new(messages: Vec<ChatMessage>) -> Self {
+        let mut conversation = Self {
+            id: Uuid::new_v4(),
+            title: String::new(),
+            title_generated: false,
+            created_at: Utc::now(),
+            messages,
+            notes: Vec::new(),
+        };
+        conversation.update_title();
+        conversation
+    }
+
+    /// Title from the first user message, or "New conversation". Kept
+    /// once the AI has written one.
+    pub fn update_title(&mut self) {
+        if self.title_generated {
+            return;
+        }
+        self.title = match self.messages.iter().find(|m| m.role == "user" && !m.content.trim().is_empty()) {
+            Some(msg) => {
+                let first_line = msg.content.trim().lines().next().unwrap_or_default();
+                let mut title: String = first_line.chars().take(MAX_TITLE_CHARS).collect();
+                if first_line.chars().count() > MAX_TITLE_CHARS {
+                    title.push('…');
+                }
+                title
+            }
+            None => "New conversation".to_string(),
+        };
+    }
+}
+
+/// Conversations on disk
+pub struct ConversationStore {
+    dir: PathBuf,
+}
+
+impl Default for ConversationStore {
+    fn default() -> Self {
+        let dir = dirs::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("little-helper")
+            .join("conversations");
+        Self { dir }
+    }
+}
+
+impl ConversationStore {
+    fn path_for(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn exists(&self, id: Uuid) -> bool {
+        self.path_for(id).exists()
+    }
+
+    pub fn save(&self, conversation: &Conversation) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path_for(conversation.id), serde_json::to_string_pretty(conversation)?)?;
+        Ok(())
+    }
+
+    pub fn load(&self, id: Uuid) -> Result<Conversation> {
+        let text = fs::read_to_string(self.path_for(id))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// All saved conversations, newest first. Unreadable files are skipped.
+    pub fn list(&self) -> Vec<Conversation> {
+        let mut conversations: Vec<Conversation> = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|e| fs::read_to_string(e.path()).ok())
+            .filter_map(|text| serde_json::from_str(&text).ok())
+            .collect();
+        conversations.sort_by_key(|c| std::cmp::Reverse(c.created_at));
+        conversations
+    }
+
+    /// The conversation saved most recently, which is the one the user
+    /// was last in rather than the last one started
+    pub fn latest(&self) -> Option<Conversation> {
+        fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+            .max_by_key(|(modified, _)| *modified)
+            .and_then(|(_, path)| serde_json::from_str(&fs::read_to_string(path).ok()?).ok())
+    }
+
+    /// Case-insensitive search of every saved message, newest conversations first
+    pub fn search(&self, query: &str) -> Result<Vec<SearchHit>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let re = regex::RegexBuilder::new(&regex::escape(query))
+            .case_insensitive(true)
+            .build()?;
+
+        let mut hits = Vec::new();
+        for conversation in self.list() {
+            for (index, message) in conversation.messages.iter().enumerate() {
+                let Some(m) = re.find(&message.content) else { continue };
+                let (preview, matched) = preview_around(&message.content, m.range());
+                hits.push(SearchHit {
+                    conversation_id: conversation.id,
+                    title: conversation.title.clone(),
+                    created_at: conversation.created_at,
+                    message_index: index,
+                    preview,
+                    matched,
+                });
+                if hits.len() >= MAX_SEARCH_HITS {
+                    return Ok(hits);
+                }
+            }
+        }
+        Ok(hits)
+    }
+
+    pub fn export_markdown(messages: &[ChatMessage], path: &Path) -> Result<()> {
+        fs::write(path, to_markdown(messages))?;
+        Ok(())
+    }
+
+    pub fn export_json(conversation: &Conversation, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(conversation)?)?;
+        Ok(())
+    }
+
+    /// Replies rated at least `min_rating`, or for a negative `min_rating`
+    /// at most it, across every saved conversation
+    pub fn export_rated_messages(&self, min_rating: i8) -> Result<Vec<(ChatMessage, i8)>> {
+        Ok(self
+            .list()
+            .into_iter()
+            .flat_map(|conversation| {
+                rated_indices(&conversation.messages, min_rating)
+                    .into_iter()
+                    .map(|(i, rating)| (conversation.messages[i].clone(), rating))
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+
+    /// Write the replies [`export_rated_messages`](Self::export_rated_messages)
+    /// picks out as fine-tuning JSONL. Returns how many were written.
+    pub fn export_fine_tuning(&self, min_rating: i8, path: &Path) -> Result<usize> {
+        let mut lines = Vec::new();
+        for conversation in self.list() {
+            for (i, rating) in rated_indices(&conversation.messages, min_rating) {
+                let messages: Vec<serde_json::Value> = conversation.messages[..=i]
+                    .iter()
+                    .filter(|m| m.role == "user" || m.role == "assistant")
+                    .map(|m| serde_json::json!({ "role": m.role, "content": m.content }))
+                    .collect();
+                lines.push(serde_json::json!({ "messages": messages, "rating": rating }).to_string());
+            }
+        }
+        if lines.is_empty() {
+            bail!("No rated replies to export");
+        }
+        fs::write(path, lines.join("\n") + "\n")?;
+        Ok(lines.len())
+    }
+
+    /// Parse a Markdown export back into messages
+    pub fn import_from_markdown(path: &Path) -> Result<Vec<ChatMessage>> {
+        let text = fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+        let messages = parse_markdown(&text);
+        if messages.is_empty() {
+            bail!("No \"{}\" or \"{}\" messages found in {}", USER_PREFIX, ASSISTANT_PREFIX, path.display());
+        }
+        Ok(messages)
+    }
+
+    /// Read a JSON export: a whole conversation or a bare list of messages
+    pub fn import_from_json(path: &Path) -> Result<Vec<ChatMessage>> {
+        let text = fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+        if let Ok(conversation) = serde_json::from_str::<Conversation>(&text) {
+            return Ok(conversation.messages);
+        }
+        serde_json::from_str::<Vec<ChatMessage>>(&text)
+            .map_err(|e| anyhow!("{} isn't a Little Helper conversation: {}", path.display(), e))
+    }
+
+    /// Import a `.md` or `.json` file as a new saved conversation. The
+    /// conversation always gets a fresh id, so it never overwrites another.
+    pub fn import(&self, path: &Path) -> Result<Conversation> {
+        let ext = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+        let messages = match ext.as_deref() {
+            Some("md") | Some("markdown") => Self::import_from_markdown(path)?,
+            Some("json") => Self::import_from_json(path)?,
+            _ => bail!("Only .md and .json conversations can be imported"),
+        };
+
+        let mut conversation = Conversation::new(messages);
+        while self.exists(conversation.id) {
+            conversation.id = Uuid::new_v4();
+        }
+        self.save(&conversation)?;
+        Ok(conversation)
+    }
+}
+
+/// Put `conversation` in `index`, a newest-first list like `list` gives,
+/// in place of any earlier copy, so a save doesn't mean reading every file
+/// again. The entry has no messages; it's loaded when opened.
+pub fn update_index(index: &mut Vec<Conversation>, conversation: &Conversation) {
+    index.retain(|c| c.id != conversation.id);
+    let at = index.partition_point(|c| c.created_at > conversation.created_at);
+    let entry = Conversation {
+        id: conversation.id,
+        title: conversation.title.clone(),
+        title_generated: conversation.title_generated,
+        created_at: conversation.created_at,
+        messages: Vec::new(),
+        notes: Vec::new(),
+    };
+    index.insert(at, entry);
+}
+
+/// Index and rating of each assistant message matching `min_rating`
+fn rated_indices(messages: &[ChatMessage], min_rating: i8) -> Vec<(usize, i8)> {
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.role == "assistant")
+        .filter_map(|(i, m)| Some((i, m.rating?)))
+        .filter(|&(_, rating)| if min_rating < 0 { rating <= min_rating } else { rating >= min_rating })
+        .collect()
+}
+
+/// Cut `text` down to some context around `range`, on one line, and
+/// return the match's new range
+fn preview_around(text: &str, range: std::ops::Range<usize>) -> (String, std::ops::Range<usize>) {
+    let start = text[..range.start]
+        .char_indices()
+        .rev()
+        .nth(PREVIEW_BEFORE - 1)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let end = text[range.end..]
+        .char_indices()
+        .nth(PREVIEW_AFTER)
+        .map(|(i, _)| range.end + i)
+        .unwrap_or(text.len());
+
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < text.len() { "…" } else { "" };
+    let one_line = |s: &str| s.replace(['\n', '\r'], " ");
+    let before = format!("{}{}", prefix, one_line(&text[start..range.start]));
+    let matched = one_line(&text[range.clone()]);
+    let preview = format!("{}{}{}{}", before, matched, one_line(&text[range.end..end]), suffix);
+    (preview, before.len()..before.len() + matched.len())
+}
+
+fn to_markdown(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            let prefix = if m.role == "user" { USER_PREFIX } else { ASSISTANT_PREFIX };
+            format!("{} {}", prefix, m.content.trim())
+        })
+        .collect::<Vec<_>>()
+        .join(MESSAGE_SEPARATOR)
+}
+
+/// Split on `---` lines and read the speaker from each block's prefix.
+/// Blocks without a prefix continue the previous message (the content
+/// itself contained a `---`).
+fn parse_markdown(text: &str) -> Vec<ChatMessage> {
+    let timestamp = crate::timestamp::now();
+    let mut messages: Vec<ChatMessage> = Vec::new();
+    let mut block = String::new();
+
+    let flush = |block: &mut String, messages: &mut Vec<ChatMessage>| {
+        let trimmed = block.trim();
+        let parsed = [(USER_PREFIX, "user"), (ASSISTANT_PREFIX, "assistant")]
+            .iter()
+            .find_map(|(prefix, role)| trimmed.strip_prefix(prefix).map(|rest| (role, rest.trim())));
+        match (parsed, messages.last_mut()) {
+            (Some((role, content)), _) => messages.push(ChatMessage {
+                role: role.to_string(),
+                content: content.to_string(),
+                // Counted when the conversation is opened
+                token_count: 0,
+                timestamp: timestamp.clone(),
+                ..Default::default()
+            }),
+            (None, Some(last)) if !trimmed.is_empty() => {
+                last.content.push_str("\n\n---\n\n");
+                last.content.push_str(trimmed);
+            }
+            _ => {}
+        }
+        block.clear();
+    };
+
+    for line in text.lines() {
+        if line.trim() == "---" {
+            flush(&mut block, &mut messages);
+        } else {
+            block.push_str(line);
+            block.push('\n');
+        }
+    }
+    flush(&mut block, &mut messages);
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            token_count: 0,
+            timestamp: "12:00".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        let messages = vec![
+            message("user", "Find my tax return"),
+            message("assistant", "Found it:\n\n---\n\n~/Documents/taxes.pdf"),
+        ];
+        let parsed = parse_markdown(&to_markdown(&messages));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].role, "user");
+        assert_eq!(parsed[0].content, "Find my tax return");
+        assert_eq!(parsed[1].content, messages[1].content);
+    }
+
+    #[test]
+    fn test_search_preview() {
+        let text = format!("{}Needle{}", "a".repeat(60), "b".repeat(100));
+        let re = regex::RegexBuilder::new("needle").case_insensitive(true).build().unwrap();
+        let (preview, matched) = preview_around(&text, re.find(&text).unwrap().range());
+        assert_eq!(&preview[matched], "Needle");
+        assert!(preview.starts_with('…') && preview.ends_with('…'));
+    }
+
+    #[test]
+    fn test_import_gets_fresh_id() {
+        let dir = std::env::temp_dir().join(format!("lh-conversations-{}", std::process::id()));
+        let store = ConversationStore { dir: dir.clone() };
+        let original = Conversation::new(vec![message("user", "hi")]);
+        store.save(&original).unwrap();
+
+        let export = dir.join("export.json");
+        ConversationStore::export_json(&original, &export).unwrap();
+        let imported = store.import(&export).unwrap();
+        assert_ne!(imported.id, original.id);
+        assert_eq!(imported.messages.len(), 1);
+        assert_eq!(imported.title, "hi");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_latest_is_last_saved() {
+        let dir = std::env::temp_dir().join(format!("lh-latest-{}", std::process::id()));
+        let store = ConversationStore { dir: dir.clone() };
+        assert!(store.latest().is_none());
+
+        let older = Conversation::new(vec![message("user", "older")]);
+        let newer = Conversation::new(vec![message("user", "newer")]);
+        store.save(&newer).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        store.save(&older).unwrap();
+        assert_eq!(store.latest().map(|c| c.id), Some(older.id));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_update_index() {
+        let mut first = Conversation::new(vec![message("user", "first")]);
+        let mut second = Conversation::new(vec![message("user", "second")]);
+        second.created_at = first.created_at + chrono::Duration::seconds(1);
+        let mut index = Vec::new();
+        update_index(&mut index, &first);
+        update_index(&mut index, &second);
+        first.title = "Renamed".to_string();
+        update_index(&mut index, &first);
+
+        let titles: Vec<&str> = index.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["second", "Renamed"]);
+        assert!(index.iter().all(|c| c.messages.is_empty()));
+    }
+
+    #[test]
+    fn test_export_rated_messages() {
+        let dir = std::env::temp_dir().join(format!("lh-rated-{}", std::process::id()));
+        let store = ConversationStore { dir: dir.clone() };
+        let rated = |content: &str, rating| ChatMessage { rating, ..message("assistant", content) };
+        let conversation = Conversation::new(vec![
+            message("user", "Find my tax return"),
+            rated("Found it", Some(1)),
+            message("user", "And last year's?"),
+            rated("No idea", Some(-1)),
+            rated("Unrated", None),
+        ]);
+        store.save(&conversation).unwrap();
+
+        let good = store.export_rated_messages(1).unwrap();
+        assert_eq!(good.len(), 1);
+        assert_eq!((good[0].0.content.as_str(), good[0].1), ("Found it", 1));
+        assert_eq!(store.export_rated_messages(-1).unwrap()[0].0.content, "No idea");
+
+        let path = dir.join("poor.jsonl");
+        assert_eq!(store.export_fine_tuning(-1, &path).unwrap(), 1);
+        let line: serde_json::Value = serde_json::from_str(fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(line["rating"], -1);
+        assert_eq!(line["messages"].as_array().unwrap().len(), 4);
+        assert_eq!(line["messages"][3]["content"], "No idea");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,237 @@
+//! Editing an organizer plan before it runs: drag actions by their handle
+//! to change the order, remove the ones you don't want and add renames or
+//! moves by hand. Nothing changes on disk until "Apply N actions".
+
+use crate::AppState;
+use eframe::egui;
+use services::organizer::{self, OrganizeAction, ProposedPlan};
+use std::path::{Path, PathBuf};
+
+/// Payload while a card is dragged: its index in the plan
+struct DraggedAction(usize);
+
+#[derive(Clone, Copy, PartialEq)]
+enum NewActionKind {
+    Rename,
+    Move,
+}
+
+/// The "Add action" form
+struct NewAction {
+    kind: NewActionKind,
+    from: String,
+    /// New file name for a rename, folder for a move
+    to: String,
+}
+
+impl NewAction {
+    fn new(dir: &Path) -> Self {
+        Self { kind: NewActionKind::Rename, from: format!("{}/", dir.display()), to: String::new() }
+    }
+
+    fn action(&self) -> Option<OrganizeAction> {
+        let from = self.from.trim();
+        let to = self.to.trim();
+        if from.is_empty() || to.is_empty() || from.ends_with('/') {
+            return None;
+        }
+        let action = match self.kind {
+            NewActionKind::Rename => {
+                let parent = Path::new(from).parent().unwrap_or_else(|| Path::new("."));
+                OrganizeAction::Rename { from: from.to_string(), to: parent.join(to).to_string_lossy().into_owned() }
+            }
+            NewActionKind::Move => OrganizeAction::Move { from: from.to_string(), to_dir: to.to_string() },
+        };
+        Some(action)
+    }
+}
+
+pub struct PlanEditor {
+    plan: ProposedPlan,
+    /// Folder the plan is for; new actions start here
+    dir: PathBuf,
+    new_action: Option<NewAction>,
+    /// Card under the pointer last frame, which shows its remove button
+    hovered: Option<usize>,
+}
+
+impl PlanEditor {
+    pub fn new(plan: ProposedPlan, dir: PathBuf) -> Self {
+        Self { plan, dir, new_action: None, hovered: None }
+    }
+}
+
+/// Move the action at `from` so it ends up just before the one now at
+/// `to` (or last, for `to == actions.len()`)
+fn move_action(actions: &mut Vec<OrganizeAction>, from: usize, to: usize) {
+    if from >= actions.len() || to > actions.len() || from == to || from + 1 == to {
+        return;
+    }
+    let action = actions.remove(from);
+    let to = if from < to { to - 1 } else { to };
+    actions.insert(to, action);
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path.to_string())
+}
+
+/// "Rename a.txt → b.txt", "Move a.txt → /home/me/Archive"
+fn describe(action: &OrganizeAction) -> (&'static str, String) {
+    match action {
+        OrganizeAction::Rename { from, to } => ("Rename", format!("{} → {}", file_name(from), file_name(to))),
+        OrganizeAction::Move { from, to_dir } => ("Move", format!("{} → {}", file_name(from), to_dir)),
+    }
+}
+
+/// The editor window, shown while there's a plan being edited
+pub fn render(s: &mut AppState, ctx: &egui::Context) {
+    let Some(editor) = &mut s.plan_editor else { return };
+
+    let mut open = true;
+    let mut apply = false;
+    let mut remove = None;
+    let mut reorder = None;
+    egui::Window::new(format!("Organize {}", editor.dir.file_name().unwrap_or_default().to_string_lossy()))
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(520.0)
+        .show(ctx, |ui| {
+            if editor.plan.actions.is_empty() {
+                ui.label(egui::RichText::new("No actions yet - add one below.").weak());
+            } else {
+                ui.label(egui::RichText::new("Drag ⠿ to change the order. Actions run top to bottom.").size(12.0).weak());
+            }
+            ui.add_space(4.0);
+
+            egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                let count = editor.plan.actions.len();
+                let hovered = editor.hovered.take();
+                for (i, action) in editor.plan.actions.iter().enumerate() {
+                    let card = egui::Frame::group(ui.style()).show(ui, |ui| {
+                        ui.set_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.dnd_drag_source(egui::Id::new(("plan_action", i)), DraggedAction(i), |ui| {
+                                ui.label(egui::RichText::new("⠿").color(egui::Color32::GRAY));
+                            });
+                            let (verb, detail) = describe(action);
+                            ui.label(egui::RichText::new(verb).strong());
+                            ui.label(detail);
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                if hovered == Some(i) && ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                                    remove = Some(i);
+                                }
+                            });
+                        });
+                    });
+
+                    // Dropping on the top half of a card puts the action before it
+                    let rect = card.response.rect;
+                    if card.response.contains_pointer() {
+                        editor.hovered = Some(i);
+                    }
+                    if let (Some(pointer), Some(_)) =
+                        (ui.ctx().pointer_interact_pos(), card.response.dnd_hover_payload::<DraggedAction>())
+                    {
+                        let before = pointer.y < rect.center().y;
+                        let y = if before { rect.top() } else { rect.bottom() };
+                        ui.painter().hline(rect.x_range(), y, ui.visuals().selection.stroke);
+                        if let Some(dragged) = card.response.dnd_release_payload::<DraggedAction>() {
+                            reorder = Some((dragged.0, if before { i } else { i + 1 }));
+                        }
+                    }
+                    if i + 1 < count {
+                        ui.add_space(2.0);
+                    }
+                }
+            });
+
+            ui.add_space(6.0);
+            match &mut editor.new_action {
+                Some(new_action) => {
+                    let mut add = false;
+                    let mut cancel = false;
+                    egui::Frame::group(ui.style()).show(ui, |ui| {
+                        egui::Grid::new("plan_new_action").num_columns(2).spacing(egui::vec2(8.0, 4.0)).show(ui, |ui| {
+                            ui.label("Action");
+                            ui.horizontal(|ui| {
+                                ui.selectable_value(&mut new_action.kind, NewActionKind::Rename, "Rename");
+                                ui.selectable_value(&mut new_action.kind, NewActionKind::Move, "Move");
+                            });
+                            ui.end_row();
+                            ui.label("File");
+                            ui.add(egui::TextEdit::singleline(&mut new_action.from).desired_width(320.0));
+                            ui.end_row();
+                            ui.label(if new_action.kind == NewActionKind::Rename { "New name" } else { "To folder" });
+                            ui.add(egui::TextEdit::singleline(&mut new_action.to).desired_width(320.0));
+                            ui.end_row();
+                        });
+                        ui.horizontal(|ui| {
+                            add = ui.add_enabled(new_action.action().is_some(), egui::Button::new("Add")).clicked();
+                            cancel = ui.button("Cancel").clicked();
+                        });
+                    });
+                    if add {
+                        editor.plan.actions.extend(new_action.action());
+                        editor.new_action = None;
+                    } else if cancel {
+                        editor.new_action = None;
+                    }
+                }
+                None => {
+                    if ui.button("➕ Add action").clicked() {
+                        editor.new_action = Some(NewAction::new(&editor.dir));
+                    }
+                }
+            }
+
+            ui.add_space(6.0);
+            let count = editor.plan.actions.len();
+            apply = ui.add_enabled(count > 0, egui::Button::new(format!("Apply {} actions", count))).clicked();
+        });
+
+    if let Some(i) = remove {
+        editor.plan.actions.remove(i);
+    }
+    if let Some((from, to)) = reorder {
+        move_action(&mut editor.plan.actions, from, to);
+    }
+
+    if apply {
+        let plan = editor.plan.clone();
+        s.organize_status = Some(match organizer::apply_and_record(plan, &mut s.organize_history) {
+            Ok(report) if report.errors.is_empty() && report.skipped == 0 => format!("Applied {} actions", report.applied),
+            Ok(report) => format!(
+                "Applied {} actions, {} could not be done",
+                report.applied,
+                report.errors.len() + report.skipped
+            ),
+            Err(e) => format!("Error: {}", e),
+        });
+        s.plan_editor = None;
+    } else if !open {
+        s.plan_editor = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_action() {
+        let rename = |name: &str| OrganizeAction::Rename { from: name.to_string(), to: String::new() };
+        let names = |actions: &[OrganizeAction]| -> Vec<String> {
+            actions.iter().map(|a| match a {
+                OrganizeAction::Rename { from, .. } | OrganizeAction::Move { from, .. } => from.clone(),
+            }).collect()
+        };
+        let mut actions = vec![rename("a"), rename("b"), rename("c")];
+        move_action(&mut actions, 2, 0);
+        assert_eq!(names(&actions), ["c", "a", "b"]);
+        move_action(&mut actions, 0, 3);
+        assert_eq!(names(&actions), ["a", "b", "c"]);
+        move_action(&mut actions, 1, 2);
+        assert_eq!(names(&actions), ["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,228 @@
+//! The "Set up AI providers" step of onboarding
+//!
+//! Looks for API keys in the environment and for a local Ollama server with
+//! models pulled, shows what it found and lets the user paste keys for the
+//! rest. "Finish setup" saves the keys and a provider order built from what
+//! is available.
+
+use crate::AppState;
+use eframe::egui;
+use shared::settings::{ModelProvider, ProviderAuth};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+const OLLAMA_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The cloud providers the wizard asks about, in the order they're used:
+/// settings key, name shown and environment variable
+const CLOUD_PROVIDERS: [(&str, &str, &str); 3] = [
+    ("openai", "OpenAI", "OPENAI_API_KEY"),
+    ("anthropic", "Anthropic", "ANTHROPIC_API_KEY"),
+    ("gemini", "Google Gemini", "GEMINI_API_KEY"),
+];
+
+/// What was found on this machine
+#[derive(Debug, Clone, Default)]
+pub struct Detection {
+    /// Settings keys of the cloud providers with a key in the environment
+    pub env_keys: Vec<&'static str>,
+    /// Models Ollama has pulled, or `None` if it isn't running
+    pub ollama_models: Option<Vec<String>>,
+}
+
+impl Detection {
+    fn run() -> Self {
+        let env_keys = CLOUD_PROVIDERS
+            .iter()
+            .filter(|(_, _, var)| std::env::var(var).is_ok_and(|v| !v.trim().is_empty()))
+            .map(|(key, _, _)| *key)
+            .collect();
+        let ollama_models = tokio::runtime::Runtime::new()
+            .ok()
+            .and_then(|rt| rt.block_on(providers::ollama::installed_models(OLLAMA_TIMEOUT)).ok());
+        Self { env_keys, ollama_models }
+    }
+
+    fn ollama_ready(&self) -> bool {
+        self.ollama_models.as_ref().is_some_and(|m| !m.is_empty())
+    }
+}
+
+fn auth_mut<'a>(model: &'a mut ModelProvider, key: &str) -> &'a mut ProviderAuth {
+    match key {
+        "anthropic" => &mut model.anthropic_auth,
+        "gemini" => &mut model.gemini_auth,
+        _ => &mut model.openai_auth,
+    }
+}
+
+fn saved_key(model: &ModelProvider, key: &str) -> Option<String> {
+    let auth = match key {
+        "anthropic" => &model.anthropic_auth,
+        "gemini" => &model.gemini_auth,
+        _ => &model.openai_auth,
+    };
+    auth.api_key.clone().filter(|k| !k.trim().is_empty())
+}
+
+/// Ollama first if it has a model, then every cloud provider with a key.
+/// OpenAI alone when nothing is available yet.
+fn preference(detection: &Detection, has_key: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut providers = Vec::new();
+    if detection.ollama_ready() {
+        providers.push("local".to_string());
+    }
+    providers.extend(CLOUD_PROVIDERS.iter().filter(|(key, _, _)| has_key(key)).map(|(key, _, _)| key.to_string()));
+    if providers.is_empty() {
+        providers.push("openai".to_string());
+    }
+    providers
+}
+
+pub struct ProviderSetup {
+    detecting: Option<Receiver<Detection>>,
+    detection: Detection,
+    /// Keys typed or pasted in the wizard, by settings key
+    keys: [String; 3],
+}
+
+impl ProviderSetup {
+    /// Starts detection in the background; keys already in settings are
+    /// filled in
+    pub fn start(model: &ModelProvider) -> Self {
+        let keys = CLOUD_PROVIDERS.map(|(key, _, _)| saved_key(model, key).unwrap_or_default());
+        let mut setup = Self { detecting: None, detection: Detection::default(), keys };
+        setup.detect();
+        setup
+    }
+
+    fn detect(&mut self) {
+        let (tx, rx) = channel();
+        self.detecting = Some(rx);
+        std::thread::spawn(move || {
+            let _ = tx.send(Detection::run());
+        });
+    }
+
+    fn has_key(&self, key: &str) -> bool {
+        self.detection.env_keys.contains(&key)
+            || CLOUD_PROVIDERS
+                .iter()
+                .zip(&self.keys)
+                .any(|((k, _, _), typed)| *k == key && !typed.trim().is_empty())
+    }
+
+    /// Keys and provider order into `model`; the first pulled Ollama model
+    /// is used if the configured one isn't installed
+    fn apply(&self, model: &mut ModelProvider) {
+        for ((key, _, _), typed) in CLOUD_PROVIDERS.iter().zip(&self.keys) {
+            let typed = typed.trim();
+            auth_mut(model, key).api_key = (!typed.is_empty()).then(|| typed.to_string());
+        }
+        if let Some(models) = &self.detection.ollama_models {
+            if !models.contains(&model.local_model) {
+                if let Some(first) = models.first() {
+                    model.local_model = first.clone();
+                }
+            }
+        }
+        model.provider_preference = preference(&self.detection, |key| self.has_key(key));
+    }
+}
+
+fn status_row(ui: &mut egui::Ui, found: bool, name: &str, detail: &str) {
+    ui.horizontal(|ui| {
+        if found {
+            ui.label(egui::RichText::new("✔").size(16.0).color(egui::Color32::from_rgb(80, 170, 90)));
+        } else {
+            ui.label(egui::RichText::new("✖").size(16.0).color(egui::Color32::GRAY));
+        }
+        ui.label(egui::RichText::new(name).size(14.0).strong());
+        ui.label(egui::RichText::new(detail).size(12.0).weak());
+    });
+}
+
+/// The provider checklist. Returns true when the user finished setup.
+pub fn render(s: &mut AppState, ui: &mut egui::Ui) -> bool {
+    let Some(setup) = &mut s.provider_setup else { return false };
+    if let Some(detection) = setup.detecting.as_ref().and_then(|rx| rx.try_recv().ok()) {
+        setup.detection = detection;
+        setup.detecting = None;
+    }
+    let detecting = setup.detecting.is_some();
+
+    ui.label(egui::RichText::new("Set up AI providers").size(18.0).strong());
+    ui.add_space(4.0);
+    ui.label(
+        egui::RichText::new("I need at least one AI to think with. Here's what I found on this computer:")
+            .size(13.0)
+            .weak(),
+    );
+    ui.add_space(12.0);
+
+    if detecting {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Looking for providers...");
+        });
+        ui.ctx().request_repaint_after(Duration::from_millis(200));
+    } else {
+        let ollama = match &setup.detection.ollama_models {
+            Some(models) if !models.is_empty() => format!("running, {} model(s): {}", models.len(), models.join(", ")),
+            Some(_) => "running, but no models pulled yet - try `ollama pull llama3.2`".to_string(),
+            None => "not running - free and private, get it at ollama.com".to_string(),
+        };
+        status_row(ui, setup.detection.ollama_ready(), "Ollama (local)", &ollama);
+        ui.add_space(6.0);
+
+        for ((key, name, var), typed) in CLOUD_PROVIDERS.iter().zip(setup.keys.iter_mut()) {
+            if setup.detection.env_keys.contains(key) {
+                status_row(ui, true, name, &format!("found {}", var));
+            } else {
+                status_row(ui, !typed.trim().is_empty(), name, "paste an API key to use it");
+                ui.horizontal(|ui| {
+                    ui.add_space(28.0);
+                    ui.add(
+                        egui::TextEdit::singleline(typed)
+                            .password(true)
+                            .hint_text(format!("{} API key", name))
+                            .desired_width(300.0),
+                    );
+                });
+            }
+            ui.add_space(6.0);
+        }
+    }
+
+    ui.add_space(12.0);
+    let mut finished = false;
+    ui.horizontal(|ui| {
+        if ui.add_enabled(!detecting, egui::Button::new("Check again")).clicked() {
+            setup.detect();
+        }
+        let finish = egui::Button::new(egui::RichText::new("Finish setup").strong())
+            .min_size(egui::vec2(160.0, 32.0));
+        if ui.add_enabled(!detecting, finish).clicked() {
+            setup.apply(&mut s.settings.model);
+            finished = true;
+        }
+    });
+    finished
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preference() {
+        let no_keys = |_: &str| false;
+        let with_ollama = Detection { env_keys: Vec::new(), ollama_models: Some(vec!["llama3.2:3b".to_string()]) };
+        assert_eq!(preference(&with_ollama, |k| k == "anthropic"), ["local", "anthropic"]);
+        assert_eq!(preference(&Detection::default(), no_keys), ["openai"]);
+
+        // A running server without models can't answer anything
+        let empty_ollama = Detection { env_keys: Vec::new(), ollama_models: Some(Vec::new()) };
+        assert_eq!(preference(&empty_ollama, |k| k != "openai"), ["anthropic", "gemini"]);
+    }
+}
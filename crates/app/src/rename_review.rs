@@ -0,0 +1,139 @@
+//! "Suggest better names" for a folder: the AI proposes names, the user
+//! picks which to keep, and the renames run through the organizer so they
+//! can be undone from Settings.
+
+use crate::plan_editor::PlanEditor;
+use crate::AppState;
+use eframe::egui;
+use services::organizer::{self, OrganizeSuggestor};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+
+/// Most files in a folder sent to the AI at once
+const MAX_RENAME_SUGGESTIONS: usize = 25;
+
+/// (file, suggested name) pairs, or why there are none
+type Suggestions = Result<Vec<(PathBuf, String)>, String>;
+
+pub struct RenameReview {
+    dir: PathBuf,
+    rx: Option<Receiver<Suggestions>>,
+    /// File, suggested name (editable) and whether to rename it
+    suggestions: Vec<(PathBuf, String, bool)>,
+    error: Option<String>,
+}
+
+/// Ask for name suggestions for the files directly in `dir`
+pub fn start(s: &mut AppState, dir: PathBuf, ctx: &egui::Context) {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+
+    let (tx, rx) = channel();
+    s.agent_host.settings = s.settings.clone();
+    let agent_host = s.agent_host.clone();
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let result = tokio::runtime::Runtime::new()
+            .map_err(|e| e.to_string())
+            .and_then(|rt| {
+                rt.block_on(OrganizeSuggestor::new(&agent_host).suggest_renames(files, MAX_RENAME_SUGGESTIONS))
+                    .map_err(|e| e.to_string())
+            });
+        let _ = tx.send(result);
+        ctx.request_repaint();
+    });
+    s.rename_review = Some(RenameReview { dir, rx: Some(rx), suggestions: Vec::new(), error: None });
+}
+
+/// The review window, shown while there's a review
+pub fn render(s: &mut AppState, ctx: &egui::Context) {
+    let Some(review) = &mut s.rename_review else { return };
+    if let Some(result) = review.rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+        review.rx = None;
+        match result {
+            Ok(suggestions) => {
+                review.suggestions = suggestions.into_iter().map(|(path, name)| (path, name, true)).collect();
+            }
+            Err(e) => review.error = Some(e),
+        }
+    }
+
+    let mut open = true;
+    let mut apply = false;
+    let mut edit_plan = false;
+    let title = format!("Suggested names for {}", review.dir.file_name().unwrap_or_default().to_string_lossy());
+    egui::Window::new(title)
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(520.0)
+        .show(ctx, |ui| {
+            if review.rx.is_some() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Looking at the files…");
+                });
+                return;
+            }
+            if let Some(error) = &review.error {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Couldn't get suggestions: {}", error));
+                return;
+            }
+            if review.suggestions.is_empty() {
+                ui.label("These names already look clear.");
+                return;
+            }
+            egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                egui::Grid::new("rename_suggestions").num_columns(3).spacing(egui::vec2(8.0, 4.0)).show(ui, |ui| {
+                    for (path, name, keep) in &mut review.suggestions {
+                        ui.checkbox(keep, path.file_name().unwrap_or_default().to_string_lossy());
+                        ui.label("→");
+                        ui.add_enabled(*keep, egui::TextEdit::singleline(name).desired_width(240.0));
+                        ui.end_row();
+                    }
+                });
+            });
+            ui.add_space(6.0);
+            let count = review.suggestions.iter().filter(|(_, name, keep)| *keep && !name.trim().is_empty()).count();
+            ui.horizontal(|ui| {
+                apply = ui.add_enabled(count > 0, egui::Button::new(format!("Rename {} files", count))).clicked();
+                edit_plan = ui
+                    .add_enabled(count > 0, egui::Button::new("Edit plan…"))
+                    .on_hover_text("Reorder, remove or add actions before anything is renamed")
+                    .clicked();
+            });
+        });
+
+    let accepted = || -> Vec<(PathBuf, String)> {
+        review
+            .suggestions
+            .iter()
+            .filter(|(_, name, keep)| *keep && !name.trim().is_empty())
+            .map(|(path, name, _)| (path.clone(), name.trim().to_string()))
+            .collect()
+    };
+    if edit_plan {
+        let plan = organizer::rename_plan(&accepted());
+        s.plan_editor = Some(PlanEditor::new(plan, review.dir.clone()));
+        s.rename_review = None;
+    } else if apply {
+        let plan = organizer::rename_plan(&accepted());
+        s.organize_status = Some(match organizer::apply_and_record(plan, &mut s.organize_history) {
+            Ok(report) if report.errors.is_empty() && report.skipped == 0 => format!("Renamed {} files", report.applied),
+            Ok(report) => format!(
+                "Renamed {} files, {} could not be renamed",
+                report.applied,
+                report.errors.len() + report.skipped
+            ),
+            Err(e) => format!("Error: {}", e),
+        });
+        s.rename_review = None;
+    } else if !open {
+        s.rename_review = None;
+    }
+}
@@ -0,0 +1,296 @@
+//! Content mode post schedule
+//!
+//! Shows posts the AI proposed with `<schedule-post>` tags as a small
+//! month calendar, and exports them as an iCalendar (RFC 5545) file.
+
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use shared::settings::ContentSchedule;
+use std::fs;
+use std::path::Path;
+
+/// Anything the schedule panel asks the app to do
+#[derive(Default)]
+pub struct ScheduleAction {
+    /// Schedule was edited and should be saved
+    pub changed: bool,
+    pub status: Option<String>,
+}
+
+/// First day of the month containing `date`
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+fn add_months(month: NaiveDate, delta: i32) -> NaiveDate {
+    let total = month.year() * 12 + month.month0() as i32 + delta;
+    NaiveDate::from_ymd_opt(total.div_euclid(12), total.rem_euclid(12) as u32 + 1, 1).unwrap_or(month)
+}
+
+/// Render the month grid and post list. `month` is the first day of the month shown.
+pub fn render_schedule_panel(
+    ui: &mut egui::Ui,
+    schedule: &mut Vec<ContentSchedule>,
+    month: &mut NaiveDate,
+) -> ScheduleAction {
+    let mut action = ScheduleAction::default();
+
+    ui.heading("Post Schedule");
+    ui.add_space(4.0);
+
+    // Month navigation
+    ui.horizontal(|ui| {
+        if ui.small_button("◀").clicked() {
+            *month = add_months(*month, -1);
+        }
+        ui.label(egui::RichText::new(month.format("%B %Y").to_string()).strong());
+        if ui.small_button("▶").clicked() {
+            *month = add_months(*month, 1);
+        }
+    });
+    ui.add_space(4.0);
+
+    month_grid(ui, schedule, *month);
+
+    ui.add_space(8.0);
+    ui.separator();
+
+    if schedule.is_empty() {
+        ui.label(
+            egui::RichText::new("No posts scheduled yet. Ask me to plan some posts!")
+                .size(12.0)
+                .weak(),
+        );
+        return action;
+    }
+
+    // Upcoming posts, soonest first
+    schedule.sort_by_key(|p| p.scheduled_at);
+    let mut remove = None;
+    egui::ScrollArea::vertical()
+        .id_source("schedule_list")
+        .max_height(ui.available_height() - 40.0)
+        .show(ui, |ui| {
+            for (i, post) in schedule.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} · {}",
+                            post.scheduled_at.format("%b %d %H:%M"),
+                            post.platform
+                        ))
+                        .size(12.0)
+                        .strong(),
+                    );
+                    if ui.small_button("✕").on_hover_text("Remove from schedule").clicked() {
+                        remove = Some(i);
+                    }
+                });
+                ui.label(egui::RichText::new(&post.post).size(12.0));
+                ui.add_space(6.0);
+            }
+        });
+    if let Some(i) = remove {
+        schedule.remove(i);
+        action.changed = true;
+    }
+
+    ui.add_space(4.0);
+    if ui.button("Export as iCal").clicked() {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("iCalendar", &["ics"])
+            .set_file_name("content-schedule.ics")
+            .save_file()
+        {
+            action.status = Some(match export_ical(schedule, &path) {
+                Ok(()) => format!("Exported {} posts to {}", schedule.len(), path.display()),
+                Err(e) => format!("Error: {}", e),
+            });
+        }
+    }
+
+    action
+}
+
+/// Seven-column month calendar; days with posts are highlighted with a count
+fn month_grid(ui: &mut egui::Ui, schedule: &[ContentSchedule], month: NaiveDate) {
+    let today = Utc::now().date_naive();
+    // Weeks start on Monday
+    let offset = month.weekday().num_days_from_monday() as i64;
+    let grid_start = month - Duration::days(offset);
+    let accent = egui::Color32::from_rgb(70, 130, 180);
+
+    egui::Grid::new("schedule_grid")
+        .spacing(egui::vec2(2.0, 2.0))
+        .show(ui, |ui| {
+            for day in ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"] {
+                ui.label(egui::RichText::new(day).size(11.0).weak());
+            }
+            ui.end_row();
+
+            for week in 0..6 {
+                for weekday in 0..7 {
+                    let date = grid_start + Duration::days(week * 7 + weekday);
+                    let posts: Vec<&ContentSchedule> = schedule
+                        .iter()
+                        .filter(|p| p.scheduled_at.date_naive() == date)
+                        .collect();
+
+                    let mut text = egui::RichText::new(date.day().to_string()).size(11.0);
+                    if date.month() != month.month() {
+                        text = text.weak();
+                    }
+                    if date == today {
+                        text = text.underline();
+                    }
+                    let button = if posts.is_empty() {
+                        egui::Button::new(text).frame(false)
+                    } else {
+                        egui::Button::new(text.color(egui::Color32::WHITE).strong()).fill(accent)
+                    };
+
+                    let response = ui.add_sized([28.0, 22.0], button);
+                    if !posts.is_empty() {
+                        response.on_hover_ui(|ui| {
+                            for post in posts {
+                                ui.label(format!(
+                                    "{} ({}): {}",
+                                    post.platform,
+                                    post.scheduled_at.format("%H:%M"),
+                                    post.post
+                                ));
+                            }
+                        });
+                    }
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// Write the schedule to an .ics file
+pub fn export_ical(schedule: &[ContentSchedule], path: &Path) -> anyhow::Result<()> {
+    fs::write(path, to_ical(schedule))?;
+    Ok(())
+}
+
+/// Build an RFC 5545 calendar with one event per post
+pub fn to_ical(schedule: &[ContentSchedule]) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Little Helper//Content Schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+
+    for (i, post) in schedule.iter().enumerate() {
+        let start = post.scheduled_at.format("%Y%m%dT%H%M%SZ");
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}@little-helper", start, i));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", start));
+        lines.push(format!("SUMMARY:{}", escape_text(&format!("{} post", post.platform))));
+        lines.push(format!("DESCRIPTION:{}", escape_text(&post.post)));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    // Content lines end with CRLF and are folded at 75 octets
+    lines.iter().map(|l| fold_line(l) + "\r\n").collect()
+}
+
+/// Escape TEXT values (RFC 5545 section 3.3.11)
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold long content lines (RFC 5545 section 3.1) without splitting UTF-8 characters
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+    for c in line.chars() {
+        let len = c.len_utf8();
+        if width + len > 75 {
+            folded.push_str("\r\n ");
+            width = 1; // The leading space counts
+        }
+        folded.push(c);
+        width += len;
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// Property values of each event, the way a calendar app reads them:
+    /// lines unfolded and TEXT unescaped
+    fn parse_events(ics: &str) -> Vec<Vec<(String, String)>> {
+        let unfolded = ics.replace("\r\n ", "");
+        let mut events = Vec::new();
+        for line in unfolded.split("\r\n") {
+            match line {
+                "BEGIN:VEVENT" => events.push(Vec::new()),
+                _ => {
+                    let (Some(event), Some((name, value))) = (events.last_mut(), line.split_once(':')) else { continue };
+                    let mut text = String::new();
+                    let mut chars = value.chars();
+                    while let Some(c) = chars.next() {
+                        if c != '\\' {
+                            text.push(c);
+                            continue;
+                        }
+                        match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some(other) => text.push(other),
+                            None => {}
+                        }
+                    }
+                    event.push((name.to_string(), text));
+                }
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn test_ical_round_trip() {
+        let long = "é".repeat(60);
+        let schedule = vec![
+            ContentSchedule {
+                post: "Sale today; 20% off, see C:\\deals\nReply below".to_string(),
+                platform: "twitter".to_string(),
+                scheduled_at: Utc.with_ymd_and_hms(2024, 12, 31, 9, 0, 0).unwrap(),
+            },
+            ContentSchedule {
+                post: long.clone(),
+                platform: "mastodon".to_string(),
+                scheduled_at: Utc.with_ymd_and_hms(2025, 1, 2, 14, 30, 0).unwrap(),
+            },
+        ];
+        let ics = to_ical(&schedule);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n") && ics.ends_with("END:VCALENDAR\r\n"));
+        // Folded lines stay within 75 octets
+        assert!(ics.split("\r\n").all(|line| line.len() <= 75));
+
+        let events = parse_events(&ics);
+        assert_eq!(events.len(), 2);
+        let value = |event: &[(String, String)], name: &str| {
+            event.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone()).unwrap()
+        };
+        assert_eq!(value(&events[0], "DTSTART"), "20241231T090000Z");
+        assert_eq!(value(&events[0], "SUMMARY"), "twitter post");
+        assert_eq!(value(&events[0], "DESCRIPTION"), schedule[0].post);
+        assert_eq!(value(&events[1], "DTSTART"), "20250102T143000Z");
+        assert_eq!(value(&events[1], "DESCRIPTION"), long);
+        assert_ne!(value(&events[0], "UID"), value(&events[1], "UID"));
+
+        assert!(parse_events(&to_ical(&[])).is_empty());
+    }
+}
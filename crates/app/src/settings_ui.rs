@@ -0,0 +1,787 @@
+//! Settings window, opened from the gear button in the header
+
+use crate::{save_settings, tts, AppState, ChatMode};
+use agent_host::audit::{self, AuditEntry};
+use eframe::egui;
+use providers::benchmark::{self, BenchmarkResult, ModelBenchmark};
+use providers::openai;
+use services::organizer;
+use services::language::ProgrammingLanguage;
+use shared::settings::{default_audit_log_path, default_language_hints, ModelProvider, TimeFormat, WatchMode};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+/// Providers offered in the per-mode pickers
+pub(crate) const PROVIDERS: &[&str] = &["local", "openai", "anthropic", "gemini", "cohere"];
+
+/// Timeout sliders in display order: (settings key, label, hint)
+const TIMEOUT_LEVELS: &[(&str, &str, &str)] = &[
+    ("safe", "Safe commands", "Read-only commands like ls, cat, grep"),
+    ("needs_confirmation", "Needs confirmation", "Commands that change files, like cp or mkdir"),
+    ("dangerous", "Dangerous", "Destructive or long-running commands, like rm or builds"),
+    ("needs_sudo", "Needs sudo", "Commands that run with administrator rights"),
+];
+
+/// Benchmark table columns, for sorting
+#[derive(Clone, Copy, PartialEq)]
+enum BenchmarkColumn {
+    Model,
+    Speed,
+    FirstToken,
+    Quality,
+}
+
+/// The local model benchmark: running, or its last results
+pub struct ModelBenchmarks {
+    running: Option<Receiver<Result<Vec<BenchmarkResult>, String>>>,
+    results: Vec<BenchmarkResult>,
+    error: Option<String>,
+    sort: BenchmarkColumn,
+    descending: bool,
+}
+
+impl Default for ModelBenchmarks {
+    fn default() -> Self {
+        Self { running: None, results: Vec::new(), error: None, sort: BenchmarkColumn::Quality, descending: true }
+    }
+}
+
+impl ModelBenchmarks {
+    /// Benchmark every model Ollama has in the background
+    fn start(&mut self) {
+        let (tx, rx) = channel();
+        self.running = Some(rx);
+        self.error = None;
+        std::thread::spawn(move || {
+            let result = tokio::runtime::Runtime::new().map_err(|e| e.to_string()).and_then(|rt| {
+                rt.block_on(async {
+                    let models = providers::ollama::installed_models(Duration::from_secs(5))
+                        .await
+                        .map_err(|_| "Ollama isn't running".to_string())?;
+                    if models.is_empty() {
+                        return Err("Ollama has no models yet - pull one with `ollama pull`".to_string());
+                    }
+                    Ok(ModelBenchmark::run(&models, benchmark::default_prompts()).await)
+                })
+            });
+            let _ = tx.send(result);
+        });
+    }
+
+    fn sort(&mut self) {
+        let column = self.sort;
+        self.results.sort_by(|a, b| {
+            let order = match column {
+                BenchmarkColumn::Model => a.model.cmp(&b.model),
+                BenchmarkColumn::Speed => a.avg_tokens_per_sec.total_cmp(&b.avg_tokens_per_sec),
+                BenchmarkColumn::FirstToken => a.avg_first_token_ms.total_cmp(&b.avg_first_token_ms),
+                BenchmarkColumn::Quality => a.quality_score_avg.total_cmp(&b.quality_score_avg),
+            };
+            if self.descending { order.reverse() } else { order }
+        });
+    }
+}
+
+pub fn render_settings_window(s: &mut AppState, ctx: &egui::Context) {
+    let mut open = s.show_settings;
+    let mut changed = false;
+
+    egui::Window::new("Settings")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .default_width(380.0)
+        .show(ctx, |ui| {
+            egui::CollapsingHeader::new("Timeouts")
+                .default_open(true)
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new("How long a command may run before it's stopped")
+                            .size(12.0)
+                            .weak(),
+                    );
+                    ui.add_space(4.0);
+                    egui::Grid::new("timeout_settings")
+                        .num_columns(2)
+                        .spacing(egui::vec2(12.0, 6.0))
+                        .show(ui, |ui| {
+                            for (key, label, hint) in TIMEOUT_LEVELS {
+                                ui.label(*label).on_hover_text(*hint);
+                                let secs = s
+                                    .settings
+                                    .timeouts
+                                    .entry(key.to_string())
+                                    .or_insert(agent_host::executor::DEFAULT_TIMEOUT_SECS);
+                                changed |= ui
+                                    .add(egui::Slider::new(secs, 1..=600).suffix(" s"))
+                                    .changed();
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            egui::CollapsingHeader::new("Security")
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new(
+                            "Extra commands on top of the built-in lists. Enter a command name, \
+                            a simple prefix like \"git stash list\", or a pattern like \"~/bin/*\".",
+                        )
+                        .size(12.0)
+                        .weak(),
+                    );
+                    ui.add_space(4.0);
+                    let settings = &mut s.settings;
+                    changed |= tag_input(
+                        ui,
+                        "Run without asking",
+                        "extra_safe_commands",
+                        &mut settings.extra_safe_commands,
+                    );
+                    changed |= tag_input(
+                        ui,
+                        "Always ask first",
+                        "extra_needs_confirmation_commands",
+                        &mut settings.extra_needs_confirmation_commands,
+                    );
+                    changed |= tag_input(
+                        ui,
+                        "Never run",
+                        "extra_blocked_commands",
+                        &mut settings.extra_blocked_commands,
+                    );
+                    ui.add_space(4.0);
+                    changed |= ui
+                        .checkbox(&mut settings.preview_batch, "Show all of a reply's commands before any run")
+                        .changed();
+                    changed |= ui
+                        .checkbox(
+                            &mut settings.auto_extend_allowed_dirs,
+                            "Ask to allow other folders when a command needs them",
+                        )
+                        .on_hover_text("Folders you allow are only kept until Little Helper closes")
+                        .changed();
+                    let session_dirs = s.agent_host.session_allowed_dirs();
+                    if !session_dirs.is_empty() {
+                        ui.add_space(4.0);
+                        ui.label(egui::RichText::new("Allowed this session").size(12.0).weak());
+                        for dir in session_dirs {
+                            ui.horizontal(|ui| {
+                                ui.label(egui::RichText::new(&dir).monospace());
+                                if ui.small_button("Keep").on_hover_text("Add to the allowed folders for good").clicked() {
+                                    s.agent_host.forget_session_dir(&dir);
+                                    s.settings.allowed_dirs.push(dir.clone());
+                                    changed = true;
+                                }
+                            });
+                        }
+                    }
+                });
+
+            egui::CollapsingHeader::new("Providers by mode")
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new("Which provider each mode tries first; the others follow as fallbacks")
+                            .size(12.0)
+                            .weak(),
+                    );
+                    ui.add_space(4.0);
+                    egui::Grid::new("mode_providers")
+                        .num_columns(2)
+                        .spacing(egui::vec2(12.0, 6.0))
+                        .show(ui, |ui| {
+                            for mode in ChatMode::ALL {
+                                ui.label(mode.label());
+                                changed |= mode_provider_picker(ui, &mut s.settings.model, mode.key());
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            egui::CollapsingHeader::new("Local model benchmark")
+                .default_open(false)
+                .show(ui, |ui| benchmark_section(&mut s.benchmark, ui));
+
+            egui::CollapsingHeader::new("Cohere")
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new("Used when \"cohere\" is in the provider list")
+                            .size(12.0)
+                            .weak(),
+                    );
+                    ui.add_space(4.0);
+                    egui::Grid::new("cohere_settings")
+                        .num_columns(2)
+                        .spacing(egui::vec2(12.0, 6.0))
+                        .show(ui, |ui| {
+                            let model = &mut s.settings.model;
+                            ui.label("Model");
+                            changed |= ui
+                                .add(egui::TextEdit::singleline(&mut model.cohere_model).desired_width(220.0))
+                                .changed();
+                            ui.end_row();
+
+                            ui.label("API key");
+                            let key = model.cohere_auth.api_key.get_or_insert_with(String::new);
+                            changed |= ui
+                                .add(egui::TextEdit::singleline(key).password(true).desired_width(220.0))
+                                .changed();
+                            if key.is_empty() {
+                                model.cohere_auth.api_key = None;
+                            }
+                            ui.end_row();
+                        });
+                });
+
+            egui::CollapsingHeader::new("OpenAI-compatible server")
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new(
+                            "Send \"openai\" requests to Azure OpenAI, LM Studio, vLLM or another server \
+                            that speaks the same API. Leave blank for OpenAI.",
+                        )
+                        .size(12.0)
+                        .weak(),
+                    );
+                    ui.add_space(4.0);
+                    egui::Grid::new("openai_server_settings")
+                        .num_columns(2)
+                        .spacing(egui::vec2(12.0, 6.0))
+                        .show(ui, |ui| {
+                            let model = &mut s.settings.model;
+                            ui.label("Base URL");
+                            changed |= optional_text(ui, &mut model.openai_base_url, openai::DEFAULT_BASE_URL);
+                            ui.end_row();
+
+                            ui.label("API version");
+                            changed |= optional_text(ui, &mut model.openai_api_version, "Azure only, e.g. 2024-06-01");
+                            ui.end_row();
+                        });
+                });
+
+            egui::CollapsingHeader::new("Chat")
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Time format");
+                        egui::ComboBox::from_id_source("time_format")
+                            .selected_text(s.settings.time_format.label())
+                            .show_ui(ui, |ui| {
+                                for format in TimeFormat::ALL {
+                                    changed |= ui
+                                        .selectable_value(&mut s.settings.time_format, format, format.label())
+                                        .changed();
+                                }
+                            });
+                    });
+                    changed |= ui
+                        .checkbox(&mut s.settings.ai_command_summaries, "Summarize long command output with AI")
+                        .on_hover_text("The output is sent to your first AI provider that's set up")
+                        .changed();
+                });
+
+            egui::CollapsingHeader::new("Files")
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Reload open files");
+                        egui::ComboBox::from_id_source("file_watch")
+                            .selected_text(s.settings.file_watch.label())
+                            .show_ui(ui, |ui| {
+                                for mode in WatchMode::ALL {
+                                    changed |= ui
+                                        .selectable_value(&mut s.settings.file_watch, mode, mode.label())
+                                        .changed();
+                                }
+                            });
+                    });
+                    changed |= ui
+                        .checkbox(&mut s.settings.auto_open_files, "Open the first file a reply mentions")
+                        .changed();
+                });
+
+            egui::CollapsingHeader::new("Languages")
+                .default_open(false)
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new("Told to the AI when the open file is in that language")
+                            .size(12.0)
+                            .weak(),
+                    );
+                    ui.add_space(4.0);
+                    for language in ProgrammingLanguage::ALL {
+                        ui.label(language.display_name());
+                        let hint = s.settings.language_hints.entry(language.key().to_string()).or_default();
+                        changed |= ui
+                            .add(egui::TextEdit::multiline(hint).desired_rows(2).desired_width(f32::INFINITY))
+                            .changed();
+                    }
+                    if ui.small_button("Reset to defaults").clicked() {
+                        s.settings.language_hints = default_language_hints();
+                        changed = true;
+                    }
+                });
+
+            egui::CollapsingHeader::new("Macros")
+                .default_open(false)
+                .show(ui, |ui| macros_section(s, ui));
+
+            egui::CollapsingHeader::new("Speech")
+                .default_open(false)
+                .show(ui, |ui| {
+                    changed |= ui
+                        .checkbox(&mut s.settings.tts_enabled, "Read responses aloud")
+                        .changed();
+                    changed |= voice_selector(ui, &mut s.settings.tts_voice);
+                });
+
+            egui::CollapsingHeader::new("Debug")
+                .default_open(false)
+                .show(ui, |ui| {
+                    changed |= ui
+                        .checkbox(&mut s.settings.debug_mode, "Show timing details under responses")
+                        .on_hover_text("How long the AI and each command took, and which model answered")
+                        .changed();
+                });
+
+            egui::CollapsingHeader::new("Organizer history")
+                .default_open(false)
+                .show(ui, |ui| organize_history_section(s, ui));
+
+            let command_log = egui::CollapsingHeader::new("Command log")
+                .default_open(false)
+                .show(ui, |ui| changed |= audit_log_section(s, ui));
+            // Read again next time it's opened
+            if command_log.body_returned.is_none() {
+                s.audit_log_cache = None;
+            }
+        });
+
+    s.show_settings = open;
+    if changed {
+        save_settings(&s.settings);
+    }
+}
+
+/// Text box for a setting that's unset when blank
+fn optional_text(ui: &mut egui::Ui, value: &mut Option<String>, hint: &str) -> bool {
+    let text = value.get_or_insert_with(String::new);
+    let changed = ui
+        .add(egui::TextEdit::singleline(text).hint_text(hint).desired_width(220.0))
+        .changed();
+    if text.trim().is_empty() {
+        *value = None;
+    }
+    changed
+}
+
+/// Dropdown of the provider `mode` tries first. Picking one puts it ahead
+/// of the usual preference order; "Default" uses that order unchanged.
+fn mode_provider_picker(ui: &mut egui::Ui, model: &mut ModelProvider, mode: &str) -> bool {
+    let current = model.mode_providers.get(mode).and_then(|p| p.first()).cloned();
+    let mut choice = current.clone();
+    egui::ComboBox::from_id_source(("mode_provider", mode))
+        .selected_text(current.as_deref().unwrap_or("Default"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut choice, None, "Default");
+            for provider in PROVIDERS {
+                ui.selectable_value(&mut choice, Some(provider.to_string()), *provider);
+            }
+        });
+    if choice == current {
+        return false;
+    }
+    match choice {
+        Some(first) => {
+            let rest = model.provider_preference.iter().filter(|p| **p != first).cloned();
+            let order = std::iter::once(first.clone()).chain(rest).collect();
+            model.mode_providers.insert(mode.to_string(), order);
+        }
+        None => {
+            model.mode_providers.remove(mode);
+        }
+    }
+    true
+}
+
+/// Voice picker from the voices the system reports. Voices are detected
+/// once, the first time the section is shown.
+fn voice_selector(ui: &mut egui::Ui, voice: &mut Option<String>) -> bool {
+    let voices_id = egui::Id::new("tts_voices");
+    let voices: Vec<String> = match ui.data(|d| d.get_temp(voices_id)) {
+        Some(voices) => voices,
+        None => {
+            let voices = tts::list_voices();
+            ui.data_mut(|d| d.insert_temp(voices_id, voices.clone()));
+            voices
+        }
+    };
+
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Voice");
+        if voices.is_empty() {
+            ui.label(
+                egui::RichText::new("No voices found - is espeak installed?")
+                    .size(12.0)
+                    .weak(),
+            );
+            return;
+        }
+        egui::ComboBox::from_id_source("tts_voice")
+            .selected_text(voice.as_deref().unwrap_or("System default"))
+            .show_ui(ui, |ui| {
+                changed |= ui.selectable_value(voice, None, "System default").changed();
+                for name in &voices {
+                    changed |= ui.selectable_value(voice, Some(name.clone()), name).changed();
+                }
+            });
+        if ui.small_button("▶").on_hover_text("Try this voice").clicked() {
+            tts::speak("Hi! I'm your Little Helper.", voice.as_deref());
+        }
+    });
+    changed
+}
+
+/// Removable chips plus a text field for adding entries. Entries are
+/// validated before they're added. Returns true if the list changed.
+fn tag_input(ui: &mut egui::Ui, label: &str, id: &str, tags: &mut Vec<String>) -> bool {
+    let input_id = egui::Id::new(("tag_input", id));
+    let error_id = egui::Id::new(("tag_input_error", id));
+    let mut changed = false;
+
+    ui.label(egui::RichText::new(label).strong());
+    ui.horizontal_wrapped(|ui| {
+        let mut remove = None;
+        for (i, tag) in tags.iter().enumerate() {
+            egui::Frame::none()
+                .fill(ui.visuals().faint_bg_color)
+                .rounding(egui::Rounding::same(8.0))
+                .inner_margin(egui::Margin::symmetric(6.0, 2.0))
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(tag).monospace().size(12.0));
+                        if ui.small_button("✕").on_hover_text("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                });
+        }
+        if let Some(i) = remove {
+            tags.remove(i);
+            changed = true;
+        }
+    });
+
+    let mut input: String = ui.data_mut(|d| d.get_temp(input_id).unwrap_or_default());
+    ui.horizontal(|ui| {
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut input)
+                .hint_text("e.g. rg or ~/bin/*")
+                .desired_width(200.0),
+        );
+        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        if (ui.button("Add").clicked() || submitted) && !input.trim().is_empty() {
+            match shared::settings::validate_command_prefix(&input) {
+                Ok(entry) => {
+                    if !tags.contains(&entry) {
+                        tags.push(entry);
+                        changed = true;
+                    }
+                    input.clear();
+                    ui.data_mut(|d| d.remove::<String>(error_id));
+                }
+                Err(e) => ui.data_mut(|d| d.insert_temp(error_id, e)),
+            }
+        }
+    });
+    ui.data_mut(|d| d.insert_temp(input_id, input));
+
+    if let Some(error) = ui.data(|d| d.get_temp::<String>(error_id)) {
+        ui.label(egui::RichText::new(error).size(12.0).color(ui.visuals().error_fg_color));
+    }
+    ui.add_space(6.0);
+    changed
+}
+
+/// Run the benchmark, then show its results and a pick for each mode
+fn benchmark_section(bench: &mut ModelBenchmarks, ui: &mut egui::Ui) {
+    if let Some(result) = bench.running.as_ref().and_then(|rx| rx.try_recv().ok()) {
+        bench.running = None;
+        match result {
+            Ok(results) => {
+                bench.results = results;
+                bench.sort();
+            }
+            Err(e) => bench.error = Some(e),
+        }
+    }
+
+    ui.label(
+        egui::RichText::new("Times each Ollama model on a few prompts and checks three factual answers")
+            .size(12.0)
+            .weak(),
+    );
+    ui.add_space(4.0);
+    if bench.running.is_some() {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Benchmarking - this can take a few minutes...");
+        });
+        ui.ctx().request_repaint_after(Duration::from_millis(500));
+    } else if ui.button("Benchmark local models").clicked() {
+        bench.start();
+    }
+    if let Some(error) = &bench.error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+    if bench.results.is_empty() {
+        return;
+    }
+
+    ui.add_space(6.0);
+    let mut resort = None;
+    egui::Grid::new("benchmark_results")
+        .num_columns(4)
+        .striped(true)
+        .spacing(egui::vec2(12.0, 4.0))
+        .show(ui, |ui| {
+            for (column, title) in [
+                (BenchmarkColumn::Model, "Model"),
+                (BenchmarkColumn::Speed, "Tokens/s"),
+                (BenchmarkColumn::FirstToken, "First token"),
+                (BenchmarkColumn::Quality, "Quality"),
+            ] {
+                let arrow = match (bench.sort == column, bench.descending) {
+                    (true, true) => " ⏷",
+                    (true, false) => " ⏶",
+                    _ => "",
+                };
+                if ui.selectable_label(bench.sort == column, format!("{}{}", title, arrow)).clicked() {
+                    resort = Some(column);
+                }
+            }
+            ui.end_row();
+
+            for result in &bench.results {
+                ui.label(&result.model);
+                match &result.error {
+                    Some(error) => {
+                        ui.colored_label(ui.visuals().error_fg_color, "failed").on_hover_text(error);
+                        ui.label("");
+                        ui.label("");
+                    }
+                    None => {
+                        ui.label(format!("{:.1}", result.avg_tokens_per_sec));
+                        ui.label(format!("{:.0} ms", result.avg_first_token_ms));
+                        ui.label(format!("{:.0}%", result.quality_score_avg * 100.0));
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    if let Some(column) = resort {
+        // Clicking the sorted column again flips the order; a new column
+        // starts with the best models on top
+        bench.descending = if bench.sort == column {
+            !bench.descending
+        } else {
+            matches!(column, BenchmarkColumn::Speed | BenchmarkColumn::Quality)
+        };
+        bench.sort = column;
+        bench.sort();
+    }
+
+    ui.add_space(6.0);
+    ui.label(egui::RichText::new("Best fit per mode").size(12.0).weak());
+    for mode in ChatMode::ALL {
+        if let Some(best) = benchmark::recommend(&bench.results, mode.key()) {
+            ui.label(format!("{}: {}", mode.label(), best.model));
+        }
+    }
+}
+
+/// Past organizer runs, newest first, each with an Undo button
+fn organize_history_section(s: &mut AppState, ui: &mut egui::Ui) {
+    let mut undo = None;
+    let last = s.organize_history.iter().rposition(|e| !e.undone);
+
+    if ui
+        .add_enabled(last.is_some(), egui::Button::new("↶ Undo last organize"))
+        .clicked()
+    {
+        undo = last;
+    }
+    ui.add_space(4.0);
+
+    if s.organize_history.is_empty() {
+        ui.label(egui::RichText::new("No organizer runs yet").size(12.0).weak());
+    }
+    for (i, entry) in s.organize_history.iter().enumerate().rev() {
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(entry.timestamp.with_timezone(&chrono::Local).format("%b %d %H:%M").to_string())
+                    .size(12.0)
+                    .weak(),
+            );
+            let mut summary = egui::RichText::new(entry.summary()).size(12.0);
+            if entry.undone {
+                summary = summary.strikethrough();
+            }
+            ui.label(summary);
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.add_enabled(!entry.undone, egui::Button::new("Undo").small()).clicked() {
+                    undo = Some(i);
+                }
+            });
+        });
+    }
+
+    if let Some(i) = undo {
+        s.organize_status = Some(match organizer::undo_entry(&mut s.organize_history, i) {
+            Ok(report) if report.errors.is_empty() => format!("Restored {} files", report.applied),
+            Ok(report) => {
+                let failed: Vec<String> = report.errors.iter().map(|e| format!("{} ({})", e.action, e.error)).collect();
+                format!(
+                    "Restored {} files. These could not be moved back; undo again to retry them:\n{}",
+                    report.applied,
+                    failed.join("\n")
+                )
+            }
+            Err(e) => format!("Error: {}", e),
+        });
+    }
+    if let Some(status) = &s.organize_status {
+        ui.add_space(4.0);
+        ui.label(egui::RichText::new(status).size(12.0));
+    }
+}
+
+/// Entries shown from the end of the command log
+const AUDIT_LOG_ROWS: usize = 20;
+
+/// The command log entries on show, read when the section opens and again
+/// after a command is logged
+pub(crate) struct AuditLogCache {
+    path: PathBuf,
+    writes: usize,
+    entries: Vec<AuditEntry>,
+}
+
+/// Turn the command log on or off, and show its latest entries
+fn audit_log_section(s: &mut AppState, ui: &mut egui::Ui) -> bool {
+    let mut keep = s.settings.audit_log_path.is_some();
+    let changed = ui
+        .checkbox(&mut keep, "Keep a log of the commands that run")
+        .on_hover_text("Passwords given for sudo are never written to it")
+        .changed();
+    if changed {
+        s.settings.audit_log_path = if keep { default_audit_log_path() } else { None };
+    }
+    let Some(path) = &s.settings.audit_log_path else { return changed };
+    ui.label(egui::RichText::new(path.display().to_string()).size(12.0).weak().monospace());
+    ui.add_space(4.0);
+
+    let writes = audit::write_count();
+    let fresh = s.audit_log_cache.as_ref().is_some_and(|cache| cache.writes == writes && &cache.path == path);
+    if !fresh {
+        s.audit_log_cache = Some(AuditLogCache {
+            path: path.clone(),
+            writes,
+            entries: agent_host::read_audit_log(path, AUDIT_LOG_ROWS),
+        });
+    }
+    let entries = s.audit_log_cache.as_ref().map(|cache| cache.entries.as_slice()).unwrap_or_default();
+    if entries.is_empty() {
+        ui.label(egui::RichText::new("No commands logged yet").size(12.0).weak());
+    }
+    egui::ScrollArea::vertical().id_source("audit_log").max_height(200.0).show(ui, |ui| {
+        for entry in entries {
+            ui.horizontal(|ui| {
+                ui.label(
+                    egui::RichText::new(entry.timestamp.with_timezone(&chrono::Local).format("%b %d %H:%M").to_string())
+                        .size(12.0)
+                        .weak(),
+                );
+                let mut command = egui::RichText::new(&entry.command).size(12.0).monospace();
+                if entry.exit_code != 0 {
+                    command = command.color(egui::Color32::from_rgb(200, 60, 60));
+                }
+                ui.label(command).on_hover_text(format!(
+                    "{}, exit code {}, {} ms",
+                    entry.danger.key(),
+                    entry.exit_code,
+                    entry.duration_ms
+                ));
+            });
+        }
+    });
+    changed
+}
+
+/// Record a macro, or run a saved one
+fn macros_section(s: &mut AppState, ui: &mut egui::Ui) {
+    ui.label(
+        egui::RichText::new(format!("Saved in {}", s.macros.dir().display()))
+            .size(12.0)
+            .weak(),
+    );
+    ui.add_space(4.0);
+
+    if let Some(steps) = &s.recording {
+        ui.label(
+            egui::RichText::new(format!("● Recording: {} message{}", steps.len(), if steps.len() == 1 { "" } else { "s" }))
+                .color(egui::Color32::from_rgb(200, 60, 60)),
+        );
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut s.macro_name).hint_text("Macro name").desired_width(180.0));
+            if ui.button("Stop and save").clicked() {
+                let mut recorded = s.stop_recording();
+                recorded.name = s.macro_name.trim().to_string();
+                match s.macros.save(recorded.clone()) {
+                    Ok(_) => s.macro_status = None,
+                    Err(e) => {
+                        // Keep recording so nothing is lost
+                        s.recording = Some(recorded.steps);
+                        s.macro_status = Some(format!("Couldn't save macro: {}", e));
+                    }
+                }
+            }
+            if ui.button("Discard").clicked() {
+                s.stop_recording();
+                s.macro_status = None;
+            }
+        });
+    } else if ui.button("● Record a macro").on_hover_text("Messages you send from now on are recorded").clicked() {
+        s.start_recording();
+    }
+    if let Some(status) = &s.macro_status {
+        ui.colored_label(ui.visuals().error_fg_color, status);
+    }
+
+    ui.add_space(6.0);
+    let mut run = None;
+    for (i, m) in s.macros.macros.iter().enumerate() {
+        ui.horizontal(|ui| {
+            let steps: Vec<&str> = m.steps.iter().map(|step| step.input.as_str()).collect();
+            ui.label(&m.name).on_hover_text(steps.join("\n"));
+            ui.label(egui::RichText::new(format!("{} steps", m.steps.len())).size(12.0).weak());
+            if ui.add_enabled(!s.is_thinking && s.recording.is_none(), egui::Button::new("Run")).clicked() {
+                run = Some(i);
+            }
+        });
+    }
+    if s.macros.macros.is_empty() && s.recording.is_none() {
+        ui.label(egui::RichText::new("No macros yet").size(12.0).weak());
+    }
+    if let Some(i) = run {
+        let m = s.macros.macros[i].clone();
+        // The replies land in the chat as they come; nothing else needs them
+        s.replay_macro(&m);
+    }
+}
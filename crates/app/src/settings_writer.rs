@@ -0,0 +1,113 @@
+//! Background settings saves
+//!
+//! Settings change on the UI thread (sliders, toggles, the schedule), and
+//! writing them there makes frames stutter. [`save`] hands the latest
+//! settings to a background task, which waits until they have been quiet
+//! for [`DEBOUNCE`] and then writes them to a temp file and renames it over
+//! `settings.json`, so a crash mid-write never leaves a half written file.
+
+use anyhow::Result;
+use shared::settings::AppSettings;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How long settings must stay unchanged before they are written
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+struct SettingsWriter {
+    tx: watch::Sender<Option<AppSettings>>,
+    path: PathBuf,
+    /// Held while writing, so a flush on exit can't interleave with the task
+    write_lock: Arc<Mutex<()>>,
+}
+
+static WRITER: OnceLock<Option<SettingsWriter>> = OnceLock::new();
+
+fn writer() -> Option<&'static SettingsWriter> {
+    WRITER.get_or_init(|| crate::config_path().and_then(|path| spawn(path).ok())).as_ref()
+}
+
+/// Queue `settings` to be written. Never blocks on disk I/O.
+pub fn save(settings: &AppSettings) {
+    if let Some(writer) = writer() {
+        writer.tx.send_replace(Some(settings.clone()));
+    }
+}
+
+/// Write the latest queued settings now. Called on exit, when a debounce in
+/// flight would otherwise be lost.
+pub fn flush() {
+    let Some(writer) = WRITER.get().and_then(Option::as_ref) else { return };
+    let _guard = writer.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(settings) = writer.tx.borrow().clone() {
+        if let Err(e) = write_atomic(&writer.path, &settings) {
+            tracing::warn!("Failed to save settings: {}", e);
+        }
+    }
+}
+
+fn spawn(path: PathBuf) -> Result<SettingsWriter> {
+    let (tx, mut rx) = watch::channel::<Option<AppSettings>>(None);
+    let write_lock = Arc::new(Mutex::new(()));
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build()?;
+
+    let task_path = path.clone();
+    let task_lock = write_lock.clone();
+    std::thread::spawn(move || {
+        runtime.block_on(async move {
+            while rx.changed().await.is_ok() {
+                // Wait out a burst of changes (e.g. dragging a slider)
+                while let Ok(Ok(())) = tokio::time::timeout(DEBOUNCE, rx.changed()).await {}
+                let Some(settings) = rx.borrow_and_update().clone() else { continue };
+                let path = task_path.clone();
+                let lock = task_lock.clone();
+                let written = tokio::task::spawn_blocking(move || {
+                    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+                    write_atomic(&path, &settings)
+                })
+                .await;
+                if let Ok(Err(e)) = written {
+                    tracing::warn!("Failed to save settings: {}", e);
+                }
+            }
+        });
+    });
+    Ok(SettingsWriter { tx, path, write_lock })
+}
+
+/// Write to `<path>.tmp`, then rename it over `path`
+fn write_atomic(path: &Path, settings: &AppSettings) -> Result<()> {
+    let bytes = serde_json::to_vec_pretty(settings)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_last_settings_after_burst() {
+        let dir = std::env::temp_dir().join(format!("lh-settings-writer-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let writer = spawn(path.clone()).unwrap();
+
+        for name in ["a", "b", "c"] {
+            let mut settings = AppSettings::default();
+            settings.user_profile.name = name.to_string();
+            writer.tx.send_replace(Some(settings));
+        }
+        std::thread::sleep(DEBOUNCE * 5);
+
+        let saved: AppSettings = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved.user_profile.name, "c");
+        assert!(!path.with_extension("json.tmp").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,97 @@
+//! Slash commands typed into the chat input
+//!
+//! Input starting with `/` is handled by the app instead of being sent to
+//! the AI as a message.
+
+use crate::ChatMode;
+
+/// Commands and what they do, for `/help`
+pub const SLASH_COMMANDS: &[(&str, &str)] = &[
+    ("/summarize", "Summarize this conversation in 3 bullet points"),
+    ("/clear", "Clear the chat and start a new conversation"),
+    ("/mode find|fix|research|data|content", "Switch mode"),
+    ("/note <text>", "Add an instruction the AI follows for the rest of this conversation"),
+    ("/batch", "Ask each following line as its own question, one after another"),
+    ("/help", "List these commands"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlashCommand {
+    Summarize,
+    Clear,
+    Mode(ChatMode),
+    Help,
+    /// Questions to ask in turn, one per line after `/batch`
+    Batch(Vec<String>),
+    /// Standing instruction for the rest of the conversation
+    Note(String),
+}
+
+/// Parse input starting with `/`. The error is shown under the input.
+pub fn parse(input: &str) -> Result<SlashCommand, String> {
+    let input = input.trim().trim_start_matches('/');
+    let mut words = input.split_whitespace();
+    let name = words.next().unwrap_or_default().to_lowercase();
+    if name == "batch" {
+        let queries: Vec<String> = input["batch".len()..]
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        if queries.is_empty() {
+            return Err("Put each question for /batch on its own line".to_string());
+        }
+        return Ok(SlashCommand::Batch(queries));
+    }
+    if name == "note" {
+        let note = input["note".len()..].trim();
+        if note.is_empty() {
+            return Err("What should the note say? e.g. /note Always answer in bullet points".to_string());
+        }
+        return Ok(SlashCommand::Note(note.to_string()));
+    }
+    let arg = words.next();
+    match (name.as_str(), arg) {
+        ("summarize", None) => Ok(SlashCommand::Summarize),
+        ("clear", None) => Ok(SlashCommand::Clear),
+        ("help", None) => Ok(SlashCommand::Help),
+        ("mode", Some(mode)) => ChatMode::from_key(&mode.to_lowercase())
+            .map(SlashCommand::Mode)
+            .ok_or_else(|| format!("Unknown mode \"{}\". Try find, fix, research, data or content.", mode)),
+        ("mode", None) => Err("Which mode? Try /mode find, fix, research, data or content.".to_string()),
+        ("summarize" | "clear" | "help", Some(_)) => Err(format!("/{} doesn't take any arguments", name)),
+        _ => Err(format!("Unknown command \"/{}\". Type /help to see the commands.", name)),
+    }
+}
+
+/// The `/help` reply
+pub fn help_text() -> String {
+    let lines: Vec<String> = SLASH_COMMANDS
+        .iter()
+        .map(|(command, description)| format!("- `{}`: {}", command, description))
+        .collect();
+    format!("Commands you can type:\n\n{}", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slash_commands() {
+        assert_eq!(parse("/summarize"), Ok(SlashCommand::Summarize));
+        assert_eq!(parse(" /Mode Research "), Ok(SlashCommand::Mode(ChatMode::Research)));
+        assert!(parse("/mode").is_err());
+        assert!(parse("/mode cooking").is_err());
+        assert!(parse("/clear everything").is_err());
+        assert!(parse("/frobnicate").unwrap_err().contains("/help"));
+        assert_eq!(
+            parse("/batch What is Rust?\n\n  Who wrote it? \n"),
+            Ok(SlashCommand::Batch(vec!["What is Rust?".to_string(), "Who wrote it?".to_string()]))
+        );
+        assert!(parse("/batch\n  \n").is_err());
+        assert_eq!(parse("/note  Use bullet points "), Ok(SlashCommand::Note("Use bullet points".to_string())));
+        assert!(parse("/note").is_err());
+    }
+}
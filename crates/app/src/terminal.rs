@@ -0,0 +1,354 @@
+//! Inline terminal panel for interactive commands
+//!
+//! Runs a command (`python3`, `psql`, ...) in a pseudo-terminal and renders
+//! its output as a grid of character cells. Output is parsed with a small
+//! VT100 subset: cursor movement, erase, carriage return / line feed and
+//! backspace. Colors and other attributes are ignored. The command is
+//! added to the audit log once it exits or its tab is closed.
+
+use agent_host::audit::{self, AuditEntry};
+use agent_host::DangerLevel;
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use vte::{Params, Parser, Perform};
+
+const ROWS: usize = 24;
+const COLS: usize = 80;
+/// Lines kept above the visible screen
+const MAX_SCROLLBACK: usize = 1000;
+
+/// Character grid plus cursor, updated from the PTY output
+struct Screen {
+    cells: Vec<Vec<char>>,
+    scrollback: Vec<String>,
+    row: usize,
+    col: usize,
+    exited: bool,
+}
+
+impl Screen {
+    fn new() -> Self {
+        Self {
+            cells: vec![vec![' '; COLS]; ROWS],
+            scrollback: Vec::new(),
+            row: 0,
+            col: 0,
+            exited: false,
+        }
+    }
+
+    fn line_feed(&mut self) {
+        if self.row + 1 < ROWS {
+            self.row += 1;
+            return;
+        }
+        // Scroll the top line into the scrollback
+        let top = self.cells.remove(0);
+        self.scrollback.push(top.iter().collect::<String>().trim_end().to_string());
+        if self.scrollback.len() > MAX_SCROLLBACK {
+            self.scrollback.remove(0);
+        }
+        self.cells.push(vec![' '; COLS]);
+    }
+
+    fn erase_line(&mut self, mode: u16) {
+        let line = &mut self.cells[self.row];
+        let range = match mode {
+            1 => 0..(self.col + 1).min(COLS),
+            2 => 0..COLS,
+            _ => self.col.min(COLS)..COLS,
+        };
+        for cell in &mut line[range] {
+            *cell = ' ';
+        }
+    }
+
+    fn erase_display(&mut self, mode: u16) {
+        match mode {
+            1 => {
+                for row in 0..self.row {
+                    self.cells[row].fill(' ');
+                }
+                self.erase_line(1);
+            }
+            2 | 3 => {
+                for row in &mut self.cells {
+                    row.fill(' ');
+                }
+            }
+            _ => {
+                self.erase_line(0);
+                for row in (self.row + 1)..ROWS {
+                    self.cells[row].fill(' ');
+                }
+            }
+        }
+    }
+}
+
+impl Perform for Screen {
+    fn print(&mut self, c: char) {
+        if self.col >= COLS {
+            // Wrap to the next line
+            self.col = 0;
+            self.line_feed();
+        }
+        self.cells[self.row][self.col] = c;
+        self.col += 1;
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.line_feed(),
+            b'\r' => self.col = 0,
+            0x08 => self.col = self.col.saturating_sub(1),
+            b'\t' => self.col = ((self.col / 8) + 1) * 8,
+            _ => {}
+        }
+        self.col = self.col.min(COLS);
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, _intermediates: &[u8], _ignore: bool, action: char) {
+        let mut values = params.iter().map(|p| p.first().copied().unwrap_or(0));
+        let first = values.next().unwrap_or(0);
+        let count = first.max(1) as usize;
+
+        match action {
+            'A' => self.row = self.row.saturating_sub(count),
+            'B' => self.row = (self.row + count).min(ROWS - 1),
+            'C' => self.col = (self.col + count).min(COLS - 1),
+            'D' => self.col = self.col.saturating_sub(count),
+            'G' => self.col = (count - 1).min(COLS - 1),
+            'H' | 'f' => {
+                let col = values.next().unwrap_or(0).max(1) as usize;
+                self.row = (count - 1).min(ROWS - 1);
+                self.col = (col - 1).min(COLS - 1);
+            }
+            'J' => self.erase_display(first),
+            'K' => self.erase_line(first),
+            _ => {} // Colors (m), modes (h/l) etc. are ignored
+        }
+    }
+}
+
+/// A command running in a pseudo-terminal
+pub struct TerminalPanel {
+    command: String,
+    screen: Arc<Mutex<Screen>>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    // Dropping the master closes the terminal
+    _master: Box<dyn MasterPty + Send>,
+    input: String,
+    danger: DangerLevel,
+    started: Instant,
+    /// Where the command is recorded; taken once it has been
+    audit_log: Option<PathBuf>,
+}
+
+impl TerminalPanel {
+    /// Spawn `command` through the shell in a new PTY, to be recorded in
+    /// `audit_log` if given
+    pub fn spawn(
+        command: &str,
+        danger: DangerLevel,
+        audit_log: Option<PathBuf>,
+        ctx: &egui::Context,
+    ) -> Result<Self> {
+        let pty_system = native_pty_system();
+        let pair = pty_system
+            .openpty(PtySize {
+                rows: ROWS as u16,
+                cols: COLS as u16,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| anyhow!("Failed to open terminal: {}", e))?;
+
+        let mut cmd = if cfg!(windows) {
+            let mut cmd = CommandBuilder::new("cmd");
+            cmd.args(["/C", command]);
+            cmd
+        } else {
+            let mut cmd = CommandBuilder::new("sh");
+            cmd.args(["-c", command]);
+            cmd
+        };
+        if let Some(home) = dirs::home_dir() {
+            cmd.cwd(home);
+        }
+        cmd.env("TERM", "vt100");
+
+        let child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| anyhow!("Failed to start '{}': {}", command, e))?;
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().map_err(|e| anyhow!("{}", e))?;
+        let writer = pair.master.take_writer().map_err(|e| anyhow!("{}", e))?;
+
+        // Feed PTY output through the parser on a background thread
+        let screen = Arc::new(Mutex::new(Screen::new()));
+        let thread_screen = screen.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let mut parser = Parser::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        let mut screen = thread_screen.lock();
+                        for byte in &buf[..n] {
+                            parser.advance(&mut *screen, *byte);
+                        }
+                        drop(screen);
+                        ctx.request_repaint();
+                    }
+                }
+            }
+            thread_screen.lock().exited = true;
+            ctx.request_repaint();
+        });
+
+        Ok(Self {
+            command: command.to_string(),
+            screen,
+            writer,
+            child,
+            _master: pair.master,
+            input: String::new(),
+            danger,
+            started: Instant::now(),
+            audit_log,
+        })
+    }
+
+    /// Add the command to the audit log, with its exit code if it has
+    /// exited (-1 if it's being stopped)
+    fn record(&mut self) {
+        let Some(path) = self.audit_log.take() else { return };
+        let exit_code = match self.child.try_wait() {
+            Ok(Some(status)) => status.exit_code() as i32,
+            _ => -1,
+        };
+        audit::append(
+            &path,
+            &AuditEntry {
+                timestamp: chrono::Utc::now(),
+                danger: self.danger,
+                exit_code,
+                duration_ms: self.started.elapsed().as_millis() as u64,
+                command: self.command.clone(),
+            },
+        );
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    fn send(&mut self, bytes: &[u8]) {
+        let _ = self.writer.write_all(bytes);
+        let _ = self.writer.flush();
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let exited = self.screen.lock().exited;
+        if exited {
+            self.record();
+        }
+
+        // Input line at the bottom
+        egui::TopBottomPanel::bottom("terminal_input")
+            .frame(egui::Frame::none())
+            .show_inside(ui, |ui| {
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    let response = ui.add_enabled(
+                        !exited,
+                        egui::TextEdit::singleline(&mut self.input)
+                            .hint_text("Type input and press Enter")
+                            .font(egui::TextStyle::Monospace)
+                            .desired_width(ui.available_width() - 120.0),
+                    );
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        let line = std::mem::take(&mut self.input);
+                        self.send(format!("{}\r", line).as_bytes());
+                        response.request_focus();
+                    }
+                    if ui.add_enabled(!exited, egui::Button::new("Ctrl+C").small()).clicked() {
+                        self.send(&[0x03]);
+                    }
+                    if ui.add_enabled(!exited, egui::Button::new("Ctrl+D").small()).clicked() {
+                        self.send(&[0x04]);
+                    }
+                });
+            });
+
+        // Character grid, with the scrollback above it
+        let screen = self.screen.lock();
+        egui::ScrollArea::both()
+            .stick_to_bottom(true)
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                let font = egui::TextStyle::Monospace;
+                for line in &screen.scrollback {
+                    ui.label(egui::RichText::new(line).text_style(font.clone()));
+                }
+                let last_row = screen
+                    .cells
+                    .iter()
+                    .rposition(|row| row.iter().any(|c| *c != ' '))
+                    .unwrap_or(0)
+                    .max(screen.row);
+                for (r, row) in screen.cells.iter().enumerate().take(last_row + 1) {
+                    let mut text: String = row.iter().collect();
+                    if r == screen.row && !exited {
+                        // Block cursor
+                        let col = screen.col.min(COLS - 1);
+                        text = row
+                            .iter()
+                            .enumerate()
+                            .map(|(c, ch)| if c == col { '█' } else { *ch })
+                            .collect();
+                    }
+                    ui.label(egui::RichText::new(text.trim_end()).text_style(font.clone()));
+                }
+                if exited {
+                    ui.label(egui::RichText::new("[Process exited]").weak());
+                }
+            });
+    }
+}
+
+impl viewers::Viewer for TerminalPanel {
+    fn load(&mut self, _path: &Path) -> Result<()> {
+        Err(anyhow!("The terminal runs a command, it doesn't open files"))
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        TerminalPanel::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    fn is_loaded(&self) -> bool {
+        true
+    }
+}
+
+impl Drop for TerminalPanel {
+    fn drop(&mut self) {
+        self.record();
+        let _ = self.child.kill();
+    }
+}
@@ -0,0 +1,109 @@
+//! Chat message timestamps
+//!
+//! Messages store when they were sent as an RFC 3339 UTC string and are
+//! shown in local time: "3:42 PM" today, "Yesterday at 3:42 PM", then
+//! "Dec 30 at 15:42"; hovering shows the exact time and how long ago it
+//! was. Conversations saved before this stored only "HH:MM",
+//! which is shown as it is.
+
+use chrono::{DateTime, Local, Utc};
+use shared::settings::TimeFormat;
+
+/// Locales that write times on a 12-hour clock
+const TWELVE_HOUR_LOCALES: &[&str] = &["en_US", "en_CA", "en_AU", "en_NZ", "en_PH", "en_IN"];
+
+/// Timestamp for a new message
+pub fn now() -> String {
+    Utc::now().to_rfc3339()
+}
+
+pub fn parse(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp).ok().map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Local time of a message, with the date when it isn't from today
+pub fn format(timestamp: &str, time_format: TimeFormat) -> String {
+    match parse(timestamp) {
+        Some(sent) => format_at(sent.with_timezone(&Local), Local::now(), twelve_hour(time_format)),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Tooltip for a message time: the exact UTC time in ISO 8601 and how long
+/// ago that was. `None` for old "HH:MM" timestamps.
+pub fn hover_text(timestamp: &str) -> Option<String> {
+    let sent = parse(timestamp)?;
+    Some(format!("{}\n{}", sent.format("%Y-%m-%dT%H:%M:%SZ"), format_relative(sent)))
+}
+
+/// "just now", "5 minutes ago", "3 hours ago", "2 days ago", then the date
+pub fn format_relative(dt: DateTime<Utc>) -> String {
+    relative_to(dt, Utc::now())
+}
+
+fn twelve_hour(time_format: TimeFormat) -> bool {
+    match time_format {
+        TimeFormat::TwelveHour => true,
+        TimeFormat::TwentyFourHour => false,
+        TimeFormat::SystemLocale => ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+            .is_some_and(|locale| TWELVE_HOUR_LOCALES.iter().any(|l| locale.starts_with(l))),
+    }
+}
+
+fn format_at(sent: DateTime<Local>, now: DateTime<Local>, twelve_hour: bool) -> String {
+    let time = if twelve_hour {
+        sent.format("%-I:%M %p").to_string()
+    } else {
+        sent.format("%H:%M").to_string()
+    };
+    let days = now.date_naive().signed_duration_since(sent.date_naive()).num_days();
+    match days {
+        0 => time,
+        1 => format!("Yesterday at {}", time),
+        _ if sent.format("%Y").to_string() == now.format("%Y").to_string() => {
+            format!("{} at {}", sent.format("%b %-d"), time)
+        }
+        _ => format!("{} at {}", sent.format("%b %-d, %Y"), time),
+    }
+}
+
+fn relative_to(sent: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now.signed_duration_since(sent);
+    let (count, unit) = if elapsed.num_minutes() < 1 {
+        return "just now".to_string();
+    } else if elapsed.num_hours() < 1 {
+        (elapsed.num_minutes(), "minute")
+    } else if elapsed.num_days() < 1 {
+        (elapsed.num_hours(), "hour")
+    } else if elapsed.num_days() < 7 {
+        (elapsed.num_days(), "day")
+    } else {
+        return sent.with_timezone(&Local).format("%b %-d, %Y").to_string();
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_format_and_relative() {
+        let now = Local.with_ymd_and_hms(2024, 12, 31, 18, 0, 0).unwrap();
+        let at = |d, h, m| Local.with_ymd_and_hms(2024, 12, d, h, m, 0).unwrap();
+        assert_eq!(format_at(at(31, 15, 42), now, true), "3:42 PM");
+        assert_eq!(format_at(at(31, 15, 42), now, false), "15:42");
+        assert_eq!(format_at(at(30, 15, 42), now, true), "Yesterday at 3:42 PM");
+        assert_eq!(format_at(at(28, 15, 42), now, false), "Dec 28 at 15:42");
+
+        let now = now.with_timezone(&Utc);
+        assert_eq!(relative_to(now - chrono::Duration::hours(2), now), "2 hours ago");
+        assert_eq!(relative_to(now - chrono::Duration::minutes(1), now), "1 minute ago");
+        assert_eq!(relative_to(now - chrono::Duration::days(6), now), "6 days ago");
+        assert_eq!(relative_to(at(20, 9, 0).with_timezone(&Utc), now), "Dec 20, 2024");
+        assert_eq!(format("12:00", TimeFormat::TwelveHour), "12:00");
+    }
+}
@@ -0,0 +1,158 @@
+//! Text-to-speech through the system's speech tools
+//!
+//! `say` on macOS, `espeak` on Linux and SAPI5 through PowerShell on
+//! Windows. Only one message is spoken at a time: starting a new one stops
+//! the previous.
+
+use parking_lot::Mutex;
+use regex::Regex;
+use std::process::{Child, Command, Stdio};
+
+/// The speech process currently running, if any
+static SPEAKING: Mutex<Option<Child>> = parking_lot::const_mutex(None);
+
+/// Speak `text` (Markdown is stripped first) in the background.
+/// `voice` is a name from [`list_voices`]; `None` uses the system default.
+pub fn speak(text: &str, voice: Option<&str>) {
+    let text = strip_markdown(text);
+    if text.trim().is_empty() {
+        return;
+    }
+    let voice = voice.map(str::to_string);
+
+    std::thread::spawn(move || {
+        stop();
+        match speech_command(&text, voice.as_deref())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => *SPEAKING.lock() = Some(child),
+            Err(e) => tracing::warn!("Text-to-speech failed: {}", e),
+        }
+    });
+}
+
+/// Stop any speech in progress
+pub fn stop() {
+    if let Some(mut child) = SPEAKING.lock().take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+fn speech_command(text: &str, voice: Option<&str>) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("say");
+        if let Some(voice) = voice {
+            cmd.args(["-v", voice]);
+        }
+        cmd.arg(text);
+        cmd
+    } else if cfg!(windows) {
+        // PowerShell single-quoted strings escape ' as ''
+        let quote = |s: &str| s.replace('\'', "''");
+        let select = voice
+            .map(|v| format!("$s.SelectVoice('{}'); ", quote(v)))
+            .unwrap_or_default();
+        let script = format!(
+            "Add-Type -AssemblyName System.Speech; \
+            $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; {}$s.Speak('{}')",
+            select,
+            quote(text)
+        );
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-Command", &script]);
+        cmd
+    } else {
+        let mut cmd = Command::new("espeak");
+        if let Some(voice) = voice {
+            cmd.args(["-v", voice]);
+        }
+        // "--" so text starting with '-' isn't read as an option
+        cmd.args(["--", text]);
+        cmd
+    }
+}
+
+/// Voices the system speech tool reports. Empty if it isn't installed.
+pub fn list_voices() -> Vec<String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("say").args(["-v", "?"]).output()
+    } else if cfg!(windows) {
+        Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                "Add-Type -AssemblyName System.Speech; \
+                (New-Object System.Speech.Synthesis.SpeechSynthesizer).GetInstalledVoices() | \
+                ForEach-Object { $_.VoiceInfo.Name }",
+            ])
+            .output()
+    } else {
+        Command::new("espeak").arg("--voices").output()
+    };
+    let Ok(output) = output else { return Vec::new() };
+    parse_voices(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_voices(output: &str) -> Vec<String> {
+    let mut voices: Vec<String> = if cfg!(target_os = "macos") {
+        // "Alex                en_US    # Most people recognize me by my voice."
+        let columns = Regex::new(r"\s{2,}").unwrap();
+        output
+            .lines()
+            .filter_map(|l| columns.split(l.trim()).next())
+            .map(str::to_string)
+            .collect()
+    } else if cfg!(windows) {
+        output.lines().map(|l| l.trim().to_string()).collect()
+    } else {
+        // " 5  en-gb          M  english              en            (en 2)"
+        output
+            .lines()
+            .skip(1)
+            .filter_map(|l| l.split_whitespace().nth(3))
+            .map(str::to_string)
+            .collect()
+    };
+    voices.retain(|v| !v.is_empty());
+    voices.dedup();
+    voices
+}
+
+/// Plain text for speaking: drops code blocks, heading marks, bullets,
+/// emphasis and link targets
+pub fn strip_markdown(text: &str) -> String {
+    let code_block = Regex::new(r"(?s)```.*?```").unwrap();
+    let heading = Regex::new(r"(?m)^\s*#{1,6}\s+").unwrap();
+    let bullet = Regex::new(r"(?m)^\s*(?:[-*+]|\d+\.)\s+").unwrap();
+    let rule = Regex::new(r"(?m)^\s*(?:-{3,}|\*{3,})\s*$").unwrap();
+    let link = Regex::new(r"!?\[([^\]]*)\]\([^)]*\)").unwrap();
+    let emphasis = Regex::new(r"(\*\*|__|~~|\*|`)").unwrap();
+
+    let text = code_block.replace_all(text, " ");
+    let text = heading.replace_all(&text, "");
+    let text = bullet.replace_all(&text, "");
+    let text = rule.replace_all(&text, "");
+    let text = link.replace_all(&text, "$1");
+    let text = emphasis.replace_all(&text, "");
+
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_markdown() {
+        let text = "## Results\n\nFound **3** files:\n- `a.txt`\n1. [docs](https://x)\n\n```bash\nls\n```\nDone";
+        assert_eq!(strip_markdown(text), "Results\nFound 3 files:\na.txt\ndocs\nDone");
+    }
+}
@@ -2,20 +2,66 @@ use anyhow::{anyhow, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use shared::agent_api::ChatMessage;
+use crate::error::{check_status, ProviderError};
+use crate::sse::{self, Chunk};
 use shared::settings::ProviderAuth;
 use std::env;
+use tokio::sync::mpsc;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AnthropicRequest {
     model: String,
     max_tokens: i32,
+    /// System prompt; the API doesn't take system messages in `messages`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
     messages: Vec<AnthropicMessage>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AnthropicMessage {
     role: String,
-    content: String,
+    content: Vec<AnthropicBlock>,
+}
+
+/// Content block in a request message
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicBlock {
+    Text { text: String },
+    Image { source: AnthropicImageSource },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AnthropicImageSource {
+    #[serde(rename = "type")]
+    source_type: String, // always "base64"
+    media_type: String,
+    data: String,
+}
+
+impl From<ChatMessage> for AnthropicMessage {
+    fn from(m: ChatMessage) -> Self {
+        // Images go before the text, as Anthropic recommends
+        let mut content: Vec<AnthropicBlock> = m
+            .attachments
+            .iter()
+            .map(|attachment| AnthropicBlock::Image {
+                source: AnthropicImageSource {
+                    source_type: "base64".to_string(),
+                    media_type: attachment.media_type().to_string(),
+                    data: attachment.to_base64(),
+                },
+            })
+            .collect();
+        // The API rejects empty text blocks, e.g. with only an image
+        if !m.content.is_empty() {
+            content.push(AnthropicBlock::Text { text: m.content });
+        }
+        Self { role: m.role, content }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,6 +76,31 @@ struct AnthropicResponse {
     content: Vec<AnthropicContent>,
 }
 
+/// Data of a `content_block_delta` event
+#[derive(Debug, Deserialize)]
+struct AnthropicDeltaEvent {
+    delta: AnthropicDelta,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicDelta {
+    TextDelta { text: String },
+    #[serde(other)]
+    Other,
+}
+
+/// Data of an `error` event
+#[derive(Debug, Deserialize)]
+struct AnthropicErrorEvent {
+    error: AnthropicErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicErrorDetail {
+    message: String,
+}
+
 pub struct AnthropicClient {
     http: Client,
     auth_token: String,
@@ -60,41 +131,118 @@ impl AnthropicClient {
     }
 
     pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
-        let url = "https://api.anthropic.com/v1/messages";
+        let req = self.request(messages, None, false);
+        let resp = self.send(&req).await?;
+        let body: AnthropicResponse = resp.json().await?;
+        let text = body
+            .content
+            .first()
+            .map(|c| c.text.clone())
+            .unwrap_or_default();
+        Ok(text)
+    }
+
+    /// Like `generate`, sending the reply on `tx` as it arrives. `system`
+    /// goes before any system messages in `messages`.
+    pub async fn generate_streaming(
+        &self,
+        messages: Vec<ChatMessage>,
+        system: Option<&str>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<()> {
+        let req = self.request(messages, system, true);
+        let resp = self.send(&req).await?;
+        sse::forward("anthropic", resp, tx, |event| match event.event.as_deref()? {
+            "content_block_delta" => match serde_json::from_str::<AnthropicDeltaEvent>(&event.data).ok()?.delta {
+                AnthropicDelta::TextDelta { text } => Some(Chunk::Text(text)),
+                AnthropicDelta::Other => None,
+            },
+            "message_stop" => Some(Chunk::Done),
+            "error" => Some(Chunk::Error(
+                serde_json::from_str::<AnthropicErrorEvent>(&event.data)
+                    .map(|e| e.error.message)
+                    .unwrap_or_else(|_| event.data.clone()),
+            )),
+            _ => None,
+        })
+        .await
+    }
 
-        // Anthropic doesn't support system messages in the same array, so filter them out
-        // and handle system prompt separately if needed
-        let anthropic_messages: Vec<AnthropicMessage> = messages
+    /// System messages are taken out of `messages` and joined into the
+    /// request's `system` field, after `system` if given
+    fn request(&self, messages: Vec<ChatMessage>, system: Option<&str>, stream: bool) -> AnthropicRequest {
+        let (system_messages, messages): (Vec<ChatMessage>, Vec<ChatMessage>) =
+            messages.into_iter().partition(|m| m.role == "system");
+        let system: Vec<String> = system
+            .map(str::to_string)
             .into_iter()
-            .filter(|m| m.role != "system")
-            .map(|m| AnthropicMessage { role: m.role, content: m.content })
+            .chain(system_messages.into_iter().map(|m| m.content))
+            .filter(|s| !s.trim().is_empty())
             .collect();
-
-        let req = AnthropicRequest {
+        AnthropicRequest {
             model: self.model.clone(),
             max_tokens: 4096,
-            messages: anthropic_messages,
-        };
+            system: (!system.is_empty()).then(|| system.join("\n\n")),
+            messages: messages.into_iter().map(Into::into).collect(),
+            stream,
+        }
+    }
 
+    async fn send(&self, req: &AnthropicRequest) -> Result<reqwest::Response> {
         let resp = self.http
-            .post(url)
+            .post("https://api.anthropic.com/v1/messages")
             .header("x-api-key", &self.auth_token)
             .header("anthropic-version", "2023-06-01")
             .header("Content-Type", "application/json")
-            .json(&req)
+            .json(req)
             .send()
-            .await?;
+            .await
+            .map_err(ProviderError::network)?;
+        check_status(resp, "anthropic")
+    }
+}
 
-        if !resp.status().is_success() {
-            return Err(anyhow!("anthropic error: {}", resp.status()));
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let body: AnthropicResponse = resp.json().await?;
-        let text = body
-            .content
-            .get(0)
-            .map(|c| c.text.clone())
-            .unwrap_or_default();
-        Ok(text)
+    #[test]
+    fn test_system_messages_go_in_system_field() {
+        let client = AnthropicClient { http: Client::new(), auth_token: String::new(), model: "claude".to_string() };
+        let message = |role: &str, content: &str| ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            attachments: Vec::new(),
+        };
+        let req = client.request(
+            vec![message("system", "Be brief."), message("user", "hi")],
+            Some("You are helpful."),
+            true,
+        );
+        assert_eq!(req.system.as_deref(), Some("You are helpful.\n\nBe brief."));
+        assert_eq!(req.messages.len(), 1);
+        assert_eq!(serde_json::to_value(&req).unwrap()["stream"], true);
+
+        // `generate` sends the same request without `stream`
+        let req = client.request(vec![message("system", "Be brief."), message("user", "hi")], None, false);
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["system"], "Be brief.");
+        assert_eq!(json["messages"][0]["role"], "user");
+        assert!(json.get("stream").is_none());
+        assert!(client.request(vec![message("user", "hi")], None, false).system.is_none());
+    }
+
+    #[test]
+    fn test_image_only_message_has_no_text_block() {
+        let message = |content: &str| ChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+            attachments: vec![shared::agent_api::MessageAttachment::Image(b"\x89PNG".to_vec())],
+        };
+        let json = serde_json::to_value(AnthropicMessage::from(message(""))).unwrap();
+        assert_eq!(json["content"].as_array().unwrap().len(), 1);
+        assert_eq!(json["content"][0]["type"], "image");
+        let json = serde_json::to_value(AnthropicMessage::from(message("What's this?"))).unwrap();
+        assert_eq!(json["content"][1]["text"], "What's this?");
     }
 }
@@ -0,0 +1,226 @@
+//! Comparing local Ollama models on speed and a small quality check
+//!
+//! Each model answers the test prompts and three factual questions,
+//! streamed so the time to the first token can be measured. Speed comes
+//! from Ollama's own token counts; quality is the share of the factual
+//! questions whose answer contains an expected word.
+
+use crate::error::{check_status, ProviderError};
+use crate::ollama;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Longest one answer may take, including loading the model
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Questions with one right answer, and the words that count as it
+const RUBRIC: [(&str, &[&str]); 3] = [
+    ("What is the capital of France? Answer in one word.", &["paris"]),
+    ("How many legs does a spider have? Answer with a number.", &["8", "eight"]),
+    ("What is the chemical symbol for gold?", &["au"]),
+];
+
+/// Prompts for the speed part, like the requests Little Helper sends
+pub fn default_prompts() -> Vec<String> {
+    vec![
+        "Explain in two sentences what the command `ls -la` shows.".to_string(),
+        "Write a short, friendly email declining a meeting invitation.".to_string(),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkResult {
+    pub model: String,
+    pub avg_tokens_per_sec: f64,
+    pub avg_first_token_ms: f64,
+    /// 0.0 to 1.0
+    pub quality_score_avg: f64,
+    /// Why the model couldn't be benchmarked; the numbers are zero then
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct GenerateRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    stream: bool,
+}
+
+/// One line of a streamed `/api/generate` response
+#[derive(Debug, Deserialize)]
+struct GenerateChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+    /// Tokens generated, on the last line
+    eval_count: Option<u64>,
+    /// Nanoseconds spent generating them
+    eval_duration: Option<u64>,
+}
+
+/// Timing of one answer
+struct Sample {
+    text: String,
+    first_token_ms: f64,
+    tokens_per_sec: f64,
+}
+
+pub struct ModelBenchmark;
+
+impl ModelBenchmark {
+    /// Benchmark each model in turn; one at a time so they don't compete
+    /// for the machine
+    pub fn run(models: &[String], test_prompts: Vec<String>) -> BoxFuture<'static, Vec<BenchmarkResult>> {
+        let models = models.to_vec();
+        Box::pin(async move {
+            let http = Client::builder().timeout(PROMPT_TIMEOUT).build().unwrap_or_default();
+            let mut results = Vec::new();
+            for model in models {
+                let result = match benchmark_model(&http, &model, &test_prompts).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        tracing::warn!(model = %model, "benchmark failed: {}", e);
+                        BenchmarkResult {
+                            model,
+                            avg_tokens_per_sec: 0.0,
+                            avg_first_token_ms: 0.0,
+                            quality_score_avg: 0.0,
+                            error: Some(e.to_string()),
+                        }
+                    }
+                };
+                results.push(result);
+            }
+            results
+        })
+    }
+}
+
+async fn benchmark_model(http: &Client, model: &str, test_prompts: &[String]) -> Result<BenchmarkResult> {
+    // The first request loads the model, which would skew its timings
+    generate(http, model, "Say hi.").await?;
+
+    let mut samples = Vec::new();
+    for prompt in test_prompts {
+        samples.push(generate(http, model, prompt).await?);
+    }
+    let mut correct = 0;
+    for (question, answers) in RUBRIC {
+        let sample = generate(http, model, question).await?;
+        if is_correct(&sample.text, answers) {
+            correct += 1;
+        }
+        samples.push(sample);
+    }
+
+    let average = |value: fn(&Sample) -> f64| samples.iter().map(value).sum::<f64>() / samples.len() as f64;
+    Ok(BenchmarkResult {
+        model: model.to_string(),
+        avg_tokens_per_sec: average(|s| s.tokens_per_sec),
+        avg_first_token_ms: average(|s| s.first_token_ms),
+        quality_score_avg: correct as f64 / RUBRIC.len() as f64,
+        error: None,
+    })
+}
+
+async fn generate(http: &Client, model: &str, prompt: &str) -> Result<Sample> {
+    let started = Instant::now();
+    let resp = http
+        .post(format!("{}/api/generate", ollama::base_url()))
+        .json(&GenerateRequest { model, prompt, stream: true })
+        .send()
+        .await
+        .map_err(ProviderError::network)?;
+    let mut stream = check_status(resp, "ollama")?.bytes_stream();
+
+    let mut pending = Vec::new();
+    let mut text = String::new();
+    let mut first_token = None;
+    let mut chunks = 0u64;
+    let mut reported = None;
+    while let Some(bytes) = stream.next().await {
+        pending.extend_from_slice(&bytes.map_err(ProviderError::network)?);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let Ok(chunk) = serde_json::from_slice::<GenerateChunk>(&line) else { continue };
+            if !chunk.response.is_empty() {
+                first_token.get_or_insert_with(|| started.elapsed());
+                chunks += 1;
+                text.push_str(&chunk.response);
+            }
+            if chunk.done {
+                reported = chunk.eval_count.zip(chunk.eval_duration);
+            }
+        }
+    }
+
+    let first_token = first_token.unwrap_or_else(|| started.elapsed());
+    // Ollama reports its own counts at the end; older versions may not
+    let tokens_per_sec = match reported {
+        Some((count, nanos)) if nanos > 0 => count as f64 / (nanos as f64 / 1e9),
+        _ => chunks as f64 / (started.elapsed() - first_token).as_secs_f64().max(0.001),
+    };
+    Ok(Sample { text, first_token_ms: first_token.as_secs_f64() * 1000.0, tokens_per_sec })
+}
+
+/// Whether any word of `text` is one of `answers`
+fn is_correct(text: &str, answers: &[&str]) -> bool {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| answers.contains(&word))
+}
+
+/// How much quality counts against speed in a chat mode. Find and Fix
+/// are quick back-and-forth; the other modes write longer answers.
+fn quality_weight(mode: &str) -> f64 {
+    match mode {
+        "find" | "fix" => 0.4,
+        _ => 0.7,
+    }
+}
+
+/// The model with the best mix of speed and quality for `mode`, among
+/// those that finished the benchmark
+pub fn recommend<'a>(results: &'a [BenchmarkResult], mode: &str) -> Option<&'a BenchmarkResult> {
+    let finished: Vec<&BenchmarkResult> = results.iter().filter(|r| r.error.is_none()).collect();
+    let fastest = finished.iter().map(|r| r.avg_tokens_per_sec).fold(0.0, f64::max);
+    let weight = quality_weight(mode);
+    let score = |r: &BenchmarkResult| {
+        let speed = if fastest > 0.0 { r.avg_tokens_per_sec / fastest } else { 0.0 };
+        weight * r.quality_score_avg + (1.0 - weight) * speed
+    };
+    finished.into_iter().max_by(|a, b| score(a).total_cmp(&score(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rubric_and_recommendation() {
+        assert!(is_correct("The capital is Paris.", &["paris"]));
+        assert!(is_correct("Au", &["au"]));
+        assert!(!is_correct("Because it is shiny", &["au"]));
+
+        let result = |model: &str, speed: f64, quality: f64| BenchmarkResult {
+            model: model.to_string(),
+            avg_tokens_per_sec: speed,
+            avg_first_token_ms: 100.0,
+            quality_score_avg: quality,
+            error: None,
+        };
+        let results = vec![
+            result("fast", 60.0, 1.0 / 3.0),
+            result("smart", 20.0, 1.0),
+            BenchmarkResult { error: Some("out of memory".to_string()), ..result("broken", 0.0, 0.0) },
+        ];
+        assert_eq!(recommend(&results, "find").unwrap().model, "fast");
+        assert_eq!(recommend(&results, "research").unwrap().model, "smart");
+        assert!(recommend(&results[2..], "find").is_none());
+    }
+}
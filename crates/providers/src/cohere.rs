@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use shared::agent_api::ChatMessage;
+use crate::error::{check_status, ProviderError};
+use shared::settings::ProviderAuth;
+use std::env;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereRequest {
+    model: String,
+    messages: Vec<CohereMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereMessage {
+    role: String,
+    content: String,
+}
+
+impl From<ChatMessage> for CohereMessage {
+    fn from(m: ChatMessage) -> Self {
+        // The v2 API takes "system", "user" and "assistant"; images aren't sent
+        let role = match m.role.as_str() {
+            "system" => "system",
+            "assistant" => "assistant",
+            _ => "user",
+        };
+        Self { role: role.to_string(), content: m.content }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereContent {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereResponseMessage {
+    #[serde(default)]
+    content: Vec<CohereContent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereResponse {
+    message: CohereResponseMessage,
+}
+
+pub struct CohereClient {
+    http: Client,
+    auth_token: String,
+    model: String,
+}
+
+impl CohereClient {
+    pub fn new(model: &str) -> Result<Self> {
+        let key = env::var("COHERE_API_KEY").map_err(|_| anyhow!("COHERE_API_KEY not set"))?;
+        Ok(Self { http: Client::new(), auth_token: key, model: model.to_string() })
+    }
+
+    pub fn from_auth(model: &str, auth: &ProviderAuth) -> Result<Self> {
+        let auth_token = if let Some(api_key) = &auth.api_key {
+            api_key.clone()
+        } else if let Some(oauth) = &auth.oauth {
+            oauth.access_token.clone()
+        } else {
+            // Try environment variable as fallback
+            env::var("COHERE_API_KEY").map_err(|_| anyhow!("No Cohere authentication configured"))?
+        };
+
+        Ok(Self {
+            http: Client::new(),
+            auth_token,
+            model: model.to_string(),
+        })
+    }
+
+    pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
+        let url = "https://api.cohere.com/v2/chat";
+        let cohere_messages: Vec<CohereMessage> = messages.into_iter().map(Into::into).collect();
+        let req = CohereRequest { model: self.model.clone(), messages: cohere_messages };
+        let resp = self.http
+            .post(url)
+            .header("Authorization", format!("Bearer {}", self.auth_token))
+            .header("Content-Type", "application/json")
+            .json(&req)
+            .send()
+            .await
+            .map_err(ProviderError::network)?;
+        let resp = check_status(resp, "cohere")?;
+        let body: CohereResponse = resp.json().await?;
+        let text = body
+            .message
+            .content
+            .first()
+            .map(|c| c.text.clone())
+            .unwrap_or_default();
+        Ok(text)
+    }
+}
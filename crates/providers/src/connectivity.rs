@@ -0,0 +1,52 @@
+//! Whether any AI provider can be reached
+//!
+//! The router marks the app offline when every provider it tried failed to
+//! connect or timed out, and online again as soon as one answers. While
+//! offline, [`ping`] checks the configured providers with a cheap request;
+//! any HTTP response at all, even an error status, means we're back online.
+
+use crate::{ollama, openai};
+use reqwest::Client;
+use shared::settings::ModelProvider;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// True after a request found no provider reachable, until one answers
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// URL to check a provider with; none of these need authentication to
+/// give some response
+fn ping_url(provider: &str, config: &ModelProvider) -> Option<String> {
+    let url = match provider {
+        "local" => format!("{}/api/tags", ollama::base_url()),
+        "openai" => format!("{}/models", openai::base_url(config)),
+        "anthropic" => "https://api.anthropic.com/v1/models".to_string(),
+        "gemini" => "https://generativelanguage.googleapis.com/v1beta/models".to_string(),
+        "cohere" => "https://api.cohere.com/v2/models".to_string(),
+        _ => return None,
+    };
+    Some(url)
+}
+
+/// Check the preferred providers in turn, updating [`is_offline`]. Returns
+/// true if any of them answered.
+pub async fn ping(config: &ModelProvider) -> bool {
+    let http = Client::builder().timeout(PING_TIMEOUT).build().unwrap_or_default();
+    for url in config.provider_preference.iter().filter_map(|p| ping_url(p, config)) {
+        if http.get(&url).send().await.is_ok() {
+            set_offline(false);
+            return true;
+        }
+    }
+    false
+}
@@ -0,0 +1,100 @@
+//! Typed provider errors so the router can decide whether to retry,
+//! fall back to the next provider, or give up.
+
+use anyhow::Result;
+use reqwest::{Response, StatusCode};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ProviderError {
+    #[error("rate limit exceeded (429 Too Many Requests)")]
+    RateLimit { retry_after: Option<Duration> },
+    #[error("unauthorized - invalid api key or expired login")]
+    AuthError,
+    #[error("server error ({0})")]
+    ServerError(u16),
+    #[error("{provider} error: {status}")]
+    HttpStatus { provider: String, status: StatusCode },
+    #[error("network error: {0}")]
+    NetworkError(String),
+    /// The provider dropped the connection partway through
+    #[error("connection reset: {0}")]
+    ConnectionReset(String),
+    /// Couldn't connect, or the connection timed out
+    #[error("can't reach the provider: {0}")]
+    Unreachable(String),
+    /// Every provider tried was unreachable
+    #[error("offline - no AI provider is reachable")]
+    Offline,
+}
+
+impl ProviderError {
+    /// Map a failed request to a network error
+    pub fn network(err: reqwest::Error) -> Self {
+        let reset = std::iter::successors(std::error::Error::source(&err), |e| e.source())
+            .filter_map(|e| e.downcast_ref::<std::io::Error>())
+            .any(|e| e.kind() == std::io::ErrorKind::ConnectionReset);
+        if err.is_connect() || err.is_timeout() {
+            ProviderError::Unreachable(err.to_string())
+        } else if reset {
+            ProviderError::ConnectionReset(err.to_string())
+        } else {
+            ProviderError::NetworkError(err.to_string())
+        }
+    }
+
+    /// Worth trying the same provider again after a wait: rate limits,
+    /// gateway errors and dropped connections. Other errors, like a bad
+    /// key or request, go straight to the next provider.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProviderError::RateLimit { .. } => true,
+            ProviderError::ServerError(status) => matches!(status, 502..=504),
+            ProviderError::ConnectionReset(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The request got an answer from the provider, so it isn't unreachable
+    pub fn is_response(&self) -> bool {
+        matches!(
+            self,
+            ProviderError::RateLimit { .. }
+                | ProviderError::AuthError
+                | ProviderError::ServerError(_)
+                | ProviderError::HttpStatus { .. }
+        )
+    }
+}
+
+/// Pass successful responses through, turning HTTP errors into `ProviderError`
+/// where the router can act on them
+pub fn check_status(resp: Response, provider: &str) -> Result<Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+
+    let err = match status {
+        StatusCode::TOO_MANY_REQUESTS => ProviderError::RateLimit {
+            retry_after: retry_after(&resp),
+        },
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ProviderError::AuthError,
+        s if s.is_server_error() => ProviderError::ServerError(s.as_u16()),
+        status => ProviderError::HttpStatus { provider: provider.to_string(), status },
+    };
+    Err(err.into())
+}
+
+/// Parse a `Retry-After` header given in seconds
+fn retry_after(resp: &Response) -> Option<Duration> {
+    resp.headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
@@ -2,8 +2,11 @@ use anyhow::{anyhow, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use shared::agent_api::ChatMessage;
+use crate::error::{check_status, ProviderError};
+use crate::sse::{self, Chunk};
 use shared::settings::ProviderAuth;
 use std::env;
+use tokio::sync::mpsc;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct GeminiContent {
@@ -12,8 +15,22 @@ struct GeminiContent {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct GeminiPart {
-    text: String,
+#[serde(untagged)]
+enum GeminiPart {
+    Text {
+        text: String,
+    },
+    InlineData {
+        #[serde(rename = "inlineData")]
+        inline_data: GeminiBlob,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiBlob {
+    #[serde(rename = "mimeType")]
+    mime_type: String,
+    data: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,6 +53,18 @@ struct GeminiResponse {
     candidates: Vec<GeminiCandidateContent>,
 }
 
+/// One server-sent event from `streamGenerateContent`
+#[derive(Debug, Deserialize)]
+struct GeminiStreamChunk {
+    #[serde(default)]
+    candidates: Vec<GeminiStreamCandidate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiStreamCandidate {
+    content: Option<GeminiCandidateContent>,
+}
+
 pub struct GeminiClient {
     http: Client,
     auth_token: String,
@@ -67,20 +96,55 @@ impl GeminiClient {
 
     pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
         let url = format!("https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}", self.model, self.auth_token);
-        let contents: Vec<GeminiContent> = messages
-            .into_iter()
-            .map(|m| GeminiContent { role: m.role, parts: vec![GeminiPart { text: m.content }] })
-            .collect();
-        let req = GeminiRequest { contents };
-        let resp = self.http.post(url).json(&req).send().await?;
-        if !resp.status().is_success() { return Err(anyhow!("gemini error: {}", resp.status())); }
+        let req = GeminiRequest { contents: to_contents(messages) };
+        let resp = self.http.post(url).json(&req).send().await.map_err(ProviderError::network)?;
+        let resp = check_status(resp, "gemini")?;
         let body: GeminiResponse = resp.json().await?;
         let text = body
             .candidates
-            .get(0)
-            .and_then(|c| c.parts.get(0))
+            .first()
+            .and_then(|c| c.parts.first())
             .map(|p| p.text.clone())
             .unwrap_or_default();
         Ok(text)
     }
+
+    /// Like `generate`, sending the reply on `tx` as it arrives
+    pub async fn generate_streaming(&self, messages: Vec<ChatMessage>, tx: mpsc::Sender<String>) -> Result<()> {
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse&key={}",
+            self.model, self.auth_token
+        );
+        let req = GeminiRequest { contents: to_contents(messages) };
+        let resp = self.http.post(url).json(&req).send().await.map_err(ProviderError::network)?;
+        let resp = check_status(resp, "gemini")?;
+        sse::forward("gemini", resp, tx, |event| {
+            let chunk: GeminiStreamChunk = serde_json::from_str(&event.data).ok()?;
+            Some(Chunk::Text(
+                chunk
+                    .candidates
+                    .iter()
+                    .filter_map(|c| c.content.as_ref())
+                    .flat_map(|c| c.parts.iter().map(|p| p.text.as_str()))
+                    .collect(),
+            ))
+        })
+        .await
+    }
+}
+
+fn to_contents(messages: Vec<ChatMessage>) -> Vec<GeminiContent> {
+    messages
+        .into_iter()
+        .map(|m| {
+            let mut parts = vec![GeminiPart::Text { text: m.content }];
+            parts.extend(m.attachments.iter().map(|attachment| GeminiPart::InlineData {
+                inline_data: GeminiBlob {
+                    mime_type: attachment.media_type().to_string(),
+                    data: attachment.to_base64(),
+                },
+            }));
+            GeminiContent { role: m.role, parts }
+        })
+        .collect()
 }
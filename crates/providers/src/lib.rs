@@ -2,5 +2,12 @@ pub mod ollama;
 pub mod gemini;
 pub mod openai;
 pub mod anthropic;
+pub mod cohere;
 pub mod router;
+pub mod error;
+pub mod models;
+pub mod quality;
+pub mod connectivity;
+pub mod benchmark;
+mod sse;
 pub mod oauth_helper;
@@ -0,0 +1,200 @@
+//! Known models per provider and model name normalization
+//!
+//! Users type model names by hand in settings ("gpt4o", "claude-3-5"),
+//! so names are mapped to the identifiers the APIs expect before use.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+
+/// What a model can do, for picking a provider that can handle a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelCapability {
+    TextChat,
+    /// Images attached to messages
+    VisionInput,
+    /// Native tool/function calling
+    FunctionCalling,
+    /// A context window of 100K tokens or more
+    LongContext,
+}
+
+/// Static information about a model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+    /// Context window in tokens
+    pub context_window: usize,
+    pub supports_vision: bool,
+    pub capabilities: Vec<ModelCapability>,
+    /// USD per 1K input tokens
+    pub cost_per_1k_input_tokens: f64,
+    /// USD per 1K output tokens
+    pub cost_per_1k_output_tokens: f64,
+}
+
+impl ModelInfo {
+    pub fn supports(&self, capability: ModelCapability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+fn model(
+    id: &str,
+    display_name: &str,
+    context_window: usize,
+    capabilities: &[ModelCapability],
+    input_cost: f64,
+    output_cost: f64,
+) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        display_name: display_name.to_string(),
+        context_window,
+        supports_vision: capabilities.contains(&ModelCapability::VisionInput),
+        capabilities: capabilities.to_vec(),
+        cost_per_1k_input_tokens: input_cost,
+        cost_per_1k_output_tokens: output_cost,
+    }
+}
+
+/// All models we know about for a provider ("local", "openai", "anthropic", "gemini", "cohere")
+pub fn known_models(provider: &str) -> Vec<ModelInfo> {
+    use ModelCapability::*;
+
+    match provider {
+        "openai" => vec![
+            model("gpt-4o", "GPT-4o", 128_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.0025, 0.01),
+            model("gpt-4o-mini", "GPT-4o mini", 128_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.00015, 0.0006),
+            model("gpt-4-turbo", "GPT-4 Turbo", 128_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.01, 0.03),
+            model("gpt-3.5-turbo", "GPT-3.5 Turbo", 16_385, &[TextChat, FunctionCalling], 0.0005, 0.0015),
+        ],
+        "anthropic" => vec![
+            model("claude-3-5-sonnet-20241022", "Claude 3.5 Sonnet", 200_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.003, 0.015),
+            model("claude-3-5-haiku-20241022", "Claude 3.5 Haiku", 200_000, &[TextChat, FunctionCalling, LongContext], 0.0008, 0.004),
+            model("claude-3-opus-20240229", "Claude 3 Opus", 200_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.015, 0.075),
+            model("claude-3-haiku-20240307", "Claude 3 Haiku", 200_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.00025, 0.00125),
+        ],
+        "gemini" => vec![
+            model("gemini-1.5-flash", "Gemini 1.5 Flash", 1_000_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.000075, 0.0003),
+            model("gemini-1.5-pro", "Gemini 1.5 Pro", 2_000_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.00125, 0.005),
+            model("gemini-2.0-flash", "Gemini 2.0 Flash", 1_000_000, &[TextChat, VisionInput, FunctionCalling, LongContext], 0.0001, 0.0004),
+        ],
+        "cohere" => vec![
+            model("command-r-plus-08-2024", "Command R+", 128_000, &[TextChat, FunctionCalling, LongContext], 0.0025, 0.01),
+            model("command-r-08-2024", "Command R", 128_000, &[TextChat, FunctionCalling, LongContext], 0.00015, 0.0006),
+        ],
+        "local" => vec![
+            model("llama3.2:3b", "Llama 3.2 3B", 128_000, &[TextChat, FunctionCalling, LongContext], 0.0, 0.0),
+            model("llama3.1:8b", "Llama 3.1 8B", 128_000, &[TextChat, FunctionCalling, LongContext], 0.0, 0.0),
+            model("llava:7b", "LLaVA 7B", 4_096, &[TextChat, VisionInput], 0.0, 0.0),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Look up a model by provider and (normalized) id
+pub fn find_model(provider: &str, model: &str) -> Option<ModelInfo> {
+    let id = normalize_model_name(model, provider);
+    known_models(provider).into_iter().find(|m| m.id == id)
+}
+
+/// Common ways people write model names, mapped to API identifiers
+fn aliases(provider: &str) -> HashMap<&'static str, &'static str> {
+    let pairs: &[(&str, &str)] = match provider {
+        "openai" => &[
+            ("gpt4o", "gpt-4o"),
+            ("gpt-4-o", "gpt-4o"),
+            ("4o", "gpt-4o"),
+            ("gpt4o-mini", "gpt-4o-mini"),
+            ("gpt-4o mini", "gpt-4o-mini"),
+            ("4o-mini", "gpt-4o-mini"),
+            ("gpt4-turbo", "gpt-4-turbo"),
+            ("gpt4", "gpt-4-turbo"),
+            ("gpt-4", "gpt-4-turbo"),
+            ("gpt35", "gpt-3.5-turbo"),
+            ("gpt-3.5", "gpt-3.5-turbo"),
+        ],
+        "anthropic" => &[
+            ("claude-3-5", "claude-3-5-sonnet-20241022"),
+            ("claude-3.5", "claude-3-5-sonnet-20241022"),
+            ("claude-3-5-sonnet", "claude-3-5-sonnet-20241022"),
+            ("claude-3.5-sonnet", "claude-3-5-sonnet-20241022"),
+            ("sonnet", "claude-3-5-sonnet-20241022"),
+            ("claude-3-5-haiku", "claude-3-5-haiku-20241022"),
+            ("claude-3.5-haiku", "claude-3-5-haiku-20241022"),
+            ("claude-3-opus", "claude-3-opus-20240229"),
+            ("opus", "claude-3-opus-20240229"),
+            ("claude-3-haiku", "claude-3-haiku-20240307"),
+            ("haiku", "claude-3-haiku-20240307"),
+        ],
+        "gemini" => &[
+            ("gemini-flash", "gemini-1.5-flash"),
+            ("gemini-1.5", "gemini-1.5-flash"),
+            ("gemini1.5-flash", "gemini-1.5-flash"),
+            ("gemini-pro", "gemini-1.5-pro"),
+            ("gemini1.5-pro", "gemini-1.5-pro"),
+            ("gemini-2", "gemini-2.0-flash"),
+            ("gemini-2.0", "gemini-2.0-flash"),
+        ],
+        "cohere" => &[
+            ("command-r-plus", "command-r-plus-08-2024"),
+            ("command-r+", "command-r-plus-08-2024"),
+            ("command-r", "command-r-08-2024"),
+        ],
+        "local" => &[
+            ("llama3.2", "llama3.2:3b"),
+            ("llama-3.2", "llama3.2:3b"),
+            ("llama3.1", "llama3.1:8b"),
+            ("llama-3.1", "llama3.1:8b"),
+        ],
+        _ => &[],
+    };
+    pairs.iter().copied().collect()
+}
+
+/// Map a user-typed model name to the provider's API identifier.
+/// Names that match no alias are passed through unchanged. An alias is
+/// logged the first time it's applied; this runs on every frame the
+/// context usage is drawn.
+pub fn normalize_model_name(model: &str, provider: &str) -> String {
+    static LOGGED: OnceLock<Mutex<HashSet<(String, String)>>> = OnceLock::new();
+
+    let key = model.trim().to_lowercase();
+    match aliases(provider).get(key.as_str()) {
+        Some(id) => {
+            let first_time = || {
+                let mut logged = LOGGED.get_or_init(Default::default).lock().unwrap();
+                logged.insert((provider.to_string(), model.to_string()))
+            };
+            if *id != model && first_time() {
+                warn!(provider, from = model, to = *id, "model name alias applied");
+            }
+            id.to_string()
+        }
+        None => model.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_model_name() {
+        assert_eq!(normalize_model_name("gpt4o", "openai"), "gpt-4o");
+        assert_eq!(normalize_model_name("Claude-3-5", "anthropic"), "claude-3-5-sonnet-20241022");
+        // Unknown names pass through
+        assert_eq!(normalize_model_name("my-finetune", "openai"), "my-finetune");
+        // Aliases are per provider
+        assert_eq!(normalize_model_name("sonnet", "openai"), "sonnet");
+    }
+
+    #[test]
+    fn test_find_model() {
+        let info = find_model("openai", "gpt4o").unwrap();
+        assert!(info.supports_vision);
+        assert!(find_model("openai", "not-a-model").is_none());
+    }
+}
@@ -84,45 +84,43 @@ impl OAuthFlow {
 }
 
 fn receive_callback(listener: &TcpListener) -> Result<(String, String)> {
-    for stream in listener.incoming() {
-        if let Ok(mut stream) = stream {
-            let mut reader = BufReader::new(&stream);
-            let mut request_line = String::new();
-            reader.read_line(&mut request_line)?;
-
-            // Parse the request line to get the URL
-            let redirect_url = request_line
-                .split_whitespace()
-                .nth(1)
-                .ok_or_else(|| anyhow!("Invalid request"))?;
-
-            let url = Url::parse(&format!("http://localhost{}", redirect_url))?;
-
-            // Extract code and state from query parameters
-            let code = url
-                .query_pairs()
-                .find(|(key, _)| key == "code")
-                .map(|(_, value)| value.to_string())
-                .ok_or_else(|| anyhow!("No authorization code in callback"))?;
-
-            let state = url
-                .query_pairs()
-                .find(|(key, _)| key == "state")
-                .map(|(_, value)| value.to_string())
-                .ok_or_else(|| anyhow!("No state in callback"))?;
-
-            // Send success response
-            let response = "HTTP/1.1 200 OK\r\n\
-                           Content-Type: text/html\r\n\r\n\
-                           <html><body>\
-                           <h1>Authentication successful!</h1>\
-                           <p>You can close this window and return to Little Helper.</p>\
-                           </body></html>";
-            stream.write_all(response.as_bytes())?;
-            stream.flush()?;
-
-            return Ok((code, state));
-        }
+    if let Some(mut stream) = listener.incoming().flatten().next() {
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        // Parse the request line to get the URL
+        let redirect_url = request_line
+            .split_whitespace()
+            .nth(1)
+            .ok_or_else(|| anyhow!("Invalid request"))?;
+
+        let url = Url::parse(&format!("http://localhost{}", redirect_url))?;
+
+        // Extract code and state from query parameters
+        let code = url
+            .query_pairs()
+            .find(|(key, _)| key == "code")
+            .map(|(_, value)| value.to_string())
+            .ok_or_else(|| anyhow!("No authorization code in callback"))?;
+
+        let state = url
+            .query_pairs()
+            .find(|(key, _)| key == "state")
+            .map(|(_, value)| value.to_string())
+            .ok_or_else(|| anyhow!("No state in callback"))?;
+
+        // Send success response
+        let response = "HTTP/1.1 200 OK\r\n\
+                       Content-Type: text/html\r\n\r\n\
+                       <html><body>\
+                       <h1>Authentication successful!</h1>\
+                       <p>You can close this window and return to Little Helper.</p>\
+                       </body></html>";
+        stream.write_all(response.as_bytes())?;
+        stream.flush()?;
+
+        return Ok((code, state));
     }
 
     Err(anyhow!("Failed to receive callback"))
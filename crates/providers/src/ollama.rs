@@ -1,8 +1,13 @@
 use anyhow::{anyhow, Result};
-use reqwest::Client;
+use futures::StreamExt;
+use reqwest::{Client, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use shared::agent_api::ChatMessage;
+use crate::error::{check_status, ProviderError};
+use crate::sse::Chunk;
 use std::env;
+use std::time::Duration;
+use tokio::sync::mpsc;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct OllamaRequest<'a> {
@@ -16,29 +21,204 @@ struct OllamaResponse {
     response: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<OllamaMessage>,
+    stream: bool,
+}
+
+/// Same roles as OpenAI; images go alongside the text as base64
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaMessage {
+    role: String,
+    content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+}
+
+impl From<&ChatMessage> for OllamaMessage {
+    fn from(m: &ChatMessage) -> Self {
+        Self {
+            role: m.role.clone(),
+            content: m.content.clone(),
+            images: m.attachments.iter().map(|a| a.to_base64()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaChatResponse {
+    message: OllamaMessage,
+}
+
+/// One line of a streamed `/api/chat` or `/api/generate` response. Ollama
+/// streams newline-delimited JSON rather than server-sent events.
+#[derive(Debug, Deserialize)]
+struct OllamaStreamLine {
+    /// Text from `/api/chat`
+    message: Option<OllamaMessage>,
+    /// Text from `/api/generate`
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+/// What one line of a streamed response holds; blank lines hold nothing
+fn parse_stream_line(line: &[u8]) -> Result<Option<Chunk>> {
+    if line.trim_ascii().is_empty() {
+        return Ok(None);
+    }
+    let line: OllamaStreamLine = serde_json::from_slice(line)?;
+    Ok(Some(if let Some(error) = line.error {
+        Chunk::Error(error)
+    } else if line.done {
+        Chunk::Done
+    } else {
+        Chunk::Text(line.message.map(|m| m.content).unwrap_or(line.response))
+    }))
+}
+
+/// Send the text of each line of `resp` on `tx` until the line marked
+/// done, the end of the response, or the receiver going away
+async fn forward_lines(resp: Response, tx: mpsc::Sender<String>) -> Result<()> {
+    let mut stream = resp.bytes_stream();
+    let mut pending = Vec::new();
+    while let Some(bytes) = stream.next().await {
+        pending.extend_from_slice(&bytes.map_err(ProviderError::network)?);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            match parse_stream_line(&line)? {
+                Some(Chunk::Text(text)) => {
+                    if !text.is_empty() && tx.send(text).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                Some(Chunk::Done) => return Ok(()),
+                Some(Chunk::Error(message)) => return Err(anyhow!("ollama stream error: {}", message)),
+                None => continue,
+            }
+        }
+    }
+    // The last line may not end in a newline
+    match parse_stream_line(&pending)? {
+        Some(Chunk::Text(text)) if !text.is_empty() => {
+            let _ = tx.send(text).await;
+        }
+        Some(Chunk::Error(message)) => return Err(anyhow!("ollama stream error: {}", message)),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Response of `/api/tags`
+#[derive(Debug, Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
 pub struct OllamaClient {
     http: Client,
     base: String,
     model: String,
 }
 
+/// `OLLAMA_BASE_URL`, or the default local server
+pub fn base_url() -> String {
+    env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:11434".to_string())
+}
+
+/// Names of the models the Ollama server has pulled, e.g. "llama3.2:3b".
+/// Fails if the server doesn't answer within `timeout`.
+pub async fn installed_models(timeout: Duration) -> Result<Vec<String>> {
+    let http = Client::builder().timeout(timeout).build()?;
+    let resp = http.get(format!("{}/api/tags", base_url())).send().await.map_err(ProviderError::network)?;
+    let tags: OllamaTags = check_status(resp, "ollama")?.json().await?;
+    Ok(tags.models.into_iter().map(|m| m.name).collect())
+}
+
 impl OllamaClient {
     pub fn new(model: String) -> Self {
-        let base = env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:11434".to_string());
-        Self { http: Client::new(), base, model }
+        Self { http: Client::new(), base: base_url(), model }
     }
 
+    /// Uses `/api/chat`, falling back to `/api/generate` for older Ollama
+    /// versions and models that don't take chat requests
     pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
+        match self.chat(&messages, false).await? {
+            Some(resp) => {
+                let body: OllamaChatResponse = resp.json().await?;
+                Ok(body.message.content)
+            }
+            None => {
+                let body: OllamaResponse = self.prompt(messages, false).await?.json().await?;
+                Ok(body.response)
+            }
+        }
+    }
+
+    /// Like `generate`, sending the reply on `tx` as the model writes it
+    pub async fn generate_streaming(&self, messages: Vec<ChatMessage>, tx: mpsc::Sender<String>) -> Result<()> {
+        let resp = match self.chat(&messages, true).await? {
+            Some(resp) => resp,
+            None => self.prompt(messages, true).await?,
+        };
+        forward_lines(resp, tx).await
+    }
+
+    /// Send a chat request, or `None` if this server or model needs
+    /// `/api/generate` instead
+    async fn chat(&self, messages: &[ChatMessage], stream: bool) -> Result<Option<Response>> {
+        let url = format!("{}/api/chat", self.base);
+        let req = OllamaChatRequest {
+            model: &self.model,
+            messages: messages.iter().map(Into::into).collect(),
+            stream,
+        };
+        let resp = self.http.post(url).json(&req).send().await.map_err(ProviderError::network)?;
+        if matches!(resp.status(), StatusCode::NOT_FOUND | StatusCode::BAD_REQUEST) {
+            tracing::info!(model = %self.model, status = %resp.status(), "ollama chat failed, using generate");
+            return Ok(None);
+        }
+        check_status(resp, "ollama").map(Some)
+    }
+
+    async fn prompt(&self, messages: Vec<ChatMessage>, stream: bool) -> Result<Response> {
+        // Attachments are skipped - the generate endpoint only takes text
         let prompt = messages
             .into_iter()
             .map(|m| format!("{}: {}", m.role, m.content))
             .collect::<Vec<_>>()
             .join("\n");
         let url = format!("{}/api/generate", self.base);
-        let req = OllamaRequest { model: &self.model, prompt, stream: false };
-        let resp = self.http.post(url).json(&req).send().await?;
-        if !resp.status().is_success() { return Err(anyhow!("ollama error: {}", resp.status())); }
-        let body: OllamaResponse = resp.json().await?;
-        Ok(body.response)
+        let req = OllamaRequest { model: &self.model, prompt, stream };
+        let resp = self.http.post(url).json(&req).send().await.map_err(ProviderError::network)?;
+        check_status(resp, "ollama")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream_line() {
+        let text = |line: &str| match parse_stream_line(line.as_bytes()).unwrap() {
+            Some(Chunk::Text(text)) => text,
+            _ => panic!("no text in {}", line),
+        };
+        assert_eq!(text(r#"{"model":"llama3.2","message":{"role":"assistant","content":"Hel"},"done":false}"#), "Hel");
+        assert_eq!(text(r#"{"model":"llama3.2","response":"lo","done":false}"#), "lo");
+        assert!(matches!(parse_stream_line(br#"{"message":{"role":"assistant","content":""},"done":true}"#), Ok(Some(Chunk::Done))));
+        assert!(matches!(parse_stream_line(br#"{"error":"model not found"}"#), Ok(Some(Chunk::Error(e))) if e == "model not found"));
+        assert!(matches!(parse_stream_line(b"\n"), Ok(None)));
+        assert!(parse_stream_line(b"{not json").is_err());
     }
 }
@@ -1,14 +1,55 @@
 use anyhow::{anyhow, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use shared::agent_api::ChatMessage;
-use shared::settings::ProviderAuth;
+use crate::error::{check_status, ProviderError};
+use crate::sse::{self, Chunk};
+use shared::settings::{ModelProvider, ProviderAuth};
 use std::env;
+use tokio::sync::mpsc;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// The configured OpenAI-compatible server, or OpenAI's own API
+pub fn base_url(config: &ModelProvider) -> &str {
+    config
+        .openai_base_url
+        .as_deref()
+        .map(|url| url.trim().trim_end_matches('/'))
+        .filter(|url| !url.is_empty())
+        .unwrap_or(DEFAULT_BASE_URL)
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenAIRequest {
     model: String,
-    messages: Vec<OpenAIMessage>,
+    messages: Vec<OpenAIRequestMessage>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+/// Request message - `content` is a plain string, or an array of parts when images are attached
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIRequestMessage {
+    role: String,
+    content: serde_json::Value,
+}
+
+impl From<ChatMessage> for OpenAIRequestMessage {
+    fn from(m: ChatMessage) -> Self {
+        let content = if m.attachments.is_empty() {
+            serde_json::Value::String(m.content)
+        } else {
+            let mut parts = vec![json!({ "type": "text", "text": m.content })];
+            for attachment in &m.attachments {
+                let url = format!("data:{};base64,{}", attachment.media_type(), attachment.to_base64());
+                parts.push(json!({ "type": "image_url", "image_url": { "url": url } }));
+            }
+            serde_json::Value::Array(parts)
+        };
+        Self { role: m.role, content }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,16 +68,60 @@ struct OpenAIResponse {
     choices: Vec<OpenAIChoice>,
 }
 
+/// One event of a streamed reply; `content` is missing from the role and
+/// finish events
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamChunk {
+    choices: Vec<OpenAIStreamChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamChoice {
+    delta: OpenAIDelta,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIDelta {
+    content: Option<String>,
+}
+
 pub struct OpenAIClient {
     http: Client,
+    /// Empty for servers that don't need one
     auth_token: String,
     model: String,
+    base_url: String,
+    /// Sent as `api-version`, for Azure
+    api_version: Option<String>,
 }
 
 impl OpenAIClient {
     pub fn new(model: &str) -> Result<Self> {
         let key = env::var("OPENAI_API_KEY").map_err(|_| anyhow!("OPENAI_API_KEY not set"))?;
-        Ok(Self { http: Client::new(), auth_token: key, model: model.to_string() })
+        Ok(Self::with_token(model, key))
+    }
+
+    /// For local servers like LM Studio that take requests without a key
+    pub fn without_key(model: &str) -> Self {
+        Self::with_token(model, String::new())
+    }
+
+    fn with_token(model: &str, auth_token: String) -> Self {
+        Self {
+            http: Client::new(),
+            auth_token,
+            model: model.to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            api_version: None,
+        }
+    }
+
+    /// Send requests to an OpenAI-compatible server at `base_url` instead,
+    /// with `api_version` for servers that want one
+    pub fn with_endpoint(mut self, base_url: &str, api_version: Option<&str>) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self.api_version = api_version.map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+        self
     }
 
     pub fn from_auth(model: &str, auth: &ProviderAuth) -> Result<Self> {
@@ -49,36 +134,89 @@ impl OpenAIClient {
             env::var("OPENAI_API_KEY").map_err(|_| anyhow!("No OpenAI authentication configured"))?
         };
 
-        Ok(Self {
-            http: Client::new(),
-            auth_token,
-            model: model.to_string(),
-        })
+        Ok(Self::with_token(model, auth_token))
     }
 
     pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
-        let url = "https://api.openai.com/v1/chat/completions";
-        let openai_messages: Vec<OpenAIMessage> = messages
-            .into_iter()
-            .map(|m| OpenAIMessage { role: m.role, content: m.content })
-            .collect();
-        let req = OpenAIRequest { model: self.model.clone(), messages: openai_messages };
-        let resp = self.http
-            .post(url)
-            .header("Authorization", format!("Bearer {}", self.auth_token))
-            .header("Content-Type", "application/json")
-            .json(&req)
-            .send()
-            .await?;
-        if !resp.status().is_success() {
-            return Err(anyhow!("openai error: {}", resp.status()));
-        }
+        let openai_messages: Vec<OpenAIRequestMessage> = messages.into_iter().map(Into::into).collect();
+        let req = OpenAIRequest { model: self.model.clone(), messages: openai_messages, stream: false };
+        let resp = self.send(&req).await?;
         let body: OpenAIResponse = resp.json().await?;
         let text = body
             .choices
-            .get(0)
+            .first()
             .map(|c| c.message.content.clone())
             .unwrap_or_default();
         Ok(text)
     }
+
+    /// Like `generate`, sending the reply on `tx` as it arrives
+    pub async fn generate_streaming(&self, messages: Vec<ChatMessage>, tx: mpsc::Sender<String>) -> Result<()> {
+        let openai_messages: Vec<OpenAIRequestMessage> = messages.into_iter().map(Into::into).collect();
+        let req = OpenAIRequest { model: self.model.clone(), messages: openai_messages, stream: true };
+        let resp = self.send(&req).await?;
+        sse::forward("openai", resp, tx, |event| {
+            let chunk: OpenAIStreamChunk = serde_json::from_str(&event.data).ok()?;
+            chunk.choices.into_iter().next().and_then(|c| c.delta.content).map(Chunk::Text)
+        })
+        .await
+    }
+
+    fn request(&self, req: &OpenAIRequest) -> reqwest::RequestBuilder {
+        let mut builder = self
+            .http
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("Content-Type", "application/json")
+            .json(req);
+        if let Some(version) = &self.api_version {
+            builder = builder.query(&[("api-version", version)]);
+        }
+        if !self.auth_token.is_empty() {
+            builder = builder.bearer_auth(&self.auth_token);
+            // Azure takes its keys in `api-key`
+            if self.api_version.is_some() {
+                builder = builder.header("api-key", &self.auth_token);
+            }
+        }
+        builder
+    }
+
+    async fn send(&self, req: &OpenAIRequest) -> Result<reqwest::Response> {
+        let resp = self.request(req).send().await.map_err(ProviderError::network)?;
+        check_status(resp, "openai")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_endpoint() {
+        let req = OpenAIRequest { model: "gpt-4o".to_string(), messages: Vec::new(), stream: false };
+        let client = OpenAIClient::with_token("gpt-4o", "key".to_string());
+        let request = client.request(&req).build().unwrap();
+        assert_eq!(request.url().as_str(), "https://api.openai.com/v1/chat/completions");
+        assert_eq!(request.headers()["authorization"], "Bearer key");
+        assert!(request.headers().get("api-key").is_none());
+
+        let azure = OpenAIClient::with_token("gpt-4o", "key".to_string())
+            .with_endpoint("https://me.openai.azure.com/openai/deployments/gpt-4o/", Some("2024-06-01"));
+        let request = azure.request(&req).build().unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://me.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-06-01"
+        );
+        assert_eq!(request.headers()["api-key"], "key");
+
+        let local = OpenAIClient::without_key("qwen").with_endpoint("http://localhost:1234/v1", None);
+        let request = local.request(&req).build().unwrap();
+        assert_eq!(request.url().as_str(), "http://localhost:1234/v1/chat/completions");
+        assert!(request.headers().get("authorization").is_none());
+
+        let mut config = shared::settings::AppSettings::default().model;
+        assert_eq!(base_url(&config), DEFAULT_BASE_URL);
+        config.openai_base_url = Some("  ".to_string());
+        assert_eq!(base_url(&config), DEFAULT_BASE_URL);
+    }
 }
@@ -0,0 +1,165 @@
+//! Checks on what a provider sent back
+//!
+//! Providers sometimes answer with an empty string, stop mid-sentence or
+//! repeat themselves. [`score_response`] catches those so the router can
+//! retry or move on, and each provider's recent scores are kept so one that
+//! keeps giving bad answers is tried after the others.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// Responses this short or shorter count as empty
+const MIN_RESPONSE_CHARS: usize = 20;
+/// Words in each window compared when looking for repetition
+const REPEAT_WINDOW_WORDS: usize = 5;
+/// Fewer windows than this is too little text to call repetitive
+const MIN_REPEAT_WINDOWS: usize = 10;
+/// Below this share of distinct windows the response is repetitive
+const MIN_DISTINCT_RATIO: f32 = 0.5;
+/// Responses scoring below this are retried or passed over
+pub const ACCEPTABLE_SCORE: f32 = 0.5;
+/// Scores kept per provider
+const QUALITY_WINDOW: usize = 20;
+/// Scores needed before a provider can count as degraded
+const MIN_QUALITY_SAMPLES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityIssue {
+    TooShort,
+    Repetitive,
+    Truncated,
+}
+
+impl fmt::Display for QualityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            QualityIssue::TooShort => "empty or too short",
+            QualityIssue::Repetitive => "repeats itself",
+            QualityIssue::Truncated => "cut off mid-sentence",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseQuality {
+    /// 1.0 for a response with no issues, down to 0.0
+    pub score: f32,
+    pub issues: Vec<QualityIssue>,
+}
+
+impl ResponseQuality {
+    pub fn is_acceptable(&self) -> bool {
+        self.score >= ACCEPTABLE_SCORE
+    }
+}
+
+pub fn score_response(response: &str) -> ResponseQuality {
+    let text = response.trim();
+    if text.is_empty() {
+        return ResponseQuality { score: 0.0, issues: vec![QualityIssue::TooShort] };
+    }
+
+    let mut score = 1.0f32;
+    let mut issues = Vec::new();
+    if text.chars().count() <= MIN_RESPONSE_CHARS {
+        score -= 0.6;
+        issues.push(QualityIssue::TooShort);
+    }
+    if is_repetitive(text) {
+        score -= 0.6;
+        issues.push(QualityIssue::Repetitive);
+    }
+    if is_truncated(text) {
+        // Lightly weighted: plenty of good answers end without a full stop
+        score -= 0.3;
+        issues.push(QualityIssue::Truncated);
+    }
+    ResponseQuality { score: score.max(0.0), issues }
+}
+
+/// Share of distinct word windows, sliding one word at a time
+fn is_repetitive(text: &str) -> bool {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    let windows: Vec<&[String]> = words.windows(REPEAT_WINDOW_WORDS).collect();
+    if windows.len() < MIN_REPEAT_WINDOWS {
+        return false;
+    }
+    let distinct: HashSet<&[String]> = windows.iter().copied().collect();
+    (distinct.len() as f32 / windows.len() as f32) < MIN_DISTINCT_RATIO
+}
+
+/// Ends in the middle of a word or sentence. List items, table rows and
+/// code blocks often end without punctuation, so those don't count.
+fn is_truncated(text: &str) -> bool {
+    let last_line = text.lines().last().unwrap_or_default().trim_start();
+    if ["-", "*", "|", "```", "#"].iter().any(|p| last_line.starts_with(p))
+        || last_line.chars().next().is_some_and(|c| c.is_ascii_digit())
+    {
+        return false;
+    }
+    text.chars()
+        .last()
+        .is_some_and(|c| c.is_alphanumeric() || matches!(c, ',' | ';' | '-' | '('))
+}
+
+fn recent_scores() -> &'static Mutex<HashMap<String, VecDeque<f32>>> {
+    static SCORES: OnceLock<Mutex<HashMap<String, VecDeque<f32>>>> = OnceLock::new();
+    SCORES.get_or_init(Default::default)
+}
+
+/// Remember a response score for `provider`
+pub fn record(provider: &str, score: f32) {
+    let mut scores = recent_scores().lock().unwrap_or_else(|e| e.into_inner());
+    let window = scores.entry(provider.to_string()).or_default();
+    window.push_back(score);
+    if window.len() > QUALITY_WINDOW {
+        window.pop_front();
+    }
+}
+
+/// Average of the provider's recent scores
+pub fn average(provider: &str) -> Option<f32> {
+    let scores = recent_scores().lock().unwrap_or_else(|e| e.into_inner());
+    let window = scores.get(provider).filter(|w| !w.is_empty())?;
+    Some(window.iter().sum::<f32>() / window.len() as f32)
+}
+
+/// The provider's recent responses have mostly been poor
+pub fn is_degraded(provider: &str) -> bool {
+    let scores = recent_scores().lock().unwrap_or_else(|e| e.into_inner());
+    scores.get(provider).is_some_and(|w| {
+        w.len() >= MIN_QUALITY_SAMPLES && w.iter().sum::<f32>() / (w.len() as f32) < ACCEPTABLE_SCORE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_response() {
+        let good = score_response("Rust's ownership rules make sure each value has exactly one owner.");
+        assert!(good.issues.is_empty());
+        assert!(good.is_acceptable());
+
+        assert_eq!(score_response("  ").issues, vec![QualityIssue::TooShort]);
+        assert!(!score_response("  ").is_acceptable());
+        assert!(score_response("- first item\n- second item without a stop").issues.is_empty());
+
+        let cut = score_response("The main reasons are the cost of the parts and the");
+        assert_eq!(cut.issues, vec![QualityIssue::Truncated]);
+        assert!(cut.is_acceptable());
+
+        let looping = "I can help with that. ".repeat(10);
+        let quality = score_response(&looping);
+        assert_eq!(quality.issues, vec![QualityIssue::Repetitive]);
+        assert!(!quality.is_acceptable());
+
+        for _ in 0..MIN_QUALITY_SAMPLES {
+            record("test-provider", 0.2);
+        }
+        assert!(is_degraded("test-provider"));
+        assert!(!is_degraded("other-provider"));
+    }
+}
@@ -1,10 +1,19 @@
 use anyhow::{anyhow, Result};
 use shared::agent_api::ChatMessage;
-use shared::settings::ModelProvider;
+use shared::settings::{ModelProvider, RetryPolicy};
 use crate::gemini::GeminiClient;
 use crate::ollama::OllamaClient;
-use crate::openai::OpenAIClient;
+use crate::openai::{self, OpenAIClient};
 use crate::anthropic::AnthropicClient;
+use crate::cohere::CohereClient;
+use crate::connectivity;
+use crate::error::ProviderError;
+use crate::models::{find_model, normalize_model_name, ModelCapability};
+use crate::quality::{self, score_response};
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
 
 pub struct ProviderRouter {
     config: ModelProvider,
@@ -15,43 +24,415 @@ impl ProviderRouter {
         Self { config }
     }
 
+    /// Router for a chat mode, trying the mode's own providers when it has
+    /// some set in `mode_providers`
+    pub fn new_for_mode(mut config: ModelProvider, mode: &str) -> Self {
+        config.provider_preference = config.providers_for_mode(mode).to_vec();
+        Self { config }
+    }
+
     pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
+        self.generate_with_provider(messages)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    /// Like `generate`, but also returns the name of the provider that answered
+    pub async fn generate_with_provider(&self, messages: Vec<ChatMessage>) -> Result<(String, String)> {
+        self.generate_with_provider_requiring(messages, &[]).await
+    }
+
+    /// Like `generate`, skipping providers whose configured model lacks any of
+    /// `requirements` (e.g. `VisionInput` when images are attached)
+    pub async fn generate_with_requirements(
+        &self,
+        messages: Vec<ChatMessage>,
+        requirements: &[ModelCapability],
+    ) -> Result<String> {
+        self.generate_with_provider_requiring(messages, requirements)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    /// `generate_with_requirements` that also returns the provider that answered
+    pub async fn generate_with_provider_requiring(
+        &self,
+        messages: Vec<ChatMessage>,
+        requirements: &[ModelCapability],
+    ) -> Result<(String, String)> {
         let mut last_error = None;
+        let mut reachability = Reachability::default();
+        // Best poor-quality response so far, used if no provider does better
+        let mut fallback: Option<(f32, String, String)> = None;
 
-        // Try providers in order of preference
-        for provider in &self.config.provider_preference {
-            let result = match provider.as_str() {
-                "local" => {
-                    let client = OllamaClient::new(self.config.local_model.clone());
-                    client.generate(messages.clone()).await
-                }
-                "openai" => {
-                    let client = OpenAIClient::from_auth(&self.config.openai_model, &self.config.openai_auth)?;
-                    client.generate(messages.clone()).await
-                }
-                "anthropic" => {
-                    let client = AnthropicClient::from_auth(&self.config.anthropic_model, &self.config.anthropic_auth)?;
-                    client.generate(messages.clone()).await
-                }
-                "gemini" => {
-                    let client = GeminiClient::from_auth(&self.config.gemini_model, &self.config.gemini_auth)?;
-                    client.generate(messages.clone()).await
+        // Try providers in order of preference, leaving ones that have
+        // recently given poor responses until last
+        let mut providers = self.providers_for(requirements);
+        providers.sort_by_key(|p| quality::is_degraded(p));
+        for provider in providers {
+            let mut attempt = 0;
+            let mut quality_retried = false;
+            loop {
+                let result = match self.call_provider(provider, &messages).await {
+                    Some(result) => result,
+                    None => {
+                        last_error = Some(anyhow!("Unknown provider: {}", provider));
+                        break;
+                    }
+                };
+
+                let err = match result {
+                    Ok(response) => {
+                        let quality = score_response(&response);
+                        quality::record(provider, quality.score);
+                        reachability.answered = true;
+                        if quality.is_acceptable() {
+                            connectivity::set_offline(false);
+                            if attempt > 0 {
+                                info!(provider = %provider, retries = attempt, "provider succeeded after retrying");
+                            }
+                            return Ok((response, provider.clone()));
+                        }
+
+                        let issues: Vec<String> = quality.issues.iter().map(ToString::to_string).collect();
+                        warn!(provider = %provider, score = quality.score, ?issues, "poor quality response");
+                        if fallback.as_ref().is_none_or(|(score, _, _)| quality.score > *score) {
+                            fallback = Some((quality.score, response, provider.clone()));
+                        }
+                        if !quality_retried {
+                            quality_retried = true;
+                            continue;
+                        }
+                        last_error = Some(anyhow!("{} gave a poor response: {}", provider, issues.join(", ")));
+                        break;
+                    }
+                    Err(e) => e,
+                };
+
+                match self.retry_delay(&err, attempt) {
+                    Some(delay) => {
+                        attempt += 1;
+                        warn!(provider = %provider, retry = attempt, delay_ms = delay.as_millis() as u64, error = %err, "retrying provider");
+                        tokio::time::sleep(delay).await;
+                    }
+                    None => {
+                        // Auth errors and exhausted retries fall through to the next provider
+                        warn!(provider = %provider, retries = attempt, error = %err, "provider failed, trying next");
+                        reachability.record(&err);
+                        last_error = Some(anyhow!("{} error: {}", provider, err));
+                        break;
+                    }
                 }
-                _ => {
-                    last_error = Some(anyhow!("Unknown provider: {}", provider));
-                    continue;
+            }
+        }
+
+        if let Some((_, response, provider)) = fallback {
+            connectivity::set_offline(false);
+            return Ok((response, provider));
+        }
+        if reachability.offline() {
+            connectivity::set_offline(true);
+            return Err(ProviderError::Offline.into());
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("No providers configured")))
+    }
+
+    /// Like `generate`, also sending the reply on `tx` as it arrives. Local,
+    /// Gemini, OpenAI and Anthropic models stream; Cohere sends its whole
+    /// reply as one piece.
+    /// Errors are retried as `generate` retries them, and a provider that
+    /// fails before sending anything falls through to the next one; once
+    /// text has been sent the error is returned instead. A reply that has
+    /// been sent can't be taken back, so a poor one is only scored against
+    /// its provider rather than asked for again.
+    pub async fn generate_streaming(&self, messages: Vec<ChatMessage>, tx: mpsc::Sender<String>) -> Result<String> {
+        let mut last_error = None;
+        let mut reachability = Reachability::default();
+        let mut providers = self.providers_for(&[]);
+        providers.sort_by_key(|p| quality::is_degraded(p));
+        for provider in providers {
+            let mut attempt = 0;
+            loop {
+                let (inner_tx, mut inner_rx) = mpsc::channel::<String>(32);
+                let forward = async {
+                    let mut sent = String::new();
+                    while let Some(text) = inner_rx.recv().await {
+                        sent.push_str(&text);
+                        let _ = tx.send(text).await;
+                    }
+                    sent
+                };
+                let (result, sent) = tokio::join!(self.stream_provider(provider, &messages, inner_tx), forward);
+                let err = match result {
+                    Ok(()) => {
+                        let quality = score_response(&sent);
+                        quality::record(provider, quality.score);
+                        if !quality.is_acceptable() {
+                            let issues: Vec<String> = quality.issues.iter().map(ToString::to_string).collect();
+                            warn!(provider = %provider, score = quality.score, ?issues, "poor quality response");
+                        }
+                        connectivity::set_offline(false);
+                        return Ok(sent);
+                    }
+                    Err(e) if !sent.is_empty() => return Err(anyhow!("{} error: {}", provider, e)),
+                    Err(e) => e,
+                };
+
+                match self.retry_delay(&err, attempt) {
+                    Some(delay) => {
+                        attempt += 1;
+                        warn!(provider = %provider, retry = attempt, delay_ms = delay.as_millis() as u64, error = %err, "retrying provider");
+                        tokio::time::sleep(delay).await;
+                    }
+                    None => {
+                        warn!(provider = %provider, retries = attempt, error = %err, "provider failed, trying next");
+                        reachability.record(&err);
+                        last_error = Some(anyhow!("{} error: {}", provider, err));
+                        break;
+                    }
                 }
-            };
+            }
+        }
+        if reachability.offline() {
+            connectivity::set_offline(true);
+            return Err(ProviderError::Offline.into());
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("No providers configured")))
+    }
+
+    /// How long to wait before trying a provider again after `err`, or
+    /// `None` if it shouldn't be retried (auth errors, retries used up)
+    fn retry_delay(&self, err: &anyhow::Error, attempt: u32) -> Option<Duration> {
+        let policy = &self.config.retry;
+        match err.downcast_ref::<ProviderError>() {
+            Some(e) if e.is_retryable() && attempt < u32::from(policy.max_attempts) => {
+                let max = Duration::from_millis(policy.max_delay_ms);
+                Some(match e {
+                    ProviderError::RateLimit { retry_after: Some(wait) } => (*wait).min(max),
+                    _ => jitter(backoff_delay(policy, attempt)),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Stream one request from a provider, or send its whole reply for
+    /// providers that don't stream
+    async fn stream_provider(&self, provider: &str, messages: &[ChatMessage], tx: mpsc::Sender<String>) -> Result<()> {
+        let messages = messages.to_vec();
+        match provider {
+            "local" => {
+                OllamaClient::new(normalize_model_name(&self.config.local_model, provider))
+                    .generate_streaming(messages, tx)
+                    .await
+            }
+            "openai" => self.openai_client()?.generate_streaming(messages, tx).await,
+            "anthropic" => {
+                AnthropicClient::from_auth(&normalize_model_name(&self.config.anthropic_model, provider), &self.config.anthropic_auth)?
+                    .generate_streaming(messages, None, tx)
+                    .await
+            }
+            "gemini" => {
+                GeminiClient::from_auth(&normalize_model_name(&self.config.gemini_model, provider), &self.config.gemini_auth)?
+                    .generate_streaming(messages, tx)
+                    .await
+            }
+            _ => {
+                let response = self
+                    .call_provider(provider, &messages)
+                    .await
+                    .unwrap_or_else(|| Err(anyhow!("Unknown provider: {}", provider)))?;
+                let _ = tx.send(response).await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Preferred providers that are set up, in order: the local model
+    /// always, the others once they have a key, token or custom server
+    pub fn configured_providers(&self) -> Vec<String> {
+        self.config
+            .provider_preference
+            .iter()
+            .filter(|provider| self.is_configured(provider))
+            .cloned()
+            .collect()
+    }
 
-            match result {
-                Ok(response) => return Ok(response),
-                Err(e) => {
-                    last_error = Some(e);
-                    continue;
+    fn is_configured(&self, provider: &str) -> bool {
+        match provider {
+            "local" => true,
+            "openai" => self.openai_client().is_ok(),
+            "anthropic" => AnthropicClient::from_auth(&self.config.anthropic_model, &self.config.anthropic_auth).is_ok(),
+            "gemini" => GeminiClient::from_auth(&self.config.gemini_model, &self.config.gemini_auth).is_ok(),
+            "cohere" => CohereClient::from_auth(&self.config.cohere_model, &self.config.cohere_auth).is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Preferred providers whose model has every capability in `requirements`.
+    /// Models we don't know only count as `TextChat`. If no provider
+    /// qualifies, all of them are tried rather than failing outright.
+    fn providers_for(&self, requirements: &[ModelCapability]) -> Vec<&String> {
+        let capable: Vec<&String> = self
+            .config
+            .provider_preference
+            .iter()
+            .filter(|provider| {
+                let model = self.config.model_for(provider).unwrap_or_default();
+                match find_model(provider, model) {
+                    Some(info) => requirements.iter().all(|r| info.supports(*r)),
+                    None => requirements.iter().all(|r| *r == ModelCapability::TextChat),
                 }
+            })
+            .collect();
+        if capable.is_empty() && !requirements.is_empty() {
+            warn!(?requirements, "no provider supports the required capabilities, trying all");
+            return self.config.provider_preference.iter().collect();
+        }
+        capable
+    }
+
+    /// Client for OpenAI or the OpenAI-compatible server in the settings.
+    /// Such a server may take requests without a key, as LM Studio does.
+    fn openai_client(&self) -> Result<OpenAIClient> {
+        let model = normalize_model_name(&self.config.openai_model, "openai");
+        let client = match OpenAIClient::from_auth(&model, &self.config.openai_auth) {
+            Ok(client) => client,
+            Err(_) if openai::base_url(&self.config) != openai::DEFAULT_BASE_URL => OpenAIClient::without_key(&model),
+            Err(e) => return Err(e),
+        };
+        Ok(client.with_endpoint(openai::base_url(&self.config), self.config.openai_api_version.as_deref()))
+    }
+
+    /// Run one request against a provider (`None` if the provider name is unknown)
+    async fn call_provider(&self, provider: &str, messages: &[ChatMessage]) -> Option<Result<String>> {
+        let messages = messages.to_vec();
+        let result = match provider {
+            "local" => {
+                let client = OllamaClient::new(normalize_model_name(&self.config.local_model, provider));
+                client.generate(messages).await
             }
+            "openai" => match self.openai_client() {
+                Ok(client) => client.generate(messages).await,
+                Err(e) => Err(e),
+            },
+            "anthropic" => match AnthropicClient::from_auth(&normalize_model_name(&self.config.anthropic_model, provider), &self.config.anthropic_auth) {
+                Ok(client) => client.generate(messages).await,
+                Err(e) => Err(e),
+            },
+            "gemini" => match GeminiClient::from_auth(&normalize_model_name(&self.config.gemini_model, provider), &self.config.gemini_auth) {
+                Ok(client) => client.generate(messages).await,
+                Err(e) => Err(e),
+            },
+            "cohere" => match CohereClient::from_auth(&normalize_model_name(&self.config.cohere_model, provider), &self.config.cohere_auth) {
+                Ok(client) => client.generate(messages).await,
+                Err(e) => Err(e),
+            },
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+/// What the providers tried so far say about the network. Errors from
+/// setting a provider up (e.g. no API key) say nothing either way.
+#[derive(Default)]
+struct Reachability {
+    answered: bool,
+    unreachable: bool,
+}
+
+impl Reachability {
+    fn record(&mut self, err: &anyhow::Error) {
+        match err.downcast_ref::<ProviderError>() {
+            Some(ProviderError::Unreachable(_)) => self.unreachable = true,
+            Some(e) if e.is_response() => self.answered = true,
+            _ => {}
+        }
+    }
+
+    /// At least one provider couldn't be reached and none answered
+    fn offline(&self) -> bool {
+        self.unreachable && !self.answered
+    }
+}
+
+/// Exponential backoff: the base delay doubled for each retry, capped
+fn backoff_delay(policy: &RetryPolicy, attempt: u32) -> Duration {
+    Duration::from_millis(policy.base_delay_ms)
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(Duration::from_millis(policy.max_delay_ms))
+}
+
+/// Somewhere between half and all of `delay`, so clients that failed
+/// together don't all retry at the same moment
+fn jitter(delay: Duration) -> Duration {
+    let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    delay / 2 + delay.mul_f64((random % 1000) as f64 / 2000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_providers_for_requirements() {
+        let mut config = shared::settings::AppSettings::default().model;
+        config.provider_preference = vec!["local".into(), "openai".into()];
+        let router = ProviderRouter::new(config);
+        assert_eq!(router.providers_for(&[]), vec!["local", "openai"]);
+        assert_eq!(router.providers_for(&[ModelCapability::VisionInput]), vec!["openai"]);
+    }
+
+    #[test]
+    fn test_new_for_mode() {
+        let mut config = shared::settings::AppSettings::default().model;
+        config.provider_preference = vec!["local".into()];
+        config.mode_providers.insert("research".into(), vec!["anthropic".into(), "local".into()]);
+        config.mode_providers.insert("data".into(), Vec::new());
+        let research = ProviderRouter::new_for_mode(config.clone(), "research");
+        assert_eq!(research.providers_for(&[]), vec!["anthropic", "local"]);
+        assert_eq!(ProviderRouter::new_for_mode(config.clone(), "data").providers_for(&[]), vec!["local"]);
+        assert_eq!(ProviderRouter::new_for_mode(config, "find").providers_for(&[]), vec!["local"]);
+    }
+
+    #[test]
+    fn test_configured_providers() {
+        let mut config = shared::settings::AppSettings::default().model;
+        config.provider_preference = vec!["nope".into(), "anthropic".into(), "local".into()];
+        config.anthropic_auth.api_key = Some("key".into());
+        assert_eq!(ProviderRouter::new(config).configured_providers(), ["anthropic", "local"]);
+    }
+
+    #[test]
+    fn test_offline_only_when_nothing_answered() {
+        let mut reachability = Reachability::default();
+        reachability.record(&anyhow!("No OpenAI authentication configured"));
+        assert!(!reachability.offline());
+        reachability.record(&ProviderError::Unreachable("connection refused".into()).into());
+        assert!(reachability.offline());
+        reachability.record(&ProviderError::AuthError.into());
+        assert!(!reachability.offline());
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(backoff_delay(&policy, 0), Duration::from_millis(100));
+        assert_eq!(backoff_delay(&policy, 1), Duration::from_millis(200));
+        assert_eq!(backoff_delay(&policy, 2), Duration::from_millis(400));
+        assert_eq!(backoff_delay(&policy, 20), Duration::from_secs(30));
+        for _ in 0..20 {
+            let delay = jitter(Duration::from_millis(400));
+            assert!((Duration::from_millis(200)..=Duration::from_millis(400)).contains(&delay));
         }
 
-        Err(last_error.unwrap_or_else(|| anyhow!("No providers configured")))
+        assert!(ProviderError::RateLimit { retry_after: None }.is_retryable());
+        assert!(ProviderError::ServerError(503).is_retryable());
+        assert!(!ProviderError::ServerError(500).is_retryable());
+        assert!(!ProviderError::AuthError.is_retryable());
+        assert!(ProviderError::ConnectionReset("error decoding response body".into()).is_retryable());
+        assert!(!ProviderError::NetworkError("builder error".into()).is_retryable());
     }
 }
@@ -0,0 +1,110 @@
+//! Server-sent events from streaming provider responses
+//!
+//! Streaming APIs send `data: {...}` lines, some with an `event:` line
+//! naming the kind of event first, and some end with `data: [DONE]`. An
+//! event's JSON can be split across network chunks, so bytes are buffered
+//! until a line is complete.
+
+use crate::error::ProviderError;
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use reqwest::Response;
+use tokio::sync::mpsc;
+
+/// Sent by OpenAI-style APIs after the last event
+const DONE: &str = "[DONE]";
+
+#[derive(Debug, PartialEq)]
+pub struct SseEvent {
+    /// From the `event:` line before the data, if there was one
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// What a provider found in an event
+pub enum Chunk {
+    Text(String),
+    /// The reply is complete
+    Done,
+    /// The provider reported an error partway through
+    Error(String),
+}
+
+/// Carries partial lines over from one chunk to the next. Bytes are kept
+/// until the line ends so a character split across chunks isn't mangled.
+#[derive(Default)]
+struct SseBuffer {
+    pending: Vec<u8>,
+    event: Option<String>,
+}
+
+impl SseBuffer {
+    /// Every `data:` line completed by `chunk`, with its event type
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.pending.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                self.event = None;
+            } else if let Some(event) = line.strip_prefix("event:") {
+                self.event = Some(event.trim().to_string());
+            } else if let Some(data) = line.strip_prefix("data:") {
+                events.push(SseEvent { event: self.event.clone(), data: data.trim().to_string() });
+            }
+        }
+        events
+    }
+}
+
+/// Read `resp` as server-sent events, sending the text `parse` finds in
+/// each one on `tx`. Ends at `[DONE]`, [`Chunk::Done`], the end of the
+/// response, or when the receiver goes away; `tx` is dropped then. Events
+/// `parse` has no use for (`None`) are skipped.
+pub async fn forward<F>(provider: &str, resp: Response, tx: mpsc::Sender<String>, parse: F) -> Result<()>
+where
+    F: Fn(&SseEvent) -> Option<Chunk>,
+{
+    let mut stream = resp.bytes_stream();
+    let mut buffer = SseBuffer::default();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(ProviderError::network)?;
+        for event in buffer.push(&chunk) {
+            if event.data == DONE {
+                return Ok(());
+            }
+            match parse(&event) {
+                Some(Chunk::Text(text)) => {
+                    if !text.is_empty() && tx.send(text).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                Some(Chunk::Done) => return Ok(()),
+                Some(Chunk::Error(message)) => return Err(anyhow!("{} stream error: {}", provider, message)),
+                None => tracing::debug!(provider, "skipping stream event: {}", event.data),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_joins_split_lines() {
+        let mut buffer = SseBuffer::default();
+        let event = "event: message\ndata: {\"text\": \"Héllo\"}\n\n";
+        // Split inside the "é"
+        let (first, rest) = event.as_bytes().split_at(33);
+        assert!(buffer.push(first).is_empty());
+        assert_eq!(
+            buffer.push(rest),
+            vec![SseEvent { event: Some("message".to_string()), data: "{\"text\": \"Héllo\"}".to_string() }]
+        );
+        assert_eq!(buffer.push(b"data: [DONE]\n"), vec![SseEvent { event: None, data: DONE.to_string() }]);
+    }
+}
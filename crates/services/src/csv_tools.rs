@@ -0,0 +1,161 @@
+//! CSV helpers for Data mode
+//!
+//! Joining two files on a key column, like a spreadsheet VLOOKUP or a SQL
+//! join, without the user having to write a script.
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    /// Rows whose key is in both files
+    Inner,
+    /// Every left row, with right columns where the key matches
+    Left,
+    /// Every right row, with left columns where the key matches
+    Right,
+}
+
+impl JoinType {
+    /// Parse "inner", "left" or "right"
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "inner" => Some(JoinType::Inner),
+            "left" => Some(JoinType::Left),
+            "right" => Some(JoinType::Right),
+            _ => None,
+        }
+    }
+}
+
+pub struct CsvJoiner;
+
+impl CsvJoiner {
+    /// Join `left` and `right` on their key columns. The first row of the
+    /// result is the header: every left column, then the right columns
+    /// except its key. If a key appears more than once in `right`, its
+    /// first row is used.
+    pub fn join(left: &Path, right: &Path, left_key: &str, right_key: &str, join_type: JoinType) -> Result<Vec<Vec<String>>> {
+        let (left_headers, left_rows) = read_csv(left)?;
+        let (right_headers, right_rows) = read_csv(right)?;
+        join_rows(&left_headers, &left_rows, &right_headers, &right_rows, left_key, right_key, join_type)
+    }
+}
+
+/// Rows as CSV text, quoting where needed
+pub fn to_csv_string(rows: &[Vec<String>]) -> String {
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(Vec::new());
+    for row in rows {
+        // Writing to a Vec can't fail
+        let _ = writer.write_record(row);
+    }
+    String::from_utf8(writer.into_inner().unwrap_or_default()).unwrap_or_default()
+}
+
+fn read_csv(path: &Path) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| anyhow!("Couldn't read {}: {}", path.display(), e))?;
+    let headers = reader.headers()?.iter().map(|h| h.trim().to_string()).collect();
+    let rows = reader
+        .records()
+        .map(|r| r.map(|record| record.iter().map(str::to_string).collect()))
+        .collect::<Result<Vec<Vec<String>>, _>>()?;
+    Ok((headers, rows))
+}
+
+fn column(headers: &[String], name: &str, side: &str) -> Result<usize> {
+    match headers.iter().position(|h| h == name) {
+        Some(i) => Ok(i),
+        None => bail!("The {} file has no \"{}\" column (columns: {})", side, name, headers.join(", ")),
+    }
+}
+
+fn join_rows(
+    left_headers: &[String],
+    left_rows: &[Vec<String>],
+    right_headers: &[String],
+    right_rows: &[Vec<String>],
+    left_key: &str,
+    right_key: &str,
+    join_type: JoinType,
+) -> Result<Vec<Vec<String>>> {
+    let left_idx = column(left_headers, left_key, "left")?;
+    let right_idx = column(right_headers, right_key, "right")?;
+    let cell = |row: &[String], i: usize| row.get(i).cloned().unwrap_or_default();
+
+    let mut by_key: HashMap<String, Vec<String>> = HashMap::new();
+    for row in right_rows {
+        by_key.entry(cell(row, right_idx)).or_insert_with(|| row.clone());
+    }
+
+    // The right key is already in the left key column
+    let right_columns: Vec<usize> = (0..right_headers.len()).filter(|i| *i != right_idx).collect();
+    let right_part = |row: Option<&Vec<String>>| -> Vec<String> {
+        right_columns.iter().map(|i| row.map(|r| cell(r, *i)).unwrap_or_default()).collect()
+    };
+
+    let mut header = left_headers.to_vec();
+    header.extend(right_columns.iter().map(|i| right_headers[*i].clone()));
+    let mut joined = vec![header];
+
+    let mut matched = HashSet::new();
+    for row in left_rows {
+        let key = cell(row, left_idx);
+        let right = by_key.get(&key);
+        if right.is_some() {
+            matched.insert(key);
+        } else if join_type != JoinType::Left {
+            continue;
+        }
+        let mut out: Vec<String> = (0..left_headers.len()).map(|i| cell(row, i)).collect();
+        out.extend(right_part(right));
+        joined.push(out);
+    }
+
+    if join_type == JoinType::Right {
+        for (key, row) in right_rows.iter().map(|r| (cell(r, right_idx), r)) {
+            if matched.insert(key.clone()) {
+                let mut out = vec![String::new(); left_headers.len()];
+                out[left_idx] = key;
+                out.extend(right_part(Some(row)));
+                joined.push(out);
+            }
+        }
+    }
+    Ok(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_join_types() {
+        let dir = std::env::temp_dir().join(format!("lh-csv-join-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let left = dir.join("people.csv");
+        let right = dir.join("orders.csv");
+        std::fs::write(&left, "id,name\n1,Ana\n2,Bo\n").unwrap();
+        std::fs::write(&right, "customer,total\n2,9.50\n3,4.00\n").unwrap();
+
+        let inner = CsvJoiner::join(&left, &right, "id", "customer", JoinType::Inner).unwrap();
+        assert_eq!(inner, vec![strings(&["id", "name", "total"]), strings(&["2", "Bo", "9.50"])]);
+
+        let left_join = CsvJoiner::join(&left, &right, "id", "customer", JoinType::Left).unwrap();
+        assert_eq!(left_join[1], strings(&["1", "Ana", ""]));
+
+        let right_join = CsvJoiner::join(&left, &right, "id", "customer", JoinType::Right).unwrap();
+        assert_eq!(right_join.len(), 3);
+        assert_eq!(right_join[2], strings(&["3", "", "4.00"]));
+
+        assert!(CsvJoiner::join(&left, &right, "missing", "customer", JoinType::Inner).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,8 +1,12 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use glob::Pattern;
 use ignore::WalkBuilder;
 use shared::search_types::{SearchQuery, SearchResult};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct FinderOptions {
@@ -22,12 +26,138 @@ fn score_name(name: &str, query: &str) -> Option<f32> {
     }
 }
 
+/// Whether `path`, found under `root`, matches `pattern`. Patterns without
+/// a `/` only look at the file name.
+fn matches_pattern(pattern: &Pattern, root: &Path, path: &Path) -> bool {
+    if pattern.as_str().contains('/') {
+        path.strip_prefix(root).is_ok_and(|relative| pattern.matches_path(relative))
+    } else {
+        path.file_name().is_some_and(|name| pattern.matches_path(Path::new(name)))
+    }
+}
+
+/// How much of each file content search reads
+const CONTENT_PREFIX_BYTES: usize = 4096;
+
+/// BM25 term frequency saturation and length normalisation
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// Matching lines shown per file
+const SNIPPET_LINES: usize = 2;
+const SNIPPET_LINE_CHARS: usize = 160;
+
+/// Lowercase words of `text`
+fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The start of `path` as text, or `None` for binary files
+fn read_prefix(path: &Path) -> Option<String> {
+    let mut bytes = Vec::with_capacity(CONTENT_PREFIX_BYTES);
+    fs::File::open(path).ok()?.take(CONTENT_PREFIX_BYTES as u64).read_to_end(&mut bytes).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// A file that passed the filters, before it's scored
+struct Candidate {
+    path: PathBuf,
+    file_name: String,
+    name_score: Option<f32>,
+    /// Query term counts in the file's start, and its length in words
+    term_counts: Vec<usize>,
+    length: usize,
+    snippet: Option<String>,
+}
+
+/// Lines containing a query term, with each occurrence in `**bold**`
+fn snippet(text: &str, query_terms: &[String]) -> Option<String> {
+    let lines: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            let lower = line.to_lowercase();
+            query_terms.iter().any(|t| lower.contains(t.as_str()))
+        })
+        .take(SNIPPET_LINES)
+        .map(|line| highlight(&line.chars().take(SNIPPET_LINE_CHARS).collect::<String>(), query_terms))
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+fn highlight(line: &str, query_terms: &[String]) -> String {
+    // ASCII lowercasing keeps byte offsets the same as in `line`
+    let lower = line.to_ascii_lowercase();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < line.len() {
+        let hit = query_terms.iter().filter(|t| lower[i..].starts_with(t.as_str())).map(|t| t.len()).max();
+        match hit {
+            Some(len) => {
+                out.push_str("**");
+                out.push_str(&line[i..i + len]);
+                out.push_str("**");
+                i += len;
+            }
+            None => {
+                let c = line[i..].chars().next().unwrap_or_default();
+                out.push(c);
+                i += c.len_utf8().max(1);
+            }
+        }
+    }
+    out
+}
+
+/// Ordered by score, then most recently modified
+struct Ranked(SearchResult);
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.score.total_cmp(&other.0.score).then(self.0.modified.cmp(&other.0.modified))
+    }
+}
+
+/// Files matching `query`, best first. Files whose name contains the query
+/// text score by how early and how much of the name it covers. With query
+/// text, the first few KB of text files are also ranked against its words
+/// with BM25, so files that only mention it in their content are found too.
 pub fn search(opts: FinderOptions, query: SearchQuery) -> Result<Vec<SearchResult>> {
-    let mut results: Vec<SearchResult> = Vec::new();
     let exts = query.extensions.as_ref().map(|v| v.iter().map(|s| s.to_lowercase()).collect::<Vec<_>>() );
+    let pattern = match query.pattern.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(p) => Some(Pattern::new(p).with_context(|| format!("Invalid file pattern '{}'", p))?),
+        None => None,
+    };
+    let query_terms = terms(&query.text);
+
+    // Statistics over every text file read, for BM25
+    let mut documents = 0usize;
+    let mut total_length = 0usize;
+    let mut document_frequency = vec![0usize; query_terms.len()];
+    let mut candidates = Vec::new();
 
     for dir in opts.allowed_dirs {
-        let walker = WalkBuilder::new(dir)
+        let walker = WalkBuilder::new(&dir)
             .hidden(false)
             .ignore(true)
             .git_ignore(true)
@@ -44,30 +174,148 @@ pub fn search(opts: FinderOptions, query: SearchQuery) -> Result<Vec<SearchResul
                     if !exts.iter().any(|e| e == &ext) { continue; }
                 } else { continue; }
             }
-            if let Some(score) = score_name(&file_name, &query.text) {
-                let meta = fs::metadata(path);
-                let (size, modified) = match meta {
-                    Ok(m) => {
-                        let size = m.len();
-                        let ts = m.modified().ok().and_then(|t| t.elapsed().ok()).map(|e| chrono::Utc::now().timestamp() - e.as_secs() as i64);
-                        (size, ts)
-                    },
-                    Err(_) => (0, None)
-                };
-                results.push(SearchResult {
-                    path: path.to_string_lossy().into_owned(),
-                    file_name,
-                    size_bytes: size,
-                    modified,
-                    score,
-                });
+            if let Some(pattern) = &pattern {
+                if !matches_pattern(pattern, &dir, path) { continue; }
+            }
+
+            let name_score = score_name(&file_name, &query.text);
+            let mut term_counts = vec![0; query_terms.len()];
+            let mut length = 0;
+            let mut snippet_text = None;
+            if !query_terms.is_empty() {
+                if let Some(text) = read_prefix(path) {
+                    let words = terms(&text);
+                    length = words.len();
+                    for word in &words {
+                        if let Some(i) = query_terms.iter().position(|t| t == word) {
+                            term_counts[i] += 1;
+                        }
+                    }
+                    documents += 1;
+                    total_length += length;
+                    for (df, count) in document_frequency.iter_mut().zip(&term_counts) {
+                        *df += usize::from(*count > 0);
+                    }
+                    if term_counts.iter().any(|c| *c > 0) {
+                        snippet_text = snippet(&text, &query_terms);
+                    }
+                }
+            }
+            if name_score.is_none() && term_counts.iter().all(|c| *c == 0) {
+                continue;
             }
+            candidates.push(Candidate {
+                path: path.to_path_buf(),
+                file_name,
+                name_score,
+                term_counts,
+                length,
+                snippet: snippet_text,
+            });
         }
     }
 
-    // Sort by score desc, then recent first
-    results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal)
-        .then(b.modified.cmp(&a.modified)));
-    results.truncate(opts.max_results);
+    let average_length = total_length as f32 / documents.max(1) as f32;
+    let bm25 = |candidate: &Candidate| -> f32 {
+        candidate
+            .term_counts
+            .iter()
+            .zip(&document_frequency)
+            .filter(|(tf, _)| **tf > 0)
+            .map(|(tf, df)| {
+                let idf = ((documents as f32 - *df as f32 + 0.5) / (*df as f32 + 0.5) + 1.0).ln();
+                let tf = *tf as f32;
+                let norm = 1.0 - BM25_B + BM25_B * candidate.length as f32 / average_length.max(1.0);
+                idf * tf * (BM25_K1 + 1.0) / (tf + BM25_K1 * norm)
+            })
+            .sum()
+    };
+    let scores: Vec<f32> = candidates.iter().map(bm25).collect();
+    let best = scores.iter().copied().fold(0.0, f32::max);
+
+    // Name and content scores add up, with content scaled to the best match
+    let mut heap = BinaryHeap::new();
+    for (candidate, content_score) in candidates.into_iter().zip(scores) {
+        let content_score = if best > 0.0 { content_score / best } else { 0.0 };
+        let (size, modified) = match fs::metadata(&candidate.path) {
+            Ok(m) => {
+                let ts = m.modified().ok().and_then(|t| t.elapsed().ok()).map(|e| chrono::Utc::now().timestamp() - e.as_secs() as i64);
+                (m.len(), ts)
+            },
+            Err(_) => (0, None)
+        };
+        heap.push(Ranked(SearchResult {
+            path: candidate.path.to_string_lossy().into_owned(),
+            file_name: candidate.file_name,
+            size_bytes: size,
+            modified,
+            score: candidate.name_score.unwrap_or(0.0) + content_score,
+            content_snippet: candidate.snippet,
+        }));
+    }
+
+    let mut results = Vec::new();
+    while results.len() < opts.max_results {
+        let Some(Ranked(result)) = heap.pop() else { break };
+        results.push(result);
+    }
     Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_with_pattern() {
+        let dir = std::env::temp_dir().join(format!("lh-file-search-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(dir.join("src/nested/config.toml"), "").unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("app.test.ts"), "").unwrap();
+
+        let find = |pattern: &str| -> Vec<String> {
+            let opts = FinderOptions { allowed_dirs: vec![dir.clone()], max_results: 10 };
+            let query = SearchQuery { text: String::new(), extensions: None, pattern: Some(pattern.to_string()) };
+            let mut names: Vec<String> = search(opts, query).unwrap().into_iter().map(|r| r.file_name).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(find("Cargo.toml"), ["Cargo.toml"]);
+        assert_eq!(find("*.test.ts"), ["app.test.ts"]);
+        assert_eq!(find("src/**/*.toml"), ["config.toml"]);
+        assert_eq!(find("**/*.rs"), ["main.rs"]);
+        assert!(search(
+            FinderOptions { allowed_dirs: vec![dir.clone()], max_results: 10 },
+            SearchQuery { text: String::new(), extensions: None, pattern: Some("[".to_string()) },
+        )
+        .is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_search_ranks_content() {
+        let dir = std::env::temp_dir().join(format!("lh-content-search-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.md"), "Groceries\nThe Budget for March is tight.\nbudget again, budget").unwrap();
+        fs::write(dir.join("letter.txt"), "Dear Sam,\nabout the budget.\nThanks for the long and winding words").unwrap();
+        fs::write(dir.join("budget.csv"), "month,amount\n").unwrap();
+        fs::write(dir.join("other.txt"), "nothing to see").unwrap();
+        fs::write(dir.join("image.bin"), b"budget\0\x01").unwrap();
+
+        let results = search(
+            FinderOptions { allowed_dirs: vec![dir.clone()], max_results: 2 },
+            SearchQuery { text: "budget".to_string(), extensions: None, pattern: None },
+        )
+        .unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.file_name.as_str()).collect();
+        assert_eq!(names, ["notes.md", "budget.csv"]);
+        assert_eq!(results[1].content_snippet, None);
+        assert_eq!(
+            results[0].content_snippet.as_deref(),
+            Some("The **Budget** for March is tight.\n**budget** again, **budget**")
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+}
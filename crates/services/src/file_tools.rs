@@ -0,0 +1,116 @@
+//! File checksums
+//!
+//! For checking a download against the hash its website publishes, or
+//! whether a file has changed.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 4] = [HashAlgorithm::Md5, HashAlgorithm::Sha1, HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+
+    /// Parse "md5", "sha1", "sha256" or "sha512" (dashes and case ignored)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('-', "").as_str() {
+            "md5" => Some(HashAlgorithm::Md5),
+            "sha1" => Some(HashAlgorithm::Sha1),
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "sha512" => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "MD5",
+            HashAlgorithm::Sha1 => "SHA-1",
+            HashAlgorithm::Sha256 => "SHA-256",
+            HashAlgorithm::Sha512 => "SHA-512",
+        }
+    }
+}
+
+/// Hex digest of a file's contents
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> Result<String> {
+    let file = File::open(path).with_context(|| format!("Couldn't open {}", path.display()))?;
+    let bytes = match algorithm {
+        HashAlgorithm::Md5 => digest::<md5::Md5>(file)?,
+        HashAlgorithm::Sha1 => digest::<sha1::Sha1>(file)?,
+        HashAlgorithm::Sha256 => digest::<sha2::Sha256>(file)?,
+        HashAlgorithm::Sha512 => digest::<sha2::Sha512>(file)?,
+    };
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn digest<D: Digest>(mut file: File) -> Result<Vec<u8>> {
+    let mut hasher = D::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// A file's hash, and the hash it was expected to have
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileHash {
+    pub path: PathBuf,
+    pub algorithm: HashAlgorithm,
+    pub hash: String,
+    pub expected: Option<String>,
+}
+
+impl FileHash {
+    /// Whether the hash matches the expected one, if there is one
+    pub fn matches(&self) -> Option<bool> {
+        self.expected
+            .as_deref()
+            .map(|expected| expected.trim().eq_ignore_ascii_case(&self.hash))
+    }
+}
+
+/// Hash `path` and compare with `expected` (blank counts as none)
+pub fn verify_hash(path: &Path, expected: Option<&str>, algorithm: HashAlgorithm) -> Result<FileHash> {
+    Ok(FileHash {
+        path: path.to_path_buf(),
+        algorithm,
+        hash: hash_file(path, algorithm)?,
+        expected: expected.map(str::trim).filter(|e| !e.is_empty()).map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_file() {
+        let path = std::env::temp_dir().join(format!("lh-hash-{}.txt", std::process::id()));
+        std::fs::write(&path, "abc").unwrap();
+
+        assert_eq!(hash_file(&path, HashAlgorithm::Md5).unwrap(), "900150983cd24fb0d6963f7d28e17f72");
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let verified = verify_hash(&path, Some(&sha256.to_uppercase()), HashAlgorithm::Sha256).unwrap();
+        assert_eq!(verified.hash, sha256);
+        assert_eq!(verified.matches(), Some(true));
+        assert_eq!(verify_hash(&path, Some("00"), HashAlgorithm::Sha1).unwrap().matches(), Some(false));
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,120 @@
+//! Programming language of a file
+//!
+//! So answers about "this code" come in the language the user is working
+//! in. The extension decides when there is one; scripts without one are
+//! recognised by their `#!` line.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgrammingLanguage {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    Ruby,
+}
+
+impl ProgrammingLanguage {
+    pub const ALL: [ProgrammingLanguage; 6] = [
+        ProgrammingLanguage::Rust,
+        ProgrammingLanguage::Python,
+        ProgrammingLanguage::JavaScript,
+        ProgrammingLanguage::TypeScript,
+        ProgrammingLanguage::Go,
+        ProgrammingLanguage::Ruby,
+    ];
+
+    /// Key in `AppSettings::language_hints`
+    pub fn key(&self) -> &'static str {
+        match self {
+            ProgrammingLanguage::Rust => "rust",
+            ProgrammingLanguage::Python => "python",
+            ProgrammingLanguage::JavaScript => "javascript",
+            ProgrammingLanguage::TypeScript => "typescript",
+            ProgrammingLanguage::Go => "go",
+            ProgrammingLanguage::Ruby => "ruby",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ProgrammingLanguage::Rust => "Rust",
+            ProgrammingLanguage::Python => "Python",
+            ProgrammingLanguage::JavaScript => "JavaScript",
+            ProgrammingLanguage::TypeScript => "TypeScript",
+            ProgrammingLanguage::Go => "Go",
+            ProgrammingLanguage::Ruby => "Ruby",
+        }
+    }
+
+    fn from_extension(ext: &str) -> Option<Self> {
+        let language = match ext.to_lowercase().as_str() {
+            "rs" => ProgrammingLanguage::Rust,
+            "py" | "pyw" | "pyi" => ProgrammingLanguage::Python,
+            "js" | "mjs" | "cjs" | "jsx" => ProgrammingLanguage::JavaScript,
+            "ts" | "mts" | "cts" | "tsx" => ProgrammingLanguage::TypeScript,
+            "go" => ProgrammingLanguage::Go,
+            "rb" | "rake" | "gemspec" => ProgrammingLanguage::Ruby,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    /// "#!/usr/bin/env python3" -> Python
+    fn from_shebang(line: &str) -> Option<Self> {
+        let command = line.strip_prefix("#!")?;
+        let mut words = command.split_whitespace();
+        let program = words.next()?.rsplit('/').next()?;
+        // `env` names the interpreter next, maybe after flags like -S
+        let interpreter = if program == "env" {
+            words.find(|w| !w.starts_with('-'))?
+        } else {
+            program
+        };
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        match interpreter {
+            "python" => Some(ProgrammingLanguage::Python),
+            "node" | "nodejs" => Some(ProgrammingLanguage::JavaScript),
+            "deno" | "ts-node" | "tsx" => Some(ProgrammingLanguage::TypeScript),
+            "ruby" => Some(ProgrammingLanguage::Ruby),
+            _ => None,
+        }
+    }
+}
+
+/// The language `path` is written in, from its extension, or for files
+/// without one, from its `#!` line
+pub fn detect_language_from_file(path: &Path) -> Option<ProgrammingLanguage> {
+    match path.extension() {
+        Some(ext) => ProgrammingLanguage::from_extension(&ext.to_string_lossy()),
+        None => {
+            let mut first_line = String::new();
+            BufReader::new(File::open(path).ok()?).read_line(&mut first_line).ok()?;
+            ProgrammingLanguage::from_shebang(first_line.trim())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language_from_file(Path::new("src/main.rs")), Some(ProgrammingLanguage::Rust));
+        assert_eq!(detect_language_from_file(Path::new("App.TSX")), Some(ProgrammingLanguage::TypeScript));
+        assert_eq!(detect_language_from_file(Path::new("notes.txt")), None);
+
+        let script = std::env::temp_dir().join(format!("lh-language-{}", std::process::id()));
+        std::fs::write(&script, "#!/usr/bin/env python3\nprint('hi')\n").unwrap();
+        assert_eq!(detect_language_from_file(&script), Some(ProgrammingLanguage::Python));
+        std::fs::remove_file(&script).ok();
+
+        assert_eq!(ProgrammingLanguage::from_shebang("#!/usr/bin/ruby -w"), Some(ProgrammingLanguage::Ruby));
+        assert_eq!(ProgrammingLanguage::from_shebang("#!/bin/bash"), None);
+    }
+}
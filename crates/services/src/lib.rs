@@ -1,5 +1,11 @@
+pub mod csv_tools;
 pub mod file_search;
+pub mod file_tools;
+pub mod language;
 pub mod organizer;
+pub mod personas;
 pub mod support;
+pub mod templates;
 pub mod mini_swarm;
 pub mod slack;
+pub mod stats;
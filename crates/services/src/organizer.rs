@@ -1,29 +1,42 @@
 use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
 use std::fs;
+use std::future::Future;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 
-#[derive(Debug, Clone)]
+/// How many organizer runs are kept for undo
+pub const MAX_HISTORY_ENTRIES: usize = 10;
+/// Runs older than this are pruned on startup
+pub const HISTORY_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OrganizeAction {
     Rename { from: String, to: String },
     Move { from: String, to_dir: String },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProposedPlan {
     pub actions: Vec<OrganizeAction>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApplyError {
     pub action: String,
     pub error: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApplyReport {
     pub applied: usize,
     pub skipped: usize,
     pub errors: Vec<ApplyError>,
+    /// Actions that succeeded, as renames between the actual paths. Used for undo.
+    #[serde(default)]
+    pub completed: Vec<OrganizeAction>,
 }
 
 pub fn build_plan(paths: Vec<String>, move_dir: Option<String>, prefix: Option<String>) -> Result<ProposedPlan> {
@@ -49,7 +62,7 @@ pub fn build_plan(paths: Vec<String>, move_dir: Option<String>, prefix: Option<S
 }
 
 pub fn apply(plan: ProposedPlan) -> Result<ApplyReport> {
-    let mut report = ApplyReport { applied: 0, skipped: 0, errors: vec![] };
+    let mut report = ApplyReport { applied: 0, skipped: 0, errors: vec![], completed: vec![] };
     for action in plan.actions {
         match action.clone() {
             OrganizeAction::Move { from, to_dir } => {
@@ -65,6 +78,7 @@ pub fn apply(plan: ProposedPlan) -> Result<ApplyReport> {
                     report.errors.push(ApplyError { action: format!("Move {} -> {}", from, dst.display()), error: e.to_string() });
                 } else {
                     report.applied += 1;
+                    report.completed.push(OrganizeAction::Rename { from, to: dst.to_string_lossy().into_owned() });
                 }
             }
             OrganizeAction::Rename { from, to } => {
@@ -78,9 +92,376 @@ pub fn apply(plan: ProposedPlan) -> Result<ApplyReport> {
                     report.errors.push(ApplyError { action: format!("Rename {} -> {}", from, to), error: e.to_string() });
                 } else {
                     report.applied += 1;
+                    report.completed.push(OrganizeAction::Rename { from, to });
                 }
             }
         }
     }
     Ok(report)
 }
+
+/// Plan that reverses the completed actions of a report, newest first
+pub fn undo_plan(report: &ApplyReport) -> ProposedPlan {
+    let actions = report
+        .completed
+        .iter()
+        .rev()
+        .map(|action| match action {
+            OrganizeAction::Rename { from, to } => OrganizeAction::Rename { from: to.clone(), to: from.clone() },
+            OrganizeAction::Move { from, to_dir } => {
+                // Not produced by `apply`, but reverse it sensibly anyway
+                let name = Path::new(from).file_name().unwrap_or_default();
+                let moved = Path::new(to_dir).join(name);
+                OrganizeAction::Rename { from: moved.to_string_lossy().into_owned(), to: from.clone() }
+            }
+        })
+        .collect();
+    ProposedPlan { actions }
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Asks an AI model. Implemented by the agent host, which services can't
+/// depend on directly.
+pub trait TextGenerator: Send + Sync {
+    /// Reply to `content` following the system `instruction`
+    fn generate<'a>(&'a self, instruction: &'a str, content: &'a str) -> BoxFuture<'a, Result<String>>;
+}
+
+const RENAME_INSTRUCTION: &str = "Suggest clear, descriptive file names for these files. Preserve extensions. \
+    Answer with one line per file, written as: original name -> new name. Leave out files whose name is already clear.";
+/// Bytes read from each file to describe it
+const RENAME_PEEK_BYTES: usize = 64 * 1024;
+/// Characters of a text file's start shown to the AI
+const RENAME_TEXT_CHARS: usize = 200;
+
+/// AI file rename suggestions, for names like `IMG_20241201_143022.jpg`
+pub struct OrganizeSuggestor<'a> {
+    ai: &'a dyn TextGenerator,
+}
+
+impl<'a> OrganizeSuggestor<'a> {
+    pub fn new(ai: &'a dyn TextGenerator) -> Self {
+        Self { ai }
+    }
+
+    /// Describe up to `max_suggestions` files to the AI and return the
+    /// new names it suggests, as (file, new file name)
+    pub fn suggest_renames(&self, paths: Vec<PathBuf>, max_suggestions: usize) -> BoxFuture<'a, Result<Vec<(PathBuf, String)>>> {
+        let ai = self.ai;
+        Box::pin(async move {
+            let paths: Vec<PathBuf> = paths.into_iter().filter(|p| p.is_file()).take(max_suggestions).collect();
+            if paths.is_empty() {
+                return Ok(Vec::new());
+            }
+            let listing: Vec<String> = paths.iter().map(|p| describe_for_rename(p)).collect();
+            let response = ai.generate(RENAME_INSTRUCTION, &listing.join("\n")).await?;
+            Ok(parse_rename_suggestions(&response, &paths))
+        })
+    }
+}
+
+/// Renames for the accepted suggestions, for the user to review and apply
+pub fn rename_plan(suggestions: &[(PathBuf, String)]) -> ProposedPlan {
+    let actions = suggestions
+        .iter()
+        .map(|(from, name)| OrganizeAction::Rename {
+            from: from.to_string_lossy().into_owned(),
+            to: from.with_file_name(name).to_string_lossy().into_owned(),
+        })
+        .collect();
+    ProposedPlan { actions }
+}
+
+/// "- IMG_2024.jpg (image, 2.1 MB, taken 2024:12:01 14:30:22)", or with
+/// the start of the text for text files
+fn describe_for_rename(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let mut head = Vec::new();
+    if let Ok(file) = fs::File::open(path) {
+        let _ = file.take(RENAME_PEEK_BYTES as u64).read_to_end(&mut head);
+    }
+
+    // EXIF dates are plain "YYYY:MM:DD HH:MM:SS" text in the header
+    let exif_date = regex::bytes::Regex::new(r"\d{4}:\d{2}:\d{2} \d{2}:\d{2}:\d{2}")
+        .ok()
+        .and_then(|re| re.find(&head).map(|m| String::from_utf8_lossy(m.as_bytes()).into_owned()));
+    let details = match (std::str::from_utf8(&head), exif_date) {
+        (_, Some(date)) => format!("{} bytes, taken {}", size, date),
+        (Ok(text), None) if !text.trim().is_empty() => {
+            let start: String = text.chars().take(RENAME_TEXT_CHARS).collect();
+            format!("{} bytes, starts: {:?}", size, start.trim())
+        }
+        _ => format!("{} bytes", size),
+    };
+    format!("- {} ({})", name, details)
+}
+
+/// Read "original -> new" lines, keeping only names of the files asked
+/// about, new names without folders, and the original extension
+fn parse_rename_suggestions(response: &str, paths: &[PathBuf]) -> Vec<(PathBuf, String)> {
+    let clean = |s: &str| s.trim().trim_start_matches(['-', '*', ' ']).trim_matches(['`', '"', '\'', ' ']).to_string();
+    let mut suggestions: Vec<(PathBuf, String)> = Vec::new();
+    for line in response.lines() {
+        let Some((original, suggested)) = line.split_once("->").or_else(|| line.split_once('→')) else { continue };
+        let (original, mut suggested) = (clean(original), clean(suggested));
+        let Some(path) = paths.iter().find(|p| p.file_name().is_some_and(|n| n.to_string_lossy() == original)) else {
+            continue;
+        };
+        if suggested.is_empty() || suggested.contains(['/', '\\']) || suggested == original {
+            continue;
+        }
+        if let Some(ext) = path.extension().map(|e| e.to_string_lossy()) {
+            if Path::new(&suggested).extension().map(|e| e.to_string_lossy()) != Some(ext.clone()) {
+                suggested = format!("{}.{}", suggested, ext);
+            }
+        }
+        if !suggestions.iter().any(|(p, _)| p == path) {
+            suggestions.push((path.clone(), suggested));
+        }
+    }
+    suggestions
+}
+
+/// One organizer run, kept so it can be undone in a later session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizeHistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    pub plan: ProposedPlan,
+    pub report: ApplyReport,
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl OrganizeHistoryEntry {
+    pub fn new(plan: ProposedPlan, report: ApplyReport) -> Self {
+        Self { timestamp: Utc::now(), plan, report, undone: false }
+    }
+
+    /// Short description, e.g. "Moved 15 files to ~/Archive"
+    pub fn summary(&self) -> String {
+        let mut moved_to: Vec<&str> = Vec::new();
+        let mut moves = 0;
+        let mut renames = 0;
+        for action in &self.plan.actions {
+            match action {
+                OrganizeAction::Move { to_dir, .. } => {
+                    moves += 1;
+                    if !moved_to.contains(&to_dir.as_str()) {
+                        moved_to.push(to_dir);
+                    }
+                }
+                OrganizeAction::Rename { .. } => renames += 1,
+            }
+        }
+
+        let mut parts = Vec::new();
+        if moves > 0 {
+            let dest = match moved_to.as_slice() {
+                [dir] => tilde_path(dir),
+                dirs => format!("{} folders", dirs.len()),
+            };
+            parts.push(format!("Moved {} to {}", plural_files(moves), dest));
+        }
+        if renames > 0 {
+            let verb = if parts.is_empty() { "Renamed" } else { "renamed" };
+            parts.push(format!("{} {}", verb, plural_files(renames)));
+        }
+        if parts.is_empty() {
+            return "No changes".to_string();
+        }
+        let mut summary = parts.join(", ");
+        if self.report.applied < self.plan.actions.len() {
+            summary.push_str(&format!(" ({} of {} applied)", self.report.applied, self.plan.actions.len()));
+        }
+        summary
+    }
+}
+
+fn plural_files(n: usize) -> String {
+    if n == 1 { "1 file".to_string() } else { format!("{} files", n) }
+}
+
+fn tilde_path(path: &str) -> String {
+    match dirs::home_dir().and_then(|home| Path::new(path).strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.to_string(),
+    }
+}
+
+/// `~/.local/share/little-helper/organize_history.json` on Linux
+pub fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("little-helper").join("organize_history.json"))
+}
+
+/// Load saved runs, dropping any older than the retention period.
+/// A missing or unreadable file means no history.
+pub fn load_history() -> Vec<OrganizeHistoryEntry> {
+    let Some(path) = history_path() else { return Vec::new() };
+    let mut history: Vec<OrganizeHistoryEntry> = fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    prune_history(&mut history, Utc::now());
+    history
+}
+
+pub fn save_history(history: &[OrganizeHistoryEntry]) -> Result<()> {
+    let path = history_path().ok_or_else(|| anyhow::anyhow!("No data directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(history)?)?;
+    Ok(())
+}
+
+/// Drop runs older than the retention period and keep at most
+/// `MAX_HISTORY_ENTRIES`, newest last
+pub fn prune_history(history: &mut Vec<OrganizeHistoryEntry>, now: DateTime<Utc>) {
+    let cutoff = now - Duration::days(HISTORY_RETENTION_DAYS);
+    history.retain(|e| e.timestamp >= cutoff);
+    if history.len() > MAX_HISTORY_ENTRIES {
+        history.drain(..history.len() - MAX_HISTORY_ENTRIES);
+    }
+}
+
+/// Apply a plan and save the run to the history so it can be undone later
+pub fn apply_and_record(plan: ProposedPlan, history: &mut Vec<OrganizeHistoryEntry>) -> Result<ApplyReport> {
+    let report = apply(plan.clone())?;
+    if report.applied > 0 {
+        history.push(OrganizeHistoryEntry::new(plan, report.clone()));
+        prune_history(history, Utc::now());
+        save_history(history)?;
+    }
+    Ok(report)
+}
+
+/// Undo the run at `index` and save the history. See `undo_run`.
+pub fn undo_entry(history: &mut [OrganizeHistoryEntry], index: usize) -> Result<ApplyReport> {
+    let entry = history.get_mut(index).ok_or_else(|| anyhow::anyhow!("No such organizer run"))?;
+    if entry.undone {
+        anyhow::bail!("This organizer run was already undone");
+    }
+    let report = undo_run(entry)?;
+    save_history(history)?;
+    Ok(report)
+}
+
+/// Move the files of a run back. The run is marked as undone only once
+/// every step has been reversed; steps that couldn't be stay in its
+/// report, so undoing it again retries just those. Each of them is in the
+/// returned report's `errors`, including ones skipped because something
+/// is now where the file was.
+fn undo_run(entry: &mut OrganizeHistoryEntry) -> Result<ApplyReport> {
+    let plan = undo_plan(&entry.report);
+    let mut report = apply(plan.clone())?;
+    for action in &plan.actions {
+        let OrganizeAction::Rename { from, to } = action else { continue };
+        let described = format!("Rename {} -> {}", from, to);
+        if !report.completed.contains(action) && !report.errors.iter().any(|e| e.action == described) {
+            report.errors.push(ApplyError { action: described, error: format!("{} already exists", to) });
+        }
+    }
+    report.skipped = 0;
+
+    let reversed = |action: &OrganizeAction| match action {
+        OrganizeAction::Rename { from, to } => OrganizeAction::Rename { from: to.clone(), to: from.clone() },
+        other => other.clone(),
+    };
+    entry.report.completed.retain(|action| !report.completed.contains(&reversed(action)));
+    entry.undone = entry.report.completed.is_empty();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_undo() {
+        let dir = std::env::temp_dir().join(format!("lh-organize-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        fs::write(&file, "hi").unwrap();
+        let archive = dir.join("Archive");
+
+        let plan = build_plan(
+            vec![file.to_string_lossy().into_owned()],
+            Some(archive.to_string_lossy().into_owned()),
+            None,
+        )
+        .unwrap();
+        let report = apply(plan.clone()).unwrap();
+        assert!(archive.join("notes.txt").exists());
+
+        let entry = OrganizeHistoryEntry::new(plan, report.clone());
+        assert!(entry.summary().starts_with("Moved 1 file to "));
+
+        apply(undo_plan(&report)).unwrap();
+        assert!(file.exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_partial_undo_keeps_failed_steps() {
+        let dir = std::env::temp_dir().join(format!("lh-organize-undo-{}", std::process::id()));
+        let archive = dir.join("Archive");
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let paths = vec![a.to_string_lossy().into_owned(), b.to_string_lossy().into_owned()];
+        let plan = build_plan(paths, Some(archive.to_string_lossy().into_owned()), None).unwrap();
+        let mut entry = OrganizeHistoryEntry::new(plan.clone(), apply(plan).unwrap());
+
+        // Something new is where b.txt was, so it can't go back
+        fs::write(&b, "new").unwrap();
+        let report = undo_run(&mut entry).unwrap();
+        assert_eq!(report.applied, 1);
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].action.ends_with("b.txt"));
+        assert!(a.exists() && !entry.undone);
+        assert_eq!(entry.report.completed.len(), 1);
+
+        // Once it's out of the way, undoing again restores just that one
+        fs::remove_file(&b).unwrap();
+        let report = undo_run(&mut entry).unwrap();
+        assert_eq!((report.applied, report.errors.len()), (1, 0));
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+        assert!(entry.undone);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_parse_rename_suggestions() {
+        let paths = vec![PathBuf::from("/p/IMG_20241201_143022.jpg"), PathBuf::from("/p/doc1.txt")];
+        let response = "Here you go:\n\
+            - `IMG_20241201_143022.jpg` -> `beach-sunset-2024-12-01.jpg`\n\
+            doc1.txt -> meeting notes\n\
+            other.txt -> ignored.txt\n\
+            doc1.txt -> ../escape.txt";
+        assert_eq!(
+            parse_rename_suggestions(response, &paths),
+            vec![
+                (paths[0].clone(), "beach-sunset-2024-12-01.jpg".to_string()),
+                (paths[1].clone(), "meeting notes.txt".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prune_history() {
+        let now = Utc::now();
+        let entry = |days: i64| OrganizeHistoryEntry {
+            timestamp: now - Duration::days(days),
+            ..OrganizeHistoryEntry::new(ProposedPlan { actions: vec![] }, apply(ProposedPlan { actions: vec![] }).unwrap())
+        };
+        let mut history = vec![entry(40)];
+        history.extend((0..12).map(|_| entry(1)));
+        prune_history(&mut history, now);
+        assert_eq!(history.len(), MAX_HISTORY_ENTRIES);
+        assert!(history.iter().all(|e| e.timestamp > now - Duration::days(30)));
+    }
+}
@@ -0,0 +1,84 @@
+//! Persona files for Content mode
+//!
+//! Personas are Markdown files describing a target audience, kept in one
+//! of a few well-known folders. The file stem is the persona's name.
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::PathBuf;
+
+/// Folders searched for persona files, in priority order
+pub fn persona_dirs() -> Vec<PathBuf> {
+    let Some(home) = dirs::home_dir() else { return Vec::new() };
+    vec![
+        home.join("Process/personas"),
+        home.join("Projects/personas"),
+        home.join("Documents/personas"),
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct PersonaFile {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// All persona files, sorted by name. If two folders have a persona with
+/// the same name, the one in the earlier folder wins.
+pub fn discover_personas() -> Vec<PersonaFile> {
+    let mut personas: Vec<PersonaFile> = Vec::new();
+    for dir in persona_dirs() {
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        let mut found: Vec<PersonaFile> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "md"))
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_string_lossy().into_owned();
+                Some(PersonaFile { name, path })
+            })
+            .filter(|p| !personas.iter().any(|existing| existing.name == p.name))
+            .collect();
+        personas.append(&mut found);
+    }
+    personas.sort_by(|a, b| a.name.cmp(&b.name));
+    personas
+}
+
+/// One persona as a block of prompt context
+pub fn format_persona(name: &str, content: &str, path: &std::path::Path) -> String {
+    format!(
+        "=== PERSONA: {} ===\nSource: {}\n\n{}\n\n",
+        name.to_uppercase().replace('-', " "),
+        path.display(),
+        content
+    )
+}
+
+/// Load a single persona by name, formatted for the system prompt
+pub fn load_persona_by_name(name: &str) -> Result<String> {
+    let persona = discover_personas()
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!("No persona named '{}' in ~/Process/personas, ~/Projects/personas or ~/Documents/personas", name))?;
+    let content = fs::read_to_string(&persona.path)?;
+
+    Ok(format!(
+        "=== TARGET AUDIENCE PERSONA ===\n\n\
+        Write for this persona. Match their language, concerns, and messaging.\n\n{}\
+        === END PERSONA ===\n\n",
+        format_persona(&persona.name, &content, &persona.path)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_persona() {
+        let block = format_persona("small-business-owner", "Busy.", std::path::Path::new("/p/small-business-owner.md"));
+        assert!(block.starts_with("=== PERSONA: SMALL BUSINESS OWNER ===\nSource: /p/small-business-owner.md"));
+        assert!(load_persona_by_name("no-such-persona-here").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! File size and type statistics for a directory
+//!
+//! Gives the agent (and the chat) a compact overview of a folder instead of
+//! a raw `find` listing: files per extension, total size, and the largest
+//! and most recently modified files.
+
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// How many files the largest / newest lists keep
+const TOP_N: usize = 5;
+
+/// Key used for files without an extension
+pub const NO_EXTENSION: &str = "(none)";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirStats {
+    pub root: PathBuf,
+    /// Extension (lowercase) -> (file count, total bytes)
+    pub by_extension: BTreeMap<String, (usize, u64)>,
+    pub total_files: usize,
+    pub total_bytes: u64,
+    /// Largest files, biggest first
+    pub largest: Vec<(PathBuf, u64)>,
+    /// Most recently modified files, newest first
+    pub newest: Vec<(PathBuf, SystemTime)>,
+}
+
+pub struct DirectoryStats;
+
+impl DirectoryStats {
+    /// Walk `root` and collect statistics. If `allowed_dirs` is not empty,
+    /// `root` must lie inside one of them.
+    pub fn compute(root: &Path, allowed_dirs: &[String]) -> Result<DirStats> {
+        let root = match resolve_allowed_path(root, allowed_dirs)? {
+            r if r.is_dir() => r,
+            r => bail!("{} is not a directory", r.display()),
+        };
+
+        let mut stats = DirStats {
+            root: root.clone(),
+            by_extension: BTreeMap::new(),
+            total_files: 0,
+            total_bytes: 0,
+            largest: Vec::new(),
+            newest: Vec::new(),
+        };
+
+        for entry in WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let meta = match entry.metadata() {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            let size = meta.len();
+            let ext = entry
+                .path()
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_lowercase())
+                .unwrap_or_else(|| NO_EXTENSION.to_string());
+
+            let slot = stats.by_extension.entry(ext).or_insert((0, 0));
+            slot.0 += 1;
+            slot.1 += size;
+            stats.total_files += 1;
+            stats.total_bytes += size;
+
+            stats.largest.push((entry.path().to_path_buf(), size));
+            if stats.largest.len() > TOP_N * 4 {
+                keep_top(&mut stats.largest, |(_, s)| *s);
+            }
+            if let Ok(modified) = meta.modified() {
+                stats.newest.push((entry.path().to_path_buf(), modified));
+                if stats.newest.len() > TOP_N * 4 {
+                    keep_top(&mut stats.newest, |(_, t)| *t);
+                }
+            }
+        }
+
+        keep_top(&mut stats.largest, |(_, s)| *s);
+        keep_top(&mut stats.newest, |(_, t)| *t);
+        Ok(stats)
+    }
+}
+
+/// Sort descending by `key` and keep the first `TOP_N`
+fn keep_top<T, K: Ord>(items: &mut Vec<T>, key: impl Fn(&T) -> K) {
+    items.sort_by_key(|item| std::cmp::Reverse(key(item)));
+    items.truncate(TOP_N);
+}
+
+/// Expand `~` and canonicalize `path`, which must exist. If `allowed_dirs`
+/// is not empty, the path must lie inside one of them.
+pub fn resolve_allowed_path(path: &Path, allowed_dirs: &[String]) -> Result<PathBuf> {
+    let expanded = expand_tilde(path);
+    let Ok(path) = expanded.canonicalize() else {
+        bail!("{} doesn't exist", expanded.display());
+    };
+    if !allowed_dirs.is_empty() {
+        let inside = allowed_dirs
+            .iter()
+            .filter_map(|d| expand_tilde(Path::new(d)).canonicalize().ok())
+            .any(|d| path.starts_with(d));
+        if !inside {
+            bail!("{} is outside the folders Little Helper may access", path.display());
+        }
+    }
+    Ok(path)
+}
+
+fn expand_tilde(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir().map(|h| h.join(rest)).unwrap_or_else(|| path.to_path_buf()),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Human readable size, e.g. "1.4 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+impl DirStats {
+    /// Extensions sorted by total size, biggest first
+    pub fn extensions_by_size(&self) -> Vec<(&str, usize, u64)> {
+        let mut exts: Vec<_> = self
+            .by_extension
+            .iter()
+            .map(|(ext, (count, bytes))| (ext.as_str(), *count, *bytes))
+            .collect();
+        exts.sort_by_key(|(_, _, bytes)| std::cmp::Reverse(*bytes));
+        exts
+    }
+
+    /// Markdown summary for the AI to explain to the user
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "Directory: {}\nTotal: {} files, {}\n\n| Type | Files | Size |\n|---|---|---|\n",
+            self.root.display(),
+            self.total_files,
+            format_bytes(self.total_bytes)
+        );
+        for (ext, count, bytes) in self.extensions_by_size() {
+            out.push_str(&format!("| {} | {} | {} |\n", ext, count, format_bytes(bytes)));
+        }
+
+        out.push_str("\nLargest files:\n");
+        for (path, size) in &self.largest {
+            out.push_str(&format!("- {} ({})\n", path.display(), format_bytes(*size)));
+        }
+        out.push_str("\nRecently modified:\n");
+        for (path, modified) in &self.newest {
+            let when: chrono::DateTime<chrono::Local> = (*modified).into();
+            out.push_str(&format!("- {} ({})\n", path.display(), when.format("%Y-%m-%d %H:%M")));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_compute_stats() {
+        let dir = std::env::temp_dir().join(format!("lh-stats-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "hello").unwrap();
+        fs::write(dir.join("sub/b.TXT"), "hi").unwrap();
+        fs::write(dir.join("c.png"), vec![0u8; 100]).unwrap();
+        fs::write(dir.join("README"), "x").unwrap();
+
+        let stats = DirectoryStats::compute(&dir, &[]).unwrap();
+        assert_eq!(stats.total_files, 4);
+        assert_eq!(stats.total_bytes, 108);
+        assert_eq!(stats.by_extension["txt"], (2, 7));
+        assert_eq!(stats.by_extension[NO_EXTENSION], (1, 1));
+        assert!(stats.largest[0].0.ends_with("c.png"));
+
+        // Outside the allowed folders
+        let other = std::env::temp_dir().join(format!("lh-stats-other-{}", std::process::id()));
+        fs::create_dir_all(&other).unwrap();
+        assert!(DirectoryStats::compute(&dir, &[other.to_string_lossy().into_owned()]).is_err());
+
+        fs::remove_dir_all(&dir).ok();
+        fs::remove_dir_all(&other).ok();
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+    }
+}
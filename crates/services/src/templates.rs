@@ -0,0 +1,153 @@
+//! Content templates
+//!
+//! A template is a starting point for a piece of content: the opening of
+//! the request ("Write a Twitter thread about "), an example of the output
+//! to match, and the platform it's for. A few are built in; users add their
+//! own as TOML files in `~/.config/little-helper/templates/`. A user
+//! template with the same name as a built-in one replaces it.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BUILTIN_TEMPLATES: &[&str] = &[
+    include_str!("../assets/templates/twitter_thread.toml"),
+    include_str!("../assets/templates/linkedin_post.toml"),
+    include_str!("../assets/templates/blog_intro.toml"),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentTemplate {
+    pub name: String,
+    /// "twitter", "instagram", "linkedin", "blog", "email", ...
+    pub platform: String,
+    /// Start of the request, put in the chat input
+    pub prompt_prefix: String,
+    /// Sample of the output, given to the AI as a style guide
+    #[serde(default)]
+    pub example_output: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Bundled with the app rather than loaded from a file
+    #[serde(skip)]
+    pub builtin: bool,
+}
+
+impl ContentTemplate {
+    /// Instructions added to the Content mode system prompt
+    pub fn prompt_context(&self) -> String {
+        let mut context = format!("=== CONTENT TEMPLATE: {} ({}) ===\n", self.name, self.platform);
+        if !self.example_output.trim().is_empty() {
+            context.push_str("Match the structure, length and tone of this example:\n\n");
+            context.push_str(self.example_output.trim());
+            context.push('\n');
+        }
+        context.push_str("=== END TEMPLATE ===\n");
+        context
+    }
+}
+
+pub struct TemplateLibrary {
+    dir: PathBuf,
+    pub templates: Vec<ContentTemplate>,
+}
+
+impl Default for TemplateLibrary {
+    fn default() -> Self {
+        let dir = dirs::config_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("little-helper")
+            .join("templates");
+        Self::load_from(dir)
+    }
+}
+
+impl TemplateLibrary {
+    /// Built-in templates plus every `.toml` file in `dir`, sorted by name.
+    /// Files that don't parse are skipped.
+    pub fn load_from(dir: PathBuf) -> Self {
+        let mut templates: Vec<ContentTemplate> = BUILTIN_TEMPLATES
+            .iter()
+            .filter_map(|text| toml::from_str::<ContentTemplate>(text).ok())
+            .map(|t| ContentTemplate { builtin: true, ..t })
+            .collect();
+
+        let files = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "toml"));
+        for path in files {
+            let Some(template) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|text| toml::from_str::<ContentTemplate>(&text).ok())
+            else {
+                continue;
+            };
+            templates.retain(|t| t.name != template.name);
+            templates.push(template);
+        }
+        templates.sort_by_key(|t| t.name.to_lowercase());
+        Self { dir, templates }
+    }
+
+    /// Write `template` to the templates folder and add it to the library
+    pub fn save(&mut self, template: ContentTemplate) -> Result<PathBuf> {
+        let name = template.name.trim();
+        if name.is_empty() {
+            bail!("Give the template a name");
+        }
+        let path = self.dir.join(format!("{}.toml", file_stem(name)));
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&path, toml::to_string_pretty(&template)?)?;
+
+        *self = Self::load_from(std::mem::take(&mut self.dir));
+        Ok(path)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// "LinkedIn post!" -> "linkedin_post"
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    stem.split('_').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_templates_override_builtin() {
+        let dir = std::env::temp_dir().join(format!("lh-templates-{}", std::process::id()));
+        let mut library = TemplateLibrary::load_from(dir.clone());
+        assert_eq!(library.templates.len(), BUILTIN_TEMPLATES.len());
+
+        let path = library
+            .save(ContentTemplate {
+                name: "Twitter thread".to_string(),
+                platform: "twitter".to_string(),
+                prompt_prefix: "Thread about ".to_string(),
+                example_output: String::new(),
+                tags: vec!["mine".to_string()],
+                builtin: false,
+            })
+            .unwrap();
+        assert_eq!(path.file_name().unwrap(), "twitter_thread.toml");
+
+        let thread: Vec<_> = library.templates.iter().filter(|t| t.name == "Twitter thread").collect();
+        assert_eq!(thread.len(), 1);
+        assert!(!thread[0].builtin);
+        assert_eq!(thread[0].tags, vec!["mine"]);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
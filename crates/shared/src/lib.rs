@@ -1,5 +1,7 @@
 pub mod settings {
     use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct OAuthCredentials {
@@ -8,7 +10,7 @@ pub mod settings {
         pub expires_at: Option<i64>, // Unix timestamp
     }
 
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
     pub struct ProviderAuth {
         pub api_key: Option<String>,
         pub oauth: Option<OAuthCredentials>,
@@ -21,11 +23,77 @@ pub mod settings {
         pub openai_model: String,             // e.g., "gpt-4o-mini"
         pub anthropic_model: String,          // e.g., "claude-3-5-sonnet-20241022"
         pub gemini_model: String,             // e.g., "gemini-1.5-flash"
+        #[serde(default = "default_cohere_model")]
+        pub cohere_model: String,             // e.g., "command-r-plus-08-2024"
 
         // Authentication (either API key or OAuth)
         pub openai_auth: ProviderAuth,
         pub anthropic_auth: ProviderAuth,
         pub gemini_auth: ProviderAuth,
+        #[serde(default)]
+        pub cohere_auth: ProviderAuth,
+
+        /// OpenAI-compatible server to use instead of api.openai.com (Azure
+        /// OpenAI, LM Studio, vLLM), e.g. "http://localhost:1234/v1". Requests
+        /// go to `<base>/chat/completions`.
+        #[serde(default)]
+        pub openai_base_url: Option<String>,
+        /// `api-version` query parameter, which Azure OpenAI requires
+        #[serde(default)]
+        pub openai_api_version: Option<String>,
+
+        /// Provider order for a chat mode ("find", "research", ...), used
+        /// instead of `provider_preference` in that mode
+        #[serde(default)]
+        pub mode_providers: HashMap<String, Vec<String>>,
+
+        /// Retrying a provider after a rate limit or brief outage
+        #[serde(default)]
+        pub retry: RetryPolicy,
+    }
+
+    /// How often a provider is retried, with exponential backoff, before
+    /// the next one is tried
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct RetryPolicy {
+        /// Retries after the first try
+        pub max_attempts: u8,
+        /// Delay before the first retry; doubled for each one after
+        pub base_delay_ms: u64,
+        pub max_delay_ms: u64,
+    }
+
+    impl Default for RetryPolicy {
+        fn default() -> Self {
+            Self { max_attempts: 3, base_delay_ms: 100, max_delay_ms: 30_000 }
+        }
+    }
+
+    pub fn default_cohere_model() -> String {
+        "command-r-plus-08-2024".into()
+    }
+
+    impl ModelProvider {
+        /// Configured model name for a provider ("local", "openai", "anthropic", "gemini", "cohere")
+        pub fn model_for(&self, provider: &str) -> Option<&str> {
+            match provider {
+                "local" => Some(&self.local_model),
+                "openai" => Some(&self.openai_model),
+                "anthropic" => Some(&self.anthropic_model),
+                "gemini" => Some(&self.gemini_model),
+                "cohere" => Some(&self.cohere_model),
+                _ => None,
+            }
+        }
+
+        /// Provider order for `mode`: its own list if it has a non-empty one,
+        /// otherwise `provider_preference`
+        pub fn providers_for_mode(&self, mode: &str) -> &[String] {
+            match self.mode_providers.get(mode) {
+                Some(providers) if !providers.is_empty() => providers,
+                _ => &self.provider_preference,
+            }
+        }
     }
 
     /// User profile for personalization
@@ -48,8 +116,179 @@ pub mod settings {
         pub enabled: bool,
     }
 
+    /// Clock style for chat timestamps
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+    pub enum TimeFormat {
+        /// 12-hour where the locale uses it (en-US), 24-hour elsewhere
+        #[default]
+        SystemLocale,
+        TwentyFourHour,
+        TwelveHour,
+    }
+
+    impl TimeFormat {
+        pub const ALL: [TimeFormat; 3] = [Self::SystemLocale, Self::TwentyFourHour, Self::TwelveHour];
+
+        pub fn label(&self) -> &'static str {
+            match self {
+                Self::SystemLocale => "System default",
+                Self::TwentyFourHour => "24-hour (15:42)",
+                Self::TwelveHour => "12-hour (3:42 PM)",
+            }
+        }
+    }
+
+    /// How open text files notice changes on disk
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+    pub enum WatchMode {
+        /// Change events from the OS
+        Notify,
+        /// Check the modification time every second; works on network drives
+        Poll,
+        #[default]
+        Off,
+    }
+
+    impl WatchMode {
+        pub const ALL: [WatchMode; 3] = [Self::Notify, Self::Poll, Self::Off];
+
+        pub fn label(&self) -> &'static str {
+            match self {
+                Self::Notify => "When the system reports a change",
+                Self::Poll => "Check every second",
+                Self::Off => "Never",
+            }
+        }
+    }
+
+    /// How long the agent's answers should be
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+    pub enum ResponseLength {
+        Brief,
+        #[default]
+        Balanced,
+        Detailed,
+    }
+
+    impl ResponseLength {
+        pub const ALL: [ResponseLength; 3] = [Self::Brief, Self::Balanced, Self::Detailed];
+
+        pub fn label(&self) -> &'static str {
+            match self {
+                Self::Brief => "Brief",
+                Self::Balanced => "Balanced",
+                Self::Detailed => "Detailed",
+            }
+        }
+
+        /// Instruction appended to the system prompt (none for Balanced)
+        pub fn prompt_suffix(&self) -> Option<&'static str> {
+            match self {
+                Self::Brief => Some("Be concise. Use at most 3 sentences per answer."),
+                Self::Balanced => None,
+                Self::Detailed => {
+                    Some("Be thorough. Provide complete explanations, examples, and edge cases.")
+                }
+            }
+        }
+    }
+
+    /// Default response length per chat mode, keyed by lowercase mode name
+    pub fn default_response_lengths() -> HashMap<String, ResponseLength> {
+        [
+            ("find", ResponseLength::Balanced),
+            ("fix", ResponseLength::Brief),
+            ("research", ResponseLength::Detailed),
+            ("data", ResponseLength::Balanced),
+            ("content", ResponseLength::Detailed),
+        ]
+        .into_iter()
+        .map(|(mode, length)| (mode.to_string(), length))
+        .collect()
+    }
+
+    /// Default command timeouts in seconds, keyed by danger level in snake_case
+    pub fn default_timeouts() -> HashMap<String, u64> {
+        [
+            ("safe", 10),
+            ("needs_confirmation", 60),
+            ("dangerous", 120),
+            ("needs_sudo", 300),
+        ]
+        .into_iter()
+        .map(|(level, secs)| (level.to_string(), secs))
+        .collect()
+    }
+
+    /// Default answer-style hint per programming language, keyed by
+    /// lowercase language name
+    pub fn default_language_hints() -> HashMap<String, String> {
+        [
+            ("rust", "The user is working in Rust. Prefer Rust answers using the 2021 edition, idiomatic ownership and borrowing, `Result` and `?` for errors, and the standard library before crates."),
+            ("python", "The user is working in Python 3. Prefer Python answers, use f-strings, type hints where appropriate, and reference the standard library."),
+            ("javascript", "The user is working in JavaScript. Prefer modern JavaScript answers with `const`/`let`, arrow functions, `async`/`await` and ES modules."),
+            ("typescript", "The user is working in TypeScript. Prefer TypeScript answers with precise types, interfaces where they help, `async`/`await` and ES modules."),
+            ("go", "The user is working in Go. Prefer idiomatic Go answers: explicit error handling, small interfaces, and the standard library before third-party packages."),
+            ("ruby", "The user is working in Ruby. Prefer idiomatic Ruby answers using blocks and Enumerable methods, and reference the standard library."),
+        ]
+        .into_iter()
+        .map(|(language, hint)| (language.to_string(), hint.to_string()))
+        .collect()
+    }
+
+    /// Default width of the Files side panel in points
+    pub fn default_file_browser_width() -> f32 {
+        240.0
+    }
+
+    /// `audit.log` in the app's data folder
+    pub fn default_audit_log_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|d| d.join("little-helper").join("audit.log"))
+    }
+
+    /// A social media post planned in Content mode
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct ContentSchedule {
+        pub post: String,
+        pub platform: String,
+        pub scheduled_at: chrono::DateTime<chrono::Utc>,
+    }
+
+    /// Per-session limits on what the agent may spend
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct AgentGuardrails {
+        /// Estimated API spend in USD (None = unlimited)
+        pub max_cost_usd: Option<f64>,
+        /// Minutes the agent may spend on one request (None = unlimited)
+        pub max_session_minutes: Option<u32>,
+        /// Commands the agent may run on its own per session
+        pub max_commands_per_session: u32,
+    }
+
+    impl Default for AgentGuardrails {
+        fn default() -> Self {
+            Self {
+                max_cost_usd: Some(1.0),
+                max_session_minutes: Some(120),
+                max_commands_per_session: 50,
+            }
+        }
+    }
+
+    /// Layout version of `settings.json` written by this build. The app
+    /// migrates files from older versions up to it before loading them.
+    pub const SETTINGS_VERSION: u32 = 2;
+
+    /// Files from before the version was recorded
+    fn first_settings_version() -> u32 {
+        1
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct AppSettings {
+        /// Layout version the file was written with
+        #[serde(default = "first_settings_version")]
+        pub settings_version: u32,
         pub allowed_dirs: Vec<String>,
         pub model: ModelProvider,
         pub enable_internet_research: bool,
@@ -57,20 +296,111 @@ pub mod settings {
         pub user_profile: UserProfile,
         #[serde(default)]
         pub slack: SlackSettings,
+        /// Response length per chat mode ("find", "fix", "research", "data", "content")
+        #[serde(default = "default_response_lengths")]
+        pub response_length: HashMap<String, ResponseLength>,
+        #[serde(default)]
+        pub guardrails: AgentGuardrails,
+        /// Posts planned in Content mode
+        #[serde(default)]
+        pub schedule: Vec<ContentSchedule>,
+        /// Command timeouts in seconds per danger level ("safe", "needs_confirmation", ...)
+        #[serde(default = "default_timeouts")]
+        pub timeouts: HashMap<String, u64>,
+        /// Command prefixes run without confirmation, on top of the built-in list
+        #[serde(default)]
+        pub extra_safe_commands: Vec<String>,
+        /// Command prefixes that are never run
+        #[serde(default)]
+        pub extra_blocked_commands: Vec<String>,
+        /// Command prefixes that always ask first, even if built-in lists say safe
+        #[serde(default)]
+        pub extra_needs_confirmation_commands: Vec<String>,
+        /// Show every command in a response for review before any of them run
+        #[serde(default)]
+        pub preview_batch: bool,
+        /// Offer to allow a folder outside `allowed_dirs` for the rest of
+        /// the session when a command needs it, instead of keeping it out
+        #[serde(default)]
+        pub auto_extend_allowed_dirs: bool,
+        /// Show per-turn timings under assistant messages
+        #[serde(default)]
+        pub debug_mode: bool,
+        /// Read new assistant messages aloud
+        #[serde(default)]
+        pub tts_enabled: bool,
+        /// Voice name for text-to-speech, `None` for the system default
+        #[serde(default)]
+        pub tts_voice: Option<String>,
+        /// Width of the Files side panel, remembered between sessions
+        #[serde(default = "default_file_browser_width")]
+        pub file_browser_width: f32,
+        /// Clock style for chat timestamps
+        #[serde(default)]
+        pub time_format: TimeFormat,
+        /// Reload open text files when they change
+        #[serde(default)]
+        pub file_watch: WatchMode,
+        /// Open the first file a reply mentions in the preview panel
+        #[serde(default)]
+        pub auto_open_files: bool,
+        /// Ask the AI for a one sentence summary of long command output.
+        /// The output is sent to the first provider that's set up.
+        #[serde(default)]
+        pub ai_command_summaries: bool,
+        /// Added to the system prompt when the open file is in one of these
+        /// languages ("rust", "python", ...)
+        #[serde(default = "default_language_hints")]
+        pub language_hints: HashMap<String, String>,
+        /// File each command run is recorded in, with its danger level,
+        /// exit code and duration; `None` keeps no record
+        #[serde(default = "default_audit_log_path")]
+        pub audit_log_path: Option<PathBuf>,
     }
 
-    impl Default for ProviderAuth {
-        fn default() -> Self {
-            Self {
-                api_key: None,
-                oauth: None,
-            }
+    /// Characters that can't appear in a user-defined command prefix
+    const SHELL_METACHARACTERS: &[char] = &[
+        '|', '&', ';', '<', '>', '$', '`', '(', ')', '{', '}', '[', ']', '*', '?', '!', '~', '\\',
+        '\'', '"', '#',
+    ];
+
+    /// Check a user-defined command entry: a single word or a simple prefix
+    /// like "git stash list", with no shell metacharacters. `*` and `?` make
+    /// the entry a pattern, like "~/bin/*" (which may start with `~/`), but
+    /// it can't start with one. Returns the normalized entry (trimmed,
+    /// single spaces).
+    pub fn validate_command_prefix(entry: &str) -> Result<String, String> {
+        let words: Vec<&str> = entry.split_whitespace().collect();
+        if words.is_empty() {
+            return Err("Enter a command name".to_string());
+        }
+        let entry = words.join(" ");
+        let body = entry.strip_prefix("~/").unwrap_or(&entry);
+        if let Some(c) = body.chars().find(|c| SHELL_METACHARACTERS.contains(c) && !matches!(c, '*' | '?')) {
+            return Err(format!("'{}' isn't allowed - enter just the command name, like \"rg\"", c));
+        }
+        if entry.starts_with(['*', '?']) {
+            return Err("Start with a command or folder, like \"~/bin/*\"".to_string());
+        }
+        Ok(entry)
+    }
+
+    impl AppSettings {
+        /// Response length for a mode, falling back to Balanced
+        pub fn response_length_for(&self, mode: &str) -> ResponseLength {
+            self.response_length.get(mode).copied().unwrap_or_default()
+        }
+
+        /// Hint for a language key, if there is a non-empty one
+        pub fn language_hint(&self, language: &str) -> Option<&str> {
+            self.language_hints.get(language).map(|h| h.trim()).filter(|h| !h.is_empty())
         }
     }
 
     impl Default for AppSettings {
         fn default() -> Self {
             Self {
+                settings_version: SETTINGS_VERSION,
                 allowed_dirs: vec![],
                 model: ModelProvider {
                     local_model: "llama3.2:3b".into(),
@@ -78,26 +408,91 @@ pub mod settings {
                     openai_model: "gpt-4o-mini".into(),
                     anthropic_model: "claude-3-5-sonnet-20241022".into(),
                     gemini_model: "gemini-1.5-flash".into(),
+                    cohere_model: default_cohere_model(),
                     openai_auth: ProviderAuth::default(),
                     anthropic_auth: ProviderAuth::default(),
                     gemini_auth: ProviderAuth::default(),
+                    cohere_auth: ProviderAuth::default(),
+                    openai_base_url: None,
+                    openai_api_version: None,
+                    mode_providers: HashMap::new(),
+                    retry: RetryPolicy::default(),
                 },
                 enable_internet_research: false,
                 max_results: 200,
                 user_profile: UserProfile::default(),
                 slack: SlackSettings::default(),
+                response_length: default_response_lengths(),
+                guardrails: AgentGuardrails::default(),
+                schedule: Vec::new(),
+                timeouts: default_timeouts(),
+                extra_safe_commands: Vec::new(),
+                extra_blocked_commands: Vec::new(),
+                extra_needs_confirmation_commands: Vec::new(),
+                preview_batch: false,
+                auto_extend_allowed_dirs: false,
+                debug_mode: false,
+                tts_enabled: false,
+                tts_voice: None,
+                file_browser_width: default_file_browser_width(),
+                time_format: TimeFormat::default(),
+                file_watch: WatchMode::default(),
+                auto_open_files: false,
+                ai_command_summaries: false,
+                language_hints: default_language_hints(),
+                audit_log_path: default_audit_log_path(),
             }
         }
     }
 }
 
 pub mod agent_api {
+    use base64::Engine;
     use serde::{Deserialize, Serialize};
 
+    /// Extra content sent along with a message's text
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub enum MessageAttachment {
+        /// Raw image bytes, base64 encoded for providers with vision support
+        Image(Vec<u8>),
+    }
+
+    impl MessageAttachment {
+        /// MIME type sniffed from the file header (PNG, JPEG, GIF or WebP)
+        pub fn media_type(&self) -> &'static str {
+            match self {
+                MessageAttachment::Image(bytes) => {
+                    if bytes.starts_with(b"\x89PNG") {
+                        "image/png"
+                    } else if bytes.starts_with(b"GIF8") {
+                        "image/gif"
+                    } else if bytes.len() > 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+                        "image/webp"
+                    } else {
+                        "image/jpeg"
+                    }
+                }
+            }
+        }
+
+        pub fn to_base64(&self) -> String {
+            match self {
+                MessageAttachment::Image(bytes) => base64::engine::general_purpose::STANDARD.encode(bytes),
+            }
+        }
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct ChatMessage {
         pub role: String, // "system" | "user" | "assistant"
         pub content: String,
+        #[serde(default)]
+        pub attachments: Vec<MessageAttachment>,
+    }
+
+    /// Rough token estimate (about 4 characters per token)
+    pub fn token_count(text: &str) -> usize {
+        text.chars().count().div_ceil(4)
     }
 }
 
@@ -108,6 +503,11 @@ pub mod search_types {
     pub struct SearchQuery {
         pub text: String,
         pub extensions: Option<Vec<String>>, // e.g., ["pdf","md"]
+        /// Glob like `**/*.rs` or `src/**/*.toml`, matched against the path
+        /// inside the allowed folder, or against the file name when it has
+        /// no `/` (`Cargo.toml`, `*.test.ts`)
+        #[serde(default)]
+        pub pattern: Option<String>,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -117,5 +517,8 @@ pub mod search_types {
         pub size_bytes: u64,
         pub modified: Option<i64>, // unix timestamp
         pub score: f32,
+        /// Lines matching the query text, with the matches in `**bold**`
+        #[serde(default)]
+        pub content_snippet: Option<String>,
     }
 }
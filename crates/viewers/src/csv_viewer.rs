@@ -1,19 +1,45 @@
-//! CSV/TSV viewer with table display, sorting, and filtering
+//! CSV/TSV viewer with table display, sorting, filtering and paging
 
+use crate::Viewer;
 use anyhow::Result;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
 
+/// Column widths are measured from this many rows
+const WIDTH_SAMPLE_ROWS: usize = 200;
+const MIN_COLUMN_WIDTH: f32 = 60.0;
+const MAX_COLUMN_WIDTH: f32 = 300.0;
+/// Cells longer than this are cut short with "..."
+const MAX_CELL_CHARS: usize = 50;
+/// Rows shown at once; big files are paged through
+const PAGE_ROWS: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortDir {
+    Ascending,
+    Descending,
+}
+
 /// CSV viewer state
 pub struct CsvViewer {
     path: Option<PathBuf>,
     headers: Vec<String>,
     rows: Vec<Vec<String>>,
     sort_column: Option<usize>,
-    sort_ascending: bool,
+    sort_direction: SortDir,
     filter_text: String,
+    /// Rows matching the filter, in display order
     filtered_indices: Vec<usize>,
+    page: usize,
+    /// Keep the header row in view while scrolling down
+    pub freeze_header: bool,
+    /// Keep the first column in view while scrolling across
+    pub freeze_first_column: bool,
+    /// Measured on first draw, since that needs the fonts
+    column_widths: Vec<f32>,
+    /// Scroll position of the table body, copied to the frozen parts
+    scroll_offset: egui::Vec2,
 }
 
 impl Default for CsvViewer {
@@ -29,9 +55,14 @@ impl CsvViewer {
             headers: Vec::new(),
             rows: Vec::new(),
             sort_column: None,
-            sort_ascending: true,
+            sort_direction: SortDir::Ascending,
             filter_text: String::new(),
             filtered_indices: Vec::new(),
+            page: 0,
+            freeze_header: true,
+            freeze_first_column: true,
+            column_widths: Vec::new(),
+            scroll_offset: egui::Vec2::ZERO,
         }
     }
 
@@ -69,6 +100,7 @@ impl CsvViewer {
         self.path = Some(path.to_path_buf());
         self.sort_column = None;
         self.filter_text.clear();
+        self.column_widths.clear();
         self.update_filtered_indices();
 
         Ok(())
@@ -94,6 +126,7 @@ impl CsvViewer {
         }
 
         self.path = None;
+        self.column_widths.clear();
         self.update_filtered_indices();
         Ok(())
     }
@@ -145,25 +178,122 @@ impl CsvViewer {
                     _ => val_a.cmp(val_b),
                 };
 
-                if self.sort_ascending {
-                    cmp
-                } else {
-                    cmp.reverse()
+                match self.sort_direction {
+                    SortDir::Ascending => cmp,
+                    SortDir::Descending => cmp.reverse(),
                 }
             });
         }
+        self.set_page(0);
     }
 
+    /// Clicking a header sorts by it ascending, then descending, then
+    /// back to file order
     fn sort_by_column(&mut self, col: usize) {
-        if self.sort_column == Some(col) {
-            self.sort_ascending = !self.sort_ascending;
-        } else {
-            self.sort_column = Some(col);
-            self.sort_ascending = true;
+        match (self.sort_column, self.sort_direction) {
+            (Some(sorted), SortDir::Ascending) if sorted == col => self.sort_direction = SortDir::Descending,
+            (Some(sorted), SortDir::Descending) if sorted == col => self.sort_column = None,
+            _ => {
+                self.sort_column = Some(col);
+                self.sort_direction = SortDir::Ascending;
+            }
         }
         self.update_filtered_indices();
     }
 
+    fn page_count(&self) -> usize {
+        self.filtered_indices.len().div_ceil(PAGE_ROWS).max(1)
+    }
+
+    /// Show `page`, from its top
+    fn set_page(&mut self, page: usize) {
+        self.page = page.min(self.page_count() - 1);
+        self.scroll_offset.y = 0.0;
+    }
+
+    /// Indices of the rows on the current page
+    fn page_indices(&self) -> &[usize] {
+        let start = (self.page * PAGE_ROWS).min(self.filtered_indices.len());
+        let end = (start + PAGE_ROWS).min(self.filtered_indices.len());
+        &self.filtered_indices[start..end]
+    }
+
+    /// Width for each column: enough for its header and the widest of the
+    /// first rows, within limits
+    fn measure_columns(&mut self, ui: &egui::Ui) {
+        let font = egui::TextStyle::Body.resolve(ui.style());
+        let padding = ui.spacing().button_padding.x * 2.0 + 16.0;
+        let text_width = |text: &str| {
+            ui.fonts(|f| f.layout_no_wrap(text.to_string(), font.clone(), egui::Color32::WHITE).size().x)
+        };
+        self.column_widths = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(col, header)| {
+                let widest_cell = self
+                    .rows
+                    .iter()
+                    .take(WIDTH_SAMPLE_ROWS)
+                    .filter_map(|row| row.get(col))
+                    .map(|cell| text_width(&display_cell(cell)))
+                    .fold(0.0, f32::max);
+                (text_width(header) + padding).max(widest_cell + 8.0).clamp(MIN_COLUMN_WIDTH, MAX_COLUMN_WIDTH)
+            })
+            .collect();
+    }
+
+    /// Header buttons for `cols`; returns the column clicked to sort by
+    fn header_cells(&self, ui: &mut egui::Ui, cols: std::ops::Range<usize>, height: f32) -> Option<usize> {
+        let mut clicked = None;
+        for col in cols {
+            let arrow = match self.sort_column {
+                Some(sorted) if sorted == col => match self.sort_direction {
+                    SortDir::Ascending => " ^",
+                    SortDir::Descending => " v",
+                },
+                _ => "",
+            };
+            let button = egui::Button::new(format!("{}{}", self.headers[col], arrow));
+            if ui.add_sized([self.column_widths[col], height], button).clicked() {
+                clicked = Some(col);
+            }
+        }
+        clicked
+    }
+
+    /// Table row `row` (counting the header as row 0 when it isn't frozen)
+    /// for columns `cols`, on a striped background
+    fn row_cells(&self, ui: &mut egui::Ui, row: usize, cols: std::ops::Range<usize>, height: f32) -> Option<usize> {
+        let header_rows = usize::from(!self.freeze_header);
+        if row < header_rows {
+            return ui.horizontal(|ui| self.header_cells(ui, cols, height)).inner;
+        }
+        let cells = self.page_indices().get(row - header_rows).and_then(|&i| self.rows.get(i))?;
+        let background = ui.painter().add(egui::Shape::Noop);
+        let response = ui
+            .horizontal(|ui| {
+                for col in cols {
+                    let width = self.column_widths[col];
+                    let text = cells.get(col).map(|c| display_cell(c)).unwrap_or_default();
+                    ui.allocate_ui_with_layout(
+                        egui::vec2(width, height),
+                        egui::Layout::left_to_right(egui::Align::Center),
+                        |ui| {
+                            ui.set_width(width);
+                            ui.add(egui::Label::new(text).truncate(true));
+                        },
+                    );
+                }
+            })
+            .response;
+        if (row - header_rows) % 2 == 1 {
+            let rect = response.rect.expand2(egui::vec2(0.0, ui.spacing().item_spacing.y / 2.0));
+            ui.painter().set(background, egui::Shape::rect_filled(rect, 0.0, ui.visuals().faint_bg_color));
+        }
+        None
+    }
+
     pub fn ui(&mut self, ui: &mut egui::Ui) {
         // Toolbar
         ui.horizontal(|ui| {
@@ -180,6 +310,19 @@ impl CsvViewer {
                 self.row_count()
             ));
 
+            ui.separator();
+            if ui.add_enabled(self.page > 0, egui::Button::new("< Prev")).clicked() {
+                self.set_page(self.page - 1);
+            }
+            ui.label(format!("Page {} of {}", self.page + 1, self.page_count()));
+            if ui.add_enabled(self.page + 1 < self.page_count(), egui::Button::new("Next >")).clicked() {
+                self.set_page(self.page + 1);
+            }
+
+            ui.separator();
+            ui.checkbox(&mut self.freeze_header, "Freeze header");
+            ui.checkbox(&mut self.freeze_first_column, "Freeze first column");
+
             if let Some(path) = &self.path {
                 ui.separator();
                 ui.label(
@@ -201,64 +344,115 @@ impl CsvViewer {
             return;
         }
 
-        egui::ScrollArea::both()
-            .auto_shrink([false, false])
-            .show(ui, |ui| {
-                egui::Grid::new("csv_table")
-                    .num_columns(self.headers.len())
-                    .striped(true)
-                    .min_col_width(60.0)
+        if self.column_widths.len() != self.headers.len() {
+            self.measure_columns(ui);
+        }
+        let row_height = ui.spacing().interact_size.y;
+        let columns = self.headers.len();
+        let frozen = if self.freeze_first_column && columns > 1 { 1 } else { 0 };
+        let total_rows = self.page_indices().len() + usize::from(!self.freeze_header);
+        // Lets the frozen parts scroll as far as the body, whose scroll bars take up room
+        let scroll_bar_room = ui.spacing().scroll.bar_width + ui.spacing().scroll.bar_outer_margin + 8.0;
+        let mut clicked_col = None;
+        let mut offset = self.scroll_offset;
+
+        if self.freeze_header {
+            ui.horizontal(|ui| {
+                clicked_col = clicked_col.or(self.header_cells(ui, 0..frozen, row_height));
+                let header = egui::ScrollArea::horizontal()
+                    .id_source("csv_header")
+                    .horizontal_scroll_offset(self.scroll_offset.x)
+                    .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysHidden)
+                    .enable_scrolling(false)
+                    .auto_shrink([false, true])
                     .show(ui, |ui| {
-                        // Header row - collect clicks first to avoid borrow issues
-                        let mut clicked_col: Option<usize> = None;
-                        for (col, header) in self.headers.iter().enumerate() {
-                            let is_sorted = self.sort_column == Some(col);
-                            let arrow = if is_sorted {
-                                if self.sort_ascending {
-                                    " ^"
-                                } else {
-                                    " v"
-                                }
-                            } else {
-                                ""
-                            };
-
-                            if ui.button(format!("{}{}", header, arrow)).clicked() {
-                                clicked_col = Some(col);
-                            }
-                        }
-                        ui.end_row();
+                        ui.horizontal(|ui| {
+                            let clicked = self.header_cells(ui, frozen..columns, row_height);
+                            ui.add_space(scroll_bar_room);
+                            clicked
+                        })
+                        .inner
+                    });
+                clicked_col = clicked_col.or(header.inner);
+            });
+        }
 
-                        // Handle click after iteration
-                        if let Some(col) = clicked_col {
-                            self.sort_by_column(col);
+        ui.horizontal_top(|ui| {
+            let mut first_column_scrolled = false;
+            if frozen > 0 {
+                let first_column = egui::ScrollArea::vertical()
+                    .id_source("csv_first_column")
+                    .vertical_scroll_offset(self.scroll_offset.y)
+                    .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysHidden)
+                    .auto_shrink([true, false])
+                    .show_rows(ui, row_height, total_rows + 1, |ui, rows| {
+                        let mut clicked = None;
+                        for row in rows {
+                            clicked = clicked.or(self.row_cells(ui, row, 0..frozen, row_height));
                         }
+                        clicked
+                    });
+                clicked_col = clicked_col.or(first_column.inner);
+                if (first_column.state.offset.y - self.scroll_offset.y).abs() > 0.5 {
+                    offset.y = first_column.state.offset.y;
+                    first_column_scrolled = true;
+                }
+            }
 
-                        // Data rows (limited for performance)
-                        let max_display = 1000;
-                        for &row_idx in self.filtered_indices.iter().take(max_display) {
-                            if let Some(row) = self.rows.get(row_idx) {
-                                for cell in row.iter() {
-                                    // Truncate long cells
-                                    let display = if cell.len() > 50 {
-                                        format!("{}...", &cell[..47])
-                                    } else {
-                                        cell.clone()
-                                    };
-                                    ui.label(display);
-                                }
-                                ui.end_row();
-                            }
-                        }
+            let body = egui::ScrollArea::both()
+                .id_source("csv_body")
+                .scroll_offset(self.scroll_offset)
+                .auto_shrink([false, false])
+                .show_rows(ui, row_height, total_rows, |ui, rows| {
+                    let mut clicked = None;
+                    for row in rows {
+                        clicked = clicked.or(self.row_cells(ui, row, frozen..columns, row_height));
+                    }
+                    clicked
+                });
+            clicked_col = clicked_col.or(body.inner);
+            offset.x = body.state.offset.x;
+            if !first_column_scrolled {
+                offset.y = body.state.offset.y;
+            }
+        });
 
-                        if self.filtered_indices.len() > max_display {
-                            ui.label(format!(
-                                "... and {} more rows",
-                                self.filtered_indices.len() - max_display
-                            ));
-                            ui.end_row();
-                        }
-                    });
-            });
+        if offset != self.scroll_offset {
+            // The frozen parts catch up on the next frame
+            self.scroll_offset = offset;
+            ui.ctx().request_repaint();
+        }
+        if let Some(col) = clicked_col {
+            self.sort_by_column(col);
+        }
+    }
+}
+
+/// `cell` cut to `MAX_CELL_CHARS`
+pub(crate) fn display_cell(cell: &str) -> String {
+    if cell.chars().count() > MAX_CELL_CHARS {
+        let mut short: String = cell.chars().take(MAX_CELL_CHARS - 3).collect();
+        short.push_str("...");
+        short
+    } else {
+        cell.to_string()
+    }
+}
+
+impl Viewer for CsvViewer {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        CsvViewer::load(self, path)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        CsvViewer::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        CsvViewer::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        CsvViewer::is_loaded(self)
     }
 }
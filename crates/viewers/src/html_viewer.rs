@@ -1,5 +1,6 @@
 //! HTML Viewer - displays HTML with option to open in browser
 
+use crate::Viewer;
 use anyhow::Result;
 use egui::{self, ScrollArea};
 use std::path::{Path, PathBuf};
@@ -243,3 +244,21 @@ fn strip_html_tags(html: &str) -> String {
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+impl Viewer for HtmlViewer {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        HtmlViewer::load(self, path)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        HtmlViewer::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        HtmlViewer::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        HtmlViewer::is_loaded(self)
+    }
+}
@@ -1,12 +1,48 @@
 //! Image viewer with zoom and pan
 
+use crate::Viewer;
 use anyhow::Result;
 use std::path::{Path, PathBuf};
 
+/// Load an image file into an egui texture.
+///
+/// With `max_dim` set, the image is downscaled (keeping aspect ratio) so
+/// neither side exceeds it - used for thumbnails.
+pub fn load_texture(
+    path: &Path,
+    ctx: &egui::Context,
+    max_dim: Option<u32>,
+) -> Result<egui::TextureHandle> {
+    let color_image = decode_image(path, max_dim)?;
+    let name = match max_dim {
+        Some(max) => format!("{}@{}", path.to_string_lossy(), max),
+        None => path.to_string_lossy().to_string(),
+    };
+    Ok(ctx.load_texture(name, color_image, egui::TextureOptions::LINEAR))
+}
+
+/// Read and decode an image file. Unlike making the texture, this can run
+/// on any thread.
+pub fn decode_image(path: &Path, max_dim: Option<u32>) -> Result<egui::ColorImage> {
+    let image_data = std::fs::read(path)?;
+    let mut image = image::load_from_memory(&image_data)?;
+    if let Some(max) = max_dim {
+        if image.width() > max || image.height() > max {
+            image = image.thumbnail(max, max);
+        }
+    }
+    let rgba = image.to_rgba8();
+    let size = [rgba.width() as usize, rgba.height() as usize];
+    Ok(egui::ColorImage::from_rgba_unmultiplied(size, &rgba))
+}
+
 /// Image viewer state
 pub struct ImageViewer {
     path: Option<PathBuf>,
     texture: Option<egui::TextureHandle>,
+    /// Decoded by [`Viewer::load`], which has no context to make the
+    /// texture in; it's made on the next `ui` call
+    pending: Option<egui::ColorImage>,
     image_size: Option<[usize; 2]>,
     zoom: f32,
     pan_offset: egui::Vec2,
@@ -24,6 +60,7 @@ impl ImageViewer {
         Self {
             path: None,
             texture: None,
+            pending: None,
             image_size: None,
             zoom: 1.0,
             pan_offset: egui::Vec2::ZERO,
@@ -32,18 +69,19 @@ impl ImageViewer {
     }
 
     pub fn load(&mut self, path: &Path, ctx: &egui::Context) -> Result<()> {
-        let image_data = std::fs::read(path)?;
-        let image = image::load_from_memory(&image_data)?;
-        let rgba = image.to_rgba8();
-        let size = [rgba.width() as usize, rgba.height() as usize];
+        let texture = load_texture(path, ctx, None)?;
+        self.show_texture(path, texture);
+        Ok(())
+    }
 
-        let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &rgba);
+    /// Show an image already decoded with [`decode_image`]
+    pub fn load_decoded(&mut self, path: &Path, image: egui::ColorImage, ctx: &egui::Context) {
+        let texture = ctx.load_texture(path.to_string_lossy(), image, egui::TextureOptions::LINEAR);
+        self.show_texture(path, texture);
+    }
 
-        let texture = ctx.load_texture(
-            path.to_string_lossy(),
-            color_image,
-            egui::TextureOptions::LINEAR,
-        );
+    fn show_texture(&mut self, path: &Path, texture: egui::TextureHandle) {
+        let size = texture.size();
 
         self.texture = Some(texture);
         self.image_size = Some(size);
@@ -51,8 +89,6 @@ impl ImageViewer {
         self.zoom = 1.0;
         self.pan_offset = egui::Vec2::ZERO;
         self.fit_to_window = true;
-
-        Ok(())
     }
 
     pub fn path(&self) -> Option<&Path> {
@@ -64,6 +100,10 @@ impl ImageViewer {
     }
 
     pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if let (Some(image), Some(path)) = (self.pending.take(), self.path.clone()) {
+            self.load_decoded(&path, image, ui.ctx());
+        }
+
         // Toolbar
         ui.horizontal(|ui| {
             if ui.button("-").clicked() {
@@ -161,3 +201,26 @@ impl ImageViewer {
         }
     }
 }
+
+impl Viewer for ImageViewer {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        let image = decode_image(path, None)?;
+        self.texture = None;
+        self.image_size = Some(image.size);
+        self.pending = Some(image);
+        self.path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ImageViewer::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        ImageViewer::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        ImageViewer::is_loaded(self) || self.pending.is_some()
+    }
+}
@@ -1,11 +1,18 @@
-//! JSON viewer with tree view and raw mode
+//! JSON viewer with tree view and raw mode, search, and validation
+//! against a JSON Schema
+//!
+//! Nodes are identified by paths like `$.users[3].email`; right-click one
+//! to copy its path or value.
 
-use anyhow::Result;
+use crate::Viewer;
+use anyhow::{anyhow, Result};
 use serde_json::Value;
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+const MATCH_BACKGROUND: egui::Color32 = egui::Color32::from_rgb(110, 90, 20);
+
 /// JSON viewer state
 pub struct JsonViewer {
     path: Option<PathBuf>,
@@ -13,6 +20,44 @@ pub struct JsonViewer {
     raw_content: String,
     show_raw: bool,
     expanded_paths: HashSet<String>,
+    /// Schema used by "Validate"; the app picks it and remembers it
+    schema_path: Option<PathBuf>,
+    /// "Validate" was clicked with no schema chosen yet, or "Schema…"
+    schema_requested: bool,
+    validation: Option<Result<ValidationReport, String>>,
+    /// Node to scroll to on the next frame, after a click in the error list
+    scroll_to: Option<String>,
+    /// Show only the first line of multi-line strings, as YAML block
+    /// scalars usually are
+    fold_multiline: bool,
+    /// Key name or value text to look for
+    search: String,
+    /// Paths of the nodes matching `search`, in document order
+    matches: Vec<String>,
+    match_set: HashSet<String>,
+    /// The match last scrolled to
+    current_match: usize,
+    /// Levels opened by "Expand to depth"
+    expand_depth: usize,
+}
+
+/// Result of checking the loaded JSON against a schema
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<SchemaError>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaError {
+    /// Node in the tree view's form, e.g. `$.items[2].name`
+    pub path: String,
+    pub message: String,
 }
 
 impl Default for JsonViewer {
@@ -29,6 +74,16 @@ impl JsonViewer {
             raw_content: String::new(),
             show_raw: false,
             expanded_paths: HashSet::new(),
+            schema_path: None,
+            schema_requested: false,
+            validation: None,
+            scroll_to: None,
+            fold_multiline: false,
+            search: String::new(),
+            matches: Vec::new(),
+            match_set: HashSet::new(),
+            current_match: 0,
+            expand_depth: 2,
         }
     }
 
@@ -40,12 +95,29 @@ impl JsonViewer {
     }
 
     pub fn load_string(&mut self, content: &str) -> Result<()> {
-        self.raw_content = content.to_string();
-        self.value = Some(serde_json::from_str(content)?);
+        let value = serde_json::from_str(content)?;
+        self.load_value(content.to_string(), value);
+        Ok(())
+    }
+
+    /// Show `value`, parsed by the caller from `raw_content` (e.g. YAML,
+    /// which the raw view then shows as written)
+    pub(crate) fn load_value(&mut self, raw_content: String, value: Value) {
+        self.raw_content = raw_content;
+        self.value = Some(value);
         self.expanded_paths.clear();
+        self.validation = None;
         // Auto-expand root
         self.expanded_paths.insert("$".to_string());
-        Ok(())
+        self.update_matches();
+    }
+
+    pub(crate) fn set_path(&mut self, path: &Path) {
+        self.path = Some(path.to_path_buf());
+    }
+
+    pub(crate) fn fold_multiline_strings(&mut self) {
+        self.fold_multiline = true;
     }
 
     pub fn path(&self) -> Option<&Path> {
@@ -56,6 +128,173 @@ impl JsonViewer {
         self.value.is_some()
     }
 
+    pub fn schema_path(&self) -> Option<&Path> {
+        self.schema_path.as_deref()
+    }
+
+    pub fn set_schema_path(&mut self, schema_path: PathBuf) {
+        self.schema_path = Some(schema_path);
+    }
+
+    /// True once after the user asked to choose a schema file
+    pub fn take_schema_request(&mut self) -> bool {
+        std::mem::take(&mut self.schema_requested)
+    }
+
+    /// Check the loaded JSON against the schema at `schema_path`. The
+    /// result is also kept for the toolbar badge.
+    pub fn validate_against_schema(&mut self, schema_path: &Path) -> Result<ValidationReport> {
+        let result = self.check_schema(schema_path);
+        self.schema_path = Some(schema_path.to_path_buf());
+        self.validation = Some(result.as_ref().map(Clone::clone).map_err(|e| e.to_string()));
+        result
+    }
+
+    fn check_schema(&self, schema_path: &Path) -> Result<ValidationReport> {
+        let value = self.value.as_ref().ok_or_else(|| anyhow!("No JSON loaded"))?;
+        let schema: Value = serde_json::from_str(&fs::read_to_string(schema_path)?)?;
+        let compiled = jsonschema::JSONSchema::compile(&schema).map_err(|e| anyhow!("Invalid schema: {}", e))?;
+        let errors = match compiled.validate(value) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|e| SchemaError { path: tree_path(&e.instance_path), message: e.to_string() })
+                .collect(),
+        };
+        Ok(ValidationReport { errors })
+    }
+
+    /// Expand everything above `path` and scroll to it
+    fn reveal(&mut self, path: &str) {
+        for (i, c) in path.char_indices() {
+            if c == '.' || c == '[' {
+                self.expanded_paths.insert(path[..i].to_string());
+            }
+        }
+        self.show_raw = false;
+        self.scroll_to = Some(path.to_string());
+    }
+
+    /// Find the nodes matching `search` and scroll to the first
+    fn update_matches(&mut self) {
+        self.matches.clear();
+        let query = self.search.trim().to_lowercase();
+        if let (Some(value), false) = (&self.value, query.is_empty()) {
+            find_matches(value, "$", &query, &mut self.matches);
+            // A key and its value can both match
+            self.matches.dedup();
+        }
+        self.match_set = self.matches.iter().cloned().collect();
+        self.current_match = 0;
+        if let Some(first) = self.matches.first().cloned() {
+            self.reveal(&first);
+        }
+    }
+
+    fn next_match(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+        self.current_match = (self.current_match + 1) % self.matches.len();
+        let path = self.matches[self.current_match].clone();
+        self.reveal(&path);
+    }
+
+    fn search_ui(&mut self, ui: &mut egui::Ui) {
+        let search = ui.add(
+            egui::TextEdit::singleline(&mut self.search)
+                .hint_text("Search keys and values")
+                .desired_width(180.0),
+        );
+        if search.changed() {
+            self.update_matches();
+        }
+        if search.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            self.next_match();
+            search.request_focus();
+        }
+        if !self.search.trim().is_empty() {
+            if self.matches.is_empty() {
+                ui.label("No matches");
+            } else {
+                ui.label(format!("{} of {}", self.current_match + 1, self.matches.len()));
+                if ui.small_button("Next").clicked() {
+                    self.next_match();
+                }
+            }
+        }
+    }
+
+    /// Label for the node at `path`, highlighted if it matches the search,
+    /// with a menu to copy its path or value
+    fn node_label(&self, ui: &mut egui::Ui, text: egui::RichText, path: &str, value: &Value) -> egui::Response {
+        let text = if self.match_set.contains(path) { text.background_color(MATCH_BACKGROUND) } else { text };
+        let response = ui.add(egui::Label::new(text).sense(egui::Sense::click()));
+        node_menu(&response, path, value);
+        response
+    }
+
+    fn validation_ui(&mut self, ui: &mut egui::Ui) {
+        let validate = ui.button("Validate").on_hover_text(match &self.schema_path {
+            Some(schema) => format!("Check against {}", schema.display()),
+            None => "Check against a JSON Schema".to_string(),
+        });
+        if validate.clicked() {
+            match self.schema_path.clone() {
+                Some(schema) => {
+                    let _ = self.validate_against_schema(&schema);
+                }
+                None => self.schema_requested = true,
+            }
+        }
+        if self.schema_path.is_some() && ui.small_button("Schema…").on_hover_text("Choose another schema").clicked() {
+            self.schema_requested = true;
+        }
+
+        let (text, color) = match &self.validation {
+            None => return,
+            Some(Ok(report)) if report.is_valid() => ("Valid".to_string(), egui::Color32::from_rgb(60, 160, 80)),
+            Some(Ok(report)) => (
+                format!("Invalid ({} error{})", report.errors.len(), if report.errors.len() == 1 { "" } else { "s" }),
+                egui::Color32::from_rgb(200, 60, 60),
+            ),
+            Some(Err(_)) => ("Couldn't validate".to_string(), egui::Color32::from_rgb(200, 60, 60)),
+        };
+        let badge = ui.add(egui::Button::new(egui::RichText::new(text).color(egui::Color32::WHITE).strong()).fill(color));
+        let popup_id = ui.make_persistent_id("schema_errors");
+        if badge.clicked() {
+            ui.memory_mut(|m| m.toggle_popup(popup_id));
+        }
+
+        let mut reveal = None;
+        egui::popup::popup_below_widget(ui, popup_id, &badge, |ui| {
+            ui.set_min_width(360.0);
+            match &self.validation {
+                Some(Ok(report)) if report.is_valid() => {
+                    ui.label("The file matches the schema.");
+                }
+                Some(Ok(report)) => {
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for error in &report.errors {
+                            ui.horizontal_wrapped(|ui| {
+                                if ui.link(egui::RichText::new(&error.path).monospace()).clicked() {
+                                    reveal = Some(error.path.clone());
+                                }
+                                ui.label(&error.message);
+                            });
+                        }
+                    });
+                }
+                Some(Err(e)) => {
+                    ui.label(e);
+                }
+                None => {}
+            }
+        });
+        if let Some(path) = reveal {
+            self.reveal(&path);
+        }
+    }
+
     pub fn ui(&mut self, ui: &mut egui::Ui) {
         // Toolbar
         ui.horizontal(|ui| {
@@ -69,6 +308,16 @@ impl JsonViewer {
                 self.expanded_paths.clear();
                 self.expanded_paths.insert("$".to_string());
             }
+            if ui.button("Expand to depth").clicked() {
+                self.expand_to_depth(self.expand_depth);
+            }
+            ui.add(egui::DragValue::new(&mut self.expand_depth).clamp_range(1..=20));
+
+            ui.separator();
+            self.search_ui(ui);
+
+            ui.separator();
+            self.validation_ui(ui);
 
             if let Some(path) = &self.path {
                 ui.separator();
@@ -109,24 +358,37 @@ impl JsonViewer {
 
     fn expand_all(&mut self) {
         if let Some(value) = self.value.clone() {
-            self.collect_paths(&value, "$");
+            self.collect_paths(&value, "$", usize::MAX);
+        }
+    }
+
+    /// Open the first `depth` levels, the root being the first, and close
+    /// the rest
+    fn expand_to_depth(&mut self, depth: usize) {
+        self.expanded_paths.clear();
+        if let Some(value) = self.value.clone() {
+            self.collect_paths(&value, "$", depth);
         }
     }
 
-    fn collect_paths(&mut self, value: &Value, path: &str) {
+    /// Expand `path` and its descendants, down to `depth` levels
+    fn collect_paths(&mut self, value: &Value, path: &str, depth: usize) {
+        if depth == 0 {
+            return;
+        }
         self.expanded_paths.insert(path.to_string());
 
         match value {
             Value::Object(map) => {
                 for (key, val) in map {
                     let child_path = format!("{}.{}", path, key);
-                    self.collect_paths(val, &child_path);
+                    self.collect_paths(val, &child_path, depth - 1);
                 }
             }
             Value::Array(arr) => {
                 for (i, val) in arr.iter().enumerate() {
                     let child_path = format!("{}[{}]", path, i);
-                    self.collect_paths(val, &child_path);
+                    self.collect_paths(val, &child_path, depth - 1);
                 }
             }
             _ => {}
@@ -135,13 +397,20 @@ impl JsonViewer {
 
     fn render_value(&mut self, ui: &mut egui::Ui, value: &Value, path: &str, indent: usize) {
         let indent_str = "  ".repeat(indent);
+        if self.scroll_to.as_deref() == Some(path) {
+            ui.scroll_to_cursor(Some(egui::Align::Center));
+            self.scroll_to = None;
+        }
 
         match value {
             Value::Null => {
-                ui.label(
+                self.node_label(
+                    ui,
                     egui::RichText::new(format!("{}null", indent_str))
                         .monospace()
                         .color(egui::Color32::GRAY),
+                    path,
+                    value,
                 );
             }
             Value::Bool(b) => {
@@ -150,19 +419,38 @@ impl JsonViewer {
                 } else {
                     egui::Color32::RED
                 };
-                ui.label(
+                self.node_label(
+                    ui,
                     egui::RichText::new(format!("{}{}", indent_str, b))
                         .monospace()
                         .color(color),
+                    path,
+                    value,
                 );
             }
             Value::Number(n) => {
-                ui.label(
+                self.node_label(
+                    ui,
                     egui::RichText::new(format!("{}{}", indent_str, n))
                         .monospace()
                         .color(egui::Color32::from_rgb(86, 156, 214)),
+                    path,
+                    value,
                 );
             }
+            Value::String(s) if self.fold_multiline && s.trim_end().contains('\n') => {
+                let first = s.lines().next().unwrap_or_default();
+                let lines = s.trim_end().lines().count();
+                self.node_label(
+                    ui,
+                    egui::RichText::new(format!("{}\"{}…\" ({} lines)", indent_str, first, lines))
+                        .monospace()
+                        .color(egui::Color32::from_rgb(206, 145, 120)),
+                    path,
+                    value,
+                )
+                .on_hover_text(s.as_str());
+            }
             Value::String(s) => {
                 // Truncate long strings
                 let display = if s.len() > 100 {
@@ -170,10 +458,13 @@ impl JsonViewer {
                 } else {
                     format!("\"{}\"", s)
                 };
-                ui.label(
+                self.node_label(
+                    ui,
                     egui::RichText::new(format!("{}{}", indent_str, display))
                         .monospace()
                         .color(egui::Color32::from_rgb(206, 145, 120)),
+                    path,
+                    value,
                 );
             }
             Value::Array(arr) => {
@@ -181,18 +472,11 @@ impl JsonViewer {
                 let header = if is_expanded { "[-]" } else { "[+]" };
 
                 ui.horizontal(|ui| {
-                    if ui
-                        .button(
-                            egui::RichText::new(format!(
-                                "{}{} Array[{}]",
-                                indent_str,
-                                header,
-                                arr.len()
-                            ))
-                            .monospace(),
-                        )
-                        .clicked()
-                    {
+                    let button = ui.button(
+                        egui::RichText::new(format!("{}{} Array[{}]", indent_str, header, arr.len())).monospace(),
+                    );
+                    node_menu(&button, path, value);
+                    if button.clicked() {
                         if is_expanded {
                             self.expanded_paths.remove(path);
                         } else {
@@ -205,10 +489,13 @@ impl JsonViewer {
                     for (i, item) in arr.iter().enumerate() {
                         let child_path = format!("{}[{}]", path, i);
                         ui.horizontal(|ui| {
-                            ui.label(
+                            self.node_label(
+                                ui,
                                 egui::RichText::new(format!("{}  [{}]:", indent_str, i))
                                     .monospace()
                                     .weak(),
+                                &child_path,
+                                item,
                             );
                         });
                         self.render_value(ui, item, &child_path, indent + 2);
@@ -220,18 +507,11 @@ impl JsonViewer {
                 let header = if is_expanded { "[-]" } else { "[+]" };
 
                 ui.horizontal(|ui| {
-                    if ui
-                        .button(
-                            egui::RichText::new(format!(
-                                "{}{} Object{{{}}}",
-                                indent_str,
-                                header,
-                                map.len()
-                            ))
-                            .monospace(),
-                        )
-                        .clicked()
-                    {
+                    let button = ui.button(
+                        egui::RichText::new(format!("{}{} Object{{{}}}", indent_str, header, map.len())).monospace(),
+                    );
+                    node_menu(&button, path, value);
+                    if button.clicked() {
                         if is_expanded {
                             self.expanded_paths.remove(path);
                         } else {
@@ -244,10 +524,13 @@ impl JsonViewer {
                     for (key, val) in map {
                         let child_path = format!("{}.{}", path, key);
                         ui.horizontal(|ui| {
-                            ui.label(
+                            self.node_label(
+                                ui,
                                 egui::RichText::new(format!("{}  \"{}\":", indent_str, key))
                                     .monospace()
                                     .color(egui::Color32::from_rgb(156, 220, 254)),
+                                &child_path,
+                                val,
                             );
                         });
                         self.render_value(ui, val, &child_path, indent + 2);
@@ -257,3 +540,137 @@ impl JsonViewer {
         }
     }
 }
+
+/// Right-click menu to copy a node's path or value. Strings are copied
+/// without quotes, anything else as JSON.
+fn node_menu(response: &egui::Response, path: &str, value: &Value) {
+    response.context_menu(|ui| {
+        if ui.button("Copy path").clicked() {
+            ui.output_mut(|o| o.copied_text = path.to_string());
+            ui.close_menu();
+        }
+        if ui.button("Copy value").clicked() {
+            let text = match value {
+                Value::String(s) => s.clone(),
+                _ => serde_json::to_string_pretty(value).unwrap_or_default(),
+            };
+            ui.output_mut(|o| o.copied_text = text);
+            ui.close_menu();
+        }
+    });
+}
+
+/// Add the path of every node under `path` whose key or scalar value
+/// contains `query` (already lowercase) to `matches`
+fn find_matches(value: &Value, path: &str, query: &str, matches: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, val) in map {
+                let child_path = format!("{}.{}", path, key);
+                if key.to_lowercase().contains(query) {
+                    matches.push(child_path.clone());
+                }
+                find_matches(val, &child_path, query, matches);
+            }
+        }
+        Value::Array(arr) => {
+            for (i, val) in arr.iter().enumerate() {
+                find_matches(val, &format!("{}[{}]", path, i), query, matches);
+            }
+        }
+        Value::String(s) => {
+            if s.to_lowercase().contains(query) {
+                matches.push(path.to_string());
+            }
+        }
+        scalar => {
+            if scalar.to_string().to_lowercase().contains(query) {
+                matches.push(path.to_string());
+            }
+        }
+    }
+}
+
+/// `/items/2/name` -> `$.items[2].name`
+fn tree_path(pointer: &jsonschema::paths::JSONPointer) -> String {
+    let mut path = "$".to_string();
+    for chunk in pointer.iter() {
+        match chunk {
+            jsonschema::paths::PathChunk::Property(key) => {
+                path.push('.');
+                path.push_str(key);
+            }
+            jsonschema::paths::PathChunk::Index(i) => path.push_str(&format!("[{}]", i)),
+            jsonschema::paths::PathChunk::Keyword(_) => {}
+        }
+    }
+    path
+}
+
+impl Viewer for JsonViewer {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        JsonViewer::load(self, path)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        JsonViewer::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        JsonViewer::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        JsonViewer::is_loaded(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(content: &str, query: &str) -> JsonViewer {
+        let mut viewer = JsonViewer::new();
+        viewer.load_string(content).unwrap();
+        viewer.search = query.to_string();
+        viewer.update_matches();
+        viewer
+    }
+
+    #[test]
+    fn test_search_nested_keys_and_arrays() {
+        let content = r#"{"user": {"Name": "Ada", "tags": ["admin", "name-tag"]}, "items": [{"id": 7}, {"id": 17}]}"#;
+
+        // Keys match case-insensitively, and so do values inside arrays
+        let mut viewer = search(content, "name");
+        assert_eq!(viewer.matches, ["$.user.Name", "$.user.tags[1]"]);
+        assert!(viewer.expanded_paths.contains("$.user"));
+        assert_eq!(viewer.scroll_to.as_deref(), Some("$.user.Name"));
+
+        // Numbers inside objects inside arrays, revealing every level above the first
+        let viewer_ids = search(content, "7");
+        assert_eq!(viewer_ids.matches, ["$.items[0].id", "$.items[1].id"]);
+        assert!(viewer_ids.expanded_paths.contains("$.items") && viewer_ids.expanded_paths.contains("$.items[0]"));
+
+        viewer.next_match();
+        viewer.next_match();
+        assert_eq!(viewer.current_match, 0);
+    }
+
+    #[test]
+    fn test_search_without_matches() {
+        let mut viewer = search(r#"{"a": [1, 2, {"b": null}]}"#, "missing");
+        assert!(viewer.matches.is_empty() && viewer.match_set.is_empty());
+        assert!(viewer.scroll_to.is_none());
+        viewer.next_match();
+        assert_eq!(viewer.current_match, 0);
+
+        // A blank query clears earlier matches
+        viewer.search = "b".to_string();
+        viewer.update_matches();
+        assert_eq!(viewer.matches, ["$.a[2].b"]);
+        viewer.search = "  ".to_string();
+        viewer.update_matches();
+        assert!(viewer.matches.is_empty());
+    }
+}
@@ -8,6 +8,7 @@
 //! - Images (zoom/pan)
 //! - CSV/Excel (table view)
 //! - JSON (tree view)
+//! - YAML (tree view, highlighted source when it doesn't parse)
 //! - SQLite (table browser)
 
 pub mod csv_viewer;
@@ -15,13 +16,21 @@ pub mod html_viewer;
 pub mod image_viewer;
 pub mod json_viewer;
 pub mod pdf_viewer;
+pub mod sqlite_viewer;
 pub mod text_viewer;
-
-// TODO: Add later
-// pub mod sqlite_viewer;
+pub mod yaml_viewer;
 
 use anyhow::Result;
-use std::path::Path;
+use std::any::Any;
+use std::path::{Path, PathBuf};
+
+use csv_viewer::CsvViewer;
+use html_viewer::HtmlViewer;
+use json_viewer::JsonViewer;
+use pdf_viewer::PdfViewer;
+use sqlite_viewer::SqliteViewer;
+use text_viewer::TextViewer;
+use yaml_viewer::YamlViewer;
 
 /// Supported file types for viewing
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -34,6 +43,7 @@ pub enum FileType {
     Csv,
     Excel,
     Json,
+    Yaml,
     Sqlite,
     Unknown,
 }
@@ -49,8 +59,8 @@ impl FileType {
         match ext.as_deref() {
             // Text/Code
             Some(
-                "txt" | "rs" | "py" | "js" | "ts" | "sh" | "bash" | "zsh" | "toml" | "yaml" | "yml"
-                | "ini" | "cfg" | "conf" | "c" | "cpp" | "h" | "hpp" | "java" | "go" | "rb" | "php",
+                "txt" | "rs" | "py" | "js" | "ts" | "sh" | "bash" | "zsh" | "toml" | "ini"
+                | "cfg" | "conf" | "c" | "cpp" | "h" | "hpp" | "java" | "go" | "rb" | "php",
             ) => FileType::Text,
 
             // Markdown
@@ -76,6 +86,9 @@ impl FileType {
             // JSON
             Some("json" | "jsonl") => FileType::Json,
 
+            // YAML
+            Some("yaml" | "yml") => FileType::Yaml,
+
             // SQLite
             Some("db" | "sqlite" | "sqlite3") => FileType::Sqlite,
 
@@ -95,6 +108,7 @@ impl FileType {
             FileType::Csv => "CSV",
             FileType::Excel => "Excel",
             FileType::Json => "JSON",
+            FileType::Yaml => "YAML",
             FileType::Sqlite => "SQLite",
             FileType::Unknown => "Unknown",
         }
@@ -109,15 +123,80 @@ impl FileType {
                 | FileType::Html
                 | FileType::Pdf
                 | FileType::Json
+                | FileType::Yaml
                 | FileType::Csv
                 | FileType::Image
+                | FileType::Sqlite
                 | FileType::Unknown // Try as text
         )
     }
 }
 
-/// Common trait for all viewers
-pub trait Viewer {
+/// A file read and parsed for viewing. [`LoadedContent::load`] does the
+/// slow part and can run on any thread; images are decoded but left for
+/// the UI thread to turn into a texture.
+pub enum LoadedContent {
+    Text(TextViewer),
+    Image { path: PathBuf, image: egui::ColorImage },
+    Csv(CsvViewer),
+    Json(JsonViewer),
+    Yaml(Box<YamlViewer>),
+    Html(HtmlViewer),
+    Pdf(PdfViewer),
+    Sqlite(SqliteViewer),
+}
+
+impl LoadedContent {
+    /// Load `path` with the viewer for its type. Types without a viewer
+    /// are tried as text.
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(match FileType::from_path(path) {
+            FileType::Image => Self::Image {
+                path: path.to_path_buf(),
+                image: image_viewer::decode_image(path, None)?,
+            },
+            FileType::Csv => {
+                let mut viewer = CsvViewer::new();
+                viewer.load(path)?;
+                Self::Csv(viewer)
+            }
+            FileType::Json => {
+                let mut viewer = JsonViewer::new();
+                viewer.load(path)?;
+                Self::Json(viewer)
+            }
+            FileType::Yaml => {
+                let mut viewer = YamlViewer::new();
+                viewer.load(path)?;
+                Self::Yaml(Box::new(viewer))
+            }
+            FileType::Html => {
+                let mut viewer = HtmlViewer::new();
+                viewer.load(path)?;
+                Self::Html(viewer)
+            }
+            FileType::Pdf => {
+                let mut viewer = PdfViewer::new();
+                viewer.load(path)?;
+                Self::Pdf(viewer)
+            }
+            FileType::Sqlite => {
+                let mut viewer = SqliteViewer::new();
+                viewer.load(path)?;
+                Self::Sqlite(viewer)
+            }
+            _ => {
+                let mut viewer = TextViewer::new();
+                viewer.load(path)?;
+                Self::Text(viewer)
+            }
+        })
+    }
+}
+
+/// Common trait for all viewers. The preview panel holds the open one as
+/// a `Box<dyn Viewer>` inside the app state, hence `Send`.
+pub trait Viewer: Any + Send {
     /// Load file content
     fn load(&mut self, path: &Path) -> Result<()>;
 
@@ -130,3 +209,37 @@ pub trait Viewer {
     /// Check if content is loaded
     fn is_loaded(&self) -> bool;
 }
+
+impl dyn Viewer {
+    /// The viewer as its concrete type, for viewers with extra controls
+    pub fn downcast_ref<T: Viewer>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Viewer>(&mut self) -> Option<&mut T> {
+        (self as &mut dyn Any).downcast_mut()
+    }
+}
+
+/// Shown when no file is open
+pub struct NullViewer;
+
+impl Viewer for NullViewer {
+    fn load(&mut self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.centered_and_justified(|ui| {
+            ui.label("No file open");
+        });
+    }
+
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    fn is_loaded(&self) -> bool {
+        false
+    }
+}
@@ -5,11 +5,13 @@
 //! - Extracts text if possible
 //! - Provides button to open in default PDF reader
 
+use crate::Viewer;
 use anyhow::Result;
 use egui::{self, ScrollArea};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+#[derive(Default)]
 pub struct PdfViewer {
     path: Option<PathBuf>,
     file_size: u64,
@@ -17,17 +19,6 @@ pub struct PdfViewer {
     error_message: Option<String>,
 }
 
-impl Default for PdfViewer {
-    fn default() -> Self {
-        Self {
-            path: None,
-            file_size: 0,
-            extracted_text: String::new(),
-            error_message: None,
-        }
-    }
-}
-
 impl PdfViewer {
     pub fn new() -> Self {
         Self::default()
@@ -176,3 +167,21 @@ fn format_file_size(bytes: u64) -> String {
         format!("{} bytes", bytes)
     }
 }
+
+impl Viewer for PdfViewer {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        PdfViewer::load(self, path)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        PdfViewer::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        PdfViewer::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        PdfViewer::is_loaded(self)
+    }
+}
@@ -0,0 +1,333 @@
+//! SQLite viewer: pick a table, page through its rows and filter them with
+//! a `WHERE` clause. The database is opened read-only.
+
+use crate::csv_viewer::display_cell;
+use crate::Viewer;
+use anyhow::Result;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+
+const PAGE_ROWS: usize = 100;
+
+/// SQLite viewer state
+pub struct SqliteViewer {
+    path: Option<PathBuf>,
+    conn: Option<Connection>,
+    tables: Vec<String>,
+    selected: usize,
+    /// Column names of the selected table, from `PRAGMA table_info`
+    columns: Vec<String>,
+    /// `CREATE TABLE` statement of the selected table
+    schema: String,
+    /// Rows on the current page
+    rows: Vec<Vec<String>>,
+    /// Rows matching the filter
+    row_count: usize,
+    page: usize,
+    /// `WHERE` clause as typed
+    filter: String,
+    /// Why the last query failed, usually a mistake in the filter
+    error: Option<String>,
+}
+
+impl Default for SqliteViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SqliteViewer {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            conn: None,
+            tables: Vec::new(),
+            selected: 0,
+            columns: Vec::new(),
+            schema: String::new(),
+            rows: Vec::new(),
+            row_count: 0,
+            page: 0,
+            filter: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        self.show(conn)?;
+        self.path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// List the tables in `conn` and show the first
+    fn show(&mut self, conn: Connection) -> Result<()> {
+        let mut stmt =
+            conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+        self.tables = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+        drop(stmt);
+
+        self.conn = Some(conn);
+        self.select_table(0);
+        Ok(())
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.conn.is_some()
+    }
+
+    /// Show table `index` from its first page, without a filter
+    fn select_table(&mut self, index: usize) {
+        self.selected = index;
+        self.page = 0;
+        self.filter.clear();
+        self.columns.clear();
+        self.schema.clear();
+        self.rows.clear();
+        self.row_count = 0;
+        self.error = None;
+        let (Some(conn), Some(table)) = (&self.conn, self.tables.get(index)) else { return };
+
+        let columns = conn
+            .prepare("SELECT name FROM pragma_table_info(?1) ORDER BY cid")
+            .and_then(|mut stmt| stmt.query_map([table], |row| row.get(0))?.collect());
+        let schema = conn.query_row("SELECT sql FROM sqlite_master WHERE name = ?1", [table], |row| {
+            row.get::<_, Option<String>>(0)
+        });
+        match (columns, schema) {
+            (Ok(columns), Ok(schema)) => {
+                self.columns = columns;
+                self.schema = schema.unwrap_or_default();
+                self.query();
+            }
+            (Err(e), _) | (_, Err(e)) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Count the rows matching the filter and read the current page
+    fn query(&mut self) {
+        let (Some(conn), Some(table)) = (&self.conn, self.tables.get(self.selected)) else { return };
+        let table = quote_identifier(table);
+        let filter = match self.filter.trim() {
+            "" => String::new(),
+            clause => format!(" WHERE {}", clause),
+        };
+
+        let count = conn.query_row(&format!("SELECT COUNT(*) FROM {}{}", table, filter), [], |row| row.get::<_, i64>(0));
+        let rows = conn
+            .prepare(&format!(
+                "SELECT * FROM {}{} LIMIT {} OFFSET {}",
+                table,
+                filter,
+                PAGE_ROWS,
+                self.page * PAGE_ROWS
+            ))
+            .and_then(|mut stmt| {
+                let columns = stmt.column_count();
+                stmt.query_map([], |row| (0..columns).map(|i| row.get_ref(i).map(cell_text)).collect())?
+                    .collect::<rusqlite::Result<Vec<Vec<String>>>>()
+            });
+        match (count, rows) {
+            (Ok(count), Ok(rows)) => {
+                self.row_count = count.max(0) as usize;
+                self.rows = rows;
+                self.error = None;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                self.rows.clear();
+                self.row_count = 0;
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn page_count(&self) -> usize {
+        self.row_count.div_ceil(PAGE_ROWS).max(1)
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.tables.is_empty() {
+            ui.centered_and_justified(|ui| {
+                ui.label(if self.conn.is_some() { "This database has no tables" } else { "No database loaded" });
+            });
+            return;
+        }
+
+        let mut selected = self.selected;
+        let mut requery = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("sqlite_table")
+                .selected_text(&self.tables[self.selected])
+                .show_ui(ui, |ui| {
+                    for (i, table) in self.tables.iter().enumerate() {
+                        ui.selectable_value(&mut selected, i, table);
+                    }
+                });
+            ui.label(format!("{} rows", self.row_count));
+
+            ui.separator();
+            ui.label("WHERE");
+            let filter = ui.add(
+                egui::TextEdit::singleline(&mut self.filter)
+                    .hint_text("e.g. price > 10")
+                    .desired_width(220.0),
+            );
+            let submitted = filter.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Apply").clicked() || submitted {
+                self.page = 0;
+                requery = true;
+            }
+
+            ui.separator();
+            if ui.add_enabled(self.page > 0, egui::Button::new("< Prev")).clicked() {
+                self.page -= 1;
+                requery = true;
+            }
+            ui.label(format!("Page {} of {}", self.page + 1, self.page_count()));
+            if ui.add_enabled(self.page + 1 < self.page_count(), egui::Button::new("Next >")).clicked() {
+                self.page += 1;
+                requery = true;
+            }
+        });
+        if selected != self.selected {
+            self.select_table(selected);
+        } else if requery {
+            self.query();
+        }
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::from_rgb(200, 60, 60), error);
+        }
+        if !self.schema.is_empty() {
+            egui::CollapsingHeader::new("Schema").id_source("sqlite_schema").show(ui, |ui| {
+                ui.label(egui::RichText::new(&self.schema).monospace());
+            });
+        }
+        ui.separator();
+
+        egui::ScrollArea::both().id_source("sqlite_rows").auto_shrink([false, false]).show(ui, |ui| {
+            egui::Grid::new("sqlite_table_grid").striped(true).show(ui, |ui| {
+                for column in &self.columns {
+                    ui.strong(column);
+                }
+                ui.end_row();
+                for row in &self.rows {
+                    for cell in row {
+                        ui.label(display_cell(cell)).on_hover_text(cell);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+/// `name` as a quoted SQL identifier
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn cell_text(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(x) => x.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+        ValueRef::Blob(bytes) => format!("<{} byte blob>", bytes.len()),
+    }
+}
+
+impl Viewer for SqliteViewer {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        SqliteViewer::load(self, path)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        SqliteViewer::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        SqliteViewer::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        SqliteViewer::is_loaded(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Viewer on an in-memory database set up by `sql`
+    fn viewer_for(sql: &str) -> SqliteViewer {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(sql).unwrap();
+        let mut viewer = SqliteViewer::new();
+        viewer.show(conn).unwrap();
+        viewer
+    }
+
+    #[test]
+    fn test_lists_tables() {
+        let viewer = viewer_for(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY AUTOINCREMENT, body TEXT);
+             CREATE TABLE authors (name TEXT);
+             CREATE VIEW recent AS SELECT * FROM notes;
+             INSERT INTO notes (body) VALUES ('hi');",
+        );
+        // sqlite_sequence and the view are left out
+        assert_eq!(viewer.tables, ["authors", "notes"]);
+        assert_eq!(viewer.columns, ["name"]);
+        assert!(viewer.schema.starts_with("CREATE TABLE authors"));
+        assert!(viewer_for("").tables.is_empty());
+    }
+
+    #[test]
+    fn test_page_bounds() {
+        let viewer = viewer_for("CREATE TABLE empty (n INTEGER);");
+        assert_eq!((viewer.row_count, viewer.page_count()), (0, 1));
+
+        let mut viewer = viewer_for(
+            "CREATE TABLE numbers (n INTEGER);
+             WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 250)
+             INSERT INTO numbers SELECT n FROM seq;",
+        );
+        assert_eq!(viewer.row_count, 250);
+        assert_eq!(viewer.page_count(), 3);
+        assert_eq!(viewer.rows.len(), PAGE_ROWS);
+        viewer.page = 2;
+        viewer.query();
+        assert_eq!(viewer.rows.len(), 50);
+        assert_eq!(viewer.rows[0], ["201"]);
+    }
+
+    #[test]
+    fn test_filter_and_quoted_names() {
+        let mut viewer = viewer_for(
+            r#"CREATE TABLE "odd ""name""" (label TEXT, size INTEGER);
+               INSERT INTO "odd ""name""" VALUES ('it''s', 1), ('big', 20), (NULL, 30);"#,
+        );
+        assert_eq!(viewer.tables, ["odd \"name\""]);
+        assert_eq!(viewer.row_count, 3);
+
+        viewer.filter = "label = 'it''s'".to_string();
+        viewer.query();
+        assert_eq!(viewer.rows, [["it's", "1"]]);
+
+        viewer.filter = "size > 10".to_string();
+        viewer.query();
+        assert_eq!(viewer.rows, [["big", "20"], ["NULL", "30"]]);
+
+        // A mistake in the filter is shown instead of the rows
+        viewer.filter = "size >".to_string();
+        viewer.query();
+        assert!(viewer.error.is_some());
+        assert!(viewer.rows.is_empty() && viewer.row_count == 0);
+    }
+}
@@ -1,8 +1,17 @@
 //! Text/Code viewer with optional syntax highlighting
 
-use anyhow::Result;
+use crate::Viewer;
+use anyhow::{anyhow, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
 
 /// Text viewer state
 pub struct TextViewer {
@@ -11,6 +20,186 @@ pub struct TextViewer {
     line_numbers: bool,
     wrap_lines: bool,
     scroll_offset: f32,
+    /// Keyboard navigation, while on (`v` to start, `q` to leave)
+    vi_mode: Option<ViModeState>,
+    /// Reload the file when it changes on disk
+    watch: Option<FileWatch>,
+    /// Modification time when the file was last read, for polling
+    last_modified: Option<SystemTime>,
+    /// Extension of the syntax to highlight with, e.g. "yaml"
+    syntax: Option<String>,
+    /// Highlighted lines for the current content and theme
+    highlighted: Option<Highlighted>,
+}
+
+struct Highlighted {
+    dark: bool,
+    lines: Vec<egui::text::LayoutJob>,
+}
+
+/// syntect's built-in syntaxes and themes, loaded on first use
+fn highlighting() -> &'static (SyntaxSet, ThemeSet) {
+    static SETS: OnceLock<(SyntaxSet, ThemeSet)> = OnceLock::new();
+    SETS.get_or_init(|| (SyntaxSet::load_defaults_newlines(), ThemeSet::load_defaults()))
+}
+
+/// One layout job per line of `content`, or `None` if syntect has no
+/// syntax for `extension`
+fn highlight(content: &str, extension: &str, dark: bool, font: egui::FontId) -> Option<Vec<egui::text::LayoutJob>> {
+    let (syntaxes, themes) = highlighting();
+    let syntax = syntaxes.find_syntax_by_extension(extension)?;
+    let theme = &themes.themes[if dark { "base16-ocean.dark" } else { "InspiredGitHub" }];
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(content) {
+        let mut job = egui::text::LayoutJob::default();
+        for (style, text) in highlighter.highlight_line(line, syntaxes).ok()? {
+            let color = egui::Color32::from_rgb(style.foreground.r, style.foreground.g, style.foreground.b);
+            job.append(text.trim_end_matches(['\n', '\r']), 0.0, egui::TextFormat::simple(font.clone(), color));
+        }
+        lines.push(job);
+    }
+    Some(lines)
+}
+
+/// How the viewer notices the file changing
+enum FileWatch {
+    /// Check the modification time every `interval`. Works everywhere,
+    /// including network drives where change events don't arrive.
+    Poll { interval: Duration, last_check: Instant },
+    /// Change events from the OS
+    Notify {
+        _watcher: RecommendedWatcher,
+        changes: Receiver<()>,
+        /// Set by the first frame, so events can wake the UI
+        ctx: Arc<Mutex<Option<egui::Context>>>,
+    },
+}
+
+/// Vi-style navigation: `j`/`k` move a line, `Ctrl+F`/`Ctrl+B` a page,
+/// `gg`/`G` to the start/end, `/pattern` searches and `n`/`N` step
+/// through the matches
+#[derive(Default)]
+pub struct ViModeState {
+    /// Line at the top of the view
+    line: usize,
+    /// Lines that fit in the view, from the last frame
+    page_lines: usize,
+    /// First `g` of `gg` pressed
+    pending_g: bool,
+    /// Search being typed after `/`
+    search_input: Option<String>,
+    /// Pattern `n`/`N` look for
+    pattern: String,
+    /// Set when a key moved the view, so it scrolls to `line`
+    moved: bool,
+}
+
+impl ViModeState {
+    fn go_to(&mut self, line: usize, line_count: usize) {
+        self.line = line.min(line_count.saturating_sub(1));
+        self.moved = true;
+    }
+
+    /// Lines containing the pattern, ignoring case
+    fn matches(&self, content: &str) -> Vec<usize> {
+        if self.pattern.is_empty() {
+            return Vec::new();
+        }
+        let pattern = self.pattern.to_lowercase();
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.to_lowercase().contains(&pattern))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Jump to the next match after `from` (or before it, going back),
+    /// wrapping around the file
+    fn jump_to_match(&mut self, content: &str, from: usize, forward: bool) {
+        let matches = self.matches(content);
+        let next = if forward {
+            matches.iter().find(|&&m| m >= from).or(matches.first())
+        } else {
+            matches.iter().rev().find(|&&m| m < from).or(matches.last())
+        };
+        if let Some(&line) = next {
+            self.line = line;
+            self.moved = true;
+        }
+    }
+
+    /// Handle this frame's keys. Returns false once vi mode is left.
+    fn handle_input(&mut self, ui: &egui::Ui, content: &str) -> bool {
+        use egui::{Key, Modifiers};
+        let line_count = content.lines().count();
+
+        if let Some(mut input) = self.search_input.take() {
+            // Typing a search: the view follows the first match as it's typed
+            let mut done = false;
+            let mut cancelled = false;
+            ui.input_mut(|i| {
+                for event in &i.events {
+                    if let egui::Event::Text(text) = event {
+                        input.push_str(text);
+                    }
+                }
+                if i.consume_key(Modifiers::NONE, Key::Backspace) {
+                    input.pop();
+                }
+                done = i.consume_key(Modifiers::NONE, Key::Enter);
+                cancelled = i.consume_key(Modifiers::NONE, Key::Escape);
+            });
+            if cancelled {
+                return true;
+            }
+            if input != self.pattern {
+                self.pattern = input.clone();
+                self.jump_to_match(content, self.line, true);
+            }
+            if !done {
+                self.search_input = Some(input);
+            }
+            return true;
+        }
+
+        let page = self.page_lines.max(1);
+        let mut quit = false;
+        ui.input_mut(|i| {
+            if i.consume_key(Modifiers::NONE, Key::Q) || i.consume_key(Modifiers::NONE, Key::Escape) {
+                quit = true;
+            } else if i.consume_key(Modifiers::NONE, Key::Slash) {
+                self.search_input = Some(String::new());
+                // The "/" typed isn't part of the pattern
+                i.events.retain(|e| !matches!(e, egui::Event::Text(_)));
+            } else if i.consume_key(Modifiers::CTRL, Key::F) {
+                self.go_to(self.line + page, line_count);
+            } else if i.consume_key(Modifiers::CTRL, Key::B) {
+                self.go_to(self.line.saturating_sub(page), line_count);
+            } else if i.consume_key(Modifiers::SHIFT, Key::G) {
+                self.go_to(line_count, line_count);
+            } else if i.consume_key(Modifiers::NONE, Key::G) {
+                if self.pending_g {
+                    self.go_to(0, line_count);
+                }
+                self.pending_g = !self.pending_g;
+                return;
+            } else if i.consume_key(Modifiers::SHIFT, Key::N) {
+                self.jump_to_match(content, self.line, false);
+            } else if i.consume_key(Modifiers::NONE, Key::N) {
+                self.jump_to_match(content, self.line + 1, true);
+            } else if i.consume_key(Modifiers::NONE, Key::J) || i.consume_key(Modifiers::NONE, Key::ArrowDown) {
+                self.go_to(self.line + 1, line_count);
+            } else if i.consume_key(Modifiers::NONE, Key::K) || i.consume_key(Modifiers::NONE, Key::ArrowUp) {
+                self.go_to(self.line.saturating_sub(1), line_count);
+            } else {
+                return;
+            }
+            self.pending_g = false;
+        });
+        !quit
+    }
 }
 
 impl Default for TextViewer {
@@ -27,20 +216,104 @@ impl TextViewer {
             line_numbers: true,
             wrap_lines: true,
             scroll_offset: 0.0,
+            vi_mode: None,
+            watch: None,
+            last_modified: None,
+            syntax: None,
+            highlighted: None,
         }
     }
 
     pub fn load(&mut self, path: &Path) -> Result<()> {
         self.content = fs::read_to_string(path)?;
+        self.last_modified = fs::metadata(path).and_then(|m| m.modified()).ok();
         self.path = Some(path.to_path_buf());
+        self.highlighted = None;
         self.scroll_offset = 0.0;
+        self.vi_mode = None;
         Ok(())
     }
 
+    /// Reload the file whenever its modification time changes, checking
+    /// every `interval`
+    pub fn enable_poll_watch(&mut self, interval: Duration) {
+        self.watch = Some(FileWatch::Poll { interval, last_check: Instant::now() });
+    }
+
+    /// Reload the file when the OS reports a change to it
+    pub fn enable_notify_watch(&mut self) -> Result<()> {
+        let path = self.path.clone().ok_or_else(|| anyhow!("No file to watch"))?;
+        let (tx, changes) = channel();
+        let ctx: Arc<Mutex<Option<egui::Context>>> = Arc::default();
+        let wake = ctx.clone();
+        // Editors often save by replacing the file, so watch its folder
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf();
+        let file = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let ours = event.is_ok_and(|e| {
+                (e.kind.is_modify() || e.kind.is_create()) && e.paths.iter().any(|p| p.file_name() == file.file_name())
+            });
+            if ours {
+                let _ = tx.send(());
+                if let Some(ctx) = wake.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+                    ctx.request_repaint();
+                }
+            }
+        })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        self.watch = Some(FileWatch::Notify { _watcher: watcher, changes, ctx });
+        Ok(())
+    }
+
+    pub fn disable_watch(&mut self) {
+        self.watch = None;
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watch.is_some()
+    }
+
+    /// Re-read the file if the watch says it changed, keeping the view
+    fn reload_if_changed(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.path.clone() else { return };
+        let changed = match &mut self.watch {
+            None => false,
+            Some(FileWatch::Poll { interval, last_check }) => {
+                ctx.request_repaint_after(*interval);
+                if last_check.elapsed() < *interval {
+                    return;
+                }
+                *last_check = Instant::now();
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                modified.is_some() && modified != self.last_modified
+            }
+            Some(FileWatch::Notify { changes, ctx: wake, .. }) => {
+                wake.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_with(|| ctx.clone());
+                changes.try_iter().count() > 0
+            }
+        };
+        if changed {
+            if let Ok(content) = fs::read_to_string(&path) {
+                self.content = content;
+                self.highlighted = None;
+                self.last_modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            }
+        }
+    }
+
     pub fn load_string(&mut self, content: String, virtual_path: Option<&str>) {
         self.content = content;
         self.path = virtual_path.map(PathBuf::from);
+        self.highlighted = None;
         self.scroll_offset = 0.0;
+        self.vi_mode = None;
+    }
+
+    /// Highlight the content as the language with file extension
+    /// `extension`
+    pub fn set_syntax(&mut self, extension: &str) {
+        self.syntax = Some(extension.to_string());
+        self.highlighted = None;
     }
 
     pub fn content(&self) -> &str {
@@ -56,6 +329,8 @@ impl TextViewer {
     }
 
     pub fn ui(&mut self, ui: &mut egui::Ui) {
+        self.reload_if_changed(ui.ctx());
+
         // Toolbar
         ui.horizontal(|ui| {
             ui.checkbox(&mut self.line_numbers, "Line numbers");
@@ -65,18 +340,50 @@ impl TextViewer {
                 ui.separator();
                 ui.label(format!("{}", path.display()));
             }
+            if self.watch.is_some() {
+                ui.label(egui::RichText::new("Reloads on change").small().weak());
+            }
         });
 
         ui.separator();
 
+        // `v` over the viewer starts vi mode, unless something else has the keyboard
+        let keyboard_free = ui.memory(|m| m.focused().is_none());
+        if self.vi_mode.is_none()
+            && keyboard_free
+            && ui.rect_contains_pointer(ui.max_rect())
+            && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::V))
+        {
+            self.vi_mode = Some(ViModeState::default());
+        }
+        if let Some(mut vi) = self.vi_mode.take() {
+            // Keys belong to the focused widget (e.g. the chat input) while it has focus
+            if !keyboard_free || vi.handle_input(ui, &self.content) {
+                self.vi_mode_ui(ui, &mut vi);
+                self.vi_mode = Some(vi);
+                return;
+            }
+        }
+
         // Content area
         let text_style = egui::TextStyle::Monospace;
+        let dark = ui.visuals().dark_mode;
+        if let Some(extension) = &self.syntax {
+            if self.highlighted.as_ref().is_none_or(|h| h.dark != dark) {
+                let font = text_style.resolve(ui.style());
+                self.highlighted = highlight(&self.content, extension, dark, font).map(|lines| Highlighted { dark, lines });
+            }
+        }
 
         egui::ScrollArea::vertical()
             .auto_shrink([false, false])
             .show(ui, |ui| {
                 if self.line_numbers {
                     self.render_with_line_numbers(ui);
+                } else if let Some(highlighted) = &self.highlighted {
+                    for line in &highlighted.lines {
+                        ui.label(line.clone());
+                    }
                 } else {
                     ui.add(
                         egui::TextEdit::multiline(&mut self.content.as_str())
@@ -88,6 +395,56 @@ impl TextViewer {
             });
     }
 
+    /// One row per line, scrolled to the vi mode line, with a status bar
+    fn vi_mode_ui(&self, ui: &mut egui::Ui, vi: &mut ViModeState) {
+        let lines: Vec<&str> = self.content.lines().collect();
+        let gutter_width = format!("{}", lines.len()).len();
+        let matches = vi.matches(&self.content);
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let row_with_spacing = row_height + ui.spacing().item_spacing.y;
+        let status_height = row_height + 2.0 * ui.spacing().item_spacing.y + 6.0;
+
+        let mut scroll = egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .max_height((ui.available_height() - status_height).max(row_height));
+        if vi.moved {
+            scroll = scroll.vertical_scroll_offset(vi.line as f32 * row_with_spacing);
+        }
+        let output = scroll.show_rows(ui, row_height, lines.len(), |ui, range| {
+            for i in range {
+                ui.horizontal(|ui| {
+                    let number = egui::RichText::new(format!("{:>width$}", i + 1, width = gutter_width)).monospace();
+                    ui.label(if i == vi.line { number.strong() } else { number.weak() });
+                    let mut text = egui::RichText::new(lines[i]).monospace();
+                    if matches.binary_search(&i).is_ok() {
+                        text = text.background_color(ui.visuals().selection.bg_fill);
+                    }
+                    ui.label(text);
+                });
+            }
+        });
+        if !vi.moved {
+            // Follow mouse scrolling too
+            vi.line = (output.state.offset.y / row_with_spacing).round() as usize;
+        }
+        vi.moved = false;
+        vi.page_lines = (output.inner_rect.height() / row_with_spacing) as usize;
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            match &vi.search_input {
+                Some(input) => ui.label(egui::RichText::new(format!("/{}", input)).monospace()),
+                None => ui.label(egui::RichText::new("-- VI MODE --").monospace().strong()),
+            };
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.label(egui::RichText::new(format!("Line {} of {}", vi.line + 1, lines.len())).monospace());
+                if !vi.pattern.is_empty() && vi.search_input.is_none() {
+                    ui.label(egui::RichText::new(format!("{} matches", matches.len())).weak());
+                }
+            });
+        });
+    }
+
     fn render_with_line_numbers(&self, ui: &mut egui::Ui) {
         let lines: Vec<&str> = self.content.lines().collect();
         let line_count = lines.len();
@@ -108,9 +465,30 @@ impl TextViewer {
                     });
 
                     // Line content
-                    ui.label(egui::RichText::new(*line).monospace());
+                    match self.highlighted.as_ref().and_then(|h| h.lines.get(i)) {
+                        Some(job) => ui.label(job.clone()),
+                        None => ui.label(egui::RichText::new(*line).monospace()),
+                    };
                     ui.end_row();
                 }
             });
     }
 }
+
+impl Viewer for TextViewer {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        TextViewer::load(self, path)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        TextViewer::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        TextViewer::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        TextViewer::is_loaded(self)
+    }
+}
@@ -0,0 +1,227 @@
+//! YAML viewer: the JSON tree view for files that parse, with the anchors
+//! they define and the schema they follow in the toolbar, and highlighted
+//! source for files that don't
+
+use crate::json_viewer::JsonViewer;
+use crate::text_viewer::TextViewer;
+use crate::Viewer;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// YAML viewer state
+pub struct YamlViewer {
+    path: Option<PathBuf>,
+    tree: JsonViewer,
+    /// Anchors the file defines, with how often each is referenced
+    anchors: Vec<(String, usize)>,
+    /// What kind of document this is, when it can be told
+    schema: Option<String>,
+    /// Set when the file didn't parse: its source and the parse error
+    fallback: Option<(TextViewer, String)>,
+}
+
+impl Default for YamlViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl YamlViewer {
+    pub fn new() -> Self {
+        let mut tree = JsonViewer::new();
+        tree.fold_multiline_strings();
+        Self { path: None, tree, anchors: Vec::new(), schema: None, fallback: None }
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path)?;
+        self.path = Some(path.to_path_buf());
+        self.anchors = anchors(&content);
+        match parse(&content) {
+            Ok(value) => {
+                self.schema = detect_schema(&content, &value);
+                if let Some(schema) = schema_file(&content, path) {
+                    self.tree.set_schema_path(schema);
+                }
+                self.tree.load_value(content, value);
+                self.tree.set_path(path);
+                self.fallback = None;
+            }
+            Err(e) => {
+                let mut text = TextViewer::new();
+                text.load_string(content, path.to_str());
+                text.set_syntax("yaml");
+                self.schema = None;
+                self.fallback = Some((text, e.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.tree.is_loaded() || self.fallback.is_some()
+    }
+
+    /// The tree view, for choosing a schema to validate against. `None`
+    /// when the file didn't parse.
+    pub fn tree_mut(&mut self) -> Option<&mut JsonViewer> {
+        if self.fallback.is_some() {
+            None
+        } else {
+            Some(&mut self.tree)
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            egui::Frame::none()
+                .fill(egui::Color32::from_rgb(203, 23, 30))
+                .rounding(egui::Rounding::same(4.0))
+                .inner_margin(egui::Margin::symmetric(6.0, 2.0))
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new("YAML").small().strong().color(egui::Color32::WHITE));
+                });
+            if let Some((_, error)) = &self.fallback {
+                ui.label(
+                    egui::RichText::new(format!("Couldn't parse: {}", error))
+                        .small()
+                        .color(egui::Color32::from_rgb(200, 60, 60)),
+                );
+                return;
+            }
+            if let Some(schema) = &self.schema {
+                ui.label(egui::RichText::new(schema).small().weak());
+            }
+            if !self.anchors.is_empty() {
+                let names: Vec<&str> = self.anchors.iter().map(|(name, _)| name.as_str()).collect();
+                let used: Vec<String> = self
+                    .anchors
+                    .iter()
+                    .map(|(name, uses)| format!("&{} - used {} time{}", name, uses, if *uses == 1 { "" } else { "s" }))
+                    .collect();
+                ui.label(egui::RichText::new(format!("Anchors: {}", names.join(", "))).small().weak())
+                    .on_hover_text(format!("Aliases are shown expanded in the tree.\n{}", used.join("\n")));
+            }
+        });
+        ui.separator();
+
+        match &mut self.fallback {
+            Some((text, _)) => text.ui(ui),
+            None => self.tree.ui(ui),
+        }
+    }
+}
+
+/// Every document in `content`; a file with several becomes an array
+fn parse(content: &str) -> Result<Value> {
+    let mut documents = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(content) {
+        let mut value = serde_yaml::Value::deserialize(document)?;
+        value.apply_merge()?;
+        documents.push(serde_json::to_value(value)?);
+    }
+    Ok(match documents.len() {
+        0 => Value::Null,
+        1 => documents.remove(0),
+        _ => Value::Array(documents),
+    })
+}
+
+/// `&name` definitions in order, with the number of `*name` references
+fn anchors(content: &str) -> Vec<(String, usize)> {
+    let mut anchors: Vec<(String, usize)> = Vec::new();
+    let mut aliases = Vec::new();
+    for line in content.lines() {
+        let line = match line.find(" #") {
+            Some(comment) => &line[..comment],
+            None if line.trim_start().starts_with('#') => continue,
+            None => line,
+        };
+        for token in line.split_whitespace() {
+            let (sigil, rest) = token.split_at(token.chars().next().map_or(0, char::len_utf8));
+            let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.')).collect();
+            if name.is_empty() {
+                continue;
+            }
+            match sigil {
+                "&" if !anchors.iter().any(|(a, _)| *a == name) => anchors.push((name, 0)),
+                "*" => aliases.push(name),
+                _ => {}
+            }
+        }
+    }
+    for alias in aliases {
+        if let Some((_, uses)) = anchors.iter_mut().find(|(name, _)| *name == alias) {
+            *uses += 1;
+        }
+    }
+    anchors
+}
+
+/// The `$schema=` of a `# yaml-language-server:` comment
+fn schema_comment(content: &str) -> Option<&str> {
+    content.lines().find_map(|line| {
+        let comment = line.trim().strip_prefix('#')?.trim().strip_prefix("yaml-language-server:")?;
+        comment.trim().strip_prefix("$schema=").map(str::trim)
+    })
+}
+
+/// A local schema named in the file, relative to it
+fn schema_file(content: &str, path: &Path) -> Option<PathBuf> {
+    let schema = schema_comment(content)?;
+    if schema.contains("://") {
+        return None;
+    }
+    let schema = path.parent().unwrap_or(Path::new(".")).join(schema);
+    schema.is_file().then_some(schema)
+}
+
+/// "Kubernetes Deployment", "Docker Compose", ... from the top-level keys,
+/// or the schema the file names
+fn detect_schema(content: &str, value: &Value) -> Option<String> {
+    if let Some(schema) = schema_comment(content) {
+        return Some(format!("Schema: {}", schema));
+    }
+    let map = value.as_object()?;
+    let has = |key: &str| map.contains_key(key);
+    if has("apiVersion") {
+        if let Some(kind) = map.get("kind").and_then(Value::as_str) {
+            return Some(format!("Kubernetes {}", kind));
+        }
+    }
+    if has("openapi") || has("swagger") {
+        return Some("OpenAPI".to_string());
+    }
+    if has("jobs") && has("on") {
+        return Some("GitHub Actions workflow".to_string());
+    }
+    if map.get("services").is_some_and(Value::is_object) {
+        return Some("Docker Compose".to_string());
+    }
+    None
+}
+
+impl Viewer for YamlViewer {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        YamlViewer::load(self, path)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        YamlViewer::ui(self, ui)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        YamlViewer::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        YamlViewer::is_loaded(self)
+    }
+}